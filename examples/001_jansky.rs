@@ -1,26 +1,23 @@
-// use spectre::units;
-// use spectre::units::length::{foot, meter};
-// use spectre::units::spectral_flux_density::{jansky, millijansky}; // Import millijansky
-// use uom::fmt::DisplayStyle::Abbreviation;
+//! Example of formatting quantities in different units
 
-// fn main() {
-//     let l1 = units::f64::Length::new::<meter>(100.0);
+use spectre::units;
+use spectre::units::DisplayStyle::Abbreviation;
+use spectre::units::length::{foot, meter};
+use spectre::units::spectral_flux_density::{jansky, millijansky};
 
-//     println!(
-//         "{} = {}",
-//         l1.into_format_args(meter, Abbreviation),
-//         l1.into_format_args(foot, Abbreviation)
-//     );
+fn main() {
+    let l1 = units::f64::Length::new::<meter>(100.0);
 
-//     // Add similar println for Jy and mJy
-//     let s2 = units::f64::SpectralFluxDensity::new::<jansky>(134.0);
-//     println!(
-//         "{} = {}",
-//         s2.into_format_args(jansky, Abbreviation),
-//         s2.into_format_args(millijansky, Abbreviation)
-//     );
-// }
+    println!(
+        "{} = {}",
+        l1.into_format_args(meter, Abbreviation),
+        l1.into_format_args(foot, Abbreviation)
+    );
 
-fn main() {
-    println!("Hello, world!");
+    let s2 = units::f64::SpectralFluxDensity::new::<jansky>(134.0);
+    println!(
+        "{} = {}",
+        s2.into_format_args(jansky, Abbreviation),
+        s2.into_format_args(millijansky, Abbreviation)
+    );
 }
@@ -0,0 +1,543 @@
+//! Line measurements, modelled on `specutils.analysis`.
+//!
+//! Every function takes an optional [`SpectralRegion`], of any spectral kind, and measures
+//! only the channels inside it. Masked and NaN channels are ignored. Integrals
+//! are sums of flux times channel width, with widths taken from the bin edges of the axis, so
+//! they are positive whether the axis increases or decreases.
+//!
+//! Results are [`Measurement`]s carrying a unit string built from the flux unit and the SI
+//! unit of the spectral axis. Where the spectrum has an uncertainty it is propagated to first
+//! order assuming independent channels.
+//!
+//! ```
+//! use spectre::analysis::{centroid, integrated_intensity};
+//! use spectre::spectrum::{SpectralAxis, SpectralKind, Spectrum1D};
+//! use spectre::units::equivalencies::DopplerConvention;
+//! use spectre::units::f64::{Frequency, Velocity};
+//! use spectre::units::frequency::gigahertz;
+//! use spectre::units::velocity::kilometer_per_second;
+//!
+//! let v: Vec<Velocity> = (-10..=10)
+//!     .map(|i| Velocity::new::<kilometer_per_second>(f64::from(i)))
+//!     .collect();
+//! let rest = Frequency::new::<gigahertz>(115.271_202);
+//! let axis = SpectralAxis::from_velocities(&v, rest, DopplerConvention::Radio);
+//! let flux = (-10..=10).map(|i| if i == 0 { 2.0 } else { 0.0 }).collect();
+//! let spectrum = Spectrum1D::new(axis, flux, "K")?;
+//!
+//! let w = integrated_intensity(&spectrum, None)?;
+//! assert!((w.value - 2.0).abs() < 1e-9);
+//! assert_eq!(w.unit, "K km / s");
+//! assert!(centroid(&spectrum, None)?.value.abs() < 1e-9);
+//! # Ok::<(), spectre::errors::spectrum::SpectrumError>(())
+//! ```
+
+use crate::constants::SIGMA_TO_FWHM;
+use crate::errors::spectrum::SpectrumError;
+use crate::spectrum::{SpectralKind, SpectralRegion, Spectrum1D, unit_product};
+use crate::stats::{MAD_TO_STD, median};
+
+/// A measured value with its one-sigma uncertainty and unit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    pub value: f64,
+    /// One-sigma uncertainty, if the spectrum has an uncertainty and it is propagated
+    pub uncertainty: Option<f64>,
+    pub unit: String,
+}
+
+impl Measurement {
+    fn new(value: f64, variance: Option<f64>, unit: impl Into<String>) -> Self {
+        Self {
+            value,
+            uncertainty: variance.map(f64::sqrt),
+            unit: unit.into(),
+        }
+    }
+}
+
+/// Channels of a spectrum, with their absolute widths and, if known, flux variances.
+///
+/// This is shared with the per-pixel maps of [`SpectralCube`](crate::cube::SpectralCube),
+/// so spectra and cubes measure moments and propagate errors the same way.
+pub(crate) struct Channels {
+    pub(crate) x: Vec<f64>,
+    /// Absolute channel widths
+    pub(crate) dx: Vec<f64>,
+    pub(crate) flux: Vec<f64>,
+    pub(crate) variance: Option<Vec<f64>>,
+}
+
+impl Channels {
+    /// The unmasked, finite channels of `spectrum` inside `region`.
+    fn new(spectrum: &Spectrum1D, region: Option<&SpectralRegion>) -> Result<Self, SpectrumError> {
+        let inside = match region {
+            Some(region) => region.channel_mask(&spectrum.spectral_axis)?,
+            None => vec![true; spectrum.len()],
+        };
+        let edges = spectrum.spectral_axis.bin_edges();
+        let variance = spectrum.uncertainty.as_ref().map(|u| u.variance());
+        let keep: Vec<usize> = (0..spectrum.len())
+            .filter(|&i| inside[i] && !spectrum.mask[i] && spectrum.flux[i].is_finite())
+            .collect();
+        if keep.is_empty() {
+            return Err(SpectrumError::EmptySelection);
+        }
+        let x = spectrum.spectral_axis.values();
+        Ok(Self {
+            x: keep.iter().map(|&i| x[i]).collect(),
+            dx: keep
+                .iter()
+                .map(|&i| (edges[i + 1] - edges[i]).abs())
+                .collect(),
+            flux: keep.iter().map(|&i| spectrum.flux[i]).collect(),
+            variance: variance.map(|v| keep.iter().map(|&i| v[i]).collect()),
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    /// Variance of a quantity with partial derivatives `derivative(i)` with respect to the
+    /// flux of each channel.
+    pub(crate) fn propagate(&self, derivative: impl Fn(usize) -> f64) -> Option<f64> {
+        self.variance
+            .as_ref()
+            .map(|v| (0..self.len()).map(|i| derivative(i).powi(2) * v[i]).sum())
+    }
+
+    /// `Σ f Δx` and its variance.
+    pub(crate) fn integral(&self) -> (f64, Option<f64>) {
+        let value = (0..self.len()).map(|i| self.flux[i] * self.dx[i]).sum();
+        (value, self.propagate(|i| self.dx[i]))
+    }
+
+    /// Intensity-weighted mean of the spectral coordinate.
+    fn mean(&self, total: f64) -> f64 {
+        (0..self.len())
+            .map(|i| self.flux[i] * self.dx[i] * self.x[i])
+            .sum::<f64>()
+            / total
+    }
+
+    /// Intensity-weighted central moment of order `order` about `mean`.
+    fn central(&self, order: i32, total: f64, mean: f64) -> f64 {
+        (0..self.len())
+            .map(|i| self.flux[i] * self.dx[i] * (self.x[i] - mean).powi(order))
+            .sum::<f64>()
+            / total
+    }
+
+    /// Moment of order `order` and its variance: the integral for order 0, the
+    /// intensity-weighted mean for order 1 and central moments about it above.
+    pub(crate) fn moment(&self, order: i32) -> (f64, Option<f64>) {
+        let (total, variance) = self.integral();
+        if order == 0 {
+            return (total, variance);
+        }
+        let mean = self.mean(total);
+        let d_mean = |i: usize| self.dx[i] * (self.x[i] - mean) / total;
+        if order == 1 {
+            return (mean, self.propagate(d_mean));
+        }
+        let moment = self.central(order, total, mean);
+        let lower = self.central(order - 1, total, mean);
+        let variance = self.propagate(|i| {
+            let offset = self.x[i] - mean;
+            self.dx[i] * (offset.powi(order) - moment) / total
+                - f64::from(order) * lower * d_mean(i)
+        });
+        (moment, variance)
+    }
+
+    /// Square root of the second moment, and its variance.
+    pub(crate) fn dispersion(&self) -> (f64, Option<f64>) {
+        let (second, variance) = self.moment(2);
+        (second.sqrt(), variance.map(|v| v / (4.0 * second)))
+    }
+
+    pub(crate) fn peak_index(&self) -> usize {
+        (0..self.len())
+            .max_by(|&a, &b| self.flux[a].total_cmp(&self.flux[b]))
+            .unwrap()
+    }
+
+    /// Width between the points either side of the peak where the flux falls to `level`,
+    /// interpolating linearly between channels. Stops at the ends of the region.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::PeakBelowLevel`] if no channel rises above `level`.
+    fn width_at(&self, level: f64) -> Result<f64, SpectrumError> {
+        let p = self.peak_index();
+        if self.flux[p] <= level {
+            return Err(SpectrumError::PeakBelowLevel {
+                peak: self.flux[p],
+                level,
+            });
+        }
+        let crossing = |inside: usize, outside: usize| {
+            let (f0, f1) = (self.flux[inside], self.flux[outside]);
+            let t = (f0 - level) / (f0 - f1);
+            self.x[inside] + t * (self.x[outside] - self.x[inside])
+        };
+        let left = (0..p)
+            .rev()
+            .find(|&i| self.flux[i] <= level)
+            .map_or(self.x[0], |i| crossing(i + 1, i));
+        let right = (p + 1..self.len())
+            .find(|&i| self.flux[i] <= level)
+            .map_or(self.x[self.len() - 1], |i| crossing(i - 1, i));
+        Ok((right - left).abs())
+    }
+}
+
+/// SI unit of the spectral axis of `spectrum`.
+fn spectral_unit(spectrum: &Spectrum1D) -> &'static str {
+    spectrum.spectral_axis.kind().si_unit()
+}
+
+/// Integrated flux `Σ f Δx`, in the flux unit times the SI spectral unit.
+///
+/// # Errors
+/// Returns an error if the region cannot be converted to the spectral axis or contains no
+/// usable channel.
+pub fn line_flux(
+    spectrum: &Spectrum1D,
+    region: Option<&SpectralRegion>,
+) -> Result<Measurement, SpectrumError> {
+    let (value, variance) = Channels::new(spectrum, region)?.integral();
+    let unit = unit_product(&spectrum.unit, spectral_unit(spectrum));
+    Ok(Measurement::new(value, variance, unit))
+}
+
+/// Integrated intensity over velocity, in the flux unit times km s⁻¹ (e.g. K km s⁻¹).
+///
+/// # Errors
+/// Returns [`SpectrumError::MissingRestValue`] if the axis cannot be converted to velocity,
+/// and otherwise as [`line_flux`].
+pub fn integrated_intensity(
+    spectrum: &Spectrum1D,
+    region: Option<&SpectralRegion>,
+) -> Result<Measurement, SpectrumError> {
+    let velocity = spectrum.with_spectral_kind(SpectralKind::Velocity)?;
+    let m = line_flux(&velocity, region)?;
+    Ok(Measurement {
+        value: m.value / 1e3,
+        uncertainty: m.uncertainty.map(|u| u / 1e3),
+        unit: unit_product(&spectrum.unit, "km / s"),
+    })
+}
+
+/// Equivalent width `Σ (1 − f / continuum) Δx`, positive for absorption.
+///
+/// # Errors
+/// As [`line_flux`].
+pub fn equivalent_width(
+    spectrum: &Spectrum1D,
+    continuum: f64,
+    region: Option<&SpectralRegion>,
+) -> Result<Measurement, SpectrumError> {
+    let c = Channels::new(spectrum, region)?;
+    let value = (0..c.len())
+        .map(|i| (1.0 - c.flux[i] / continuum) * c.dx[i])
+        .sum();
+    let variance = c.propagate(|i| c.dx[i] / continuum);
+    Ok(Measurement::new(value, variance, spectral_unit(spectrum)))
+}
+
+/// Intensity-weighted centroid of the spectral coordinate.
+///
+/// # Errors
+/// As [`line_flux`].
+pub fn centroid(
+    spectrum: &Spectrum1D,
+    region: Option<&SpectralRegion>,
+) -> Result<Measurement, SpectrumError> {
+    let (mean, variance) = Channels::new(spectrum, region)?.moment(1);
+    Ok(Measurement::new(mean, variance, spectral_unit(spectrum)))
+}
+
+/// Intensity-weighted dispersion σ of the spectral coordinate about the centroid (the
+/// square root of the second moment).
+///
+/// # Errors
+/// As [`line_flux`].
+pub fn line_width(
+    spectrum: &Spectrum1D,
+    region: Option<&SpectralRegion>,
+) -> Result<Measurement, SpectrumError> {
+    let (sigma, variance) = Channels::new(spectrum, region)?.dispersion();
+    Ok(Measurement::new(sigma, variance, spectral_unit(spectrum)))
+}
+
+/// Standard deviation of the Gaussian with the same peak and integrated flux,
+/// `F / (peak √(2π))`.
+///
+/// Unlike [`line_width`] this is insensitive to noise in the line wings.
+///
+/// # Errors
+/// As [`line_flux`].
+pub fn gaussian_sigma_width(
+    spectrum: &Spectrum1D,
+    region: Option<&SpectralRegion>,
+) -> Result<Measurement, SpectrumError> {
+    let c = Channels::new(spectrum, region)?;
+    let (total, total_var) = c.integral();
+    let p = c.peak_index();
+    let peak = c.flux[p];
+    let sigma = total / (peak * (2.0 * std::f64::consts::PI).sqrt());
+    let variance = total_var
+        .zip(c.variance.as_ref())
+        .map(|(tv, v)| sigma * sigma * (tv / (total * total) + v[p] / (peak * peak)));
+    Ok(Measurement::new(sigma, variance, spectral_unit(spectrum)))
+}
+
+/// FWHM of the Gaussian with the same peak and integrated flux.
+///
+/// # Errors
+/// As [`line_flux`].
+pub fn gaussian_fwhm(
+    spectrum: &Spectrum1D,
+    region: Option<&SpectralRegion>,
+) -> Result<Measurement, SpectrumError> {
+    let sigma = gaussian_sigma_width(spectrum, region)?;
+    Ok(Measurement {
+        value: SIGMA_TO_FWHM * sigma.value,
+        uncertainty: sigma.uncertainty.map(|u| SIGMA_TO_FWHM * u),
+        unit: sigma.unit,
+    })
+}
+
+/// Full width at half maximum of an emission line, measured directly from the channels
+/// either side of the peak.
+///
+/// # Errors
+/// Returns [`SpectrumError::PeakBelowLevel`] if the peak is not positive, and otherwise as
+/// [`line_flux`].
+pub fn fwhm(
+    spectrum: &Spectrum1D,
+    region: Option<&SpectralRegion>,
+) -> Result<Measurement, SpectrumError> {
+    let c = Channels::new(spectrum, region)?;
+    let half = 0.5 * c.flux[c.peak_index()];
+    Ok(Measurement::new(
+        c.width_at(half)?,
+        None,
+        spectral_unit(spectrum),
+    ))
+}
+
+/// Full width at zero intensity of an emission line: the width between the points either
+/// side of the peak where the flux first reaches zero.
+///
+/// # Errors
+/// Returns [`SpectrumError::PeakBelowLevel`] if no channel is positive, and otherwise as
+/// [`line_flux`].
+pub fn fwzi(
+    spectrum: &Spectrum1D,
+    region: Option<&SpectralRegion>,
+) -> Result<Measurement, SpectrumError> {
+    let c = Channels::new(spectrum, region)?;
+    Ok(Measurement::new(
+        c.width_at(0.0)?,
+        None,
+        spectral_unit(spectrum),
+    ))
+}
+
+/// Maximum flux, with the uncertainty of that channel.
+///
+/// # Errors
+/// As [`line_flux`].
+pub fn peak(
+    spectrum: &Spectrum1D,
+    region: Option<&SpectralRegion>,
+) -> Result<Measurement, SpectrumError> {
+    let c = Channels::new(spectrum, region)?;
+    let p = c.peak_index();
+    let variance = c.variance.as_ref().map(|v| v[p]);
+    Ok(Measurement::new(
+        c.flux[p],
+        variance,
+        spectrum.unit.as_str(),
+    ))
+}
+
+/// Mean signal-to-noise ratio `mean(f / σ)` of the channels, as in `specutils`.
+///
+/// # Errors
+/// Returns [`SpectrumError::MissingUncertainty`] if the spectrum has no uncertainty, and
+/// otherwise as [`line_flux`].
+pub fn snr(
+    spectrum: &Spectrum1D,
+    region: Option<&SpectralRegion>,
+) -> Result<Measurement, SpectrumError> {
+    let c = Channels::new(spectrum, region)?;
+    let v = c
+        .variance
+        .as_ref()
+        .ok_or(SpectrumError::MissingUncertainty)?;
+    #[allow(clippy::cast_precision_loss)]
+    let value = (0..c.len()).map(|i| c.flux[i] / v[i].sqrt()).sum::<f64>() / c.len() as f64;
+    Ok(Measurement::new(value, None, ""))
+}
+
+/// Signal-to-noise ratio estimated from the flux alone with the DER_SNR algorithm
+/// (Stoehr et al. 2008): the median flux over a noise estimated from second differences
+/// of channels two apart.
+///
+/// # Errors
+/// Returns [`SpectrumError::EmptySelection`] if fewer than five channels are usable, and
+/// otherwise as [`line_flux`].
+pub fn snr_derived(
+    spectrum: &Spectrum1D,
+    region: Option<&SpectralRegion>,
+) -> Result<Measurement, SpectrumError> {
+    let c = Channels::new(spectrum, region)?;
+    let n = c.len();
+    if n < 5 {
+        return Err(SpectrumError::EmptySelection);
+    }
+    let f = &c.flux;
+    let differences: Vec<f64> = (2..n - 2)
+        .map(|i| (2.0 * f[i] - f[i - 2] - f[i + 2]).abs())
+        .collect();
+    let noise = MAD_TO_STD / 6.0_f64.sqrt() * median(&differences);
+    Ok(Measurement::new(median(f) / noise, None, ""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitting::profiles::gaussian;
+    use crate::spectrum::{SpectralAxis, Uncertainty};
+    use crate::units::f64::Frequency;
+    use crate::units::frequency::hertz;
+    use approx::assert_relative_eq;
+
+    /// Gaussian of peak 2 at x = 500 with σ = 20, on a 1 Hz grid.
+    fn spectrum() -> Spectrum1D {
+        let x: Vec<f64> = (0..1000).map(f64::from).collect();
+        let flux = x.iter().map(|&v| gaussian(v, 2.0, 500.0, 20.0)).collect();
+        Spectrum1D::new(
+            SpectralAxis::from_si(SpectralKind::Frequency, x),
+            flux,
+            "Jy",
+        )
+        .unwrap()
+        .with_uncertainty(Uncertainty::StdDev(vec![0.1; 1000]))
+        .unwrap()
+    }
+
+    fn hz(lo: f64, hi: f64) -> SpectralRegion {
+        SpectralRegion::new(Frequency::new::<hertz>(lo), Frequency::new::<hertz>(hi)).unwrap()
+    }
+
+    #[test]
+    fn test_flux_and_moments() {
+        let s = spectrum();
+        let flux = line_flux(&s, None).unwrap();
+        assert_relative_eq!(
+            flux.value,
+            2.0 * 20.0 * (2.0 * std::f64::consts::PI).sqrt(),
+            max_relative = 1e-9
+        );
+        assert_relative_eq!(
+            flux.uncertainty.unwrap(),
+            0.1 * 1000.0_f64.sqrt(),
+            max_relative = 1e-12
+        );
+        assert_eq!(flux.unit, "Jy Hz");
+        let mut per_beam = s.clone();
+        per_beam.unit = "Jy/beam".into();
+        assert_eq!(line_flux(&per_beam, None).unwrap().unit, "(Jy/beam) Hz");
+
+        let c = centroid(&s, Some(&hz(400.0, 600.0))).unwrap();
+        assert_relative_eq!(c.value, 500.0, max_relative = 1e-12);
+        assert!(c.uncertainty.unwrap() < 1.0);
+        assert_eq!(c.unit, "Hz");
+
+        let w = line_width(&s, Some(&hz(350.0, 650.0))).unwrap();
+        assert_relative_eq!(w.value, 20.0, max_relative = 1e-4);
+        assert_relative_eq!(
+            gaussian_sigma_width(&s, None).unwrap().value,
+            20.0,
+            max_relative = 1e-9
+        );
+        assert_relative_eq!(
+            gaussian_fwhm(&s, None).unwrap().value,
+            20.0 * SIGMA_TO_FWHM,
+            max_relative = 1e-9
+        );
+    }
+
+    #[test]
+    fn test_widths_and_peak() {
+        let s = spectrum();
+        assert_relative_eq!(
+            fwhm(&s, None).unwrap().value,
+            20.0 * SIGMA_TO_FWHM,
+            max_relative = 1e-3
+        );
+        let p = peak(&s, None).unwrap();
+        assert_eq!(p.value, 2.0);
+        assert_eq!(p.uncertainty, Some(0.1));
+        assert_eq!(p.unit, "Jy");
+
+        let mut box_line =
+            Spectrum1D::new(s.spectral_axis.clone(), vec![-0.5; 1000], "Jy").unwrap();
+        box_line.flux[100..110].iter_mut().for_each(|v| *v = 1.0);
+        // Interpolated zero crossings sit two thirds of a channel outside the box.
+        assert_relative_eq!(
+            fwzi(&box_line, None).unwrap().value,
+            9.0 + 4.0 / 3.0,
+            max_relative = 1e-12
+        );
+
+        let flat = Spectrum1D::new(s.spectral_axis.clone(), vec![0.0; 1000], "Jy").unwrap();
+        let no_line = Err(SpectrumError::PeakBelowLevel {
+            peak: 0.0,
+            level: 0.0,
+        });
+        assert_eq!(fwzi(&flat, None), no_line);
+        assert_eq!(fwhm(&flat, None), no_line);
+    }
+
+    #[test]
+    fn test_equivalent_width() {
+        let mut s = spectrum();
+        s.flux.iter_mut().for_each(|v| *v = 1.0 - *v / 4.0);
+        let ew = equivalent_width(&s, 1.0, None).unwrap();
+        assert_relative_eq!(
+            ew.value,
+            10.0 * (2.0 * std::f64::consts::PI).sqrt(),
+            max_relative = 1e-9
+        );
+    }
+
+    #[test]
+    fn test_snr() {
+        let s = spectrum();
+        let region = Some(&hz(499.0, 501.0));
+        assert_relative_eq!(snr(&s, region).unwrap().value, 19.99, max_relative = 1e-3);
+        let no_uncertainty =
+            Spectrum1D::new(s.spectral_axis.clone(), s.flux.clone(), "Jy").unwrap();
+        assert_eq!(
+            snr(&no_uncertainty, None).unwrap_err(),
+            SpectrumError::MissingUncertainty
+        );
+
+        let flat: Vec<f64> = (0..100)
+            .map(|i| if i % 2 == 0 { 10.1 } else { 9.9 })
+            .collect();
+        let flat = Spectrum1D::new(
+            SpectralAxis::from_si(SpectralKind::Frequency, (0..100).map(f64::from).collect()),
+            flat,
+            "Jy",
+        )
+        .unwrap();
+        // Channels two apart are identical, so the derived noise vanishes.
+        assert!(snr_derived(&flat, None).unwrap().value.is_infinite());
+    }
+}
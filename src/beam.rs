@@ -3,47 +3,16 @@ use std::cmp::PartialEq;
 use std::f64::consts::PI;
 use std::ops::{Div, Mul};
 
+use uom::typenum::P2;
+
 use crate::constants::{FWHM_TO_AREA, SIGMA_TO_FWHM};
 use crate::errors::radio::BeamError;
+use crate::units::angle::{arcsecond, degree, radian};
+use crate::units::f64::{Angle, Ratio, SolidAngle};
+use crate::units::solid_angle::steradian;
 use crate::utils::approx_eq;
 
-#[derive(Debug)]
-struct Angle {
-    value: f64,
-    unit: AngleUnit,
-}
-
-impl Angle {
-    pub fn new<T>(value: f64) -> Self
-    where
-        T: Into<AngleUnit>,
-    {
-        Self {
-            value,
-            unit: T::into(),
-        }
-    }
-}
-
-#[derive(Debug)]
-struct SolidAngle {
-    value: f64,
-    unit: SolidAngleUnit,
-}
-
-#[derive(Debug)]
-enum AngleUnit {
-    Radian,
-    Degree,
-    Arcsecond,
-}
-
-#[derive(Debug)]
-enum SolidAngleUnit {
-    Steradian,
-}
-
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Beam {
     /// The FWHM major axis
     pub major: Angle,
@@ -68,7 +37,7 @@ impl Beam {
             if major.is_some() || minor.is_some() || pa.is_some() {
                 return Err(BeamError::ExclusiveParameterConflict);
             }
-            let rad = (area.value / (2.0 * PI)).sqrt();
+            let rad = (area.get::<steradian>() / (2.0 * PI)).sqrt();
             let fwhm_rad_val = rad * SIGMA_TO_FWHM;
             let fwhm_arcsec_val = Angle::new::<radian>(fwhm_rad_val).get::<arcsecond>();
             (
@@ -153,6 +122,11 @@ impl PartialEq for Beam {
     }
 }
 
+/// Reinterpret a dimensionless quantity expressed in radians as an angle.
+fn as_angle(value: Ratio) -> Angle {
+    Angle::new::<radian>(value.value)
+}
+
 fn convolve(beam: Beam, other: Beam) -> (Angle, Angle, Angle) {
     // Unit is Angle^(-2)
     let alpha = (beam.major * beam.pa.cos()).powi(P2::new())
@@ -189,13 +163,13 @@ fn convolve(beam: Beam, other: Beam) -> (Angle, Angle, Angle) {
     let tol_arcsec = Angle::new::<arcsecond>(1e-7); // 1 microarcsec of tolerance
     let pa_check = (gamma.abs() + (alpha - beta).abs()).sqrt();
 
-    let new_pa = if approx_eq(pa_check.into(), Angle::new::<arcsecond>(0.0), tol_arcsec) {
+    let new_pa = if approx_eq(as_angle(pa_check), Angle::new::<arcsecond>(0.0), tol_arcsec) {
         Angle::new::<degree>(0.0)
     } else {
         0.5 * Angle::new::<radian>(new_par_radians)
     };
 
-    (new_major.into(), new_minor.into(), new_pa)
+    (as_angle(new_major), as_angle(new_minor), new_pa)
 }
 
 fn deconvolve(b1: &Beam, b2: &Beam) -> (Angle, Angle, Angle) {
@@ -243,7 +217,7 @@ fn deconvolve(b1: &Beam, b2: &Beam) -> (Angle, Angle, Angle) {
         let tol_arcsec = Angle::new::<arcsecond>(1e-7 / 3600.0); // 1 microarcsec of tolerance
         let pa_check = (gamma.abs() + (alpha - beta).abs()).sqrt();
 
-        let new_pa = if approx_eq(pa_check.into(), Angle::new::<arcsecond>(0.0), tol_arcsec) {
+        let new_pa = if approx_eq(as_angle(pa_check), Angle::new::<arcsecond>(0.0), tol_arcsec) {
             Angle::new::<degree>(0.0)
         } else {
             0.5 * Angle::new::<radian>(new_par_radians)
@@ -252,7 +226,7 @@ fn deconvolve(b1: &Beam, b2: &Beam) -> (Angle, Angle, Angle) {
         new_major.value += f64::EPSILON;
         new_minor.value += f64::EPSILON;
 
-        (new_major.into(), new_minor.into(), new_pa)
+        (as_angle(new_major), as_angle(new_minor), new_pa)
     }
 }
 
@@ -400,4 +374,19 @@ mod tests {
         let computed_zero_area = Beam::to_area(zero_major, zero_minor);
         assert_relative_eq!(computed_zero_area.get::<steradian>(), 0.0);
     }
+
+    #[test]
+    fn test_convolve_deconvolve_circular() {
+        let arcsec = |v| Angle::new::<arcsecond>(v);
+        let b1 = Beam::new(Some(arcsec(3.0)), None, None, None).unwrap();
+        let b2 = Beam::new(Some(arcsec(4.0)), None, None, None).unwrap();
+
+        let conv = b1 * b2;
+        assert_relative_eq!(conv.major.get::<arcsecond>(), 5.0, epsilon = 1e-9);
+        assert_relative_eq!(conv.minor.get::<arcsecond>(), 5.0, epsilon = 1e-9);
+
+        let deconv = conv / b2;
+        assert_relative_eq!(deconv.major.get::<arcsecond>(), 3.0, epsilon = 1e-6);
+        assert_relative_eq!(deconv.minor.get::<arcsecond>(), 3.0, epsilon = 1e-6);
+    }
 }
@@ -0,0 +1,397 @@
+//! A common view of spectral line catalogs.
+//!
+//! LAMDA radiative transitions, CDMS and JPL catalog entries and HITRAN lines all describe
+//! transitions between two states of a species. The [`Transition`] trait exposes what they
+//! share, [`Line`] is an owned record of it, and a [`LineCatalog`] is anything that lists
+//! transitions. Catalogs are combined with [`merge`], which keeps one line for each
+//! transition found in several catalogs.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::units::f64::{Frequency, Temperature};
+use crate::units::frequency::hertz;
+
+/// A transition between two states of a species.
+pub trait Transition {
+    /// Name of the species, e.g. `CO` or `HCO+`.
+    fn species(&self) -> &str;
+
+    /// Rest frequency of the transition.
+    fn rest_frequency(&self) -> Frequency;
+
+    /// One-sigma uncertainty of the rest frequency, if the catalog gives one.
+    fn frequency_uncertainty(&self) -> Option<Frequency> {
+        None
+    }
+
+    /// Energy of the upper state above the ground state, as E_up / k.
+    fn upper_energy(&self) -> Option<Temperature>;
+
+    /// Degeneracy g_up of the upper state.
+    fn upper_degeneracy(&self) -> Option<f64>;
+
+    /// Einstein coefficient A_ul for spontaneous emission, in s⁻¹.
+    fn einstein_a(&self) -> Option<f64>;
+
+    /// Quantum numbers of the upper and lower states, in the notation of the catalog.
+    fn quantum_numbers(&self) -> String;
+}
+
+/// A transition as listed in a catalog.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    /// Name of the species
+    pub species: String,
+    /// Rest frequency
+    pub frequency: Frequency,
+    /// One-sigma uncertainty of the rest frequency
+    pub frequency_uncertainty: Option<Frequency>,
+    /// Energy of the upper state, as E_up / k
+    pub upper_energy: Option<Temperature>,
+    /// Degeneracy of the upper state
+    pub upper_degeneracy: Option<f64>,
+    /// Einstein A coefficient (s^-1)
+    pub einstein_a: Option<f64>,
+    /// Quantum numbers of the upper and lower states, in the notation of the catalog
+    pub quantum_numbers: String,
+}
+
+impl Line {
+    /// Copy everything a transition exposes.
+    pub fn from_transition<T: Transition + ?Sized>(transition: &T) -> Self {
+        Self {
+            species: transition.species().to_string(),
+            frequency: transition.rest_frequency(),
+            frequency_uncertainty: transition.frequency_uncertainty(),
+            upper_energy: transition.upper_energy(),
+            upper_degeneracy: transition.upper_degeneracy(),
+            einstein_a: transition.einstein_a(),
+            quantum_numbers: transition.quantum_numbers(),
+        }
+    }
+}
+
+impl Transition for Line {
+    fn species(&self) -> &str {
+        &self.species
+    }
+
+    fn rest_frequency(&self) -> Frequency {
+        self.frequency
+    }
+
+    fn frequency_uncertainty(&self) -> Option<Frequency> {
+        self.frequency_uncertainty
+    }
+
+    fn upper_energy(&self) -> Option<Temperature> {
+        self.upper_energy
+    }
+
+    fn upper_degeneracy(&self) -> Option<f64> {
+        self.upper_degeneracy
+    }
+
+    fn einstein_a(&self) -> Option<f64> {
+        self.einstein_a
+    }
+
+    fn quantum_numbers(&self) -> String {
+        self.quantum_numbers.clone()
+    }
+}
+
+/// A list of transitions.
+pub trait LineCatalog {
+    /// Every line of the catalog.
+    fn lines(&self) -> Vec<Line>;
+
+    /// Lines with rest frequencies from `low` to `high` inclusive, in frequency order.
+    fn lines_between(&self, low: Frequency, high: Frequency) -> Vec<Line> {
+        let mut lines: Vec<Line> = self
+            .lines()
+            .into_iter()
+            .filter(|line| (low..=high).contains(&line.frequency))
+            .collect();
+        lines.sort_by(|a, b| a.frequency.value.total_cmp(&b.frequency.value));
+        lines
+    }
+}
+
+impl LineCatalog for [Line] {
+    fn lines(&self) -> Vec<Line> {
+        self.to_vec()
+    }
+}
+
+impl LineCatalog for Vec<Line> {
+    fn lines(&self) -> Vec<Line> {
+        self.clone()
+    }
+}
+
+/// A line left out of a merged catalog as a duplicate of one kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Duplicate {
+    /// Index in [`MergedCatalog::lines`] of the line kept instead
+    pub kept: usize,
+    /// Index of the catalog the duplicate came from
+    pub catalog: usize,
+    pub line: Line,
+}
+
+/// The result of [`merge`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedCatalog {
+    /// Lines kept, in frequency order
+    pub lines: Vec<Line>,
+    /// Index of the catalog each line came from
+    pub sources: Vec<usize>,
+    pub duplicates: Vec<Duplicate>,
+}
+
+impl LineCatalog for MergedCatalog {
+    fn lines(&self) -> Vec<Line> {
+        self.lines.clone()
+    }
+}
+
+/// Merge catalogs, listed from the most to the least preferred.
+///
+/// A line is a duplicate if an earlier catalog has a line of the same species (ignoring case
+/// and surrounding whitespace) within `tolerance` of its rest frequency; the closest such line
+/// is kept. Lines of one catalog are never duplicates of each other, so closely spaced
+/// components such as hyperfine lines survive.
+pub fn merge(catalogs: &[&dyn LineCatalog], tolerance: Frequency) -> MergedCatalog {
+    let tolerance = tolerance.get::<hertz>().abs();
+    // Kept lines, and for each species their indices by frequency. Frequencies are positive,
+    // so their bit patterns sort like the values.
+    let mut kept: Vec<(Line, usize)> = Vec::new();
+    let mut by_species: HashMap<String, BTreeMap<u64, Vec<usize>>> = HashMap::new();
+    let mut duplicates = Vec::new();
+    for (catalog, lines) in catalogs.iter().enumerate() {
+        let mut added = Vec::new();
+        for line in lines.lines() {
+            let species = line.species.trim().to_lowercase();
+            let frequency = line.frequency.get::<hertz>();
+            let key = |f: f64| f.max(0.0).to_bits();
+            let closest = by_species.get(&species).and_then(|index| {
+                index
+                    .range(key(frequency - tolerance)..=key(frequency + tolerance))
+                    .flat_map(|(_, lines)| lines)
+                    .min_by(|&&a, &&b| {
+                        let distance =
+                            |i: usize| (kept[i].0.frequency.get::<hertz>() - frequency).abs();
+                        distance(a).total_cmp(&distance(b))
+                    })
+                    .copied()
+            });
+            match closest {
+                Some(kept) => duplicates.push(Duplicate {
+                    kept,
+                    catalog,
+                    line,
+                }),
+                None => {
+                    added.push((species, key(frequency), kept.len()));
+                    kept.push((line, catalog));
+                }
+            }
+        }
+        // Added only now so that lines of this catalog are not compared with each other.
+        for (species, key, i) in added {
+            by_species
+                .entry(species)
+                .or_default()
+                .entry(key)
+                .or_default()
+                .push(i);
+        }
+    }
+
+    let mut order: Vec<usize> = (0..kept.len()).collect();
+    order.sort_by(|&a, &b| {
+        kept[a]
+            .0
+            .frequency
+            .value
+            .total_cmp(&kept[b].0.frequency.value)
+    });
+    let mut position = vec![0; kept.len()];
+    for (new, &old) in order.iter().enumerate() {
+        position[old] = new;
+    }
+    duplicates
+        .iter_mut()
+        .for_each(|d| d.kept = position[d.kept]);
+    let (lines, sources) = order.iter().map(|&i| kept[i].clone()).unzip();
+    MergedCatalog {
+        lines,
+        sources,
+        duplicates,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lamda::{LAMDAData, Level, RadTransition};
+    use crate::units::frequency::{gigahertz, megahertz};
+    use crate::units::thermodynamic_temperature::kelvin;
+    use approx::assert_relative_eq;
+
+    fn line(species: &str, ghz: f64, quantum_numbers: &str) -> Line {
+        Line {
+            species: species.to_string(),
+            frequency: Frequency::new::<gigahertz>(ghz),
+            frequency_uncertainty: None,
+            upper_energy: None,
+            upper_degeneracy: None,
+            einstein_a: None,
+            quantum_numbers: quantum_numbers.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_lamda_transitions() {
+        let level = |id, energy, weight, j: usize| Level {
+            id,
+            energy,
+            weight,
+            j,
+            label: j.to_string(),
+        };
+        let co = LAMDAData {
+            name: "CO".to_string(),
+            weight: 28.0,
+            levels: vec![level(1, 0.0, 1.0, 0), level(2, 3.845_033, 3.0, 1)],
+            radset: vec![RadTransition {
+                id: 1,
+                up: 2,
+                low: 1,
+                einst_a: 7.203e-8,
+                freq: 115.271_201_8,
+                energy: 5.53,
+            }],
+            ..LAMDAData::default()
+        };
+        let transitions: Vec<_> = co.transitions().collect();
+        assert_eq!(transitions.len(), 1);
+        let t = &transitions[0];
+        assert_eq!(t.species(), "CO");
+        assert_relative_eq!(t.rest_frequency().get::<gigahertz>(), 115.271_201_8);
+        assert_eq!(t.frequency_uncertainty(), None);
+        assert_relative_eq!(t.upper_energy().unwrap().get::<kelvin>(), 5.53);
+        assert_eq!(t.upper_degeneracy(), Some(3.0));
+        assert_eq!(t.einstein_a(), Some(7.203e-8));
+        assert_eq!(t.quantum_numbers(), "1-0");
+
+        let lines = co.lines();
+        assert_eq!(lines, vec![Line::from_transition(t)]);
+        let none = co.lines_between(
+            Frequency::new::<gigahertz>(200.0),
+            Frequency::new::<gigahertz>(300.0),
+        );
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn test_lamda_level_labels() {
+        let file = "\
+!MOLECULE
+p-H2O
+!MOLECULAR WEIGHT
+18.0
+!NUMBER OF ENERGY LEVELS
+2
+!LEVEL + ENERGIES(cm^-1) + WEIGHT + J_Kp_Ko
+    1     0.000000000   1.0   0_0_0
+    2    37.137100000   3.0   1_1_1
+!NUMBER OF RADIATIVE TRANSITIONS
+1
+!TRANS + UP + LOW + EINSTEINA(s^-1) + FREQ(GHz) + E_u(K)
+    1     2     1   1.841e-02   1113.342964     53.43
+!NUMBER OF COLL PARTNERS
+0
+";
+        let water = LAMDAData::from_reader(file.as_bytes()).unwrap();
+        assert_eq!(water.levels[1].label, "1_1_1");
+        assert_eq!(water.levels[1].j, 1);
+        let t = water.transitions().next().unwrap();
+        assert_eq!(t.quantum_numbers(), "1_1_1-0_0_0");
+    }
+
+    #[test]
+    fn test_merge() {
+        let cdms = vec![
+            line("CO", 115.271_202, "1-0"),
+            line("HCN", 88.631_602, "1-0 F=1-1"),
+            line("HCN", 88.631_848, "1-0 F=2-1"),
+        ];
+        let jpl = vec![
+            line("co", 115.271_203, "J=1-0"),
+            line("HCN", 88.631_850, "J=1-0"),
+            line("CS", 97.980_953, "2-1"),
+        ];
+        let merged = merge(&[&cdms, &jpl], Frequency::new::<megahertz>(0.1));
+        let names: Vec<_> = merged
+            .lines
+            .iter()
+            .map(|l| l.quantum_numbers.as_str())
+            .collect();
+        // Hyperfine components of one catalog are kept even though they are close.
+        assert_eq!(names, ["1-0 F=1-1", "1-0 F=2-1", "2-1", "1-0"]);
+        assert_eq!(merged.sources, [0, 0, 1, 0]);
+        assert_eq!(merged.duplicates.len(), 2);
+        assert_eq!(merged.duplicates[0].kept, 3);
+        assert_eq!(merged.duplicates[0].catalog, 1);
+        // The JPL HCN line is closest to the F=2-1 component.
+        assert_eq!(merged.duplicates[1].kept, 1);
+
+        // Nothing is a duplicate with a small enough tolerance.
+        let separate = merge(&[&cdms, &jpl], Frequency::new::<hertz>(100.0));
+        assert_eq!(separate.lines.len(), 6);
+        assert!(separate.duplicates.is_empty());
+        assert_eq!(
+            separate
+                .lines_between(
+                    Frequency::new::<gigahertz>(100.0),
+                    Frequency::new::<gigahertz>(120.0)
+                )
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_merge_three_catalogs() {
+        let first = vec![line("CO", 115.271_202, "1-0")];
+        let second = vec![
+            line(" co ", 115.271_203, "J=1-0"),
+            line("CS", 97.980_953, "2-1"),
+        ];
+        let third = vec![
+            line("CO\t", 115.271_201, "1-0"),
+            line("cs", 97.980_950, "J=2-1"),
+            line("SiO", 86.846_985, "2-1"),
+        ];
+        let merged = merge(&[&first, &second, &third], Frequency::new::<megahertz>(0.1));
+        let species: Vec<_> = merged.lines.iter().map(|l| l.species.as_str()).collect();
+        assert_eq!(species, ["SiO", "CS", "CO"]);
+        assert_eq!(merged.sources, [2, 1, 0]);
+        // Species differing only by case and surrounding whitespace are the same, and a
+        // line is a duplicate of the one kept from the most preferred catalog.
+        let duplicates: Vec<_> = merged
+            .duplicates
+            .iter()
+            .map(|d| (d.catalog, d.kept, d.line.species.as_str()))
+            .collect();
+        assert_eq!(duplicates, [(1, 2, " co "), (2, 2, "CO\t"), (2, 1, "cs")]);
+
+        // Whitespace inside a name is significant.
+        let spaced = vec![line("C O", 115.271_203, "1-0")];
+        let merged = merge(&[&first, &spaced], Frequency::new::<megahertz>(0.1));
+        assert_eq!(merged.lines.len(), 2);
+        assert!(merged.duplicates.is_empty());
+    }
+}
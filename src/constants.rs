@@ -1,3 +1,10 @@
+//! Mathematical and physical constants.
+//!
+//! The plain `f64` constants at the top level use the CODATA 2018 recommended values in SI
+//! units. For reproducibility against older tools the full CODATA 2014 set is available as
+//! [`CODATA2014`], and [`Codata`] provides the derived spectroscopic conversion factors for
+//! either set. Typed versions of the most common constants live in [`quantities`].
+
 use std::f64::consts::{LN_2, PI};
 
 /// Convert 2-dimensional Gaussian FWHM^2 to effective area.
@@ -6,3 +13,281 @@ pub const FWHM_TO_AREA: f64 = 2.0 * PI / (8.0 * LN_2);
 /// Convert 2-dimensional Gaussian sigma^2 to FWHM
 /// == sqrt(8*ln(2))
 pub const SIGMA_TO_FWHM: f64 = 2.354_820_045_03;
+
+/// Speed of light in vacuum (m s^-1)
+pub const SPEED_OF_LIGHT: f64 = CODATA2018.c;
+
+/// Planck constant (J s)
+pub const PLANCK: f64 = CODATA2018.h;
+
+/// Reduced Planck constant (J s)
+pub const REDUCED_PLANCK: f64 = CODATA2018.hbar;
+
+/// Boltzmann constant (J K^-1)
+pub const BOLTZMANN: f64 = CODATA2018.k_b;
+
+/// Elementary charge (C)
+pub const ELEMENTARY_CHARGE: f64 = CODATA2018.e;
+
+/// Avogadro constant (mol^-1)
+pub const AVOGADRO: f64 = CODATA2018.n_a;
+
+/// Atomic mass constant (kg)
+pub const ATOMIC_MASS: f64 = CODATA2018.m_u;
+
+/// Electron mass (kg)
+pub const ELECTRON_MASS: f64 = CODATA2018.m_e;
+
+/// Proton mass (kg)
+pub const PROTON_MASS: f64 = CODATA2018.m_p;
+
+/// Newtonian constant of gravitation (m^3 kg^-1 s^-2)
+pub const GRAVITATIONAL: f64 = CODATA2018.g;
+
+/// Stefan-Boltzmann constant (W m^-2 K^-4)
+pub const STEFAN_BOLTZMANN: f64 = CODATA2018.sigma_sb;
+
+/// Vacuum electric permittivity (F m^-1)
+pub const VACUUM_PERMITTIVITY: f64 = CODATA2018.eps0;
+
+/// Bohr radius (m)
+pub const BOHR_RADIUS: f64 = CODATA2018.a0;
+
+/// Second radiation constant, hc/k (m K)
+pub const SECOND_RADIATION_CONSTANT: f64 = CODATA2018.second_radiation_constant();
+
+/// Convert a wavenumber in cm^-1 to an energy in K (hc/k in cm K)
+pub const WAVENUMBER_TO_KELVIN: f64 = CODATA2018.wavenumber_to_kelvin();
+
+/// Convert a wavenumber in cm^-1 to a frequency in MHz (c in cm MHz)
+pub const WAVENUMBER_TO_MHZ: f64 = CODATA2018.wavenumber_to_mhz();
+
+/// Convert an energy in K to a frequency in MHz (k/h in MHz K^-1)
+pub const KELVIN_TO_MHZ: f64 = CODATA2018.kelvin_to_mhz();
+
+/// Convert an electric dipole moment in debye to esu (statC cm)
+pub const DEBYE_TO_ESU: f64 = 1e-18;
+
+/// Convert an electric dipole moment in debye to SI (C m), 1 D = 10^-21 / c
+pub const DEBYE_TO_SI: f64 = 1e-21 / SPEED_OF_LIGHT;
+
+/// Astronomical unit, IAU 2012 Resolution B2 (m)
+pub const ASTRONOMICAL_UNIT: f64 = 149_597_870_700.0;
+
+/// Parsec, IAU 2015 Resolution B2 (m)
+pub const PARSEC: f64 = ASTRONOMICAL_UNIT * 648_000.0 / PI;
+
+/// Julian light year (m)
+pub const LIGHT_YEAR: f64 = SPEED_OF_LIGHT * 365.25 * 86_400.0;
+
+/// Present-day temperature of the cosmic microwave background, Fixsen (2009) (K)
+pub const CMB_TEMPERATURE: f64 = 2.7255;
+
+/// A set of CODATA recommended values of the fundamental physical constants, in SI units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Codata {
+    /// Release year of the adjustment
+    pub version: u16,
+    /// Speed of light in vacuum (m s^-1)
+    pub c: f64,
+    /// Planck constant (J s)
+    pub h: f64,
+    /// Reduced Planck constant (J s)
+    pub hbar: f64,
+    /// Boltzmann constant (J K^-1)
+    pub k_b: f64,
+    /// Elementary charge (C)
+    pub e: f64,
+    /// Avogadro constant (mol^-1)
+    pub n_a: f64,
+    /// Atomic mass constant (kg)
+    pub m_u: f64,
+    /// Electron mass (kg)
+    pub m_e: f64,
+    /// Proton mass (kg)
+    pub m_p: f64,
+    /// Newtonian constant of gravitation (m^3 kg^-1 s^-2)
+    pub g: f64,
+    /// Stefan-Boltzmann constant (W m^-2 K^-4)
+    pub sigma_sb: f64,
+    /// Vacuum electric permittivity (F m^-1)
+    pub eps0: f64,
+    /// Bohr radius (m)
+    pub a0: f64,
+}
+
+/// CODATA 2018 recommended values, following the 2019 SI redefinition.
+pub const CODATA2018: Codata = Codata {
+    version: 2018,
+    c: 299_792_458.0,
+    h: 6.626_070_15e-34,
+    hbar: 1.054_571_817e-34,
+    k_b: 1.380_649e-23,
+    e: 1.602_176_634e-19,
+    n_a: 6.022_140_76e23,
+    m_u: 1.660_539_066_60e-27,
+    m_e: 9.109_383_701_5e-31,
+    m_p: 1.672_621_923_69e-27,
+    g: 6.674_30e-11,
+    sigma_sb: 5.670_374_419e-8,
+    eps0: 8.854_187_812_8e-12,
+    a0: 5.291_772_109_03e-11,
+};
+
+/// CODATA 2014 recommended values, as used by `astropy` < 4.0 and many older catalogs.
+pub const CODATA2014: Codata = Codata {
+    version: 2014,
+    c: 299_792_458.0,
+    h: 6.626_070_040e-34,
+    hbar: 1.054_571_800e-34,
+    k_b: 1.380_648_52e-23,
+    e: 1.602_176_620_8e-19,
+    n_a: 6.022_140_857e23,
+    m_u: 1.660_539_040e-27,
+    m_e: 9.109_383_56e-31,
+    m_p: 1.672_621_898e-27,
+    g: 6.674_08e-11,
+    sigma_sb: 5.670_367e-8,
+    eps0: 8.854_187_817e-12,
+    a0: 5.291_772_106_7e-11,
+};
+
+impl Codata {
+    /// Second radiation constant, hc/k (m K).
+    #[must_use]
+    pub const fn second_radiation_constant(&self) -> f64 {
+        self.h * self.c / self.k_b
+    }
+
+    /// Energy in K of a wavenumber of 1 cm^-1.
+    #[must_use]
+    pub const fn wavenumber_to_kelvin(&self) -> f64 {
+        100.0 * self.second_radiation_constant()
+    }
+
+    /// Frequency in MHz of a wavenumber of 1 cm^-1.
+    #[must_use]
+    pub const fn wavenumber_to_mhz(&self) -> f64 {
+        100.0 * self.c / 1e6
+    }
+
+    /// Frequency in MHz of an energy of 1 K.
+    #[must_use]
+    pub const fn kelvin_to_mhz(&self) -> f64 {
+        self.k_b / self.h / 1e6
+    }
+}
+
+impl Default for Codata {
+    fn default() -> Self {
+        CODATA2018
+    }
+}
+
+/// Typed versions of the CODATA 2018 and astronomical constants.
+pub mod quantities {
+    use std::marker::PhantomData;
+
+    use uom::si::{Dimension, Quantity, SI};
+
+    use crate::units::f64::{
+        Action, ElectricCharge, HeatCapacity, Length, Mass, MolarHeatCapacity, Velocity,
+    };
+
+    const fn quantity<D>(value: f64) -> Quantity<D, SI<f64>, f64>
+    where
+        D: Dimension + ?Sized,
+    {
+        Quantity {
+            dimension: PhantomData,
+            units: PhantomData,
+            value,
+        }
+    }
+
+    /// Speed of light in vacuum
+    pub const SPEED_OF_LIGHT: Velocity = quantity(super::SPEED_OF_LIGHT);
+
+    /// Planck constant
+    pub const PLANCK: Action = quantity(super::PLANCK);
+
+    /// Reduced Planck constant
+    pub const REDUCED_PLANCK: Action = quantity(super::REDUCED_PLANCK);
+
+    /// Boltzmann constant
+    pub const BOLTZMANN: HeatCapacity = quantity(super::BOLTZMANN);
+
+    /// Molar gas constant, N_A k
+    pub const GAS_CONSTANT: MolarHeatCapacity = quantity(super::AVOGADRO * super::BOLTZMANN);
+
+    /// Elementary charge
+    pub const ELEMENTARY_CHARGE: ElectricCharge = quantity(super::ELEMENTARY_CHARGE);
+
+    /// Atomic mass constant
+    pub const ATOMIC_MASS: Mass = quantity(super::ATOMIC_MASS);
+
+    /// Electron mass
+    pub const ELECTRON_MASS: Mass = quantity(super::ELECTRON_MASS);
+
+    /// Proton mass
+    pub const PROTON_MASS: Mass = quantity(super::PROTON_MASS);
+
+    /// Bohr radius
+    pub const BOHR_RADIUS: Length = quantity(super::BOHR_RADIUS);
+
+    /// Astronomical unit
+    pub const ASTRONOMICAL_UNIT: Length = quantity(super::ASTRONOMICAL_UNIT);
+
+    /// Parsec
+    pub const PARSEC: Length = quantity(super::PARSEC);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_derived_factors() {
+        assert_relative_eq!(
+            SECOND_RADIATION_CONSTANT,
+            1.438_776_877e-2,
+            max_relative = 1e-9
+        );
+        assert_relative_eq!(WAVENUMBER_TO_KELVIN, 1.438_776_877, max_relative = 1e-9);
+        assert_relative_eq!(WAVENUMBER_TO_MHZ, 29_979.245_8, max_relative = 1e-12);
+        assert_relative_eq!(KELVIN_TO_MHZ, 20_836.619_12, max_relative = 1e-9);
+        assert_relative_eq!(REDUCED_PLANCK, PLANCK / (2.0 * PI), max_relative = 1e-9);
+        assert_relative_eq!(PARSEC, 3.085_677_581_491_367e16, max_relative = 1e-15);
+    }
+
+    #[test]
+    fn test_codata2014() {
+        assert_relative_eq!(
+            CODATA2014.second_radiation_constant(),
+            1.438_777_36e-2,
+            max_relative = 1e-8
+        );
+        assert_relative_eq!(
+            CODATA2014.hbar,
+            CODATA2014.h / (2.0 * PI),
+            max_relative = 1e-8
+        );
+        assert_eq!(Codata::default(), CODATA2018);
+    }
+
+    #[test]
+    fn test_quantities() {
+        use crate::units::length::kilometer;
+        use crate::units::velocity::kilometer_per_second;
+
+        assert_relative_eq!(
+            quantities::SPEED_OF_LIGHT.get::<kilometer_per_second>(),
+            299_792.458
+        );
+        assert_relative_eq!(
+            quantities::ASTRONOMICAL_UNIT.get::<kilometer>(),
+            149_597_870.7
+        );
+    }
+}
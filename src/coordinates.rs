@@ -0,0 +1,259 @@
+//! Celestial coordinates, observatory locations and velocity reference frames, a subset of
+//! `astropy.coordinates`.
+//!
+//! Sky positions are ICRS right ascension and declination. The J2000 mean equator and
+//! equinox differs from the ICRS by about 20 mas, which is ignored throughout.
+
+mod ephemeris;
+pub mod frames;
+
+pub use frames::{Observation, VelocityFrame};
+
+use crate::units::angle::radian;
+use crate::units::f64::{Angle, Length};
+use crate::units::length::meter;
+
+/// Rotation from ICRS to Galactic Cartesian coordinates (Hipparcos, ESA 1997).
+const ICRS_TO_GALACTIC: [[f64; 3]; 3] = [
+    [
+        -0.054_875_560_416_215_4,
+        -0.873_437_090_234_885,
+        -0.483_835_015_548_713_2,
+    ],
+    [
+        0.494_109_427_875_583_7,
+        -0.444_829_629_960_011_2,
+        0.746_982_244_497_219,
+    ],
+    [
+        -0.867_666_149_019_004_7,
+        -0.198_076_373_431_201_5,
+        0.455_983_776_175_066_9,
+    ],
+];
+
+/// WGS84 equatorial radius (m).
+const WGS84_RADIUS: f64 = 6_378_137.0;
+
+/// WGS84 flattening.
+const WGS84_FLATTENING: f64 = 1.0 / 298.257_223_563;
+
+/// A position on the sky in the ICRS.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkyCoord {
+    ra: Angle,
+    dec: Angle,
+}
+
+impl SkyCoord {
+    pub fn new(ra: Angle, dec: Angle) -> Self {
+        Self { ra, dec }
+    }
+
+    /// Create a position from Galactic longitude and latitude.
+    pub fn from_galactic(l: Angle, b: Angle) -> Self {
+        let galactic = unit_vector(l.get::<radian>(), b.get::<radian>());
+        Self::from_unit_vector(transpose(&ICRS_TO_GALACTIC).apply(galactic))
+    }
+
+    pub fn ra(&self) -> Angle {
+        self.ra
+    }
+
+    pub fn dec(&self) -> Angle {
+        self.dec
+    }
+
+    /// Galactic longitude and latitude.
+    pub fn galactic(&self) -> (Angle, Angle) {
+        let (l, b) = spherical(ICRS_TO_GALACTIC.apply(self.unit_vector()));
+        (Angle::new::<radian>(l), Angle::new::<radian>(b))
+    }
+
+    /// Angular distance to `other`.
+    pub fn separation(&self, other: &Self) -> Angle {
+        let (a, b) = (self.unit_vector(), other.unit_vector());
+        let cross = cross(a, b);
+        Angle::new::<radian>(dot(cross, cross).sqrt().atan2(dot(a, b)))
+    }
+
+    /// Unit vector towards the position in ICRS Cartesian coordinates.
+    pub(crate) fn unit_vector(&self) -> [f64; 3] {
+        unit_vector(self.ra.get::<radian>(), self.dec.get::<radian>())
+    }
+
+    pub(crate) fn from_unit_vector(v: [f64; 3]) -> Self {
+        let (ra, dec) = spherical(v);
+        Self::new(Angle::new::<radian>(ra), Angle::new::<radian>(dec))
+    }
+}
+
+/// A position on the Earth, as geodetic coordinates on the WGS84 ellipsoid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EarthLocation {
+    lon: Angle,
+    lat: Angle,
+    height: Length,
+}
+
+impl EarthLocation {
+    /// Create a location from its east longitude, geodetic latitude and ellipsoidal height.
+    pub fn from_geodetic(lon: Angle, lat: Angle, height: Length) -> Self {
+        Self { lon, lat, height }
+    }
+
+    pub fn lon(&self) -> Angle {
+        self.lon
+    }
+
+    pub fn lat(&self) -> Angle {
+        self.lat
+    }
+
+    pub fn height(&self) -> Length {
+        self.height
+    }
+
+    /// Earth-fixed geocentric Cartesian coordinates (x, y, z).
+    pub fn geocentric(&self) -> [Length; 3] {
+        let (sin_lon, cos_lon) = self.lon.get::<radian>().sin_cos();
+        let (sin_lat, cos_lat) = self.lat.get::<radian>().sin_cos();
+        let h = self.height.get::<meter>();
+        let e2 = WGS84_FLATTENING * (2.0 - WGS84_FLATTENING);
+        let n = WGS84_RADIUS / (1.0 - e2 * sin_lat * sin_lat).sqrt();
+        [
+            Length::new::<meter>((n + h) * cos_lat * cos_lon),
+            Length::new::<meter>((n + h) * cos_lat * sin_lon),
+            Length::new::<meter>((n * (1.0 - e2) + h) * sin_lat),
+        ]
+    }
+}
+
+pub(crate) type Matrix3 = [[f64; 3]; 3];
+
+pub(crate) trait Rotation {
+    fn apply(&self, v: [f64; 3]) -> [f64; 3];
+}
+
+impl Rotation for Matrix3 {
+    fn apply(&self, v: [f64; 3]) -> [f64; 3] {
+        self.map(|row| dot(row, v))
+    }
+}
+
+pub(crate) fn transpose(m: &Matrix3) -> Matrix3 {
+    std::array::from_fn(|i| std::array::from_fn(|j| m[j][i]))
+}
+
+pub(crate) fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn unit_vector(lon: f64, lat: f64) -> [f64; 3] {
+    let (sin_lon, cos_lon) = lon.sin_cos();
+    let (sin_lat, cos_lat) = lat.sin_cos();
+    [cos_lat * cos_lon, cos_lat * sin_lon, sin_lat]
+}
+
+/// Longitude in `[0, 2π)` and latitude of a Cartesian vector.
+fn spherical(v: [f64; 3]) -> (f64, f64) {
+    let lon = v[1].atan2(v[0]).rem_euclid(std::f64::consts::TAU);
+    (lon, v[2].atan2(v[0].hypot(v[1])))
+}
+
+/// IAU 1976 precession matrix from the J2000 mean equator and equinox to that of date, for
+/// `t` Julian centuries of TT since J2000.0.
+pub(crate) fn precession_matrix(t: f64) -> Matrix3 {
+    let arcsec = |x: f64| x * std::f64::consts::PI / 648_000.0;
+    let zeta = arcsec(t * (2_306.218_1 + t * (0.301_88 + t * 0.017_998)));
+    let z = arcsec(t * (2_306.218_1 + t * (1.094_68 + t * 0.018_203)));
+    let theta = arcsec(t * (2_004.310_9 - t * (0.426_65 + t * 0.041_833)));
+    multiply(&rotate_z(-z), &multiply(&rotate_y(theta), &rotate_z(-zeta)))
+}
+
+/// Rotation of the coordinate frame by `angle` about the z axis.
+pub(crate) fn rotate_z(angle: f64) -> Matrix3 {
+    let (s, c) = angle.sin_cos();
+    [[c, s, 0.0], [-s, c, 0.0], [0.0, 0.0, 1.0]]
+}
+
+/// Rotation of the coordinate frame by `angle` about the y axis.
+pub(crate) fn rotate_y(angle: f64) -> Matrix3 {
+    let (s, c) = angle.sin_cos();
+    [[c, 0.0, -s], [0.0, 1.0, 0.0], [s, 0.0, c]]
+}
+
+pub(crate) fn multiply(a: &Matrix3, b: &Matrix3) -> Matrix3 {
+    std::array::from_fn(|i| std::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::angle::{degree, hour_angle};
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_galactic() {
+        // The Galactic centre and north pole.
+        let centre = SkyCoord::new(
+            Angle::new::<degree>(266.404_996_1),
+            Angle::new::<degree>(-28.936_172_4),
+        );
+        let (l, b) = centre.galactic();
+        assert_relative_eq!(l.get::<degree>().rem_euclid(360.0), 0.0, epsilon = 1e-4);
+        assert_relative_eq!(b.get::<degree>(), 0.0, epsilon = 1e-4);
+        let pole = SkyCoord::from_galactic(Angle::new::<degree>(0.0), Angle::new::<degree>(90.0));
+        assert_relative_eq!(pole.ra().get::<degree>(), 192.859_48, epsilon = 1e-4);
+        assert_relative_eq!(pole.dec().get::<degree>(), 27.128_25, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn test_separation() {
+        let a = SkyCoord::new(Angle::new::<hour_angle>(6.0), Angle::new::<degree>(0.0));
+        let b = SkyCoord::new(Angle::new::<hour_angle>(6.0), Angle::new::<degree>(30.0));
+        assert_relative_eq!(a.separation(&b).get::<degree>(), 30.0, epsilon = 1e-12);
+        assert_relative_eq!(a.separation(&a).get::<degree>(), 0.0);
+    }
+
+    #[test]
+    fn test_geocentric() {
+        let pole = EarthLocation::from_geodetic(
+            Angle::new::<degree>(0.0),
+            Angle::new::<degree>(90.0),
+            Length::new::<meter>(0.0),
+        )
+        .geocentric();
+        assert_relative_eq!(pole[2].get::<meter>(), 6_356_752.314, epsilon = 1e-3);
+        let equator = EarthLocation::from_geodetic(
+            Angle::new::<degree>(90.0),
+            Angle::new::<degree>(0.0),
+            Length::new::<meter>(100.0),
+        )
+        .geocentric();
+        assert_relative_eq!(
+            equator[1].get::<meter>(),
+            WGS84_RADIUS + 100.0,
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn test_precession() {
+        // The J2000 pole moves by 2004.3109" per century along RA 0h of date.
+        let m = precession_matrix(1.0);
+        let pole = m.apply([0.0, 0.0, 1.0]);
+        let expected: f64 = (2_004.310_9 - 0.426_65 - 0.041_833) / 206_264.806;
+        assert_relative_eq!(dot(pole, [0.0, 0.0, 1.0]), expected.cos(), epsilon = 1e-12);
+        let back = transpose(&m).apply(pole);
+        assert_relative_eq!(back[2], 1.0, epsilon = 1e-15);
+    }
+}
@@ -0,0 +1,347 @@
+//! Analytic velocity of the Earth, after Stumpff (1980, A&AS 41, 1) `BARVEL`.
+//!
+//! The series gives the heliocentric and barycentric velocity of the Earth referred to the
+//! mean equator and equinox of date with an accuracy of about 0.4 m s⁻¹ over 1800–2100. It
+//! includes the elliptic motion of the Earth–Moon barycentre, its short-period and secular
+//! planetary perturbations, the motion of the Earth about the Earth–Moon barycentre, and the
+//! reflex motion of the Sun caused by the four giant planets.
+
+use std::f64::consts::TAU;
+
+use crate::constants::ASTRONOMICAL_UNIT;
+
+/// Julian date of the B1900.0 epoch the series are referred to.
+const EPOCH: f64 = 2_415_020.0;
+
+/// Fast-changing mean elements: EMB longitude and anomaly, lunar longitude and argument of
+/// latitude, and the mean longitudes of Jupiter, Saturn, Uranus and Neptune.
+const DCFEL: [[f64; 3]; 8] = [
+    [1.740_035_3, 6.283_319_509_909_1e2, 5.2796e-6],
+    [6.256_583_6, 6.283_019_457_267_4e2, -2.6180e-6],
+    [4.719_966_6, 8.399_709_144_925_4e3, -1.9780e-5],
+    [1.963_650_5e-1, 8.433_466_291_172_0e3, -5.6044e-5],
+    [4.154_733_9, 5.299_346_676_499_7e1, 5.8845e-6],
+    [4.652_422_3, 2.135_427_591_121_3e1, 5.6797e-6],
+    [4.262_048_6, 7.502_534_219_765_6, 5.5317e-6],
+    [1.474_069_4, 3.837_733_190_919_3, 5.6093e-6],
+];
+
+/// Mean obliquity of the ecliptic.
+const DCEPS: [f64; 3] = [4.093_198e-1, -2.271_110e-4, -2.860_401e-8];
+
+/// Slowly-changing elements: EMB eccentricity, then the longitudes of perihelion, longitudes
+/// of node, eccentricities and inclinations of Jupiter, Saturn, Uranus and Neptune.
+const CCSEL: [[f64; 3]; 17] = [
+    [1.675_104e-2, -4.179_579e-5, -1.260_516e-7],
+    [2.220_221e-1, 2.809_917e-2, 1.852_532e-5],
+    [1.589_963, 3.418_075e-2, 1.430_200e-5],
+    [2.994_089, 2.590_824e-2, 4.155_840e-6],
+    [8.155_457e-1, 2.486_352e-2, 6.836_840e-6],
+    [1.735_614, 1.763_719e-2, 6.370_440e-6],
+    [1.968_564, 1.524_020e-2, -2.517_152e-6],
+    [1.282_417, 8.703_393e-3, 2.289_292e-5],
+    [2.280_820, 1.918_010e-2, 4.484_520e-6],
+    [4.833_473e-2, 1.641_773e-4, -4.654_200e-7],
+    [5.589_232e-2, -3.455_092e-4, -7.388_560e-7],
+    [4.634_443e-2, -2.658_234e-5, 7.757_000e-8],
+    [8.997_041e-3, 6.329_728e-6, -1.939_256e-9],
+    [2.284_178e-2, -9.941_590e-5, 6.787_400e-8],
+    [4.350_267e-2, -6.839_749e-5, -2.714_956e-7],
+    [1.348_204e-2, 1.091_504e-5, 6.903_760e-7],
+    [3.106_570e-2, -1.665_665e-4, -1.590_188e-7],
+];
+
+/// Arguments of the short-period perturbations of the EMB.
+const DCARGS: [[f64; 2]; 15] = [
+    [5.097_422_2, -7.860_419_545_465_2e2],
+    [3.958_496_2, -5.753_384_809_467_4e2],
+    [1.633_807_0, -1.150_676_961_893_5e3],
+    [2.548_711_1, -3.930_209_772_732_6e2],
+    [4.925_551_4, -5.884_926_566_534_8e2],
+    [1.336_346_3, -5.507_609_860_930_3e2],
+    [1.607_205_3, -5.223_750_161_667_4e2],
+    [1.362_948_0, -1.179_062_931_819_8e3],
+    [5.565_701_4, -1.097_713_497_113_5e3],
+    [5.070_820_5, -1.577_400_088_197_8e2],
+    [3.931_894_4, 5.296_346_478_000_0e1],
+    [4.898_949_7, 3.980_928_907_325_8e1],
+    [1.309_744_6, 7.754_095_963_370_8e1],
+    [3.514_714_1, 7.961_857_814_651_7e1],
+    [3.541_315_8, -5.486_833_675_802_2e2],
+];
+
+/// Amplitudes of the short-period perturbations: cosine and sine terms in longitude and
+/// radius, and the rate of the argument in rad s⁻¹.
+const CCAMPS: [[f64; 5]; 15] = [
+    [
+        -2.279_594e-5,
+        1.407_414e-5,
+        8.273_188e-6,
+        1.340_565e-5,
+        -2.490_817e-7,
+    ],
+    [
+        -3.494_537e-5,
+        2.860_401e-7,
+        1.289_448e-7,
+        1.627_237e-5,
+        -1.823_138e-7,
+    ],
+    [
+        6.593_466e-7,
+        1.322_572e-5,
+        9.258_695e-6,
+        -4.674_248e-7,
+        -3.646_275e-7,
+    ],
+    [
+        1.140_767e-5,
+        -2.049_792e-5,
+        -4.747_930e-6,
+        -2.638_763e-6,
+        -1.245_408e-7,
+    ],
+    [
+        9.516_893e-6,
+        -2.748_894e-6,
+        -1.319_381e-6,
+        -4.549_908e-6,
+        -1.864_821e-7,
+    ],
+    [
+        7.310_990e-6,
+        -1.924_710e-6,
+        -8.772_849e-7,
+        -3.334_143e-6,
+        -1.745_256e-7,
+    ],
+    [
+        -2.603_449e-6,
+        7.359_472e-6,
+        3.168_357e-6,
+        1.119_056e-6,
+        -1.655_307e-7,
+    ],
+    [
+        -3.228_859e-6,
+        1.308_997e-7,
+        1.013_137e-7,
+        2.403_899e-6,
+        -3.736_225e-7,
+    ],
+    [
+        3.442_177e-7,
+        2.671_323e-6,
+        1.832_858e-6,
+        -2.394_688e-7,
+        -3.478_444e-7,
+    ],
+    [
+        8.702_406e-6,
+        -8.421_214e-6,
+        -1.372_341e-6,
+        -1.455_234e-6,
+        -4.998_479e-8,
+    ],
+    [
+        -1.488_378e-6,
+        -1.251_789e-5,
+        5.226_868e-7,
+        -2.049_301e-7,
+        0.0,
+    ],
+    [
+        -8.043_059e-6,
+        -2.991_300e-6,
+        1.473_654e-7,
+        -3.154_542e-7,
+        0.0,
+    ],
+    [3.699_128e-6, -3.316_126e-6, 2.901_257e-7, 3.407_826e-7, 0.0],
+    [2.550_120e-6, -1.241_123e-6, 9.901_116e-8, 2.210_482e-7, 0.0],
+    [-6.351_059e-7, 2.341_650e-6, 1.061_492e-6, 2.878_231e-7, 0.0],
+];
+
+/// Time-dependent part of the third secular perturbation in longitude.
+const CCSEC3: f64 = -7.757_020e-8;
+
+/// Secular perturbations in longitude: amplitude, phase and rate.
+const CCSEC: [[f64; 3]; 4] = [
+    [1.289_600e-6, 5.550_147e-1, 2.076_942],
+    [3.102_810e-5, 4.035_027, 3.525_565e-1],
+    [9.124_190e-6, 9.990_265e-1, 2.622_706],
+    [9.793_240e-7, 5.508_259, 1.559_103e1],
+];
+
+/// Sidereal rates in longitude and mean anomaly (rad s⁻¹).
+const DCSLD: f64 = 1.990_987e-7;
+const CCSGD: f64 = 1.990_969e-7;
+
+/// Lunar contribution: Earth–EMB distance (au), lunar mean motion (rad s⁻¹) and the lunar
+/// inclination times the mean motion.
+const CCKM: f64 = 3.122_140e-5;
+const CCMLD: f64 = 2.661_699e-6;
+const CCFDI: f64 = 2.399_485e-7;
+
+/// Arguments of the perturbations of the lunar motion.
+const DCARGM: [[f64; 2]; 3] = [
+    [5.167_983_0, 8.328_691_109_527_5e3],
+    [5.491_315_0, -7.214_063_283_810_0e3],
+    [5.959_853_0, 1.554_275_438_968_5e4],
+];
+
+/// Amplitudes of the lunar perturbations in longitude, its rate, parallax and its rate.
+const CCAMPM: [[f64; 4]; 3] = [
+    [1.097_594e-1, 2.896_773e-7, 5.450_474e-2, 1.438_491e-7],
+    [-2.223_581e-2, 5.083_103e-8, 1.002_548e-2, -2.291_823e-8],
+    [1.148_966e-2, 5.658_888e-8, 8.249_439e-3, 4.063_015e-8],
+];
+
+/// Reflex velocity of the Sun (au s⁻¹) due to Jupiter, Saturn, Uranus and Neptune.
+const CCPAMV: [f64; 4] = [8.326_827e-11, 1.843_484e-11, 1.988_712e-12, 1.881_276e-12];
+
+/// One minus the mass of the Earth–Moon system in solar masses.
+const DC1MME: f64 = 0.999_996_96;
+
+/// Motion of the Earth at one instant, from [`earth_motion`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct EarthMotion {
+    /// Heliocentric velocity (m s⁻¹), referred to the mean equator and equinox of date
+    pub heliocentric: [f64; 3],
+    /// Barycentric velocity (m s⁻¹), referred to the mean equator and equinox of date
+    pub barycentric: [f64; 3],
+    /// Distance of the Earth–Moon barycentre from the Sun (m)
+    pub sun_distance: f64,
+}
+
+/// Motion of the Earth at the Terrestrial Time Julian date `jd_tt`.
+pub(crate) fn earth_motion(jd_tt: f64) -> EarthMotion {
+    let dt = (jd_tt - EPOCH) / 36_525.0;
+    let poly = |c: &[f64]| (c[0] + dt * c[1] + dt * dt * c.get(2).unwrap_or(&0.0)) % TAU;
+
+    let dml = poly(&DCFEL[0]);
+    let forbel: Vec<f64> = DCFEL[1..].iter().map(|c| poly(c)).collect();
+    let g = forbel[0];
+    let deps = poly(&DCEPS);
+    let sorbel: Vec<f64> = CCSEL.iter().map(|c| poly(c)).collect();
+    let e = sorbel[0];
+
+    // Secular and short-period perturbations of the Earth–Moon barycentre.
+    let sn: Vec<f64> = CCSEC.iter().map(|c| poly(&c[1..]).sin()).collect();
+    let mut pertl = CCSEC.iter().zip(&sn).map(|(c, s)| c[0] * s).sum::<f64>() + dt * CCSEC3 * sn[2];
+    let (mut pertld, mut pertr, mut pertrd) = (0.0, 0.0, 0.0);
+    for (arg, amp) in DCARGS.iter().zip(&CCAMPS) {
+        let (sina, cosa) = poly(arg).sin_cos();
+        pertl += amp[0] * cosa + amp[1] * sina;
+        pertr += amp[2] * cosa + amp[3] * sina;
+        pertld += (amp[1] * cosa - amp[0] * sina) * amp[4];
+        pertrd += (amp[3] * cosa - amp[2] * sina) * amp[4];
+    }
+
+    // Elliptic motion of the Earth–Moon barycentre.
+    let phi = (e * e / 4.0)
+        * ((8.0 / e - e) * g.sin() + 5.0 * (2.0 * g).sin() + (13.0 / 3.0) * e * (3.0 * g).sin());
+    let (sinf, cosf) = (g + phi).sin_cos();
+    let dpsi = (1.0 - e * e) / (1.0 + e * cosf);
+    let phid = 2.0 * e * CCSGD * ((1.0 + 1.5 * e * e) * cosf + e * (1.25 - 0.5 * sinf * sinf));
+    let psid = CCSGD * e * sinf / (1.0 - e * e).sqrt();
+
+    let d1pdro = 1.0 + pertr;
+    let drd = d1pdro * (psid + dpsi * pertrd);
+    let drld = d1pdro * dpsi * (DCSLD + phid + pertld);
+    let (dsinls, dcosls) = ((dml + phi + pertl) % TAU).sin_cos();
+    let mut dxhd = drd * dcosls - drld * dsinls;
+    let mut dyhd = drd * dsinls + drld * dcosls;
+
+    // Motion of the Earth about the Earth–Moon barycentre.
+    let (mut pertl, mut pertld, mut pertp, mut pertpd) = (0.0, 0.0, 0.0, 0.0);
+    for (arg, amp) in DCARGM.iter().zip(&CCAMPM) {
+        let (sina, cosa) = poly(arg).sin_cos();
+        pertl += amp[0] * sina;
+        pertld += amp[1] * cosa;
+        pertp += amp[2] * cosa;
+        pertpd -= amp[3] * sina;
+    }
+    let (sinlm, coslm) = (forbel[1] + pertl).sin_cos();
+    let sigma = CCKM / (1.0 + pertp);
+    let a = sigma * (CCMLD + pertld);
+    let b = sigma * pertpd;
+    dxhd += a * sinlm + b * coslm;
+    dyhd += -a * coslm + b * sinlm;
+    let dzhd = -sigma * CCFDI * forbel[2].cos();
+
+    // Reflex motion of the Sun about the barycentre.
+    let mut dxbd = dxhd * DC1MME;
+    let mut dybd = dyhd * DC1MME;
+    let mut dzbd = dzhd * DC1MME;
+    for (k, amp) in CCPAMV.iter().enumerate() {
+        let plon = forbel[k + 3];
+        let pomg = sorbel[k + 1];
+        let pecc = sorbel[k + 9];
+        let tl = (plon + 2.0 * pecc * (plon - pomg).sin()) % TAU;
+        dxbd += amp * (tl.sin() + pecc * pomg.sin());
+        dybd -= amp * (tl.cos() + pecc * pomg.cos());
+        dzbd -= amp * sorbel[k + 13] * (plon - sorbel[k + 5]).cos();
+    }
+
+    // Rotate from the ecliptic to the mean equator of date.
+    let (sineps, coseps) = deps.sin_cos();
+    let equatorial = |x: f64, y: f64, z: f64| {
+        [
+            ASTRONOMICAL_UNIT * x,
+            ASTRONOMICAL_UNIT * (coseps * y - sineps * z),
+            ASTRONOMICAL_UNIT * (sineps * y + coseps * z),
+        ]
+    };
+    EarthMotion {
+        heliocentric: equatorial(dxhd, dyhd, dzhd),
+        barycentric: equatorial(dxbd, dybd, dzbd),
+        sun_distance: ASTRONOMICAL_UNIT * dpsi * d1pdro,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinates::{Rotation, precession_matrix, transpose};
+    use crate::time::{DAYS_PER_CENTURY, J2000};
+    use approx::assert_relative_eq;
+
+    #[test]
+    #[allow(clippy::excessive_precision)]
+    fn test_against_erfa() {
+        // ERFA's `epv00` test case, in au per day in the BCRS.
+        let jd = 2_400_000.5 + 53_411.525_011_61;
+        let helio = [
+            -0.109_189_182_414_731_384_6e-1,
+            -0.124_718_726_844_084_500_8e-1,
+            -0.540_756_941_806_503_906_1e-2,
+        ];
+        let bary = [
+            -0.109_187_426_811_682_329_5e-1,
+            -0.124_652_546_173_286_153_8e-1,
+            -0.540_477_318_096_623_127_9e-2,
+        ];
+        let to_j2000 = transpose(&precession_matrix((jd - J2000) / DAYS_PER_CENTURY));
+        let motion = earth_motion(jd);
+        let (h, b) = (motion.heliocentric, motion.barycentric);
+        let scale = ASTRONOMICAL_UNIT / 86_400.0;
+        for (computed, expected) in [(h, helio), (b, bary)] {
+            let computed = to_j2000.apply(computed);
+            for i in 0..3 {
+                assert_relative_eq!(computed[i], expected[i] * scale, epsilon = 1.0);
+            }
+        }
+        // Distance of ERFA's heliocentric position of the Earth, in au. The Earth–Moon
+        // barycentre is within 3.2e-5 au of it.
+        let distance = f64::hypot(
+            f64::hypot(-0.775_723_880_929_770_681_3, 0.559_805_224_136_334_059_6),
+            0.242_699_846_648_168_699_3,
+        );
+        assert_relative_eq!(
+            motion.sun_distance / ASTRONOMICAL_UNIT,
+            distance,
+            epsilon = 1e-4
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! Velocity reference frames for spectral axes, as `SPECSYS` in FITS and
+//! `astropy.coordinates.SpectralCoord`.
+//!
+//! Each frame is represented by the velocity of its origin relative to the solar-system
+//! barycentre. Moving a spectrum between frames Doppler-shifts it by the difference of the
+//! two velocities projected onto the line of sight, which depends on the target position and,
+//! for the Earth-bound frames, on the observatory and the time of observation.
+
+use std::f64::consts::TAU;
+
+use super::ephemeris::earth_motion;
+use super::{
+    EarthLocation, ICRS_TO_GALACTIC, Rotation, SkyCoord, dot, precession_matrix, rotate_z,
+    transpose,
+};
+use crate::constants::SPEED_OF_LIGHT;
+use crate::time::{J2000, Time};
+use crate::units::angle::{arcsecond, degree, hour_angle, radian};
+use crate::units::f64::{Angle, Velocity};
+use crate::units::length::meter;
+use crate::units::velocity::meter_per_second;
+
+/// Angular velocity of the Earth's rotation (rad s⁻¹).
+const EARTH_ROTATION_RATE: f64 = 7.292_115_146_7e-5;
+
+/// Heliocentric and geocentric gravitational constants (m³ s⁻²), IAU 2015 nominal values.
+const GM_SUN: f64 = 1.327_124_4e20;
+const GM_EARTH: f64 = 3.986_004e14;
+
+/// Speed of the Sun relative to the kinematic LSR (m s⁻¹).
+const LSRK_SPEED: f64 = 20_000.0;
+
+/// Solar motion relative to the dynamical LSR (U, V, W) in m s⁻¹ (Delhaye 1965).
+const LSRD_SOLAR_MOTION: [f64; 3] = [9_000.0, 12_000.0, 7_000.0];
+
+/// Solar motion relative to the Galactic centre (U, V, W) in m s⁻¹, `astropy`'s
+/// `Galactocentric` default (Drimmel & Poggio 2018).
+const GALACTOCENTRIC_SOLAR_MOTION: [f64; 3] = [12_900.0, 245_600.0, 7_780.0];
+
+/// Velocity reference frame of a spectral axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VelocityFrame {
+    /// At rest with the observatory
+    Topocentric,
+    /// At rest with the centre of the Earth
+    Geocentric,
+    /// At rest with the solar-system barycentre
+    Barycentric,
+    /// At rest with the centre of the Sun
+    Heliocentric,
+    /// Kinematic local standard of rest: the Sun moves at 20 km s⁻¹ towards RA 18h, Dec +30°
+    /// (B1900)
+    Lsrk,
+    /// Dynamical local standard of rest: the Sun moves at (U, V, W) = (9, 12, 7) km s⁻¹
+    Lsrd,
+    /// At rest with the Galactic centre
+    Galactocentric,
+}
+
+impl VelocityFrame {
+    /// FITS `SPECSYS` keyword value.
+    pub fn specsys(&self) -> &'static str {
+        match self {
+            Self::Topocentric => "TOPOCENT",
+            Self::Geocentric => "GEOCENTR",
+            Self::Barycentric => "BARYCENT",
+            Self::Heliocentric => "HELIOCEN",
+            Self::Lsrk => "LSRK",
+            Self::Lsrd => "LSRD",
+            Self::Galactocentric => "GALACTOC",
+        }
+    }
+
+    /// Parse a FITS `SPECSYS` keyword value. The AIPS-style `LSR` and `OPTI-HEL` are accepted
+    /// as aliases of LSRK and heliocentric.
+    pub fn from_specsys(value: &str) -> Option<Self> {
+        Some(match value.trim().to_ascii_uppercase().as_str() {
+            "TOPOCENT" => Self::Topocentric,
+            "GEOCENTR" => Self::Geocentric,
+            "BARYCENT" => Self::Barycentric,
+            "HELIOCEN" | "OPTI-HEL" => Self::Heliocentric,
+            "LSRK" | "LSR" => Self::Lsrk,
+            "LSRD" => Self::Lsrd,
+            "GALACTOC" => Self::Galactocentric,
+            _ => return None,
+        })
+    }
+}
+
+/// The geometry needed to transform between velocity frames: where the telescope pointed,
+/// from where, and when.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Observation {
+    pub target: SkyCoord,
+    pub location: EarthLocation,
+    pub time: Time,
+}
+
+impl Observation {
+    pub fn new(target: SkyCoord, location: EarthLocation, time: Time) -> Self {
+        Self {
+            target,
+            location,
+            time,
+        }
+    }
+
+    /// Velocity of the origin of `frame` relative to the solar-system barycentre, projected
+    /// onto the line of sight. Positive values are towards the target.
+    pub fn frame_velocity(&self, frame: VelocityFrame) -> Velocity {
+        Velocity::new::<meter_per_second>(dot(
+            self.frame_velocity_vector(frame),
+            self.target.unit_vector(),
+        ))
+    }
+
+    /// Correction to add to a radial velocity measured in `from` to obtain the radial
+    /// velocity in `to`. This is the kinematic change of frame; see
+    /// [`Self::barycentric_correction`] for the relativistic correction to the barycentre.
+    pub fn velocity_correction(&self, from: VelocityFrame, to: VelocityFrame) -> Velocity {
+        self.frame_velocity(from) - self.frame_velocity(to)
+    }
+
+    /// Barycentric correction of a topocentric radial velocity, as `astropy`'s
+    /// `SkyCoord.radial_velocity_correction`: besides the observer's velocity, it includes
+    /// their Lorentz factor and the gravitational redshift of the Sun and the Earth at the
+    /// observatory. The Moon and planets, each below 1 mm s⁻¹, are left out.
+    pub fn barycentric_correction(&self) -> Velocity {
+        let u = self.frame_velocity_vector(VelocityFrame::Topocentric);
+        let beta = u.map(|x| x / SPEED_OF_LIGHT);
+        let gamma = 1.0 / (1.0 - dot(beta, beta)).sqrt();
+        let geocentric = self.location.geocentric().map(|x| x.get::<meter>());
+        let potential = GM_SUN / earth_motion(self.time.jd_tt()).sun_distance
+            + GM_EARTH / dot(geocentric, geocentric).sqrt();
+        let doppler = gamma * (1.0 + dot(beta, self.target.unit_vector()))
+            / (1.0 - potential / (SPEED_OF_LIGHT * SPEED_OF_LIGHT));
+        Velocity::new::<meter_per_second>(SPEED_OF_LIGHT * (doppler - 1.0))
+    }
+
+    /// Ratio of the frequency of a photon observed in `to` to its frequency in `from`.
+    pub fn doppler_factor(&self, from: VelocityFrame, to: VelocityFrame) -> f64 {
+        let beta = self.velocity_correction(from, to).get::<meter_per_second>() / SPEED_OF_LIGHT;
+        ((1.0 - beta) / (1.0 + beta)).sqrt()
+    }
+
+    /// Velocity of the origin of `frame` relative to the barycentre, in ICRS Cartesian
+    /// coordinates (m s⁻¹).
+    fn frame_velocity_vector(&self, frame: VelocityFrame) -> [f64; 3] {
+        let to_j2000 = transpose(&precession_matrix(self.time.centuries_since_j2000()));
+        let earth = || earth_motion(self.time.jd_tt());
+        match frame {
+            VelocityFrame::Barycentric => [0.0; 3],
+            VelocityFrame::Heliocentric => {
+                let earth = earth();
+                to_j2000.apply(std::array::from_fn(|i| {
+                    earth.barycentric[i] - earth.heliocentric[i]
+                }))
+            }
+            VelocityFrame::Geocentric => to_j2000.apply(earth().barycentric),
+            VelocityFrame::Topocentric => {
+                let bary = earth().barycentric;
+                let rotation = self.rotation_velocity();
+                to_j2000.apply(std::array::from_fn(|i| bary[i] + rotation[i]))
+            }
+            VelocityFrame::Lsrk => {
+                let apex = SkyCoord::new(
+                    Angle::new::<hour_angle>(18.0 + 3.0 / 60.0 + 50.29 / 3_600.0),
+                    Angle::new::<degree>(30.0) + Angle::new::<arcsecond>(16.8),
+                );
+                apex.unit_vector().map(|x| -LSRK_SPEED * x)
+            }
+            VelocityFrame::Lsrd => galactic_to_icrs(LSRD_SOLAR_MOTION.map(|x| -x)),
+            VelocityFrame::Galactocentric => {
+                galactic_to_icrs(GALACTOCENTRIC_SOLAR_MOTION.map(|x| -x))
+            }
+        }
+    }
+
+    /// Velocity of the observatory due to the Earth's rotation, referred to the mean equator
+    /// and equinox of date (m s⁻¹).
+    fn rotation_velocity(&self) -> [f64; 3] {
+        let position = self.location.geocentric().map(|x| x.get::<meter>());
+        let gmst = greenwich_mean_sidereal_time(&self.time).get::<radian>();
+        let p = rotate_z(-gmst).apply(position);
+        [-EARTH_ROTATION_RATE * p[1], EARTH_ROTATION_RATE * p[0], 0.0]
+    }
+}
+
+/// Greenwich mean sidereal time (IAU 2006), with UT1 approximated by UTC.
+pub fn greenwich_mean_sidereal_time(time: &Time) -> Angle {
+    let du = time.jd_utc() - J2000;
+    let era = TAU * (0.779_057_273_264 + 0.002_737_811_911_354_48 * du + du.fract());
+    let t = time.centuries_since_j2000();
+    let precession = 0.014_506 + t * (4_612.156_534 + t * (1.391_581_7 - t * 4.4e-7));
+    Angle::new::<radian>((era + precession * std::f64::consts::PI / 648_000.0).rem_euclid(TAU))
+}
+
+fn galactic_to_icrs(v: [f64; 3]) -> [f64; 3] {
+    transpose(&ICRS_TO_GALACTIC).apply(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::f64::Length;
+    use crate::units::velocity::kilometer_per_second;
+    use approx::assert_relative_eq;
+
+    fn keck() -> EarthLocation {
+        EarthLocation::from_geodetic(
+            Angle::new::<degree>(-155.4783),
+            Angle::new::<degree>(19.8283),
+            Length::new::<meter>(4_160.0),
+        )
+    }
+
+    #[test]
+    fn test_astropy_correction() {
+        // astropy's `radial_velocity_correction` documentation example, quoted to ~1 m s⁻¹.
+        let obs = Observation::new(
+            SkyCoord::new(
+                Angle::new::<degree>(4.88375),
+                Angle::new::<degree>(35.043_638_9),
+            ),
+            keck(),
+            Time::from_calendar_utc(2016, 6, 4, 0, 0, 0.0),
+        );
+        assert_relative_eq!(
+            obs.barycentric_correction().get::<meter_per_second>(),
+            20_077.135,
+            epsilon = 1.0
+        );
+    }
+
+    #[test]
+    fn test_lsr_frames() {
+        let time = Time::from_calendar_utc(2020, 1, 1, 0, 0, 0.0);
+        let apex = SkyCoord::new(
+            Angle::new::<degree>(270.959_54),
+            Angle::new::<degree>(30.004_67),
+        );
+        let obs = Observation::new(apex, keck(), time);
+        let v = obs.velocity_correction(VelocityFrame::Barycentric, VelocityFrame::Lsrk);
+        assert_relative_eq!(v.get::<kilometer_per_second>(), 20.0, epsilon = 1e-6);
+
+        // Towards the Galactic centre only U contributes; towards l = 90° only V.
+        let centre = |l: f64| {
+            Observation::new(
+                SkyCoord::from_galactic(Angle::new::<degree>(l), Angle::new::<degree>(0.0)),
+                keck(),
+                time,
+            )
+        };
+        let v = centre(0.0).velocity_correction(VelocityFrame::Barycentric, VelocityFrame::Lsrd);
+        assert_relative_eq!(v.get::<kilometer_per_second>(), 9.0, epsilon = 1e-9);
+        let v = centre(90.0)
+            .velocity_correction(VelocityFrame::Barycentric, VelocityFrame::Galactocentric);
+        assert_relative_eq!(v.get::<kilometer_per_second>(), 245.6, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_rotation() {
+        // An observer on the equator looking at the eastern horizon approaches the target at
+        // the full rotation speed.
+        let time = Time::from_calendar_utc(2021, 3, 1, 5, 0, 0.0);
+        let location = EarthLocation::from_geodetic(
+            Angle::new::<degree>(0.0),
+            Angle::new::<degree>(0.0),
+            Length::new::<meter>(0.0),
+        );
+        let east = greenwich_mean_sidereal_time(&time) + Angle::new::<degree>(90.0);
+        let to_j2000 = transpose(&precession_matrix(time.centuries_since_j2000()));
+        let target = SkyCoord::from_unit_vector(to_j2000.apply([
+            east.get::<radian>().cos(),
+            east.get::<radian>().sin(),
+            0.0,
+        ]));
+        let obs = Observation::new(target, location, time);
+        let v = obs.velocity_correction(VelocityFrame::Topocentric, VelocityFrame::Geocentric);
+        assert_relative_eq!(v.get::<meter_per_second>(), 465.1, epsilon = 0.1);
+    }
+
+    #[test]
+    fn test_doppler_factor() {
+        let obs = Observation::new(
+            SkyCoord::new(Angle::new::<degree>(83.8), Angle::new::<degree>(-5.4)),
+            keck(),
+            Time::from_calendar_utc(2019, 9, 1, 10, 0, 0.0),
+        );
+        let v = obs
+            .velocity_correction(VelocityFrame::Topocentric, VelocityFrame::Lsrk)
+            .get::<meter_per_second>();
+        let factor = obs.doppler_factor(VelocityFrame::Topocentric, VelocityFrame::Lsrk);
+        assert_relative_eq!(factor, 1.0 - v / SPEED_OF_LIGHT, epsilon = 1e-8);
+        assert_relative_eq!(
+            factor * obs.doppler_factor(VelocityFrame::Lsrk, VelocityFrame::Topocentric),
+            1.0,
+            epsilon = 1e-15
+        );
+        assert_eq!(
+            VelocityFrame::from_specsys("lsrk"),
+            Some(VelocityFrame::Lsrk)
+        );
+        assert_eq!(
+            VelocityFrame::from_specsys(VelocityFrame::Galactocentric.specsys()),
+            Some(VelocityFrame::Galactocentric)
+        );
+    }
+}
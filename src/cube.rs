@@ -0,0 +1,1089 @@
+//! Spectral cubes, modelled on the `spectral_cube` Python package.
+//!
+//! A [`SpectralCube`] holds a three-dimensional array of intensities with a mask, the celestial
+//! [`Wcs`] of its two spatial axes, the [`SpectralAxis`] of its channels and optionally the
+//! restoring [`Beam`] of the data, either one for the whole cube or one per channel. Data are
+//! stored in FITS order: the first spatial axis varies fastest and the channel slowest, so
+//! pixel `(x, y)` of channel `z` is element `(z × ny + y) × nx + x`. As for
+//! [`Spectrum1D`], `true` in the mask means the value is invalid; non-finite values are always
+//! masked.
+
+mod chunked;
+mod map;
+mod masking;
+mod moments;
+mod noise;
+mod pv;
+mod regrid;
+mod smoothing;
+mod statistics;
+
+pub use chunked::ChunkedCube;
+pub use map::Map;
+pub use masking::{Prune, SignalMask};
+pub use noise::{Noise, NoiseMethod};
+pub use pv::{PvPath, PvSlice};
+pub use regrid::Reprojection;
+pub use statistics::Statistics;
+
+use std::ops::Range;
+
+use crate::beam::Beam;
+use crate::coordinates::SkyCoord;
+use crate::errors::cube::CubeError;
+use crate::io::fits::{BinTableHdu, Bitpix, ColumnData, Fits, Hdu, Header, ImageHdu};
+use crate::spectrum::{SpectralAxis, SpectralKind, SpectralValue, Spectrum1D};
+use crate::units::angle::{arcminute, arcsecond, degree, radian};
+use crate::units::equivalencies::DopplerConvention;
+use crate::units::f64::{Angle, Frequency};
+use crate::units::frequency::hertz;
+use crate::wcs::Wcs;
+
+/// Restoring beams of a cube.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Beams {
+    /// One beam for all channels, from `BMAJ`, `BMIN` and `BPA`
+    Single(Beam),
+    /// One beam per channel, as in a CASA `BEAMS` table
+    PerChannel(Vec<Beam>),
+}
+
+/// A spectral cube with two celestial axes and one spectral axis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpectralCube {
+    data: Vec<f64>,
+    shape: [usize; 3],
+    mask: Vec<bool>,
+    wcs: Wcs,
+    spectral_axis: SpectralAxis,
+    unit: String,
+    beams: Option<Beams>,
+}
+
+impl SpectralCube {
+    /// Create a cube from data of shape `[nx, ny, nchan]`, the celestial WCS of the first two
+    /// axes and the spectral axis. Non-finite values are masked.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::LengthMismatch`] if the data or spectral axis do not match
+    /// `shape`, and [`CubeError::NotACube`] unless the WCS has exactly two axes, longitude
+    /// then latitude.
+    pub fn new(
+        data: Vec<f64>,
+        shape: [usize; 3],
+        wcs: Wcs,
+        spectral_axis: SpectralAxis,
+        unit: impl Into<String>,
+    ) -> Result<Self, CubeError> {
+        check_len(shape.iter().product(), data.len())?;
+        check_len(shape[2], spectral_axis.len())?;
+        if wcs.naxis() != 2 || wcs.celestial_axes() != Some((0, 1)) {
+            return Err(CubeError::NotACube(
+                "the spatial WCS must have longitude and latitude axes, in that order".to_string(),
+            ));
+        }
+        Ok(Self {
+            mask: data.iter().map(|v| !v.is_finite()).collect(),
+            data,
+            shape,
+            wcs,
+            spectral_axis,
+            unit: unit.into(),
+            beams: None,
+        })
+    }
+
+    /// Mask more values. Non-finite values stay masked.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::LengthMismatch`] if the mask and data differ in length.
+    pub fn with_mask(mut self, mask: Vec<bool>) -> Result<Self, CubeError> {
+        check_len(self.data.len(), mask.len())?;
+        self.mask = mask
+            .into_iter()
+            .zip(&self.data)
+            .map(|(m, v)| m || !v.is_finite())
+            .collect();
+        Ok(self)
+    }
+
+    /// Set a single beam for all channels.
+    #[must_use]
+    pub fn with_beam(mut self, beam: Beam) -> Self {
+        self.beams = Some(Beams::Single(beam));
+        self
+    }
+
+    /// Set one beam per channel.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::LengthMismatch`] if there is not one beam per channel.
+    pub fn with_beams(mut self, beams: Vec<Beam>) -> Result<Self, CubeError> {
+        check_len(self.nchan(), beams.len())?;
+        self.beams = Some(Beams::PerChannel(beams));
+        Ok(self)
+    }
+
+    /// Read a cube from an image HDU.
+    ///
+    /// The first two axes must be celestial. The spectral axis may be any later axis provided
+    /// all other axes, such as a Stokes axis, are degenerate. The unit is `BUNIT`, and a single
+    /// beam is read from `BMAJ`, `BMIN` and `BPA`.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::NotACube`] if the axes are not laid out as above, and
+    /// [`CubeError::Wcs`], [`CubeError::Fits`] or [`CubeError::Beam`] for invalid keywords or
+    /// data.
+    pub fn from_hdu(hdu: &ImageHdu) -> Result<Self, CubeError> {
+        let metadata = Metadata::read(hdu)?;
+        let cube = Self::new(
+            hdu.read()?,
+            metadata.shape,
+            metadata.wcs,
+            metadata.spectral_axis,
+            metadata.unit,
+        )?;
+        Ok(match metadata.beam {
+            Some(beam) => cube.with_beam(beam),
+            None => cube,
+        })
+    }
+
+    /// Read a cube from the first image HDU with data, with per-channel beams from a `BEAMS`
+    /// table extension if there is one.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::NotACube`] if there is no image with data, and otherwise as
+    /// [`Self::from_hdu`].
+    pub fn from_fits(fits: &Fits) -> Result<Self, CubeError> {
+        let cube = Self::from_hdu(find_image(fits)?)?;
+        match fits.find("BEAMS") {
+            Some(Hdu::BinTable(table)) => {
+                let beams = read_beams(table, cube.nchan())?;
+                cube.with_beams(beams)
+            }
+            _ => Ok(cube),
+        }
+    }
+
+    /// The cube as an image HDU, with masked values written as NaN (or `BLANK` for integer
+    /// types) and per-channel beams left out; see [`Self::to_fits`].
+    ///
+    /// # Errors
+    /// Returns [`CubeError::NonLinearSpectralAxis`] if the channels are not evenly spaced, as
+    /// after converting frequencies to optical velocities.
+    pub fn to_hdu(&self, bitpix: Bitpix) -> Result<ImageHdu, CubeError> {
+        let mut hdu = ImageHdu::new(&self.shape, &self.filled_data(f64::NAN), bitpix)?;
+        let header = cube_header(
+            &self.wcs,
+            &self.spectral_axis,
+            &self.unit,
+            self.beams.as_ref(),
+        )?;
+        for card in header.cards() {
+            hdu.header_mut().push(card.clone());
+        }
+        Ok(hdu)
+    }
+
+    /// The cube as a FITS file: the image in the primary HDU, followed by a CASA-style
+    /// `BEAMS` table if there are per-channel beams.
+    ///
+    /// # Errors
+    /// As [`Self::to_hdu`].
+    pub fn to_fits(&self, bitpix: Bitpix) -> Result<Fits, CubeError> {
+        let mut fits = Fits::new();
+        fits.push(self.to_hdu(bitpix)?);
+        if let Some(Beams::PerChannel(beams)) = &self.beams {
+            fits.push(beams_table(beams)?);
+        }
+        Ok(fits)
+    }
+
+    /// Axis lengths `[nx, ny, nchan]`.
+    pub fn shape(&self) -> [usize; 3] {
+        self.shape
+    }
+
+    pub fn nchan(&self) -> usize {
+        self.shape[2]
+    }
+
+    /// All values, masked or not, in FITS order.
+    pub fn data(&self) -> &[f64] {
+        &self.data
+    }
+
+    /// Values with masked elements replaced by `fill`.
+    pub fn filled_data(&self, fill: f64) -> Vec<f64> {
+        self.data
+            .iter()
+            .zip(&self.mask)
+            .map(|(&v, &m)| if m { fill } else { v })
+            .collect()
+    }
+
+    pub fn mask(&self) -> &[bool] {
+        &self.mask
+    }
+
+    /// Celestial WCS of the two spatial axes.
+    pub fn wcs(&self) -> &Wcs {
+        &self.wcs
+    }
+
+    pub fn spectral_axis(&self) -> &SpectralAxis {
+        &self.spectral_axis
+    }
+
+    pub fn unit(&self) -> &str {
+        &self.unit
+    }
+
+    pub fn beams(&self) -> Option<&Beams> {
+        self.beams.as_ref()
+    }
+
+    /// Beam of channel `channel`.
+    pub fn beam(&self, channel: usize) -> Option<Beam> {
+        match &self.beams {
+            Some(Beams::Single(beam)) => Some(*beam),
+            Some(Beams::PerChannel(beams)) => beams.get(channel).copied(),
+            None => None,
+        }
+    }
+
+    /// The beam shared by all channels, if any.
+    pub(crate) fn common_beam(&self) -> Option<Beam> {
+        match &self.beams {
+            Some(Beams::Single(beam)) => Some(*beam),
+            Some(Beams::PerChannel(beams)) => beams
+                .first()
+                .filter(|first| beams.iter().all(|b| b == *first))
+                .copied(),
+            None => None,
+        }
+    }
+
+    /// Index into [`Self::data`] of pixel `(x, y)` in channel `z`.
+    pub(crate) fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        (z * self.shape[1] + y) * self.shape[0] + x
+    }
+
+    /// Value of pixel `(x, y)` in channel `z`, or `None` if masked or out of range.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<f64> {
+        if x >= self.shape[0] || y >= self.shape[1] || z >= self.shape[2] {
+            return None;
+        }
+        let i = self.index(x, y, z);
+        (!self.mask[i]).then_some(self.data[i])
+    }
+
+    /// Cube restricted to pixel ranges along each axis.
+    ///
+    /// # Panics
+    /// Panics if a range is out of bounds.
+    #[must_use]
+    pub fn slice_index(&self, x: Range<usize>, y: Range<usize>, channels: Range<usize>) -> Self {
+        assert!(
+            x.end <= self.shape[0] && y.end <= self.shape[1] && channels.end <= self.shape[2],
+            "slice out of bounds"
+        );
+        let shape = [x.len(), y.len(), channels.len()];
+        let mut data = Vec::with_capacity(shape.iter().product());
+        let mut mask = Vec::with_capacity(data.capacity());
+        for z in channels.clone() {
+            for j in y.clone() {
+                let row = self.index(x.start, j, z)..self.index(x.end, j, z);
+                data.extend_from_slice(&self.data[row.clone()]);
+                mask.extend_from_slice(&self.mask[row]);
+            }
+        }
+        let beams = self.beams.as_ref().map(|beams| match beams {
+            Beams::Single(beam) => Beams::Single(*beam),
+            Beams::PerChannel(beams) => Beams::PerChannel(beams[channels.clone()].to_vec()),
+        });
+        Self {
+            data,
+            shape,
+            mask,
+            wcs: self.wcs.cutout(&[x.start as f64, y.start as f64]),
+            spectral_axis: self.spectral_axis.select(&channels.collect::<Vec<_>>()),
+            unit: self.unit.clone(),
+            beams,
+        }
+    }
+
+    /// Cube restricted to the channels whose spectral coordinate lies between `lo` and `hi`
+    /// (inclusive, in either order). The bounds may be of any spectral kind.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Spectrum`] if a bound cannot be converted to the axis kind, and
+    /// [`CubeError::EmptySelection`] if no channel falls in the range.
+    pub fn spectral_slab(
+        &self,
+        lo: impl Into<SpectralValue>,
+        hi: impl Into<SpectralValue>,
+    ) -> Result<Self, CubeError> {
+        let a = self.spectral_axis.convert_value(lo)?;
+        let b = self.spectral_axis.convert_value(hi)?;
+        let (lo, hi) = (a.min(b), a.max(b));
+        let inside = |v: &f64| (lo..=hi).contains(v);
+        let values = self.spectral_axis.values();
+        let first = values.iter().position(inside);
+        let last = values.iter().rposition(inside);
+        match (first, last) {
+            (Some(first), Some(last)) => {
+                Ok(self.slice_index(0..self.shape[0], 0..self.shape[1], first..last + 1))
+            }
+            _ => Err(CubeError::EmptySelection),
+        }
+    }
+
+    /// Cube restricted to the pixels inside the box with opposite corners `a` and `b`, in the
+    /// celestial frame of the cube (ICRS or Galactic).
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Wcs`] if a corner does not project, and
+    /// [`CubeError::EmptySelection`] if the box does not overlap the cube.
+    pub fn subcube(&self, a: &SkyCoord, b: &SkyCoord) -> Result<Self, CubeError> {
+        let lonlat = |c: &SkyCoord| {
+            let (lng, lat) = if self.wcs.is_galactic() {
+                c.galactic()
+            } else {
+                (c.ra(), c.dec())
+            };
+            (lng.get::<degree>(), lat.get::<degree>())
+        };
+        let ((lng_a, lat_a), (lng_b, lat_b)) = (lonlat(a), lonlat(b));
+        let corners = [
+            [lng_a, lat_a],
+            [lng_a, lat_b],
+            [lng_b, lat_a],
+            [lng_b, lat_b],
+        ]
+        .iter()
+        .map(|world| self.wcs.world_to_pixel(world))
+        .collect::<Result<Vec<_>, _>>()?;
+        let range = |axis: usize| {
+            let lo = corners
+                .iter()
+                .map(|p| p[axis])
+                .fold(f64::INFINITY, f64::min);
+            let hi = corners
+                .iter()
+                .map(|p| p[axis])
+                .fold(f64::NEG_INFINITY, f64::max);
+            let n = self.shape[axis] as f64;
+            let start = (lo + 0.5).floor().clamp(0.0, n) as usize;
+            let end = ((hi + 0.5).floor() + 1.0).clamp(0.0, n) as usize;
+            start..end.max(start)
+        };
+        let (x, y) = (range(0), range(1));
+        if x.is_empty() || y.is_empty() {
+            return Err(CubeError::EmptySelection);
+        }
+        Ok(self.slice_index(x, y, 0..self.nchan()))
+    }
+
+    /// Cube with its spectral axis expressed as `kind`. The rest frequency and velocity
+    /// convention are replaced when given; a velocity axis is first converted to frequency
+    /// with its current ones.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Spectrum`] if a conversion through velocity lacks a rest frequency.
+    pub fn with_spectral_unit(
+        &self,
+        kind: SpectralKind,
+        rest: Option<Frequency>,
+        convention: Option<DopplerConvention>,
+    ) -> Result<Self, CubeError> {
+        let current = &self.spectral_axis;
+        let mut axis = current.to_kind(SpectralKind::Frequency)?;
+        if let Some(rest) = rest.or(current.rest()) {
+            axis = axis.with_rest(rest, convention.unwrap_or(current.convention()));
+        }
+        Ok(Self {
+            spectral_axis: axis.to_kind(kind)?,
+            ..self.clone()
+        })
+    }
+
+    /// Spectrum of pixel `(x, y)`.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::OutOfRange`] if the pixel is outside the cube.
+    pub fn spectrum(&self, x: usize, y: usize) -> Result<Spectrum1D, CubeError> {
+        for (index, len) in [(x, self.shape[0]), (y, self.shape[1])] {
+            if index >= len {
+                return Err(CubeError::OutOfRange { index, len });
+            }
+        }
+        let indices = (0..self.nchan()).map(|z| self.index(x, y, z));
+        let flux = indices.clone().map(|i| self.data[i]).collect();
+        let mask = indices.map(|i| self.mask[i]).collect();
+        Ok(
+            Spectrum1D::new(self.spectral_axis.clone(), flux, self.unit.clone())?
+                .with_mask(mask)?,
+        )
+    }
+
+    /// Mean spectrum over the spatial pixels selected by `selection`, a `nx × ny` map in FITS
+    /// order. Masked values are left out; channels with no valid value are NaN and masked.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::LengthMismatch`] if the selection does not match the spatial shape,
+    /// and [`CubeError::EmptySelection`] if it selects no pixel.
+    pub fn mean_spectrum(&self, selection: &[bool]) -> Result<Spectrum1D, CubeError> {
+        let plane = self.shape[0] * self.shape[1];
+        check_len(plane, selection.len())?;
+        let pixels: Vec<usize> = (0..plane).filter(|&p| selection[p]).collect();
+        if pixels.is_empty() {
+            return Err(CubeError::EmptySelection);
+        }
+        let flux: Vec<f64> = (0..self.nchan())
+            .map(|z| {
+                let (sum, count) = pixels
+                    .iter()
+                    .map(|p| z * plane + p)
+                    .filter(|&i| !self.mask[i])
+                    .fold((0.0, 0usize), |(s, n), i| (s + self.data[i], n + 1));
+                if count == 0 {
+                    f64::NAN
+                } else {
+                    sum / count as f64
+                }
+            })
+            .collect();
+        let mask = flux.iter().map(|v| v.is_nan()).collect();
+        Ok(
+            Spectrum1D::new(self.spectral_axis.clone(), flux, self.unit.clone())?
+                .with_mask(mask)?,
+        )
+    }
+
+    /// Mean spectrum over the pixels whose centres lie within `radius` of `center`.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::EmptySelection`] if no pixel centre falls in the aperture.
+    pub fn aperture_spectrum(
+        &self,
+        center: &SkyCoord,
+        radius: Angle,
+    ) -> Result<Spectrum1D, CubeError> {
+        let mut selection = Vec::with_capacity(self.shape[0] * self.shape[1]);
+        for y in 0..self.shape[1] {
+            for x in 0..self.shape[0] {
+                selection.push(match self.wcs.pixel_to_sky(&[x as f64, y as f64]) {
+                    Ok(sky) => sky.separation(center) <= radius,
+                    Err(_) => false,
+                });
+            }
+        }
+        self.mean_spectrum(&selection)
+    }
+}
+
+/// Everything about a cube read from an image HDU except its data.
+struct Metadata {
+    shape: [usize; 3],
+    wcs: Wcs,
+    spectral_axis: SpectralAxis,
+    unit: String,
+    beam: Option<Beam>,
+}
+
+impl Metadata {
+    /// See [`SpectralCube::from_hdu`].
+    fn read(hdu: &ImageHdu) -> Result<Self, CubeError> {
+        let header = hdu.header();
+        let shape = hdu.shape();
+        let wcs = image_wcs(hdu)?;
+        let spectral = wcs
+            .spectral_index()
+            .ok_or_else(|| CubeError::NotACube("no spectral axis".to_string()))?;
+        let len = |i: usize| shape.get(i).copied().unwrap_or(1);
+        if wcs.celestial_axes() != Some((0, 1))
+            || spectral < 2
+            || (2..shape.len().max(wcs.naxis())).any(|i| i != spectral && len(i) != 1)
+        {
+            return Err(CubeError::NotACube(
+                "expected longitude, latitude and spectral axes, with any others degenerate"
+                    .to_string(),
+            ));
+        }
+        let shape = [len(0), len(1), len(spectral)];
+        let beam = match (header.get_f64("BMAJ"), header.get_f64("BMIN")) {
+            (Some(major), Some(minor)) => Some(beam(
+                major,
+                minor,
+                header.get_f64("BPA").unwrap_or(0.0),
+                "deg",
+                "deg",
+            )?),
+            _ => None,
+        };
+        Ok(Self {
+            shape,
+            spectral_axis: wcs.spectral_axis(shape[2])?,
+            wcs: wcs.sub(&[0, 1])?,
+            unit: header
+                .get_str("BUNIT")
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+            beam,
+        })
+    }
+}
+
+/// The WCS of an image HDU, with all the axes of the image.
+fn image_wcs(hdu: &ImageHdu) -> Result<Wcs, CubeError> {
+    let header = hdu.header();
+    Ok(if header.contains("WCSAXES") || header.contains("NAXIS") {
+        Wcs::from_header(header)?
+    } else {
+        // Structural keywords are kept out of the header of an image HDU.
+        let mut header = header.clone();
+        header.set("WCSAXES", hdu.shape().len());
+        Wcs::from_header(&header)?
+    })
+}
+
+/// The first image HDU with data.
+fn find_image(fits: &Fits) -> Result<&ImageHdu, CubeError> {
+    fits.hdus()
+        .iter()
+        .find_map(|hdu| match hdu {
+            Hdu::Image(image) if !image.is_empty() => Some(image),
+            _ => None,
+        })
+        .ok_or_else(|| CubeError::NotACube("no image with data".to_string()))
+}
+
+/// The non-structural header of a cube: the celestial WCS, the spectral axis as the third
+/// axis, `BUNIT` and a single beam.
+fn cube_header(
+    wcs: &Wcs,
+    spectral_axis: &SpectralAxis,
+    unit: &str,
+    beams: Option<&Beams>,
+) -> Result<Header, CubeError> {
+    let mut header = wcs.to_header();
+    header.set("WCSAXES", 3);
+    spectral_header(spectral_axis, 3, &mut header)?;
+    if !unit.is_empty() {
+        header.set("BUNIT", unit);
+    }
+    if let Some(Beams::Single(beam)) = beams {
+        write_beam(&mut header, beam);
+    }
+    Ok(header)
+}
+
+/// A CASA-style `BEAMS` table with one row per channel and a single polarisation.
+fn beams_table(beams: &[Beam]) -> Result<BinTableHdu, CubeError> {
+    let column =
+        |f: &dyn Fn(&Beam) -> f64| ColumnData::Float32(beams.iter().map(|b| f(b) as f32).collect());
+    let channels = (0..beams.len()).map(|c| c as i32).collect();
+    let mut table = BinTableHdu::new(beams.len())
+        .with_column(
+            "BMAJ",
+            Some("arcsec"),
+            column(&|b| b.major.get::<arcsecond>()),
+        )?
+        .with_column(
+            "BMIN",
+            Some("arcsec"),
+            column(&|b| b.minor.get::<arcsecond>()),
+        )?
+        .with_column("BPA", Some("deg"), column(&|b| b.pa.get::<degree>()))?
+        .with_column("CHAN", None, ColumnData::Int32(channels))?
+        .with_column("POL", None, ColumnData::Int32(vec![0; beams.len()]))?;
+    let header = table.header_mut();
+    header.set("EXTNAME", "BEAMS");
+    header.set("NCHAN", beams.len());
+    header.set("NPOL", 1);
+    Ok(table)
+}
+
+/// Write the keywords of a linear spectral axis as WCS axis `i` (one-based).
+///
+/// A [`SpectralAxis`] holds channel values only, so a one-channel axis has no width to
+/// recover. Its `CDELT` is written as 1 in the SI unit of the axis, which places the single
+/// channel correctly but says nothing about its width.
+fn spectral_header(axis: &SpectralAxis, i: usize, header: &mut Header) -> Result<(), CubeError> {
+    let values = axis.values();
+    let n = values.len();
+    let step = if n > 1 {
+        (values[n - 1] - values[0]) / (n - 1) as f64
+    } else {
+        1.0
+    };
+    let linear = values
+        .iter()
+        .enumerate()
+        .all(|(k, v)| (v - (values[0] + k as f64 * step)).abs() <= 1e-6 * step.abs());
+    if !linear {
+        return Err(CubeError::NonLinearSpectralAxis);
+    }
+    let (ctype, unit) = match axis.kind() {
+        SpectralKind::Frequency => ("FREQ", "Hz"),
+        SpectralKind::Wavelength => ("WAVE", "m"),
+        SpectralKind::Velocity => match axis.convention() {
+            DopplerConvention::Radio => ("VRAD", "m/s"),
+            DopplerConvention::Optical => ("VOPT", "m/s"),
+            DopplerConvention::Relativistic => ("VELO", "m/s"),
+        },
+    };
+    header.set(&format!("CTYPE{i}"), ctype);
+    header.set(&format!("CUNIT{i}"), unit);
+    header.set(&format!("CRPIX{i}"), 1.0);
+    header.set(&format!("CRVAL{i}"), values.first().copied().unwrap_or(0.0));
+    header.set(&format!("CDELT{i}"), step);
+    if let Some(rest) = axis.rest() {
+        header.set("RESTFRQ", rest.get::<hertz>());
+    }
+    if let Some(frame) = axis.frame() {
+        header.set("SPECSYS", frame.specsys());
+    }
+    Ok(())
+}
+
+/// An image HDU of `data` with the cards of `wcs`, `BUNIT` unless `unit` is empty, and the
+/// beam.
+fn image_hdu(
+    shape: &[usize],
+    data: &[f64],
+    bitpix: Bitpix,
+    wcs: &Wcs,
+    unit: &str,
+    beam: Option<&Beam>,
+) -> Result<ImageHdu, CubeError> {
+    let mut hdu = ImageHdu::new(shape, data, bitpix)?;
+    let header = hdu.header_mut();
+    for card in wcs.to_header().cards() {
+        header.push(card.clone());
+    }
+    if !unit.is_empty() {
+        header.set("BUNIT", unit);
+    }
+    if let Some(beam) = beam {
+        write_beam(header, beam);
+    }
+    Ok(hdu)
+}
+
+/// Write `BMAJ`, `BMIN` and `BPA` in degrees.
+fn write_beam(header: &mut Header, beam: &Beam) {
+    header.set_with_comment("BMAJ", beam.major.get::<degree>(), "[deg]");
+    header.set_with_comment("BMIN", beam.minor.get::<degree>(), "[deg]");
+    header.set_with_comment("BPA", beam.pa.get::<degree>(), "[deg]");
+}
+
+/// Read per-channel beams from a CASA `BEAMS` table, taking the first polarisation. Columns
+/// without a unit are in the CASA units, arcseconds for the axes and degrees for the position
+/// angle.
+fn read_beams(table: &BinTableHdu, nchan: usize) -> Result<Vec<Beam>, CubeError> {
+    let unit = |name: &str, default: &str| -> Result<String, CubeError> {
+        Ok(table
+            .column(name)?
+            .unit
+            .clone()
+            .filter(|u| !u.trim().is_empty())
+            .unwrap_or_else(|| default.to_string()))
+    };
+    let (major, minor, pa) = (
+        table.read_column_f64("BMAJ")?,
+        table.read_column_f64("BMIN")?,
+        table.read_column_f64("BPA")?,
+    );
+    let (major_unit, pa_unit) = (unit("BMAJ", "arcsec")?, unit("BPA", "deg")?);
+    let row_count = table.nrows();
+    let channels = match table.column("CHAN") {
+        Ok(_) => table.read_column_f64("CHAN")?,
+        Err(_) => (0..row_count).map(|c| c as f64).collect(),
+    };
+    let pols = match table.column("POL") {
+        Ok(_) => table.read_column_f64("POL")?,
+        Err(_) => vec![0.0; row_count],
+    };
+
+    let mut beams = vec![None; nchan];
+    for row in 0..row_count {
+        let channel = channels[row];
+        if pols[row] != 0.0 || !(0.0..nchan as f64).contains(&channel) {
+            continue;
+        }
+        let channel = channel as usize;
+        beams[channel] = Some(beam(
+            major[row],
+            minor[row],
+            pa[row],
+            &major_unit,
+            &pa_unit,
+        )?);
+    }
+    beams
+        .into_iter()
+        .enumerate()
+        .map(|(channel, b)| b.ok_or(CubeError::MissingChannelBeam(channel)))
+        .collect()
+}
+
+/// A beam from FWHM axes in `size_unit` and position angle in `pa_unit`.
+fn beam(
+    major: f64,
+    minor: f64,
+    pa: f64,
+    size_unit: &str,
+    pa_unit: &str,
+) -> Result<Beam, CubeError> {
+    let angle = |value: f64, unit: &str| {
+        Ok(match unit.trim() {
+            "deg" | "degree" | "degrees" => Angle::new::<degree>(value),
+            "arcmin" | "arcminute" | "arcminutes" => Angle::new::<arcminute>(value),
+            "arcsec" | "arcsecond" | "arcseconds" => Angle::new::<arcsecond>(value),
+            "rad" | "radian" | "radians" => Angle::new::<radian>(value),
+            other => return Err(CubeError::UnsupportedUnit(other.to_string())),
+        })
+    };
+    Ok(Beam::new(
+        Some(angle(major, size_unit)?),
+        Some(angle(minor, size_unit)?),
+        Some(angle(pa, pa_unit)?),
+        None,
+    )?)
+}
+
+fn check_len(expected: usize, found: usize) -> Result<(), CubeError> {
+    if expected == found {
+        Ok(())
+    } else {
+        Err(CubeError::LengthMismatch { expected, found })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::units::f64::Velocity;
+    use crate::units::frequency::gigahertz;
+    use crate::units::velocity::kilometer_per_second;
+    use approx::assert_relative_eq;
+
+    /// A header for an `nx × ny × nchan` TAN cube at (RA, Dec) = (83.8, -5.4) with 1" pixels
+    /// and 0.5 MHz channels around 115.27 GHz, in the LSRK frame.
+    pub(crate) fn cube_header(shape: [usize; 3]) -> Header {
+        let mut header = Header::new();
+        header.set("NAXIS", 3);
+        let axes = [
+            (
+                "RA---TAN",
+                shape[0] as f64 / 2.0,
+                83.8,
+                -1.0 / 3600.0,
+                "deg",
+            ),
+            ("DEC--TAN", shape[1] as f64 / 2.0, -5.4, 1.0 / 3600.0, "deg"),
+            ("FREQ", 1.0, 115.270_2e9, -0.5e6, "Hz"),
+        ];
+        for (i, (ctype, crpix, crval, cdelt, cunit)) in axes.into_iter().enumerate() {
+            header.set(&format!("NAXIS{}", i + 1), shape[i]);
+            header.set(&format!("CTYPE{}", i + 1), ctype);
+            header.set(&format!("CRPIX{}", i + 1), crpix);
+            header.set(&format!("CRVAL{}", i + 1), crval);
+            header.set(&format!("CDELT{}", i + 1), cdelt);
+            header.set(&format!("CUNIT{}", i + 1), cunit);
+        }
+        header.set("RESTFRQ", 115.271_201_8e9);
+        header.set("SPECSYS", "LSRK");
+        header
+    }
+
+    /// A cube whose value at `(x, y, z)` is `f(x, y, z)`, laid out as by [`cube_header`].
+    pub(crate) fn cube_from(
+        shape: [usize; 3],
+        f: impl Fn(usize, usize, usize) -> f64,
+    ) -> SpectralCube {
+        let header = cube_header(shape);
+        let wcs = Wcs::from_header(&header).unwrap();
+        let mut data = Vec::new();
+        for z in 0..shape[2] {
+            for y in 0..shape[1] {
+                for x in 0..shape[0] {
+                    data.push(f(x, y, z));
+                }
+            }
+        }
+        SpectralCube::new(
+            data,
+            shape,
+            wcs.sub(&[0, 1]).unwrap(),
+            wcs.spectral_axis(shape[2]).unwrap(),
+            "Jy/beam",
+        )
+        .unwrap()
+    }
+
+    fn value(x: usize, y: usize, z: usize) -> f64 {
+        (100 * z + 10 * y + x) as f64
+    }
+
+    fn arcsec(value: f64) -> Angle {
+        Angle::new::<arcsecond>(value)
+    }
+
+    #[test]
+    fn test_new_and_access() {
+        let cube = cube_from([4, 3, 5], value);
+        assert_eq!(cube.shape(), [4, 3, 5]);
+        assert_eq!(cube.get(2, 1, 3), Some(312.0));
+        assert_eq!(cube.get(4, 0, 0), None);
+        assert_eq!(cube.spectral_axis().kind(), SpectralKind::Frequency);
+        assert_relative_eq!(cube.spectral_axis().values()[1], 115.2697e9);
+
+        let mut data = cube.data().to_vec();
+        data[0] = f64::NAN;
+        let wcs = cube.wcs().clone();
+        let axis = cube.spectral_axis().clone();
+        let cube = SpectralCube::new(data, [4, 3, 5], wcs.clone(), axis.clone(), "K").unwrap();
+        assert!(cube.mask()[0] && !cube.mask()[1]);
+        assert!(matches!(
+            SpectralCube::new(vec![0.0; 10], [4, 3, 5], wcs.clone(), axis.clone(), "K"),
+            Err(CubeError::LengthMismatch {
+                expected: 60,
+                found: 10
+            })
+        ));
+        assert!(matches!(
+            cube.with_beams(vec![
+                Beam::new(Some(arcsec(1.0)), None, None, None).unwrap()
+            ]),
+            Err(CubeError::LengthMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_fits_round_trip() {
+        let beams: Vec<Beam> = (0..5)
+            .map(|c| {
+                Beam::new(
+                    Some(arcsec(2.0 + 0.1 * c as f64)),
+                    Some(arcsec(1.5)),
+                    None,
+                    None,
+                )
+                .unwrap()
+            })
+            .collect();
+        let cube = cube_from([4, 3, 5], value)
+            .with_beams(beams.clone())
+            .unwrap();
+        let fits = cube.to_fits(Bitpix::F64).unwrap();
+        let read = SpectralCube::from_fits(&Fits::from_bytes(fits.to_bytes()).unwrap()).unwrap();
+        assert_eq!(read.data(), cube.data());
+        assert_eq!(read.unit(), "Jy/beam");
+        assert_eq!(read.beams(), Some(&Beams::PerChannel(beams)));
+        for (a, b) in read
+            .spectral_axis()
+            .values()
+            .iter()
+            .zip(cube.spectral_axis().values())
+        {
+            assert_relative_eq!(*a, *b, max_relative = 1e-12);
+        }
+        assert_eq!(read.spectral_axis().frame(), cube.spectral_axis().frame());
+        let sky = read.wcs().pixel_to_sky(&[1.0, 2.0]).unwrap();
+        let expected = cube.wcs().pixel_to_sky(&[1.0, 2.0]).unwrap();
+        assert!(sky.separation(&expected).get::<arcsecond>() < 1e-9);
+
+        // A degenerate Stokes axis between the spatial and spectral axes.
+        let mut header = cube_header([4, 3, 5]);
+        for (key, value) in [("CTYPE", "STOKES"), ("CUNIT", "")] {
+            let moved = header.get_str(&format!("{key}3")).unwrap().to_string();
+            header.set(&format!("{key}4"), moved.as_str());
+            header.set(&format!("{key}3"), value);
+        }
+        for key in ["CRPIX", "CRVAL", "CDELT"] {
+            let moved = header.get_f64(&format!("{key}3")).unwrap();
+            header.set(&format!("{key}4"), moved);
+            header.set(&format!("{key}3"), 1.0);
+        }
+        header.set("BMAJ", 3.0 / 3600.0);
+        header.set("BMIN", 2.0 / 3600.0);
+        header.set("BPA", 30.0);
+        let mut hdu = ImageHdu::new(&[4, 3, 1, 5], cube.data(), Bitpix::F32).unwrap();
+        for card in header.cards() {
+            if !card.keyword.starts_with("NAXIS") {
+                hdu.header_mut().push(card.clone());
+            }
+        }
+        let read = SpectralCube::from_hdu(&hdu).unwrap();
+        assert_eq!(read.shape(), [4, 3, 5]);
+        assert_eq!(read.spectral_axis().len(), 5);
+        let beam = read.beam(3).unwrap();
+        assert_relative_eq!(beam.major.get::<arcsecond>(), 3.0, max_relative = 1e-12);
+        assert_relative_eq!(beam.pa.get::<degree>(), 30.0, max_relative = 1e-12);
+    }
+
+    #[test]
+    fn test_read_beams() {
+        let table = |unit: &str, channels: Vec<i32>| {
+            let n = channels.len();
+            let values = |v: f32| ColumnData::Float32(vec![v; n]);
+            BinTableHdu::new(n)
+                .with_column("BMAJ", Some(unit), values(2.0))
+                .unwrap()
+                .with_column("BMIN", Some(unit), values(1.0))
+                .unwrap()
+                .with_column("BPA", None, values(30.0))
+                .unwrap()
+                .with_column("CHAN", None, ColumnData::Int32(channels))
+                .unwrap()
+        };
+        // Negative channels are skipped rather than read as channel 0.
+        let beams = read_beams(&table("arcsecond", vec![0, -1, 1]), 2).unwrap();
+        assert_relative_eq!(beams[1].major.get::<arcsecond>(), 2.0);
+        assert_relative_eq!(beams[1].pa.get::<degree>(), 30.0);
+        assert!(matches!(
+            read_beams(&table("arcsec", vec![0, -1]), 2),
+            Err(CubeError::MissingChannelBeam(1))
+        ));
+        assert!(matches!(
+            read_beams(&table("mas", vec![0, 1]), 2),
+            Err(CubeError::UnsupportedUnit(unit)) if unit == "mas"
+        ));
+    }
+
+    #[test]
+    fn test_spectral_slab_and_unit() {
+        let cube = cube_from([4, 3, 10], value);
+        let slab = cube
+            .spectral_slab(
+                Frequency::new::<gigahertz>(115.2685),
+                Frequency::new::<gigahertz>(115.2670),
+            )
+            .unwrap();
+        assert_eq!(slab.shape(), [4, 3, 3]);
+        assert_eq!(slab.get(0, 0, 0), Some(400.0));
+
+        let velocity = cube
+            .with_spectral_unit(SpectralKind::Velocity, None, Some(DopplerConvention::Radio))
+            .unwrap();
+        let rest = Frequency::new::<gigahertz>(115.271_201_8);
+        let frequencies = cube.spectral_axis().frequencies().unwrap();
+        for (v, f) in velocity
+            .spectral_axis()
+            .velocities()
+            .unwrap()
+            .iter()
+            .zip(&frequencies)
+        {
+            let expected = DopplerConvention::Radio.to_velocity(*f, rest);
+            assert_relative_eq!(
+                v.get::<kilometer_per_second>(),
+                expected.get::<kilometer_per_second>(),
+                epsilon = 1e-9
+            );
+        }
+        // Slabs may be given in any spectral kind.
+        let slab_v = velocity
+            .spectral_slab(
+                Velocity::new::<kilometer_per_second>(5.0),
+                Velocity::new::<kilometer_per_second>(12.0),
+            )
+            .unwrap();
+        let slab_f = cube
+            .spectral_slab(
+                Velocity::new::<kilometer_per_second>(5.0),
+                Velocity::new::<kilometer_per_second>(12.0),
+            )
+            .unwrap();
+        assert_eq!(slab_v.data(), slab_f.data());
+        assert!(matches!(
+            cube.spectral_slab(
+                Frequency::new::<gigahertz>(100.0),
+                Frequency::new::<gigahertz>(101.0)
+            ),
+            Err(CubeError::EmptySelection)
+        ));
+
+        // An optical velocity axis is not linear and cannot be written.
+        let optical = cube
+            .with_spectral_unit(
+                SpectralKind::Velocity,
+                None,
+                Some(DopplerConvention::Optical),
+            )
+            .unwrap();
+        assert!(matches!(
+            optical.to_hdu(Bitpix::F32),
+            Err(CubeError::NonLinearSpectralAxis)
+        ));
+        let hdu = velocity.to_hdu(Bitpix::F32).unwrap();
+        assert_eq!(hdu.header().get_str("CTYPE3"), Some("VRAD"));
+    }
+
+    #[test]
+    fn test_subcube() {
+        let cube = cube_from([10, 8, 3], value);
+        let a = cube.wcs().pixel_to_sky(&[2.2, 1.9]).unwrap();
+        let b = cube.wcs().pixel_to_sky(&[5.4, 4.6]).unwrap();
+        let sub = cube.subcube(&a, &b).unwrap();
+        assert_eq!(sub.shape(), [4, 4, 3]);
+        assert_eq!(sub.get(0, 0, 1), Some(122.0));
+        let sky = sub.wcs().pixel_to_sky(&[0.0, 0.0]).unwrap();
+        let expected = cube.wcs().pixel_to_sky(&[2.0, 2.0]).unwrap();
+        assert!(sky.separation(&expected).get::<arcsecond>() < 1e-9);
+
+        let far = SkyCoord::new(Angle::new::<degree>(84.0), Angle::new::<degree>(-5.0));
+        let farther = SkyCoord::new(Angle::new::<degree>(84.1), Angle::new::<degree>(-4.9));
+        assert!(matches!(
+            cube.subcube(&far, &farther),
+            Err(CubeError::EmptySelection)
+        ));
+    }
+
+    #[test]
+    fn test_spectra() {
+        let cube = cube_from([5, 5, 4], value);
+        let spectrum = cube.spectrum(1, 2).unwrap();
+        assert_eq!(spectrum.flux, vec![21.0, 121.0, 221.0, 321.0]);
+        assert_eq!(spectrum.spectral_axis, *cube.spectral_axis());
+        assert!(matches!(
+            cube.spectrum(5, 0),
+            Err(CubeError::OutOfRange { index: 5, len: 5 })
+        ));
+
+        // The aperture holds the centre pixel and its four neighbours.
+        let center = cube.wcs().pixel_to_sky(&[2.0, 2.0]).unwrap();
+        let aperture = cube.aperture_spectrum(&center, arcsec(1.2)).unwrap();
+        assert_relative_eq!(aperture.flux[0], 22.0);
+
+        let mut mask = vec![false; 100];
+        mask[cube.index(2, 2, 1)] = true;
+        let masked = cube.clone().with_mask(mask).unwrap();
+        let aperture = masked.aperture_spectrum(&center, arcsec(1.2)).unwrap();
+        assert_relative_eq!(aperture.flux[1], (112.0 + 132.0 + 121.0 + 123.0) / 4.0);
+        assert_eq!(aperture.mask, vec![false; 4]);
+
+        // Only the centre pixel lies within the smallest aperture.
+        let aperture = cube.aperture_spectrum(&center, arcsec(0.1)).unwrap();
+        assert_eq!(aperture.flux, vec![22.0, 122.0, 222.0, 322.0]);
+        assert_eq!(aperture.mask, vec![false; 4]);
+
+        // A channel masked at every pixel of the aperture is NaN and masked.
+        let mut mask = vec![false; 100];
+        mask[cube.index(2, 2, 3)] = true;
+        let masked = cube.clone().with_mask(mask).unwrap();
+        let aperture = masked.aperture_spectrum(&center, arcsec(0.1)).unwrap();
+        assert_eq!(aperture.flux[..3], [22.0, 122.0, 222.0]);
+        assert!(aperture.flux[3].is_nan());
+        assert_eq!(aperture.mask, vec![false, false, false, true]);
+        assert!(matches!(
+            cube.mean_spectrum(&[false; 25]),
+            Err(CubeError::EmptySelection)
+        ));
+    }
+}
@@ -0,0 +1,1164 @@
+//! Out-of-core processing of cubes larger than memory.
+//!
+//! A [`ChunkedCube`] reads a cube lazily from a memory-mapped FITS file and processes it in
+//! chunks: bands of whole rows with every channel for operations along the spectral axis
+//! (moments, spectral smoothing, per-pixel noise), and runs of whole channels for operations
+//! on images (convolution to a common beam), per-channel noise and statistics. Each chunk is
+//! loaded as an in-memory [`SpectralCube`] and handed to the same code, so results are
+//! identical to processing the whole cube in memory. Chunks are sized so that those
+//! processed at once fit in the memory budget, and are processed in parallel with rayon.
+//!
+//! Operations that produce a cube write it to a FITS file as the chunks complete. Masks are
+//! predicates evaluated as chunks are loaded, with one exception: the dilated signal mask
+//! depends on regions that cross chunk boundaries. Its regions are labelled band by band and
+//! joined across bands with union-find, which keeps in memory only a node for each region
+//! touching another band. The mask itself is stored as one bit per voxel in a memory-mapped
+//! scratch file, a thirty-second of the size of 32-bit data, which the operating system pages
+//! in and out as needed.
+
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use memmap2::MmapMut;
+use rayon::prelude::*;
+
+use crate::beam::Beam;
+use crate::errors::cube::CubeError;
+use crate::io::fits::{Bitpix, Fits, Hdu, ImageHdu, ImageWriter};
+use crate::manipulation::smoothing::Kernel1D;
+use crate::spectrum::SpectralAxis;
+use crate::wcs::Wcs;
+
+use super::masking::{Regions, SignalMask, grown};
+use super::statistics::{Statistics, Sums};
+use super::{
+    Beams, Map, Metadata, Noise, NoiseMethod, SpectralCube, beams_table, cube_header, find_image,
+    read_beams,
+};
+
+/// Memory budget by default, 1 GiB.
+pub const DEFAULT_MEMORY_BUDGET: usize = 1 << 30;
+
+/// Bytes of working memory per value of a chunk: the value, its mask, and room for the
+/// result and intermediate copies.
+const BYTES_PER_VALUE: usize = 32;
+
+/// A predicate masking the value at `(x, y, z)`, in pixels of the whole cube.
+type MaskFn = dyn Fn(usize, usize, usize, f64) -> bool + Send + Sync;
+
+/// A shared mask predicate, which `Debug` shows without its body.
+#[derive(Clone)]
+struct Mask(Arc<MaskFn>);
+
+impl fmt::Debug for Mask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Mask(..)")
+    }
+}
+
+/// A cube backed by a memory-mapped FITS image, processed in chunks.
+#[derive(Debug, Clone)]
+pub struct ChunkedCube {
+    hdu: ImageHdu,
+    shape: [usize; 3],
+    wcs: Wcs,
+    spectral_axis: SpectralAxis,
+    unit: String,
+    beams: Option<Beams>,
+    mask: Option<Mask>,
+    memory_budget: usize,
+    parallel: bool,
+    /// Directory of scratch files, the system temporary directory if `None`
+    scratch_dir: Option<PathBuf>,
+}
+
+impl ChunkedCube {
+    /// Open the cube in the first image HDU with data of the file at `path`, with
+    /// per-channel beams from a `BEAMS` table if there is one. Only headers are read.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Fits`] if the file cannot be read, and otherwise as
+    /// [`SpectralCube::from_hdu`].
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, CubeError> {
+        Self::from_fits(&Fits::open(path)?)
+    }
+
+    /// As [`Self::open`], from a file already opened. The image shares the file's mapping.
+    ///
+    /// # Errors
+    /// As [`SpectralCube::from_fits`].
+    pub fn from_fits(fits: &Fits) -> Result<Self, CubeError> {
+        let hdu = find_image(fits)?;
+        let metadata = Metadata::read(hdu)?;
+        let beams = match fits.find("BEAMS") {
+            Some(Hdu::BinTable(table)) => {
+                Some(Beams::PerChannel(read_beams(table, metadata.shape[2])?))
+            }
+            _ => metadata.beam.map(Beams::Single),
+        };
+        Ok(Self {
+            hdu: hdu.clone(),
+            shape: metadata.shape,
+            wcs: metadata.wcs,
+            spectral_axis: metadata.spectral_axis,
+            unit: metadata.unit,
+            beams,
+            mask: None,
+            memory_budget: DEFAULT_MEMORY_BUDGET,
+            parallel: true,
+            scratch_dir: None,
+        })
+    }
+
+    /// Limit the memory used by the chunks processed at once to about `bytes`. Chunks are at
+    /// least one row or one channel, whatever the budget.
+    #[must_use]
+    pub fn with_memory_budget(mut self, bytes: usize) -> Self {
+        self.memory_budget = bytes;
+        self
+    }
+
+    /// Process chunks one at a time instead of in parallel.
+    #[must_use]
+    pub fn with_parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Keep scratch files, such as that of [`Self::with_signal_mask`], in `dir` rather than
+    /// the system temporary directory.
+    #[must_use]
+    pub fn with_scratch_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.scratch_dir = Some(dir.into());
+        self
+    }
+
+    /// Mask, in addition to non-finite values, the values for which `mask(x, y, z, value)`
+    /// is true. The predicate is evaluated as chunks are loaded.
+    #[must_use]
+    pub fn with_mask_fn(
+        mut self,
+        mask: impl Fn(usize, usize, usize, f64) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.mask = Some(Mask(Arc::new(mask)));
+        self
+    }
+
+    /// Axis lengths `[nx, ny, nchan]`.
+    pub fn shape(&self) -> [usize; 3] {
+        self.shape
+    }
+
+    pub fn nchan(&self) -> usize {
+        self.shape[2]
+    }
+
+    /// Celestial WCS of the two spatial axes.
+    pub fn wcs(&self) -> &Wcs {
+        &self.wcs
+    }
+
+    pub fn spectral_axis(&self) -> &SpectralAxis {
+        &self.spectral_axis
+    }
+
+    pub fn unit(&self) -> &str {
+        &self.unit
+    }
+
+    pub fn beams(&self) -> Option<&Beams> {
+        self.beams.as_ref()
+    }
+
+    /// Load the whole cube into memory.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Fits`] if the data cannot be read.
+    pub fn load(&self) -> Result<SpectralCube, CubeError> {
+        self.spectral_chunk(0..self.nchan())
+    }
+
+    /// Load channels `channels` into memory.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Fits`] if the range is out of bounds.
+    pub fn spectral_chunk(&self, channels: Range<usize>) -> Result<SpectralCube, CubeError> {
+        let plane = self.shape[0] * self.shape[1];
+        let data = self
+            .hdu
+            .read_range(channels.start * plane..channels.end * plane)?;
+        let beams = self.beams.as_ref().map(|beams| match beams {
+            Beams::Single(beam) => Beams::Single(*beam),
+            Beams::PerChannel(beams) => Beams::PerChannel(beams[channels.clone()].to_vec()),
+        });
+        let axis = self
+            .spectral_axis
+            .select(&channels.clone().collect::<Vec<_>>());
+        let shape = [self.shape[0], self.shape[1], channels.len()];
+        self.chunk(
+            data,
+            shape,
+            [0, 0, channels.start],
+            self.wcs.clone(),
+            axis,
+            beams,
+        )
+    }
+
+    /// Load rows `rows` of every channel into memory.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Fits`] if the range is out of bounds.
+    pub fn spatial_chunk(&self, rows: Range<usize>) -> Result<SpectralCube, CubeError> {
+        let [nx, ny, nchan] = self.shape;
+        let mut data = Vec::with_capacity(nx * rows.len() * nchan);
+        for z in 0..nchan {
+            let start = (z * ny + rows.start) * nx;
+            data.extend(self.hdu.read_range(start..start + rows.len() * nx)?);
+        }
+        let wcs = self.wcs.cutout(&[0.0, rows.start as f64]);
+        let shape = [nx, rows.len(), nchan];
+        let axis = self.spectral_axis.clone();
+        self.chunk(
+            data,
+            shape,
+            [0, rows.start, 0],
+            wcs,
+            axis,
+            self.beams.clone(),
+        )
+    }
+
+    /// An in-memory cube of data read from `origin`, with the mask applied.
+    fn chunk(
+        &self,
+        data: Vec<f64>,
+        shape: [usize; 3],
+        origin: [usize; 3],
+        wcs: Wcs,
+        spectral_axis: SpectralAxis,
+        beams: Option<Beams>,
+    ) -> Result<SpectralCube, CubeError> {
+        let mut cube = SpectralCube::new(data, shape, wcs, spectral_axis, self.unit.clone())?;
+        cube.beams = beams;
+        let Some(Mask(mask)) = &self.mask else {
+            return Ok(cube);
+        };
+        let mut values = Vec::with_capacity(cube.data.len());
+        for z in 0..shape[2] {
+            for y in 0..shape[1] {
+                for x in 0..shape[0] {
+                    let value = cube.data[values.len()];
+                    values.push(mask(x + origin[0], y + origin[1], z + origin[2], value));
+                }
+            }
+        }
+        cube.with_mask(values)
+    }
+
+    /// Number of chunks processed at once.
+    fn workers(&self) -> usize {
+        if self.parallel {
+            rayon::current_num_threads()
+        } else {
+            1
+        }
+    }
+
+    /// Split `len` rows or channels of `size` values each into chunks that fit the budget.
+    fn split(&self, len: usize, size: usize) -> Vec<Range<usize>> {
+        let per_chunk = self.memory_budget / (self.workers() * size.max(1) * BYTES_PER_VALUE);
+        let per_chunk = per_chunk.clamp(1, len.max(1));
+        (0..len)
+            .step_by(per_chunk)
+            .map(|start| start..(start + per_chunk).min(len))
+            .collect()
+    }
+
+    /// Apply `process` to each chunk, a batch of [`Self::workers`] at a time, and hand the
+    /// results to `sink` in order.
+    fn for_each_chunk<T: Send>(
+        &self,
+        chunks: Vec<Range<usize>>,
+        process: impl Fn(Range<usize>) -> Result<T, CubeError> + Sync,
+        mut sink: impl FnMut(Range<usize>, T) -> Result<(), CubeError>,
+    ) -> Result<(), CubeError> {
+        for batch in chunks.chunks(self.workers()) {
+            let results: Vec<_> = if self.parallel {
+                batch.par_iter().map(|r| process(r.clone())).collect()
+            } else {
+                batch.iter().map(|r| process(r.clone())).collect()
+            };
+            for (range, result) in batch.iter().zip(results) {
+                sink(range.clone(), result?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Row bands, with every channel, that fit the budget.
+    fn row_chunks(&self) -> Vec<Range<usize>> {
+        self.split(self.shape[1], self.shape[0] * self.nchan())
+    }
+
+    /// Runs of whole channels that fit the budget.
+    fn channel_chunks(&self) -> Vec<Range<usize>> {
+        self.split(self.nchan(), self.shape[0] * self.shape[1])
+    }
+
+    /// Compute a map from each band of rows and assemble them.
+    ///
+    /// # Errors
+    /// Returns any error of `f`, or [`CubeError::Fits`] if the data cannot be read.
+    pub fn map_rows(
+        &self,
+        f: impl Fn(&SpectralCube, Range<usize>) -> Result<Map, CubeError> + Sync,
+    ) -> Result<Map, CubeError> {
+        let nx = self.shape[0];
+        let mut data = Vec::with_capacity(nx * self.shape[1]);
+        let mut uncertainty: Option<Vec<f64>> = None;
+        let mut first: Option<Map> = None;
+        self.for_each_chunk(
+            self.row_chunks(),
+            |rows| f(&self.spatial_chunk(rows.clone())?, rows),
+            |_, map| {
+                data.extend_from_slice(map.data());
+                if let Some(u) = map.uncertainty() {
+                    uncertainty
+                        .get_or_insert_with(Vec::new)
+                        .extend_from_slice(u);
+                }
+                first.get_or_insert(map);
+                Ok(())
+            },
+        )?;
+        let first = first.ok_or(CubeError::EmptySelection)?;
+        let map = Map::new(data, [nx, self.shape[1]], self.wcs.clone(), first.unit())?
+            .with_beam(first.beam());
+        match uncertainty {
+            Some(uncertainty) => map.with_uncertainty(uncertainty),
+            None => Ok(map),
+        }
+    }
+
+    /// See [`SpectralCube::moment`].
+    ///
+    /// # Errors
+    /// As [`SpectralCube::moment`].
+    pub fn moment(&self, order: u8, noise: Option<&Noise>) -> Result<Map, CubeError> {
+        if let Some(noise) = noise {
+            noise.check(self.shape)?;
+        }
+        self.map_rows(|chunk, rows| {
+            let noise = noise.map(|n| n.rows(self.shape[0], rows));
+            chunk.moment(order, noise.as_ref())
+        })
+    }
+
+    /// See [`SpectralCube::linewidth_sigma`].
+    ///
+    /// # Errors
+    /// As [`SpectralCube::moment`].
+    pub fn linewidth_sigma(&self, noise: Option<&Noise>) -> Result<Map, CubeError> {
+        if let Some(noise) = noise {
+            noise.check(self.shape)?;
+        }
+        self.map_rows(|chunk, rows| {
+            let noise = noise.map(|n| n.rows(self.shape[0], rows));
+            chunk.linewidth_sigma(noise.as_ref())
+        })
+    }
+
+    /// See [`SpectralCube::linewidth_fwhm`].
+    ///
+    /// # Errors
+    /// As [`SpectralCube::moment`].
+    pub fn linewidth_fwhm(&self, noise: Option<&Noise>) -> Result<Map, CubeError> {
+        if let Some(noise) = noise {
+            noise.check(self.shape)?;
+        }
+        self.map_rows(|chunk, rows| {
+            let noise = noise.map(|n| n.rows(self.shape[0], rows));
+            chunk.linewidth_fwhm(noise.as_ref())
+        })
+    }
+
+    /// See [`SpectralCube::peak_intensity`].
+    ///
+    /// # Errors
+    /// As [`SpectralCube::moment`].
+    pub fn peak_intensity(&self, noise: Option<&Noise>) -> Result<Map, CubeError> {
+        if let Some(noise) = noise {
+            noise.check(self.shape)?;
+        }
+        self.map_rows(|chunk, rows| {
+            let noise = noise.map(|n| n.rows(self.shape[0], rows));
+            chunk.peak_intensity(noise.as_ref())
+        })
+    }
+
+    /// See [`SpectralCube::velocity_at_peak`].
+    ///
+    /// # Errors
+    /// As [`SpectralCube::velocity_at_peak`].
+    pub fn velocity_at_peak(&self) -> Result<Map, CubeError> {
+        self.map_rows(|chunk, _| chunk.velocity_at_peak())
+    }
+
+    /// See [`SpectralCube::channel_count`].
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Fits`] if the data cannot be read.
+    pub fn channel_count(&self) -> Result<Map, CubeError> {
+        self.map_rows(|chunk, _| Ok(chunk.channel_count()))
+    }
+
+    /// See [`SpectralCube::statistics`].
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Fits`] if the data cannot be read.
+    pub fn statistics(&self) -> Result<Statistics, CubeError> {
+        let mut total = Sums::default();
+        self.for_each_chunk(
+            self.channel_chunks(),
+            |channels| Ok(self.spectral_chunk(channels)?.channel_sums()),
+            |_, sums| {
+                sums.iter().for_each(|s| total.merge(s));
+                Ok(())
+            },
+        )?;
+        Ok(total.finish())
+    }
+
+    /// See [`SpectralCube::channel_noise`].
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Fits`] if the data cannot be read.
+    pub fn channel_noise(&self, method: NoiseMethod) -> Result<Noise, CubeError> {
+        let mut sigma = Vec::with_capacity(self.nchan());
+        self.for_each_chunk(
+            self.channel_chunks(),
+            |channels| Ok(self.spectral_chunk(channels)?.channel_noise(method)),
+            |_, noise| {
+                if let Noise::PerChannel(s) = noise {
+                    sigma.extend(s);
+                }
+                Ok(())
+            },
+        )?;
+        Ok(Noise::PerChannel(sigma))
+    }
+
+    /// See [`SpectralCube::pixel_noise`].
+    ///
+    /// # Errors
+    /// As [`SpectralCube::pixel_noise`], or [`CubeError::Fits`] if the data cannot be read.
+    pub fn pixel_noise(
+        &self,
+        method: NoiseMethod,
+        line_free: &[Range<usize>],
+    ) -> Result<Noise, CubeError> {
+        let mut sigma = Vec::with_capacity(self.shape[0] * self.shape[1]);
+        self.for_each_chunk(
+            self.row_chunks(),
+            |rows| self.spatial_chunk(rows)?.pixel_noise(method, line_free),
+            |_, noise| {
+                if let Noise::PerPixel(s) = noise {
+                    sigma.extend(s);
+                }
+                Ok(())
+            },
+        )?;
+        Ok(Noise::PerPixel(sigma))
+    }
+
+    /// See [`SpectralCube::with_signal_mask`]. The cube is read band by band two to four
+    /// times, and the signal mask, one bit per voxel, is written to a scratch file that is
+    /// removed when the last cube using it is dropped.
+    ///
+    /// # Errors
+    /// As [`SpectralCube::with_signal_mask`], [`CubeError::Fits`] if the data cannot be read,
+    /// or [`CubeError::Io`] if the scratch file cannot be created.
+    pub fn with_signal_mask(&self, noise: &Noise, params: &SignalMask) -> Result<Self, CubeError> {
+        noise.check(self.shape)?;
+        let [nx, ny, nchan] = self.shape;
+        // The shape of a band, its seeds and its low signal-to-noise mask.
+        let thresholds = |rows: &Range<usize>| {
+            let chunk = self.spatial_chunk(rows.clone())?;
+            let noise = noise.rows(nx, rows.clone());
+            Ok::<_, CubeError>((
+                chunk.shape(),
+                chunk.above(&noise, params.high, params.min_channels),
+                chunk.above(&noise, params.low, params.min_channels),
+            ))
+        };
+        let seed_regions = if params.prunes_seeds() {
+            Some(self.join(params.min_area, |rows| {
+                let (shape, seeds, _) = thresholds(rows)?;
+                Ok((Regions::new(shape, &seeds), None))
+            })?)
+        } else {
+            None
+        };
+        // The regions of a band that seeds grow into, and the seeds left after pruning.
+        let grown_regions = |rows: &Range<usize>| {
+            let (shape, mut seeds, low) = thresholds(rows)?;
+            if let Some(joined) = &seed_regions {
+                let regions = Regions::new(shape, &seeds);
+                let keep = joined.apply(rows, &regions, regions.keep(None, params.min_area));
+                seeds = regions.select(&keep);
+            }
+            Ok((Regions::new(shape, &grown(&seeds, &low)), Some(seeds)))
+        };
+        let joined = self.join(params.grown_min_area(), grown_regions)?;
+
+        let dir = self.scratch_dir.clone().unwrap_or_else(std::env::temp_dir);
+        let mut signal = ScratchMask::create(&dir, nx * ny * nchan)?;
+        self.for_each_chunk(
+            self.row_chunks(),
+            |rows| {
+                let (regions, seeds) = grown_regions(&rows)?;
+                let local = regions.keep(seeds.as_deref(), params.grown_min_area());
+                Ok(regions.select(&joined.apply(&rows, &regions, local)))
+            },
+            |rows, band| {
+                let width = nx * rows.len();
+                for (i, _) in band.iter().enumerate().filter(|(_, s)| **s) {
+                    let (z, offset) = (i / width, i % width);
+                    signal.set((z * ny + rows.start) * nx + offset);
+                }
+                Ok(())
+            },
+        )?;
+        let previous = self.mask.clone();
+        Ok(self.clone().with_mask_fn(move |x, y, z, value| {
+            !signal.get((z * ny + y) * nx + x)
+                || previous.as_ref().is_some_and(|m| m.0(x, y, z, value))
+        }))
+    }
+
+    /// Label the regions of each band of rows with `regions`, which also gives the seeds of
+    /// the band if regions must hold one, and join them across bands. Regions are kept if
+    /// they hold a seed and cover at least `min_area` pixels on the sky.
+    fn join(
+        &self,
+        min_area: usize,
+        regions: impl Fn(&Range<usize>) -> Result<(Regions, Option<Vec<bool>>), CubeError> + Sync,
+    ) -> Result<Joined, CubeError> {
+        let ny = self.shape[1];
+        let mut joined = Joined {
+            ny,
+            starts: Vec::new(),
+            offsets: Vec::new(),
+            keep: Vec::new(),
+        };
+        let mut parent = Vec::new();
+        let mut seeded = Vec::new();
+        // Node of each voxel of the last row of the previous band, plus one.
+        let mut previous: Vec<usize> = Vec::new();
+        self.for_each_chunk(
+            self.row_chunks(),
+            |rows| {
+                let (regions, seeds) = regions(&rows)?;
+                Ok(BandEdges::new(&regions, seeds.as_deref(), &rows, ny))
+            },
+            |rows, band| {
+                let offset = parent.len();
+                joined.starts.push(rows.start);
+                joined.offsets.push(offset);
+                parent.extend(offset..offset + band.seeded.len());
+                seeded.extend(band.seeded);
+                for (&a, &b) in previous.iter().zip(&band.first) {
+                    if a != 0 && b != 0 {
+                        union(&mut parent, a - 1, offset + b - 1);
+                    }
+                }
+                previous = band
+                    .last
+                    .iter()
+                    .map(|&e| if e == 0 { 0 } else { offset + e })
+                    .collect();
+                Ok(())
+            },
+        )?;
+        let roots: Vec<usize> = (0..parent.len()).map(|n| find(&mut parent, n)).collect();
+        let mut keep = vec![false; roots.len()];
+        for (&root, seeded) in roots.iter().zip(seeded) {
+            keep[root] |= seeded;
+        }
+        if min_area > 0 {
+            let mut areas = vec![0; roots.len()];
+            self.for_each_chunk(
+                self.row_chunks(),
+                |rows| {
+                    let (regions, _) = regions(&rows)?;
+                    let offset = joined.offset(&rows);
+                    let edges = joined.edges(&rows, &regions);
+                    Ok(regions.group_areas(|label| {
+                        (edges[label] != 0).then(|| roots[offset + edges[label] - 1])
+                    }))
+                },
+                |_, band| {
+                    band.into_iter()
+                        .for_each(|(root, area)| areas[root] += area);
+                    Ok(())
+                },
+            )?;
+            for (keep, area) in keep.iter_mut().zip(areas) {
+                *keep &= area >= min_area;
+            }
+        }
+        joined.keep = roots.iter().map(|&root| keep[root]).collect();
+        Ok(joined)
+    }
+
+    /// Apply `f` to each band of rows and write the resulting cube, which must keep the
+    /// spatial shape, to a FITS file at `path`.
+    ///
+    /// # Errors
+    /// Returns any error of `f`, [`CubeError::LengthMismatch`] if a result changes the
+    /// spatial shape or the bands disagree on the number of channels, and
+    /// [`CubeError::Fits`] if the file cannot be written.
+    pub fn write_rows(
+        &self,
+        path: impl AsRef<Path>,
+        bitpix: Bitpix,
+        f: impl Fn(SpectralCube) -> Result<SpectralCube, CubeError> + Sync,
+    ) -> Result<(), CubeError> {
+        let [nx, ny, _] = self.shape;
+        let mut output = Output::new(path.as_ref(), bitpix);
+        self.for_each_chunk(
+            self.row_chunks(),
+            |rows| f(self.spatial_chunk(rows)?),
+            |rows, cube| {
+                check_shape([nx, rows.len()], &cube)?;
+                if output.beams.is_empty()
+                    && let Some(Beams::PerChannel(beams)) = cube.beams()
+                {
+                    output.beams = beams.clone();
+                }
+                let shape = [nx, ny, cube.nchan()];
+                let writer = output.writer(&self.wcs, cube.spectral_axis(), &cube, shape)?;
+                let filled = cube.filled_data(f64::NAN);
+                let band = nx * rows.len();
+                for (z, values) in filled.chunks_exact(band).enumerate() {
+                    writer.write_at((z * ny + rows.start) * nx, values)?;
+                }
+                Ok(())
+            },
+        )?;
+        output.finish()
+    }
+
+    /// Apply `f` to each run of channels and write the resulting cube, which must keep the
+    /// shape and spectral axis, to a FITS file at `path`.
+    ///
+    /// # Errors
+    /// As [`Self::write_rows`].
+    pub fn write_channels(
+        &self,
+        path: impl AsRef<Path>,
+        bitpix: Bitpix,
+        f: impl Fn(SpectralCube) -> Result<SpectralCube, CubeError> + Sync,
+    ) -> Result<(), CubeError> {
+        let [nx, ny, _] = self.shape;
+        let mut output = Output::new(path.as_ref(), bitpix);
+        self.for_each_chunk(
+            self.channel_chunks(),
+            |channels| f(self.spectral_chunk(channels)?),
+            |channels, cube| {
+                check_shape([nx, ny], &cube)?;
+                if cube.nchan() != channels.len() {
+                    return Err(CubeError::LengthMismatch {
+                        expected: channels.len(),
+                        found: cube.nchan(),
+                    });
+                }
+                if let Some(Beams::PerChannel(beams)) = cube.beams() {
+                    output.beams.extend_from_slice(beams);
+                }
+                let writer = output.writer(&self.wcs, &self.spectral_axis, &cube, self.shape)?;
+                writer.write_at(channels.start * nx * ny, &cube.filled_data(f64::NAN))?;
+                Ok(())
+            },
+        )?;
+        output.finish()
+    }
+
+    /// Write the cube, with masked values as NaN (or `BLANK`), to a FITS file at `path`.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Fits`] if the file cannot be written.
+    pub fn write(&self, path: impl AsRef<Path>, bitpix: Bitpix) -> Result<(), CubeError> {
+        self.write_channels(path, bitpix, Ok)
+    }
+
+    /// See [`SpectralCube::convolve_to`]; the result is written to `path`.
+    ///
+    /// # Errors
+    /// As [`SpectralCube::convolve_to`] and [`Self::write_channels`].
+    pub fn convolve_to(
+        &self,
+        target: Beam,
+        path: impl AsRef<Path>,
+        bitpix: Bitpix,
+    ) -> Result<(), CubeError> {
+        self.write_channels(path, bitpix, |chunk| chunk.convolve_to(target))
+    }
+
+    /// See [`SpectralCube::spectral_smooth`]; the result is written to `path`.
+    ///
+    /// # Errors
+    /// As [`Self::write_rows`].
+    pub fn spectral_smooth(
+        &self,
+        kernel: &Kernel1D,
+        path: impl AsRef<Path>,
+        bitpix: Bitpix,
+    ) -> Result<(), CubeError> {
+        self.write_rows(path, bitpix, |chunk| Ok(chunk.spectral_smooth(kernel)))
+    }
+}
+
+/// A cube being written chunk by chunk. The file is created with the first chunk, whose
+/// spectral axis, unit and beams describe the whole cube.
+struct Output<'a> {
+    path: &'a Path,
+    bitpix: Bitpix,
+    writer: Option<ImageWriter>,
+    /// Per-channel beams, written to a `BEAMS` table
+    beams: Vec<Beam>,
+}
+
+impl<'a> Output<'a> {
+    fn new(path: &'a Path, bitpix: Bitpix) -> Self {
+        Self {
+            path,
+            bitpix,
+            writer: None,
+            beams: Vec::new(),
+        }
+    }
+
+    /// The writer, creating the file of shape `shape` on the first call.
+    fn writer(
+        &mut self,
+        wcs: &Wcs,
+        spectral_axis: &SpectralAxis,
+        chunk: &SpectralCube,
+        shape: [usize; 3],
+    ) -> Result<&mut ImageWriter, CubeError> {
+        if self.writer.is_none() {
+            let beams = match chunk.beams() {
+                Some(Beams::Single(beam)) => Some(Beams::Single(*beam)),
+                _ => None,
+            };
+            let header = cube_header(wcs, spectral_axis, chunk.unit(), beams.as_ref())?;
+            self.writer = Some(ImageWriter::create(
+                self.path,
+                &shape,
+                self.bitpix,
+                &header,
+            )?);
+        }
+        Ok(self.writer.as_mut().unwrap())
+    }
+
+    fn finish(self) -> Result<(), CubeError> {
+        let Some(writer) = self.writer else {
+            return Err(CubeError::EmptySelection);
+        };
+        let extensions = if !self.beams.is_empty() {
+            vec![Hdu::BinTable(beams_table(&self.beams)?)]
+        } else {
+            Vec::new()
+        };
+        writer.finish(&extensions)?;
+        Ok(())
+    }
+}
+
+/// The regions of the bands of a cube that touch another band, joined across bands, and the
+/// choice of which to keep. Each such region of a band is a node, numbered band by band.
+struct Joined {
+    ny: usize,
+    /// First row of each band
+    starts: Vec<usize>,
+    /// First node of each band
+    offsets: Vec<usize>,
+    /// Whether the joined region of each node is kept
+    keep: Vec<bool>,
+}
+
+impl Joined {
+    /// The first node of the band of rows `rows`.
+    fn offset(&self, rows: &Range<usize>) -> usize {
+        self.offsets[self.starts.binary_search(&rows.start).unwrap()]
+    }
+
+    /// The node of each region of the band of rows `rows`, less its offset and plus one, or
+    /// 0 for regions within the band.
+    fn edges(&self, rows: &Range<usize>, regions: &Regions) -> Vec<usize> {
+        regions.edges(rows.start > 0, rows.end < self.ny)
+    }
+
+    /// `local`, whether to keep each region of the band of rows `rows` judged from the band
+    /// alone, with the choice for the joined region in place of that of regions touching
+    /// another band.
+    fn apply(&self, rows: &Range<usize>, regions: &Regions, mut local: Vec<bool>) -> Vec<bool> {
+        let offset = self.offset(rows);
+        for (keep, edge) in local.iter_mut().zip(self.edges(rows, regions)) {
+            if edge != 0 {
+                *keep = self.keep[offset + edge - 1];
+            }
+        }
+        local
+    }
+}
+
+/// The regions of a band that touch another band.
+struct BandEdges {
+    /// Whether each region holds a seed
+    seeded: Vec<bool>,
+    /// Region of each voxel of the first row, channel by channel, counted from 1 with 0
+    /// outside the mask, or empty in the first band
+    first: Vec<usize>,
+    /// As `first`, for the last row and the last band
+    last: Vec<usize>,
+}
+
+impl BandEdges {
+    fn new(regions: &Regions, seeds: Option<&[bool]>, rows: &Range<usize>, ny: usize) -> Self {
+        let (first, last) = (rows.start > 0, rows.end < ny);
+        let edges = regions.edges(first, last);
+        let row = |on: bool, y: usize| {
+            if on {
+                regions
+                    .row(y)
+                    .into_iter()
+                    .map(|label| edges[label])
+                    .collect()
+            } else {
+                Vec::new()
+            }
+        };
+        Self {
+            seeded: edges
+                .iter()
+                .zip(regions.keep(seeds, 0))
+                .filter(|(edge, _)| **edge != 0)
+                .map(|(_, seeded)| seeded)
+                .collect(),
+            first: row(first, 0),
+            last: row(last, rows.len() - 1),
+        }
+    }
+}
+
+/// The root of node `n`, halving the paths to it.
+fn find(parent: &mut [usize], mut n: usize) -> usize {
+    while parent[n] != n {
+        parent[n] = parent[parent[n]];
+        n = parent[n];
+    }
+    n
+}
+
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parent, a), find(parent, b));
+    parent[a.max(b)] = a.min(b);
+}
+
+/// Number of scratch files created by this process, to name them uniquely.
+static SCRATCH_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// One bit per voxel in a memory-mapped scratch file, which is removed when dropped.
+struct ScratchMask {
+    map: MmapMut,
+    // Declared after the mapping so that the file is removed once unmapped.
+    _path: ScratchPath,
+}
+
+struct ScratchPath(PathBuf);
+
+impl Drop for ScratchPath {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+impl ScratchMask {
+    /// A mask of `len` bits, all unset, in a new file in `dir`.
+    fn create(dir: &Path, len: usize) -> Result<Self, CubeError> {
+        let n = SCRATCH_FILES.fetch_add(1, Ordering::Relaxed);
+        let path = ScratchPath(dir.join(format!("spectre-{}-{n}.mask", process::id())));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path.0)?;
+        file.set_len(len.div_ceil(8).max(1) as u64)?;
+        // SAFETY: the file was created with a name unique to this mask, and nothing but the
+        // mapping reads or writes it.
+        let map = unsafe { MmapMut::map_mut(&file)? };
+        Ok(Self { map, _path: path })
+    }
+
+    fn set(&mut self, i: usize) {
+        self.map[i / 8] |= 1 << (i % 8);
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self.map[i / 8] & (1 << (i % 8)) != 0
+    }
+}
+
+fn check_shape(expected: [usize; 2], cube: &SpectralCube) -> Result<(), CubeError> {
+    let [nx, ny, _] = cube.shape();
+    if [nx, ny] == expected {
+        Ok(())
+    } else {
+        Err(CubeError::LengthMismatch {
+            expected: expected[0] * expected[1],
+            found: nx * ny,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::masking::Prune;
+    use crate::cube::tests::cube_from;
+    use crate::units::angle::{arcsecond, degree};
+    use crate::units::f64::Angle;
+
+    fn beam(fwhm: f64) -> Beam {
+        Beam::new(
+            Some(Angle::new::<arcsecond>(fwhm)),
+            Some(Angle::new::<arcsecond>(fwhm)),
+            Some(Angle::new::<degree>(0.0)),
+            None,
+        )
+        .unwrap()
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("spectre-{name}-{}.fits", std::process::id()))
+    }
+
+    fn bits(values: &[f64]) -> Vec<u64> {
+        values.iter().map(|v| v.to_bits()).collect()
+    }
+
+    fn assert_same_map(chunked: &Map, in_memory: &Map) {
+        assert_eq!(bits(chunked.data()), bits(in_memory.data()));
+        assert_eq!(
+            chunked.uncertainty().map(bits),
+            in_memory.uncertainty().map(bits)
+        );
+        assert_eq!(chunked.unit(), in_memory.unit());
+        assert_eq!(chunked.beam(), in_memory.beam());
+        assert_eq!(chunked.wcs(), in_memory.wcs());
+    }
+
+    /// Cubes compare unequal when they hold NaN, so compare the bits of the values.
+    fn assert_same_cube(chunked: &SpectralCube, in_memory: &SpectralCube) {
+        assert_eq!(bits(chunked.data()), bits(in_memory.data()));
+        assert_eq!(chunked.mask(), in_memory.mask());
+        assert_eq!(chunked.shape(), in_memory.shape());
+        assert_eq!(chunked.wcs(), in_memory.wcs());
+        assert_eq!(chunked.spectral_axis(), in_memory.spectral_axis());
+        assert_eq!(chunked.unit(), in_memory.unit());
+        assert_eq!(chunked.beams(), in_memory.beams());
+    }
+
+    #[test]
+    fn test_chunked_matches_in_memory() {
+        // A Gaussian line whose centre moves across the cube, with a blanked pixel.
+        let shape = [9, 7, 12];
+        let cube = cube_from(shape, |x, y, z| {
+            if (x, y, z) == (4, 3, 5) {
+                return f64::NAN;
+            }
+            let centre = 4.0 + 0.3 * x as f64 - 0.2 * y as f64;
+            (-(z as f64 - centre).powi(2) / 4.0).exp() * (1.0 + 0.1 * y as f64)
+        })
+        .with_beams((0..12).map(|z| beam(2.0 + 0.1 * z as f64)).collect())
+        .unwrap();
+        let path = temp_path("chunked");
+        cube.to_fits(Bitpix::F64).unwrap().write(&path).unwrap();
+        let in_memory = SpectralCube::from_fits(&Fits::open(&path).unwrap()).unwrap();
+
+        // A budget this small gives one row or one channel per chunk.
+        for parallel in [false, true] {
+            let chunked = ChunkedCube::open(&path)
+                .unwrap()
+                .with_memory_budget(1)
+                .with_parallel(parallel);
+            assert_eq!(chunked.shape(), shape);
+            assert_eq!(chunked.beams(), in_memory.beams());
+            assert_same_cube(&chunked.load().unwrap(), &in_memory);
+            assert_eq!(chunked.row_chunks().len(), 7);
+
+            let noise = Noise::PerPixel((0..63).map(|i| 0.01 * (1 + i % 5) as f64).collect());
+            for order in 0..3 {
+                assert_same_map(
+                    &chunked.moment(order, Some(&noise)).unwrap(),
+                    &in_memory.moment(order, Some(&noise)).unwrap(),
+                );
+            }
+            assert_same_map(
+                &chunked.linewidth_fwhm(None).unwrap(),
+                &in_memory.linewidth_fwhm(None).unwrap(),
+            );
+            assert_same_map(
+                &chunked.peak_intensity(Some(&noise)).unwrap(),
+                &in_memory.peak_intensity(Some(&noise)).unwrap(),
+            );
+            assert_same_map(
+                &chunked.velocity_at_peak().unwrap(),
+                &in_memory.velocity_at_peak().unwrap(),
+            );
+            assert_same_map(
+                &chunked.channel_count().unwrap(),
+                &in_memory.channel_count(),
+            );
+            assert_eq!(chunked.statistics().unwrap(), in_memory.statistics());
+
+            let method = NoiseMethod::Mad;
+            assert_eq!(
+                chunked.channel_noise(method).unwrap(),
+                in_memory.channel_noise(method)
+            );
+            let pixel = chunked.pixel_noise(method, &[0..3, 9..12]).unwrap();
+            assert_eq!(
+                pixel,
+                in_memory.pixel_noise(method, &[0..3, 9..12]).unwrap()
+            );
+
+            // Regions grow across chunk boundaries as in memory.
+            let params = SignalMask::new(4.0, 1.5).with_min_area(3);
+            let masked = chunked
+                .with_mask_fn(|x, _, _, _| x == 8)
+                .with_signal_mask(&Noise::Uniform(0.2), &params)
+                .unwrap();
+            let expected = in_memory
+                .clone()
+                .with_mask(
+                    (0..in_memory.data().len())
+                        .map(|i| in_memory.mask()[i] || i % 9 == 8)
+                        .collect(),
+                )
+                .unwrap()
+                .with_signal_mask(&Noise::Uniform(0.2), &params)
+                .unwrap();
+            assert_same_cube(&masked.load().unwrap(), &expected);
+            assert!(expected.statistics().npix > 0);
+            assert!(expected.statistics().npix < 9 * 7 * 12);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_signal_mask_across_bands() {
+        // Bands are single rows. A seed region at x = 0 covers two pixels, but enters row 1
+        // twice, in channels 0 and 2, joined only through row 0. A seed region at x = 3
+        // spans all three rows and grows into a faint voxel at (2, 2, 3). The faint voxel at
+        // (1, 2, 5) touches no seed.
+        let cube = cube_from([4, 3, 6], |x, y, z| match (x, y, z) {
+            (0, 0, 0..=2) | (0, 1, 0 | 2) | (3, 0..=2, 3..=4) => 10.0,
+            (2, 2, 3) | (1, 2, 5) => 3.0,
+            _ => 0.0,
+        });
+        let path = temp_path("signal-bands");
+        cube.to_fits(Bitpix::F64).unwrap().write(&path).unwrap();
+        let in_memory = SpectralCube::from_fits(&Fits::open(&path).unwrap()).unwrap();
+        let scratch = std::env::temp_dir().join(format!("spectre-scratch-{}", process::id()));
+        fs::create_dir_all(&scratch).unwrap();
+        let chunked = ChunkedCube::open(&path)
+            .unwrap()
+            .with_memory_budget(1)
+            .with_scratch_dir(&scratch);
+        assert_eq!(chunked.row_chunks().len(), 3);
+
+        let noise = Noise::Uniform(1.0);
+        let params = SignalMask::new(4.0, 2.0).with_min_channels(1);
+        for params in [
+            params,
+            params.with_min_area(3),
+            params.with_min_area(3).with_prune(Prune::Grown),
+        ] {
+            let masked = chunked.with_signal_mask(&noise, &params).unwrap();
+            let expected = in_memory.with_signal_mask(&noise, &params).unwrap();
+            assert_same_cube(&masked.load().unwrap(), &expected);
+            assert_eq!(fs::read_dir(&scratch).unwrap().count(), 1);
+        }
+        // The scratch file goes with the last cube using it.
+        assert_eq!(fs::read_dir(&scratch).unwrap().count(), 0);
+
+        let masked = chunked
+            .with_signal_mask(&noise, &params.with_min_area(3))
+            .unwrap()
+            .load()
+            .unwrap();
+        // Counting row 1 of the first region once per entry would give it three pixels.
+        assert!(masked.get(0, 0, 1).is_none() && masked.get(0, 1, 2).is_none());
+        assert_eq!(masked.get(3, 1, 4), Some(10.0));
+        assert_eq!(masked.get(2, 2, 3), Some(3.0));
+        assert!(masked.get(1, 2, 5).is_none());
+        assert_eq!(masked.statistics().npix, 7);
+
+        fs::remove_dir(&scratch).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_chunked_writes_match_in_memory() {
+        let shape = [15, 13, 6];
+        let point = |x: usize, y: usize, z: usize| {
+            if (x, y) == (7, 6) {
+                1.0 + z as f64
+            } else {
+                0.0
+            }
+        };
+        let cube = cube_from(shape, point)
+            .with_beams((0..6).map(|z| beam(2.0 + 0.2 * z as f64)).collect())
+            .unwrap();
+        let path = temp_path("chunked-input");
+        cube.to_fits(Bitpix::F64).unwrap().write(&path).unwrap();
+        let cube = SpectralCube::from_fits(&Fits::open(&path).unwrap()).unwrap();
+        let chunked = ChunkedCube::open(&path).unwrap().with_memory_budget(1);
+        let output = temp_path("chunked-output");
+        let read = || SpectralCube::from_fits(&Fits::open(&output).unwrap()).unwrap();
+
+        let target = beam(4.0);
+        chunked.convolve_to(target, &output, Bitpix::F64).unwrap();
+        assert_same_cube(&read(), &cube.convolve_to(target).unwrap());
+
+        let kernel = Kernel1D::boxcar(3.0).unwrap();
+        chunked
+            .spectral_smooth(&kernel, &output, Bitpix::F64)
+            .unwrap();
+        assert_same_cube(&read(), &cube.spectral_smooth(&kernel));
+
+        // Masked values are written blank.
+        let masked = chunked.with_mask_fn(|x, _, z, _| x == 0 || z == 5);
+        masked.write(&output, Bitpix::F32).unwrap();
+        let written = read();
+        assert_eq!(written.beams(), cube.beams());
+        assert_eq!(written.statistics(), masked.statistics().unwrap());
+        assert_eq!(written.statistics().npix, 14 * 13 * 5);
+        assert!(written.get(0, 3, 2).is_none());
+        assert_eq!(written.get(7, 6, 4), Some(5.0));
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&output).unwrap();
+    }
+}
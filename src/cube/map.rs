@@ -0,0 +1,150 @@
+//! Two-dimensional images derived from a cube.
+
+use crate::beam::Beam;
+use crate::errors::cube::CubeError;
+use crate::io::fits::{Bitpix, ImageHdu};
+use crate::wcs::Wcs;
+
+use super::{SpectralCube, check_len, image_hdu};
+
+/// A two-dimensional image on the celestial grid of a cube, such as a moment map. Invalid
+/// pixels, for example where every channel is masked, are NaN.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Map {
+    data: Vec<f64>,
+    shape: [usize; 2],
+    wcs: Wcs,
+    unit: String,
+    uncertainty: Option<Vec<f64>>,
+    beam: Option<Beam>,
+}
+
+impl Map {
+    /// Create a map from data of shape `[nx, ny]` in FITS order and a two-axis celestial WCS.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::LengthMismatch`] if the data do not match `shape`.
+    pub fn new(
+        data: Vec<f64>,
+        shape: [usize; 2],
+        wcs: Wcs,
+        unit: impl Into<String>,
+    ) -> Result<Self, CubeError> {
+        check_len(shape[0] * shape[1], data.len())?;
+        Ok(Self {
+            data,
+            shape,
+            wcs,
+            unit: unit.into(),
+            uncertainty: None,
+            beam: None,
+        })
+    }
+
+    /// A map on the grid of `cube`, with its beam if all channels share one.
+    pub(super) fn from_cube(
+        cube: &SpectralCube,
+        data: Vec<f64>,
+        uncertainty: Option<Vec<f64>>,
+        unit: impl Into<String>,
+    ) -> Self {
+        let [nx, ny, _] = cube.shape();
+        Self {
+            data,
+            shape: [nx, ny],
+            wcs: cube.wcs().clone(),
+            unit: unit.into(),
+            uncertainty,
+            beam: cube.common_beam(),
+        }
+    }
+
+    /// Attach a one-sigma uncertainty to each pixel.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::LengthMismatch`] if the uncertainty and data differ in length.
+    pub fn with_uncertainty(mut self, uncertainty: Vec<f64>) -> Result<Self, CubeError> {
+        check_len(self.data.len(), uncertainty.len())?;
+        self.uncertainty = Some(uncertainty);
+        Ok(self)
+    }
+
+    #[must_use]
+    pub fn with_beam(mut self, beam: Option<Beam>) -> Self {
+        self.beam = beam;
+        self
+    }
+
+    /// Axis lengths `[nx, ny]`.
+    pub fn shape(&self) -> [usize; 2] {
+        self.shape
+    }
+
+    pub fn data(&self) -> &[f64] {
+        &self.data
+    }
+
+    /// Value of pixel `(x, y)`, NaN if invalid.
+    ///
+    /// # Panics
+    /// Panics if the pixel is outside the map.
+    pub fn get(&self, x: usize, y: usize) -> f64 {
+        assert!(
+            x < self.shape[0] && y < self.shape[1],
+            "pixel out of bounds"
+        );
+        self.data[y * self.shape[0] + x]
+    }
+
+    pub fn wcs(&self) -> &Wcs {
+        &self.wcs
+    }
+
+    pub fn unit(&self) -> &str {
+        &self.unit
+    }
+
+    /// One-sigma uncertainty of each pixel, if propagated from a noise estimate.
+    pub fn uncertainty(&self) -> Option<&[f64]> {
+        self.uncertainty.as_deref()
+    }
+
+    pub fn beam(&self) -> Option<Beam> {
+        self.beam
+    }
+
+    /// The map as an image HDU with its celestial WCS, `BUNIT` and beam.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Fits`] if the data cannot be encoded with `bitpix`.
+    pub fn to_hdu(&self, bitpix: Bitpix) -> Result<ImageHdu, CubeError> {
+        image_hdu(
+            &self.shape,
+            &self.data,
+            bitpix,
+            &self.wcs,
+            &self.unit,
+            self.beam.as_ref(),
+        )
+    }
+
+    /// The uncertainty as an image HDU, with the same header as [`Self::to_hdu`].
+    ///
+    /// # Errors
+    /// As [`Self::to_hdu`].
+    pub fn uncertainty_hdu(&self, bitpix: Bitpix) -> Result<Option<ImageHdu>, CubeError> {
+        self.uncertainty
+            .as_ref()
+            .map(|uncertainty| {
+                let mut hdu = Self {
+                    data: uncertainty.clone(),
+                    uncertainty: None,
+                    ..self.clone()
+                }
+                .to_hdu(bitpix)?;
+                hdu.header_mut().set("EXTNAME", "ERROR");
+                Ok(hdu)
+            })
+            .transpose()
+    }
+}
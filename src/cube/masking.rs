@@ -0,0 +1,442 @@
+//! Signal masks built by dilation, as in the PHANGS-ALMA pipeline (Leroy et al. 2021).
+//!
+//! A high signal-to-noise mask seeds the regions of emission, which are then grown into the
+//! connected regions of a low signal-to-noise mask. Both masks keep only voxels in runs of
+//! at least `min_channels` consecutive channels above the threshold, which rejects single
+//! channel noise spikes. Regions covering fewer than `min_area` pixels on the sky are
+//! dropped, as chosen by [`Prune`]: either the seed regions before growing, or, as
+//! PHANGS-ALMA does, the regions of the grown mask. The first also rejects small bright
+//! seeds with large faint surroundings. Voxels are connected through their faces.
+
+use std::collections::HashMap;
+
+use crate::errors::cube::CubeError;
+
+use super::{Noise, SpectralCube};
+
+/// Parameters of a dilated signal mask.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SignalMask {
+    /// Signal-to-noise ratio of the seeds
+    pub high: f64,
+    /// Signal-to-noise ratio down to which seeds are grown
+    pub low: f64,
+    /// Minimum number of consecutive channels above each threshold
+    pub min_channels: usize,
+    /// Minimum area of a region projected on the sky, in pixels
+    pub min_area: usize,
+    /// Which regions `min_area` applies to
+    pub prune: Prune,
+}
+
+/// The regions pruned by [`SignalMask::min_area`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Prune {
+    /// The seed regions, before they are grown
+    #[default]
+    Seeds,
+    /// The regions of the grown mask, as in PHANGS-ALMA
+    Grown,
+}
+
+impl Default for SignalMask {
+    /// The PHANGS-ALMA defaults: seeds at 4σ in 2 channels grown down to 2σ in 2 channels.
+    fn default() -> Self {
+        Self::new(4.0, 2.0)
+    }
+}
+
+impl SignalMask {
+    /// Seeds at `high` times the noise grown down to `low` times the noise, both in at least
+    /// two consecutive channels, without pruning by area.
+    pub fn new(high: f64, low: f64) -> Self {
+        Self {
+            high,
+            low,
+            min_channels: 2,
+            min_area: 0,
+            prune: Prune::Seeds,
+        }
+    }
+
+    #[must_use]
+    pub fn with_min_channels(mut self, channels: usize) -> Self {
+        self.min_channels = channels;
+        self
+    }
+
+    #[must_use]
+    pub fn with_min_area(mut self, pixels: usize) -> Self {
+        self.min_area = pixels;
+        self
+    }
+
+    #[must_use]
+    pub fn with_prune(mut self, prune: Prune) -> Self {
+        self.prune = prune;
+        self
+    }
+}
+
+impl SpectralCube {
+    /// Mask everything but the signal found with `params`, given the noise of the cube. The
+    /// result can be passed on to [`Self::moment`] and the other maps.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::LengthMismatch`] if `noise` does not match the cube.
+    pub fn with_signal_mask(&self, noise: &Noise, params: &SignalMask) -> Result<Self, CubeError> {
+        noise.check(self.shape)?;
+        let seeds = self.above(noise, params.high, params.min_channels);
+        let low = self.above(noise, params.low, params.min_channels);
+        let signal = signal(self.shape, seeds, &low, params);
+        let mask = self
+            .mask
+            .iter()
+            .zip(signal)
+            .map(|(&masked, signal)| masked || !signal)
+            .collect();
+        self.clone().with_mask(mask)
+    }
+
+    /// Voxels at least `level` times the noise in a run of `min_channels` or more
+    /// consecutive unmasked channels.
+    pub(super) fn above(&self, noise: &Noise, level: f64, min_channels: usize) -> Vec<bool> {
+        let plane = self.shape[0] * self.shape[1];
+        let mut result = vec![false; self.data.len()];
+        for pixel in 0..plane {
+            let mut start = 0;
+            for z in 0..=self.nchan() {
+                let i = z * plane + pixel;
+                let is_above = z < self.nchan()
+                    && !self.mask[i]
+                    && self.data[i] >= level * noise.sigma(pixel, z);
+                if is_above {
+                    continue;
+                }
+                if z - start >= min_channels.max(1) {
+                    (start..z).for_each(|c| result[c * plane + pixel] = true);
+                }
+                start = z + 1;
+            }
+        }
+        result
+    }
+}
+
+impl SignalMask {
+    /// Whether the seed regions are pruned by area before they are grown.
+    pub(super) fn prunes_seeds(&self) -> bool {
+        self.min_area > 0 && self.prune == Prune::Seeds
+    }
+
+    /// Minimum area of the grown regions, zero unless they are pruned.
+    pub(super) fn grown_min_area(&self) -> usize {
+        match self.prune {
+            Prune::Seeds => 0,
+            Prune::Grown => self.min_area,
+        }
+    }
+}
+
+/// The voxels of a cube of shape `shape` found by growing `seeds` into `low`, with the
+/// regions smaller than `params.min_area` dropped.
+pub(super) fn signal(
+    shape: [usize; 3],
+    seeds: Vec<bool>,
+    low: &[bool],
+    params: &SignalMask,
+) -> Vec<bool> {
+    let seeds = if params.prunes_seeds() {
+        let regions = Regions::new(shape, &seeds);
+        regions.select(&regions.keep(None, params.min_area))
+    } else {
+        seeds
+    };
+    let regions = Regions::new(shape, &grown(&seeds, low));
+    regions.select(&regions.keep(Some(&seeds), params.grown_min_area()))
+}
+
+/// The voxels that seeds may grow into: the seeds and the voxels of `low`.
+pub(super) fn grown(seeds: &[bool], low: &[bool]) -> Vec<bool> {
+    seeds.iter().zip(low).map(|(&s, &l)| s || l).collect()
+}
+
+/// Connected regions of a mask over a cube, or over a band of rows of one.
+pub(super) struct Regions {
+    shape: [usize; 3],
+    /// Region of each voxel, counted from 1, or 0 outside the mask
+    labels: Vec<usize>,
+    count: usize,
+}
+
+impl Regions {
+    /// Label the connected regions of `mask`, a cube of shape `shape`.
+    pub(super) fn new(shape: [usize; 3], mask: &[bool]) -> Self {
+        let mut labels = vec![0; mask.len()];
+        let mut count = 0;
+        let mut stack = Vec::new();
+        for start in 0..mask.len() {
+            if !mask[start] || labels[start] != 0 {
+                continue;
+            }
+            count += 1;
+            labels[start] = count;
+            stack.push(start);
+            while let Some(i) = stack.pop() {
+                for j in neighbours(shape, i) {
+                    if mask[j] && labels[j] == 0 {
+                        labels[j] = count;
+                        stack.push(j);
+                    }
+                }
+            }
+        }
+        Self {
+            shape,
+            labels,
+            count,
+        }
+    }
+
+    /// Region of the voxel of sky pixel `pixel` in channel `z`, or 0 outside the mask.
+    pub(super) fn label(&self, pixel: usize, z: usize) -> usize {
+        self.labels[z * self.shape[0] * self.shape[1] + pixel]
+    }
+
+    /// Which regions to keep, indexed by region: those holding a voxel of `seeds`, if
+    /// given, and covering at least `min_area` pixels on the sky.
+    pub(super) fn keep(&self, seeds: Option<&[bool]>, min_area: usize) -> Vec<bool> {
+        let mut keep = vec![seeds.is_none(); self.count + 1];
+        if let Some(seeds) = seeds {
+            for (&label, _) in self.labels.iter().zip(seeds).filter(|(_, s)| **s) {
+                keep[label] = true;
+            }
+        }
+        if min_area > 0 {
+            for (keep, area) in keep.iter_mut().zip(self.areas()) {
+                *keep &= area >= min_area;
+            }
+        }
+        keep[0] = false;
+        keep
+    }
+
+    /// Number of sky pixels covered by each region, indexed by region.
+    fn areas(&self) -> Vec<usize> {
+        let mut areas = vec![0; self.count + 1];
+        for (label, area) in self.group_areas(|label| (label != 0).then_some(label)) {
+            areas[label] = area;
+        }
+        areas
+    }
+
+    /// Number of sky pixels covered by each group of regions, where `group` gives the group
+    /// of a region, if any.
+    pub(super) fn group_areas(
+        &self,
+        group: impl Fn(usize) -> Option<usize>,
+    ) -> HashMap<usize, usize> {
+        let mut areas = HashMap::new();
+        // The groups already counted in the current pixel.
+        let mut counted = Vec::new();
+        for pixel in 0..self.shape[0] * self.shape[1] {
+            counted.clear();
+            for z in 0..self.shape[2] {
+                if let Some(g) = group(self.label(pixel, z))
+                    && !counted.contains(&g)
+                {
+                    counted.push(g);
+                    *areas.entry(g).or_insert(0) += 1;
+                }
+            }
+        }
+        areas
+    }
+
+    /// Regions of the voxels of row `y`, channel by channel.
+    pub(super) fn row(&self, y: usize) -> Vec<usize> {
+        let nx = self.shape[0];
+        (0..self.shape[2])
+            .flat_map(|z| (0..nx).map(move |x| self.label(y * nx + x, z)))
+            .collect()
+    }
+
+    /// Number each region with a voxel in the first row, if `first`, or the last row, if
+    /// `last`, from 1 in order of region, indexed by region; other regions are 0.
+    pub(super) fn edges(&self, first: bool, last: bool) -> Vec<usize> {
+        let mut edges = vec![0; self.count + 1];
+        let rows = [(first, 0), (last, self.shape[1] - 1)];
+        for (_, y) in rows.into_iter().filter(|(on, _)| *on) {
+            self.row(y).into_iter().for_each(|label| edges[label] = 1);
+        }
+        edges[0] = 0;
+        let mut next = 0;
+        for edge in edges.iter_mut().filter(|e| **e != 0) {
+            next += 1;
+            *edge = next;
+        }
+        edges
+    }
+
+    /// The voxels of the regions for which `keep` is true.
+    pub(super) fn select(&self, keep: &[bool]) -> Vec<bool> {
+        self.labels.iter().map(|&label| keep[label]).collect()
+    }
+}
+
+/// The voxels sharing a face with voxel `i` of a cube of shape `shape`.
+fn neighbours(shape: [usize; 3], i: usize) -> impl Iterator<Item = usize> {
+    let [nx, ny, nchan] = shape;
+    let plane = nx * ny;
+    let (x, y, z) = (i % nx, i / nx % ny, i / plane);
+    [
+        (x > 0).then(|| i - 1),
+        (x + 1 < nx).then(|| i + 1),
+        (y > 0).then(|| i - nx),
+        (y + 1 < ny).then(|| i + nx),
+        (z > 0).then(|| i - plane),
+        (z + 1 < nchan).then(|| i + plane),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::tests::cube_from;
+
+    #[test]
+    fn test_signal_mask() {
+        // A bright source at (2, 2–3) with a faint wing at (3, 2), a faint isolated region
+        // at (6, 6), a one-channel spike at (0, 6) and a one-pixel bright source at (6, 0).
+        let cube = cube_from([8, 8, 8], |x, y, z| match (x, y, z) {
+            (2, 2..=3, 2..=5) => 10.0,
+            (2, 2, 1 | 6) | (3, 2, 2..=4) => 3.0,
+            (6, 6, 2..=4) => 3.0,
+            (0, 6, 3) => 10.0,
+            (6, 0, 3..=4) => 10.0,
+            _ => 0.0,
+        });
+        let noise = Noise::Uniform(1.0);
+        let masked = cube
+            .with_signal_mask(&noise, &SignalMask::default())
+            .unwrap();
+        let signal = |x, y, z| masked.get(x, y, z).is_some();
+        assert!((1..=6).all(|z| signal(2, 2, z)));
+        assert!((2..=4).all(|z| signal(3, 2, z)));
+        assert!(!signal(2, 2, 0) && !signal(2, 2, 7));
+        assert!(!signal(6, 6, 3));
+        assert!(!signal(0, 6, 3));
+        assert!(signal(6, 0, 3));
+        assert_eq!(masked.statistics().npix, 8 + 2 + 3 + 2);
+
+        // The small source covers one pixel on the sky and is pruned with the spike.
+        let pruned = cube
+            .with_signal_mask(&noise, &SignalMask::default().with_min_area(2))
+            .unwrap();
+        assert_eq!(pruned.statistics().npix, 13);
+        assert!(pruned.get(6, 0, 3).is_none());
+
+        // The area is that of the seeds: the bright source's seeds cover two pixels, so it
+        // is dropped although it would grow to cover three.
+        let pruned = cube
+            .with_signal_mask(&noise, &SignalMask::default().with_min_area(3))
+            .unwrap();
+        assert_eq!(pruned.statistics().npix, 0);
+
+        // With single-channel runs allowed the spike is kept.
+        let spiky = cube
+            .with_signal_mask(&noise, &SignalMask::new(4.0, 2.0).with_min_channels(1))
+            .unwrap();
+        assert!(spiky.get(0, 6, 3).is_some());
+
+        // Pruning the grown regions keeps the bright source, which grows to three pixels.
+        let grown = cube
+            .with_signal_mask(
+                &noise,
+                &SignalMask::default()
+                    .with_min_area(3)
+                    .with_prune(Prune::Grown),
+            )
+            .unwrap();
+        assert_eq!(grown.statistics().npix, 13);
+        assert!(grown.get(6, 0, 3).is_none());
+
+        assert!(matches!(
+            cube.with_signal_mask(&Noise::PerChannel(vec![1.0; 3]), &SignalMask::default()),
+            Err(CubeError::LengthMismatch { .. })
+        ));
+    }
+
+    /// The cube of `test_signal_mask` without its faint region and spike, with an
+    /// optional blanked voxel.
+    fn sources(blank: Option<(usize, usize, usize)>) -> SpectralCube {
+        cube_from([8, 8, 8], |x, y, z| match (x, y, z) {
+            _ if Some((x, y, z)) == blank => f64::NAN,
+            (2, 2..=3, 2..=5) => 10.0,
+            (2, 2, 1 | 6) | (3, 2, 2..=4) => 3.0,
+            (6, 0, 3..=4) => 10.0,
+            _ => 0.0,
+        })
+    }
+
+    #[test]
+    fn test_signal_mask_min_channels() {
+        let cube = sources(None);
+        let noise = Noise::Uniform(1.0);
+        // The two-channel source is dropped; the three-channel wing is kept.
+        let masked = cube
+            .with_signal_mask(&noise, &SignalMask::default().with_min_channels(3))
+            .unwrap();
+        assert_eq!(masked.statistics().npix, 13);
+        assert!(masked.get(6, 0, 3).is_none());
+        assert!(masked.get(3, 2, 3).is_some());
+        let masked = cube
+            .with_signal_mask(&noise, &SignalMask::default().with_min_channels(4))
+            .unwrap();
+        assert_eq!(masked.statistics().npix, 10);
+        assert!(masked.get(3, 2, 3).is_none());
+    }
+
+    #[test]
+    fn test_signal_mask_blanked_input() {
+        let noise = Noise::Uniform(1.0);
+        // A NaN splits the run of (2, 3) into one channel, which is dropped, and two.
+        let cube = sources(Some((2, 3, 3)));
+        let masked = cube
+            .with_signal_mask(&noise, &SignalMask::default())
+            .unwrap();
+        assert_eq!(masked.statistics().npix, 13);
+        assert!(masked.get(2, 3, 2).is_none());
+        assert!(masked.get(2, 3, 4).is_some());
+
+        // A masked value breaks runs the same way.
+        let mut mask = cube.mask().to_vec();
+        mask[(3 * 8) * 8 + 6] = true;
+        let masked = cube
+            .with_mask(mask)
+            .unwrap()
+            .with_signal_mask(&noise, &SignalMask::default())
+            .unwrap();
+        assert_eq!(masked.statistics().npix, 11);
+        assert!(masked.get(6, 0, 4).is_none());
+    }
+
+    #[test]
+    fn test_signal_mask_pixel_noise() {
+        let cube = sources(None);
+        // Noisy enough at (6, 0) for its source to fall below both thresholds.
+        let mut sigma = vec![1.0; 64];
+        sigma[6] = 10.0;
+        let masked = cube
+            .with_signal_mask(&Noise::PerPixel(sigma), &SignalMask::default())
+            .unwrap();
+        assert_eq!(masked.statistics().npix, 13);
+        assert!(masked.get(6, 0, 3).is_none());
+        assert!(matches!(
+            cube.with_signal_mask(&Noise::PerPixel(vec![1.0; 8]), &SignalMask::default()),
+            Err(CubeError::LengthMismatch { .. })
+        ));
+    }
+}
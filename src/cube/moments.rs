@@ -0,0 +1,319 @@
+//! Moment maps and other maps computed along the spectral axis, modelled on
+//! `SpectralCube.moment` and its relatives.
+//!
+//! Masked channels are left out. Integrals are sums of intensity times channel width, with
+//! widths taken from the bin edges of the spectral axis, and spectral coordinates are in the
+//! SI unit of the axis, so a cube in velocity gives a moment 0 in, say, `K m / s`. Convert the
+//! axis with [`SpectralCube::with_spectral_unit`] first to take moments in another kind.
+//!
+//! Given a [`Noise`], uncertainties are propagated to first order assuming independent
+//! channels.
+
+use crate::analysis::Channels;
+use crate::constants::SIGMA_TO_FWHM;
+use crate::errors::cube::CubeError;
+use crate::spectrum::{SpectralKind, unit_product};
+
+use super::{Map, Noise, SpectralCube};
+
+impl SpectralCube {
+    /// Apply `f` to the unmasked channels of every spatial pixel, giving a value and, given
+    /// a noise, a variance. Pixels with no unmasked channel are NaN.
+    fn map_channels(
+        &self,
+        x: &[f64],
+        noise: Option<&Noise>,
+        f: impl Fn(&Channels) -> (f64, Option<f64>),
+    ) -> Result<(Vec<f64>, Option<Vec<f64>>), CubeError> {
+        if let Some(noise) = noise {
+            noise.check(self.shape)?;
+        }
+        let edges = self.spectral_axis.bin_edges();
+        let plane = self.shape[0] * self.shape[1];
+        let mut values = Vec::with_capacity(plane);
+        let mut errors = Vec::with_capacity(plane);
+        for pixel in 0..plane {
+            let keep: Vec<usize> = (0..self.nchan())
+                .filter(|z| !self.mask[z * plane + pixel])
+                .collect();
+            if keep.is_empty() {
+                values.push(f64::NAN);
+                errors.push(f64::NAN);
+                continue;
+            }
+            let channels = Channels {
+                x: keep.iter().map(|&z| x[z]).collect(),
+                dx: keep
+                    .iter()
+                    .map(|&z| (edges[z + 1] - edges[z]).abs())
+                    .collect(),
+                flux: keep.iter().map(|&z| self.data[z * plane + pixel]).collect(),
+                variance: noise.map(|n| keep.iter().map(|&z| n.sigma(pixel, z).powi(2)).collect()),
+            };
+            let (value, variance) = f(&channels);
+            values.push(value);
+            errors.push(variance.map_or(f64::NAN, f64::sqrt));
+        }
+        Ok((values, noise.map(|_| errors)))
+    }
+
+    /// Moment of order `order` along the spectral axis: the integrated intensity for order 0,
+    /// the intensity-weighted mean spectral coordinate for order 1, and central moments
+    /// about that mean for higher orders (so order 2 is the variance of the line).
+    ///
+    /// # Errors
+    /// Returns [`CubeError::LengthMismatch`] if the noise does not match the cube.
+    pub fn moment(&self, order: u8, noise: Option<&Noise>) -> Result<Map, CubeError> {
+        let order = i32::from(order);
+        let (values, errors) =
+            self.map_channels(self.spectral_axis.values(), noise, |c| c.moment(order))?;
+        let spectral = self.spectral_axis.kind().si_unit();
+        let unit = match order {
+            0 => unit_product(&self.unit, spectral),
+            _ => power_unit(spectral, order),
+        };
+        Ok(Map::from_cube(self, values, errors, unit))
+    }
+
+    /// Line width σ, the square root of the second moment. Pixels where the second moment is
+    /// negative, as in noise-dominated spectra, are NaN.
+    ///
+    /// # Errors
+    /// As [`Self::moment`].
+    pub fn linewidth_sigma(&self, noise: Option<&Noise>) -> Result<Map, CubeError> {
+        let (values, errors) =
+            self.map_channels(self.spectral_axis.values(), noise, Channels::dispersion)?;
+        let unit = self.spectral_axis.kind().si_unit();
+        Ok(Map::from_cube(self, values, errors, unit))
+    }
+
+    /// Line full width at half maximum, `√(8 ln 2) σ` for a Gaussian line.
+    ///
+    /// # Errors
+    /// As [`Self::moment`].
+    pub fn linewidth_fwhm(&self, noise: Option<&Noise>) -> Result<Map, CubeError> {
+        let sigma = self.linewidth_sigma(noise)?;
+        let scale = |values: &[f64]| values.iter().map(|v| SIGMA_TO_FWHM * v).collect();
+        Ok(Map::from_cube(
+            self,
+            scale(sigma.data()),
+            sigma.uncertainty().map(scale),
+            sigma.unit(),
+        ))
+    }
+
+    /// Maximum intensity along the spectral axis, with the noise of that channel.
+    ///
+    /// # Errors
+    /// As [`Self::moment`].
+    pub fn peak_intensity(&self, noise: Option<&Noise>) -> Result<Map, CubeError> {
+        let (values, errors) = self.map_channels(self.spectral_axis.values(), noise, |c| {
+            let p = c.peak_index();
+            (c.flux[p], c.variance.as_ref().map(|v| v[p]))
+        })?;
+        Ok(Map::from_cube(self, values, errors, self.unit.as_str()))
+    }
+
+    /// Velocity, in m/s, of the channel of maximum intensity.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Spectrum`] if the spectral axis cannot be converted to velocity.
+    pub fn velocity_at_peak(&self) -> Result<Map, CubeError> {
+        let velocity = self.spectral_axis.to_kind(SpectralKind::Velocity)?;
+        let (values, _) =
+            self.map_channels(velocity.values(), None, |c| (c.x[c.peak_index()], None))?;
+        Ok(Map::from_cube(
+            self,
+            values,
+            None,
+            SpectralKind::Velocity.si_unit(),
+        ))
+    }
+
+    /// Number of unmasked channels of each spatial pixel.
+    pub fn channel_count(&self) -> Map {
+        let plane = self.shape[0] * self.shape[1];
+        let counts = (0..plane)
+            .map(|pixel| {
+                (0..self.nchan())
+                    .filter(|z| !self.mask[z * plane + pixel])
+                    .count() as f64
+            })
+            .collect();
+        Map::from_cube(self, counts, None, "")
+    }
+}
+
+/// A unit such as `m / s` raised to a positive integer power, as in `m2 / s2`.
+fn power_unit(unit: &str, power: i32) -> String {
+    if power == 1 {
+        return unit.to_string();
+    }
+    unit.split(" / ")
+        .map(|part| format!("{part}{power}"))
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::centroid;
+    use crate::cube::tests::cube_from;
+    use crate::io::fits::Bitpix;
+    use crate::spectrum::Uncertainty;
+    use crate::units::equivalencies::DopplerConvention;
+    use approx::assert_relative_eq;
+
+    const SIGMA: f64 = 6e3;
+
+    /// A cube in radio velocity with Gaussian lines of σ = 6 km/s centred on
+    /// `30 + 5x` km/s with peak `1 + y`.
+    fn gaussian_cube() -> SpectralCube {
+        let velocity = cube_from([3, 2, 60], |_, _, _| 0.0)
+            .with_spectral_unit(SpectralKind::Velocity, None, Some(DopplerConvention::Radio))
+            .unwrap();
+        let v = velocity.spectral_axis().values().to_vec();
+        let cube = cube_from([3, 2, 60], |x, y, z| {
+            let center = 30e3 + 5e3 * x as f64;
+            (1.0 + y as f64) * (-0.5 * ((v[z] - center) / SIGMA).powi(2)).exp()
+        });
+        SpectralCube {
+            spectral_axis: velocity.spectral_axis().clone(),
+            ..cube
+        }
+    }
+
+    #[test]
+    fn test_moments() {
+        let cube = gaussian_cube();
+        let width = (cube.spectral_axis().values()[1] - cube.spectral_axis().values()[0]).abs();
+        let m0 = cube.moment(0, None).unwrap();
+        let m1 = cube.moment(1, None).unwrap();
+        let m2 = cube.moment(2, None).unwrap();
+        let m3 = cube.moment(3, None).unwrap();
+        assert_eq!(m0.unit(), "(Jy/beam) m / s");
+        assert_eq!(m1.unit(), "m / s");
+        assert_eq!(m2.unit(), "m2 / s2");
+        assert_eq!(m3.unit(), "m3 / s3");
+        assert_eq!(m0.shape(), [3, 2]);
+        assert!(m0.uncertainty().is_none());
+        for y in 0..2 {
+            for x in 0..3 {
+                let amplitude = 1.0 + y as f64;
+                let expected = amplitude * SIGMA * (2.0 * std::f64::consts::PI).sqrt();
+                assert_relative_eq!(m0.get(x, y), expected, max_relative = 1e-5);
+                assert_relative_eq!(m1.get(x, y), 30e3 + 5e3 * x as f64, max_relative = 1e-5);
+                assert_relative_eq!(m2.get(x, y), SIGMA * SIGMA, max_relative = 1e-4);
+                assert!(m3.get(x, y).abs() < 1e-3 * SIGMA.powi(3));
+            }
+        }
+
+        let sigma = cube.linewidth_sigma(None).unwrap();
+        let fwhm = cube.linewidth_fwhm(None).unwrap();
+        assert_relative_eq!(sigma.get(1, 1), m2.get(1, 1).sqrt());
+        assert_relative_eq!(fwhm.get(1, 1), SIGMA_TO_FWHM * sigma.get(1, 1));
+        assert_eq!(fwhm.unit(), "m / s");
+
+        let peak = cube.peak_intensity(None).unwrap();
+        let at_peak = cube.velocity_at_peak().unwrap();
+        assert_relative_eq!(peak.get(0, 1), 2.0, max_relative = 1e-2);
+        assert!((at_peak.get(2, 0) - 40e3).abs() <= width / 2.0);
+        assert_eq!(peak.unit(), "Jy/beam");
+
+        // The maps carry the celestial WCS of the cube.
+        let hdu = m0.to_hdu(Bitpix::F64).unwrap();
+        assert_eq!(hdu.shape(), vec![3, 2]);
+        assert_eq!(hdu.header().get_str("BUNIT"), Some("(Jy/beam) m / s"));
+        assert_eq!(hdu.header().get_str("CTYPE1"), Some("RA---TAN"));
+    }
+
+    #[test]
+    fn test_mask() {
+        let cube = gaussian_cube();
+        let mut mask = vec![false; 3 * 2 * 60];
+        for z in 0..60 {
+            mask[cube.index(0, 0, z)] = true;
+            // Hide the upper half of the line of pixel (1, 0).
+            if cube.spectral_axis().values()[z] > 35e3 {
+                mask[cube.index(1, 0, z)] = true;
+            }
+        }
+        let masked = cube.with_mask(mask).unwrap();
+        let count = masked.channel_count();
+        assert_eq!(count.get(0, 0), 0.0);
+        assert_eq!(count.get(1, 1), 60.0);
+        let m0 = masked.moment(0, None).unwrap();
+        let m1 = masked.moment(1, None).unwrap();
+        assert!(m0.get(0, 0).is_nan());
+        assert!(m1.get(1, 0) < 35e3 - 3e3);
+        assert!(masked.peak_intensity(None).unwrap().get(0, 0).is_nan());
+    }
+
+    #[test]
+    fn test_uncertainty() {
+        let cube = gaussian_cube();
+        let width = (cube.spectral_axis().values()[1] - cube.spectral_axis().values()[0]).abs();
+        let m0 = cube.moment(0, Some(&Noise::Uniform(0.1))).unwrap();
+        assert_relative_eq!(
+            m0.uncertainty().unwrap()[0],
+            0.1 * width * 60.0_f64.sqrt(),
+            max_relative = 1e-3
+        );
+
+        // The centroid error agrees with that of the spectrum of each pixel.
+        let noise: Vec<f64> = (0..60).map(|z| 0.05 + 0.001 * z as f64).collect();
+        let m1 = cube
+            .moment(1, Some(&Noise::PerChannel(noise.clone())))
+            .unwrap();
+        let spectrum = cube
+            .spectrum(2, 1)
+            .unwrap()
+            .with_uncertainty(Uncertainty::StdDev(noise))
+            .unwrap();
+        let expected = centroid(&spectrum, None).unwrap();
+        assert_relative_eq!(m1.get(2, 1), expected.value, max_relative = 1e-12);
+        assert_relative_eq!(
+            m1.uncertainty().unwrap()[5],
+            expected.uncertainty.unwrap(),
+            max_relative = 1e-9
+        );
+
+        // Higher moments against finite differences.
+        let pixel = cube.spectrum(1, 0).unwrap();
+        let sigma = 0.02;
+        let channels = |flux: Vec<f64>| Channels {
+            x: pixel.spectral_axis.values().to_vec(),
+            dx: vec![width; 60],
+            flux,
+            variance: Some(vec![sigma * sigma; 60]),
+        };
+        for order in [2, 3] {
+            let (value, variance) = channels(pixel.flux.clone()).moment(order);
+            let variance = variance.unwrap();
+            let numeric: f64 = (0..60)
+                .map(|z| {
+                    let mut flux = pixel.flux.clone();
+                    let step = 1e-6;
+                    flux[z] += step;
+                    let derivative = (channels(flux).moment(order).0 - value) / step;
+                    (derivative * sigma).powi(2)
+                })
+                .sum();
+            assert_relative_eq!(variance, numeric, max_relative = 1e-4);
+        }
+        let map = cube
+            .linewidth_sigma(Some(&Noise::PerPixel(vec![sigma; 6])))
+            .unwrap();
+        let (_, variance) = channels(pixel.flux.clone()).dispersion();
+        assert_relative_eq!(
+            map.uncertainty().unwrap()[1],
+            variance.unwrap().sqrt(),
+            max_relative = 1e-9
+        );
+        assert!(matches!(
+            cube.moment(0, Some(&Noise::PerChannel(vec![0.1; 3]))),
+            Err(CubeError::LengthMismatch { .. })
+        ));
+    }
+}
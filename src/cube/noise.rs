@@ -0,0 +1,186 @@
+//! Noise models of a cube, used to propagate uncertainties into derived maps, and robust
+//! estimates of them from the data.
+
+use std::ops::Range;
+
+use crate::errors::cube::CubeError;
+use crate::stats::{mad_std, sigma_clipped_stats};
+
+use super::{SpectralCube, check_len};
+
+/// One-sigma noise of the values of a cube, in the unit of the cube. Channels are assumed
+/// independent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Noise {
+    /// The same noise everywhere
+    Uniform(f64),
+    /// One value per channel
+    PerChannel(Vec<f64>),
+    /// One value per spatial pixel, an `nx × ny` map in FITS order
+    PerPixel(Vec<f64>),
+}
+
+impl Noise {
+    /// Check that the noise matches a cube of shape `[nx, ny, nchan]`.
+    pub(crate) fn check(&self, shape: [usize; 3]) -> Result<(), CubeError> {
+        match self {
+            Self::Uniform(_) => Ok(()),
+            Self::PerChannel(sigma) => check_len(shape[2], sigma.len()),
+            Self::PerPixel(sigma) => check_len(shape[0] * shape[1], sigma.len()),
+        }
+    }
+
+    /// The noise of rows `rows` of a cube `nx` pixels wide.
+    pub(crate) fn rows(&self, nx: usize, rows: Range<usize>) -> Self {
+        match self {
+            Self::PerPixel(sigma) => Self::PerPixel(sigma[rows.start * nx..rows.end * nx].to_vec()),
+            _ => self.clone(),
+        }
+    }
+
+    /// Noise of spatial pixel `pixel` (an index into the `nx × ny` plane) in channel `z`.
+    pub(crate) fn sigma(&self, pixel: usize, z: usize) -> f64 {
+        match self {
+            Self::Uniform(sigma) => *sigma,
+            Self::PerChannel(sigma) => sigma[z],
+            Self::PerPixel(sigma) => sigma[pixel],
+        }
+    }
+}
+
+/// How the noise is estimated from a set of values that may contain emission.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoiseMethod {
+    /// Standard deviation from the median absolute deviation, see [`mad_std`]
+    Mad,
+    /// Standard deviation after iteratively clipping values more than `sigma` standard
+    /// deviations from the median, see [`sigma_clipped_stats`]
+    SigmaClip { sigma: f64, max_iters: usize },
+}
+
+impl NoiseMethod {
+    fn estimate(self, values: &[f64]) -> f64 {
+        match self {
+            Self::Mad => mad_std(values),
+            Self::SigmaClip { sigma, max_iters } => sigma_clipped_stats(values, sigma, max_iters).2,
+        }
+    }
+}
+
+impl SpectralCube {
+    /// Noise of each channel, estimated from its unmasked values. Channels without any are
+    /// NaN.
+    pub fn channel_noise(&self, method: NoiseMethod) -> Noise {
+        let plane = self.shape[0] * self.shape[1];
+        let sigma = (0..self.nchan())
+            .map(|z| method.estimate(&self.unmasked(z * plane..(z + 1) * plane, 1)))
+            .collect();
+        Noise::PerChannel(sigma)
+    }
+
+    /// Noise of each spatial pixel, estimated from its unmasked values in the emission-free
+    /// channel ranges `line_free`, or in all channels if there are none. Pixels without any
+    /// values are NaN.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::OutOfRange`] if a range extends past the last channel.
+    pub fn pixel_noise(
+        &self,
+        method: NoiseMethod,
+        line_free: &[Range<usize>],
+    ) -> Result<Noise, CubeError> {
+        let nchan = self.nchan();
+        if let Some(range) = line_free.iter().find(|r| r.end > nchan) {
+            return Err(CubeError::OutOfRange {
+                index: range.end,
+                len: nchan,
+            });
+        }
+        let all = 0..nchan;
+        let ranges = if line_free.is_empty() {
+            std::slice::from_ref(&all)
+        } else {
+            line_free
+        };
+        let plane = self.shape[0] * self.shape[1];
+        let sigma = (0..plane)
+            .map(|pixel| {
+                let values: Vec<f64> = ranges
+                    .iter()
+                    .flat_map(|r| self.unmasked(r.start * plane + pixel..r.end * plane, plane))
+                    .collect();
+                method.estimate(&values)
+            })
+            .collect();
+        Ok(Noise::PerPixel(sigma))
+    }
+
+    /// Unmasked values of every `step`-th element in `range` of the data.
+    fn unmasked(&self, range: Range<usize>, step: usize) -> Vec<f64> {
+        range
+            .step_by(step)
+            .filter(|&i| !self.mask[i])
+            .map(|i| self.data[i])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::tests::cube_from;
+    use approx::assert_relative_eq;
+
+    /// Alternating ±σ noise, σ = 1 + x in pixel column x, with a strong line in channels 4–5
+    /// of pixel (1, 1).
+    fn noisy_cube() -> SpectralCube {
+        cube_from([3, 2, 10], |x, y, z| {
+            let sign = if (x + y + z) % 2 == 0 { 1.0 } else { -1.0 };
+            let line = if (x, y) == (1, 1) && (4..6).contains(&z) {
+                100.0
+            } else {
+                0.0
+            };
+            sign * (1.0 + x as f64) + line
+        })
+    }
+
+    #[test]
+    fn test_pixel_noise() {
+        let cube = noisy_cube();
+        let Noise::PerPixel(all) = cube.pixel_noise(NoiseMethod::Mad, &[]).unwrap() else {
+            panic!("expected per-pixel noise");
+        };
+        // The MAD of ±σ is σ; the line does not affect it at this level.
+        assert_relative_eq!(all[0], 1.482_602_218_505_602, max_relative = 1e-12);
+
+        let clip = NoiseMethod::SigmaClip {
+            sigma: 3.0,
+            max_iters: 10,
+        };
+        let Noise::PerPixel(line_free) = cube.pixel_noise(clip, &[0..4, 6..10]).unwrap() else {
+            panic!("expected per-pixel noise");
+        };
+        for (pixel, sigma) in line_free.iter().enumerate() {
+            assert_relative_eq!(*sigma, 1.0 + (pixel % 3) as f64, max_relative = 1e-12);
+        }
+        assert!(matches!(
+            cube.pixel_noise(clip, &[0..2, 8..11]),
+            Err(CubeError::OutOfRange { index: 11, len: 10 })
+        ));
+    }
+
+    #[test]
+    fn test_channel_noise() {
+        let mut mask = vec![false; 60];
+        mask[54..60].fill(true);
+        let cube = noisy_cube().with_mask(mask).unwrap();
+        let Noise::PerChannel(sigma) = cube.channel_noise(NoiseMethod::Mad) else {
+            panic!("expected per-channel noise");
+        };
+        assert_eq!(sigma.len(), 10);
+        assert!(sigma[9].is_nan());
+        // Values ±1, ±2, ±3 have median 0 and a MAD of 2.
+        assert_relative_eq!(sigma[0], 2.0 * 1.482_602_218_505_602, max_relative = 1e-12);
+    }
+}
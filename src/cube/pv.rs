@@ -0,0 +1,319 @@
+//! Position–velocity slices, modelled on `pvextractor`.
+//!
+//! The cube is sampled at steps of one pixel (its geometric mean pixel size) along a path on
+//! the sky. A path without width is interpolated bilinearly at each step; a path with any
+//! non-zero width is averaged over the rectangle one step long and `width` across centred on
+//! it, each pixel weighted by its area of overlap with the rectangle.
+
+use crate::beam::Beam;
+use crate::coordinates::SkyCoord;
+use crate::errors::cube::CubeError;
+use crate::io::fits::{Bitpix, Header, ImageHdu};
+use crate::spectrum::SpectralAxis;
+use crate::units::angle::{degree, radian};
+use crate::units::f64::Angle;
+use crate::wcs::Wcs;
+
+use super::{SpectralCube, image_hdu, spectral_header};
+
+/// A path along which to extract a position–velocity slice.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PvPath {
+    /// Straight segments through two or more positions, averaged over `width` across the path
+    Polyline { points: Vec<SkyCoord>, width: Angle },
+    /// A straight slit `length` long centred on `center`, at position angle `pa` east of
+    /// north, averaged over `width` across the slit. Offsets increase towards `pa`.
+    Slit {
+        center: SkyCoord,
+        length: Angle,
+        pa: Angle,
+        width: Angle,
+    },
+}
+
+/// A position–velocity slice: offset along the path on the first axis and the spectral axis
+/// of the cube on the second, in FITS order. Invalid values are NaN.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PvSlice {
+    data: Vec<f64>,
+    shape: [usize; 2],
+    wcs: Wcs,
+    spectral_axis: SpectralAxis,
+    unit: String,
+    beam: Option<Beam>,
+}
+
+impl PvSlice {
+    /// Axis lengths `[noffset, nchan]`.
+    pub fn shape(&self) -> [usize; 2] {
+        self.shape
+    }
+
+    pub fn data(&self) -> &[f64] {
+        &self.data
+    }
+
+    /// Value at offset `offset` in channel `channel`, NaN if invalid.
+    ///
+    /// # Panics
+    /// Panics if the position is outside the slice.
+    pub fn get(&self, offset: usize, channel: usize) -> f64 {
+        assert!(
+            offset < self.shape[0] && channel < self.shape[1],
+            "position out of bounds"
+        );
+        self.data[channel * self.shape[0] + offset]
+    }
+
+    /// Offsets along the path of the centres of the samples.
+    pub fn offsets(&self) -> Vec<Angle> {
+        let (start, step) = (self.wcs.crval()[0], self.wcs.cdelt()[0]);
+        (0..self.shape[0])
+            .map(|k| Angle::new::<degree>(start + k as f64 * step))
+            .collect()
+    }
+
+    /// A linear `OFFSET` axis in degrees followed by the spectral axis.
+    pub fn wcs(&self) -> &Wcs {
+        &self.wcs
+    }
+
+    pub fn spectral_axis(&self) -> &SpectralAxis {
+        &self.spectral_axis
+    }
+
+    pub fn unit(&self) -> &str {
+        &self.unit
+    }
+
+    pub fn beam(&self) -> Option<Beam> {
+        self.beam
+    }
+
+    /// The slice as an image HDU with its WCS, `BUNIT` and beam.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Fits`] if the data cannot be encoded with `bitpix`.
+    pub fn to_hdu(&self, bitpix: Bitpix) -> Result<ImageHdu, CubeError> {
+        image_hdu(
+            &self.shape,
+            &self.data,
+            bitpix,
+            &self.wcs,
+            &self.unit,
+            self.beam.as_ref(),
+        )
+    }
+}
+
+impl SpectralCube {
+    /// Extract a position–velocity slice along `path`.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::EmptySelection`] if the path has fewer than two distinct points
+    /// or is shorter than a pixel, and [`CubeError::Wcs`] if it cannot be placed on the grid.
+    pub fn pv_slice(&self, path: &PvPath) -> Result<PvSlice, CubeError> {
+        let matrix = self.wcs.matrix();
+        let det = matrix[0][0] * matrix[1][1] - matrix[0][1] * matrix[1][0];
+        // Degrees per pixel, and the step along the path.
+        let step = det.abs().sqrt();
+        let (points, width) = match path {
+            PvPath::Polyline { points, width } => (points.clone(), *width),
+            PvPath::Slit {
+                center,
+                length,
+                pa,
+                width,
+            } => {
+                // Pixel offset of a step of one degree at the position angle, from the
+                // inverse of the matrix taking pixels to degrees east and north.
+                let (sin, cos) = pa.get::<radian>().sin_cos();
+                let dx = (matrix[1][1] * sin - matrix[0][1] * cos) / det;
+                let dy = (matrix[0][0] * cos - matrix[1][0] * sin) / det;
+                let half = length.get::<degree>() / 2.0;
+                let (x, y) = self.wcs.sky_to_pixel(center)?;
+                let ends = [
+                    self.wcs.pixel_to_sky(&[x - half * dx, y - half * dy])?,
+                    self.wcs.pixel_to_sky(&[x + half * dx, y + half * dy])?,
+                ];
+                (ends.to_vec(), *width)
+            }
+        };
+        // Repeated points would make segments of zero length.
+        let mut distinct: Vec<SkyCoord> = Vec::with_capacity(points.len());
+        let mut lengths = Vec::with_capacity(points.len());
+        for point in points {
+            if let Some(last) = distinct.last() {
+                let length = last.separation(&point).get::<degree>();
+                if length == 0.0 {
+                    continue;
+                }
+                lengths.push(length);
+            }
+            distinct.push(point);
+        }
+        if distinct.len() < 2 {
+            return Err(CubeError::EmptySelection);
+        }
+
+        let pixels = distinct
+            .iter()
+            .map(|p| self.wcs.sky_to_pixel(p).map(|(x, y)| [x, y]))
+            .collect::<Result<Vec<_>, _>>()?;
+        let total: f64 = lengths.iter().sum();
+        let n = (total / step + 1e-6).floor() as usize;
+        if n == 0 {
+            return Err(CubeError::EmptySelection);
+        }
+
+        let half_width = width.get::<degree>() / step / 2.0;
+        let mut weights = Vec::new();
+        for k in 0..n {
+            // The segment containing the sample, and its position along it.
+            let mut s = (k as f64 + 0.5) * step;
+            let mut i = 0;
+            while i + 1 < lengths.len() && s >= lengths[i] {
+                s -= lengths[i];
+                i += 1;
+            }
+            let (a, b) = (pixels[i], pixels[i + 1]);
+            let delta = [b[0] - a[0], b[1] - a[1]];
+            let t = s / lengths[i];
+            let centre = [a[0] + t * delta[0], a[1] + t * delta[1]];
+            if half_width == 0.0 {
+                self.bilinear(centre[0], centre[1], k, &mut weights);
+                continue;
+            }
+            // Half a step along the segment and half the width across it, in pixels.
+            let along = delta.map(|d| d * step / lengths[i] / 2.0);
+            let norm = delta[0].hypot(delta[1]);
+            let across = [-delta[1] / norm * half_width, delta[0] / norm * half_width];
+            let corner =
+                |sa: f64, sc: f64| [0, 1].map(|j| centre[j] + sa * along[j] + sc * across[j]);
+            let rectangle = [
+                corner(-1.0, -1.0),
+                corner(1.0, -1.0),
+                corner(1.0, 1.0),
+                corner(-1.0, 1.0),
+            ];
+            self.overlaps(&rectangle, k, &mut weights);
+        }
+
+        let mut header = Header::new();
+        header.set("WCSAXES", 2);
+        header.set("CTYPE1", "OFFSET");
+        header.set("CUNIT1", "deg");
+        header.set("CRPIX1", 1.0);
+        header.set("CRVAL1", step / 2.0);
+        header.set("CDELT1", step);
+        spectral_header(&self.spectral_axis, 2, &mut header)?;
+        Ok(PvSlice {
+            data: self.weighted_planes(&weights, n),
+            shape: [n, self.nchan()],
+            wcs: Wcs::from_header(&header)?,
+            spectral_axis: self.spectral_axis.clone(),
+            unit: self.unit.clone(),
+            beam: self.common_beam(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::tests::cube_from;
+    use crate::units::angle::arcsecond;
+    use approx::assert_relative_eq;
+
+    fn arcsec(value: f64) -> Angle {
+        Angle::new::<arcsecond>(value)
+    }
+
+    #[test]
+    fn test_slit() {
+        // Linear in x, so both interpolation and averaging across the slit are exact.
+        let cube = cube_from([10, 7, 3], |x, _, z| x as f64 + 10.0 * z as f64);
+        let center = cube.wcs().pixel_to_sky(&[4.5, 3.0]).unwrap();
+        for width in [0.0, 3.0] {
+            // East is towards decreasing x, so the slit runs from x = 8.5 to 0.5.
+            let slit = PvPath::Slit {
+                center,
+                length: arcsec(8.0),
+                pa: Angle::new::<degree>(90.0),
+                width: arcsec(width),
+            };
+            let pv = cube.pv_slice(&slit).unwrap();
+            assert_eq!(pv.shape(), [8, 3]);
+            for k in 0..8 {
+                for z in 0..3 {
+                    let expected = 8.0 - k as f64 + 10.0 * z as f64;
+                    assert_relative_eq!(pv.get(k, z), expected, epsilon = 1e-6);
+                }
+            }
+            let offsets = pv.offsets();
+            assert_relative_eq!(offsets[0].get::<arcsecond>(), 0.5, max_relative = 1e-9);
+            assert_relative_eq!(offsets[7].get::<arcsecond>(), 7.5, max_relative = 1e-9);
+        }
+
+        // Half a pixel across at y = 3.0–3.5 lies within the pixels of row 3, where bilinear
+        // interpolation at y = 3.25 would mix in row 4.
+        let cube = cube_from([10, 7, 1], |_, y, _| (y * y) as f64);
+        let slit = PvPath::Slit {
+            center: cube.wcs().pixel_to_sky(&[4.5, 3.25]).unwrap(),
+            length: arcsec(4.0),
+            pa: Angle::new::<degree>(90.0),
+            width: arcsec(0.5),
+        };
+        let pv = cube.pv_slice(&slit).unwrap();
+        assert!(pv.data().iter().all(|&v| (v - 9.0).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_polyline() {
+        let mut mask = vec![false; 10 * 7 * 2];
+        mask[3 * 10 + 2] = true;
+        let cube = cube_from([10, 7, 2], |x, y, _| (x + 100 * y) as f64)
+            .with_mask(mask)
+            .unwrap();
+        let sky = |x: f64, y: f64| cube.wcs().pixel_to_sky(&[x, y]).unwrap();
+        // Up from (2, 0) to (2, 3), then along to (6, 3).
+        let path = PvPath::Polyline {
+            points: vec![sky(2.0, 0.0), sky(2.0, 3.0), sky(6.0, 3.0)],
+            width: Angle::new::<degree>(0.0),
+        };
+        let pv = cube.pv_slice(&path).unwrap();
+        assert_eq!(pv.shape(), [7, 2]);
+        assert_relative_eq!(pv.get(0, 1), 52.0, epsilon = 1e-6);
+        assert_relative_eq!(pv.get(5, 1), 304.5, epsilon = 1e-6);
+        // The masked pixel is left out of the interpolation at (2, 2.5) in channel 0.
+        assert_relative_eq!(pv.get(2, 0), 202.0, epsilon = 1e-6);
+        assert_relative_eq!(pv.get(2, 1), 252.0, epsilon = 1e-6);
+
+        let hdu = pv.to_hdu(Bitpix::F32).unwrap();
+        assert_eq!(hdu.shape(), vec![7, 2]);
+        assert_eq!(hdu.header().get_str("CTYPE1"), Some("OFFSET"));
+        assert_eq!(hdu.header().get_str("CTYPE2"), Some("FREQ"));
+        assert_eq!(
+            pv.wcs().spectral_axis(2).unwrap().values(),
+            &cube.spectral_axis().values()[..2]
+        );
+
+        // Repeated points are dropped rather than making a segment of zero length.
+        let repeated = PvPath::Polyline {
+            points: vec![sky(2.0, 0.0), sky(2.0, 3.0), sky(6.0, 3.0), sky(6.0, 3.0)],
+            width: Angle::new::<degree>(0.0),
+        };
+        assert_eq!(cube.pv_slice(&repeated).unwrap(), pv);
+
+        for points in [vec![sky(2.0, 0.0)], vec![sky(2.0, 0.0), sky(2.0, 0.0)]] {
+            let short = PvPath::Polyline {
+                points,
+                width: arcsec(1.0),
+            };
+            assert!(matches!(
+                cube.pv_slice(&short),
+                Err(CubeError::EmptySelection)
+            ));
+        }
+    }
+}
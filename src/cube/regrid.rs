@@ -0,0 +1,474 @@
+//! Regridding cubes onto a new spectral axis or a new celestial grid, modelled on
+//! `SpectralCube.spectral_interpolate` and the `reproject` package.
+//!
+//! Spectral interpolation resamples every spectrum with one of the
+//! [`crate::manipulation::resample`] resamplers. Spatial reprojection maps every output pixel
+//! through the sky, so the two grids may differ in projection, pixel size, orientation and
+//! celestial frame. Masked values are left out, and output values with no unmasked input are
+//! masked.
+
+use std::ops::Range;
+
+use crate::constants::SIGMA_TO_FWHM;
+use crate::errors::cube::CubeError;
+use crate::errors::wcs::WcsError;
+use crate::io::fits::ImageHdu;
+use crate::manipulation::resample::Resampler;
+use crate::manipulation::smoothing::Kernel1D;
+use crate::spectrum::SpectralAxis;
+use crate::wcs::Wcs;
+
+use super::{Beams, SpectralCube, image_wcs};
+
+/// How values are carried onto a new celestial grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reprojection {
+    /// Bilinear interpolation between the four input pixels around each output pixel centre
+    Bilinear,
+    /// Each input pixel is spread over the output pixels it overlaps, in proportion to the
+    /// area of overlap, as in drizzle with a pixel fraction of one. Surface brightness is
+    /// preserved, and so is the integrated flux.
+    FluxConserving,
+}
+
+impl SpectralCube {
+    /// Resample every spectrum onto `new_axis` with `resampler`.
+    ///
+    /// The new axis may be of any spectral kind that the axis of the cube can be converted
+    /// to. If `smooth` is true and the new channels are wider than the old, the cube is
+    /// first smoothed with a Gaussian whose FWHM, added in quadrature to the old channel
+    /// width, gives the new one, so that the resampled spectra are not aliased. Per-channel
+    /// beams are taken from the nearest old channel.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::EmptySelection`] if `new_axis` is empty, and
+    /// [`CubeError::Spectrum`] if it cannot be converted to the kind of the cube's axis.
+    pub fn spectral_interpolate(
+        &self,
+        new_axis: &SpectralAxis,
+        resampler: &impl Resampler,
+        smooth: bool,
+    ) -> Result<Self, CubeError> {
+        if new_axis.is_empty() {
+            return Err(CubeError::EmptySelection);
+        }
+        let new_x = (0..new_axis.len())
+            .map(|i| self.spectral_axis.convert_value(new_axis.get(i).unwrap()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let x = self.spectral_axis.values();
+
+        let channel_width = |values: &[f64]| {
+            (values[values.len() - 1] - values[0]).abs() / (values.len() - 1).max(1) as f64
+        };
+        let (old_width, new_width) = (channel_width(x), channel_width(&new_x));
+        let smoothed;
+        let source = if smooth && x.len() > 1 && new_width > old_width {
+            let fwhm = (new_width.powi(2) - old_width.powi(2)).sqrt() / old_width;
+            smoothed = self.spectral_smooth(&Kernel1D::gaussian(fwhm / SIGMA_TO_FWHM)?);
+            &smoothed
+        } else {
+            self
+        };
+
+        let [nx, ny, nchan] = self.shape;
+        let plane = nx * ny;
+        let mut data = vec![f64::NAN; plane * new_x.len()];
+        let mut mask = vec![true; data.len()];
+        for pixel in 0..plane {
+            let y: Vec<f64> = (0..nchan).map(|z| source.data[z * plane + pixel]).collect();
+            let masked: Vec<bool> = (0..nchan).map(|z| source.mask[z * plane + pixel]).collect();
+            let result = resampler.resample_values(x, &y, None, Some(&masked), &new_x);
+            for (z, (value, masked)) in result.flux.into_iter().zip(result.mask).enumerate() {
+                data[z * plane + pixel] = value;
+                mask[z * plane + pixel] = masked;
+            }
+        }
+
+        let beams = self.beams.as_ref().map(|beams| match beams {
+            Beams::Single(beam) => Beams::Single(*beam),
+            Beams::PerChannel(beams) => Beams::PerChannel(
+                new_x
+                    .iter()
+                    .map(|&v| {
+                        let nearest = (0..nchan)
+                            .min_by(|&a, &b| (x[a] - v).abs().total_cmp(&(x[b] - v).abs()))
+                            .unwrap();
+                        beams[nearest]
+                    })
+                    .collect(),
+            ),
+        });
+        let cube = Self::new(
+            data,
+            [nx, ny, new_x.len()],
+            self.wcs.clone(),
+            new_axis.clone(),
+            self.unit.clone(),
+        )?
+        .with_mask(mask)?;
+        Ok(Self { beams, ..cube })
+    }
+
+    /// Reproject every channel onto the celestial grid of `wcs`, `shape[0]` pixels along
+    /// longitude by `shape[1]` along latitude. Only the celestial axes of `wcs` are used.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Wcs`] if `wcs` has no celestial axes.
+    pub fn reproject(
+        &self,
+        wcs: &Wcs,
+        shape: [usize; 2],
+        method: Reprojection,
+    ) -> Result<Self, CubeError> {
+        let (lng, lat) = wcs.celestial_axes().ok_or(WcsError::NotCelestial)?;
+        let wcs = wcs.sub(&[lng, lat])?;
+        let weights = match method {
+            Reprojection::Bilinear => self.bilinear_weights(&wcs, shape),
+            Reprojection::FluxConserving => self.overlap_weights(&wcs, shape),
+        };
+        let cube = Self::new(
+            self.weighted_planes(&weights, shape[0] * shape[1]),
+            [shape[0], shape[1], self.nchan()],
+            wcs,
+            self.spectral_axis.clone(),
+            self.unit.clone(),
+        )?;
+        Ok(Self {
+            beams: self.beams.clone(),
+            ..cube
+        })
+    }
+
+    /// Reproject onto the celestial grid of an image, such as a cube or map from another
+    /// telescope.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Wcs`] if the image has no celestial axes, and otherwise as
+    /// [`Self::reproject`].
+    pub fn reproject_like(&self, hdu: &ImageHdu, method: Reprojection) -> Result<Self, CubeError> {
+        let wcs = image_wcs(hdu)?;
+        let (lng, lat) = wcs.celestial_axes().ok_or(WcsError::NotCelestial)?;
+        let shape = hdu.shape();
+        let len = |i: usize| shape.get(i).copied().unwrap_or(1);
+        self.reproject(&wcs, [len(lng), len(lat)], method)
+    }
+
+    /// Bilinear weights `(input pixel, output pixel, weight)` of the input pixels around the
+    /// centre of each output pixel. Output pixels more than half a pixel outside the input
+    /// grid get none.
+    fn bilinear_weights(&self, wcs: &Wcs, shape: [usize; 2]) -> Vec<(usize, usize, f64)> {
+        let mut weights = Vec::new();
+        for v in 0..shape[1] {
+            for u in 0..shape[0] {
+                let Some((px, py)) = wcs
+                    .pixel_to_sky(&[u as f64, v as f64])
+                    .and_then(|sky| self.wcs.sky_to_pixel(&sky))
+                    .ok()
+                else {
+                    continue;
+                };
+                self.bilinear(px, py, v * shape[0] + u, &mut weights);
+            }
+        }
+        weights
+    }
+
+    /// Add the bilinear weights `(input pixel, output, weight)` of the input pixels around
+    /// pixel position `(px, py)`, if it is no more than half a pixel outside the grid.
+    pub(super) fn bilinear(
+        &self,
+        px: f64,
+        py: f64,
+        output: usize,
+        weights: &mut Vec<(usize, usize, f64)>,
+    ) {
+        let [nx, ny, _] = self.shape;
+        let inside = |p: f64, n: usize| (-0.5..=n as f64 - 0.5).contains(&p);
+        if !inside(px, nx) || !inside(py, ny) {
+            return;
+        }
+        let (x0, tx) = corner(px, nx);
+        let (y0, ty) = corner(py, ny);
+        for (x, wx) in [(x0, 1.0 - tx), (x0 + 1, tx)] {
+            for (y, wy) in [(y0, 1.0 - ty), (y0 + 1, ty)] {
+                if x < nx && y < ny && wx * wy > 0.0 {
+                    weights.push((y * nx + x, output, wx * wy));
+                }
+            }
+        }
+    }
+
+    /// Weights `(input pixel, output, area)` of the overlap of the input pixels with
+    /// `polygon`, in pixel coordinates.
+    pub(super) fn overlaps(
+        &self,
+        polygon: &[[f64; 2]],
+        output: usize,
+        weights: &mut Vec<(usize, usize, f64)>,
+    ) {
+        let [nx, ny, _] = self.shape;
+        for y in pixel_range(polygon, 1, ny) {
+            for x in pixel_range(polygon, 0, nx) {
+                let (x0, y0) = (x as f64 - 0.5, y as f64 - 0.5);
+                let area = clipped_area(polygon, [x0, y0], [x0 + 1.0, y0 + 1.0]);
+                if area > 0.0 {
+                    weights.push((y * nx + x, output, area));
+                }
+            }
+        }
+    }
+
+    /// For each channel, the weighted means over `len` outputs of the unmasked values of the
+    /// input pixels, given weights `(input pixel, output, weight)`. Outputs without any
+    /// unmasked input are NaN.
+    pub(super) fn weighted_planes(&self, weights: &[(usize, usize, f64)], len: usize) -> Vec<f64> {
+        let plane = self.shape[0] * self.shape[1];
+        let mut data = vec![f64::NAN; len * self.nchan()];
+        for z in 0..self.nchan() {
+            let (mut sum_w, mut sum_wf) = (vec![0.0; len], vec![0.0; len]);
+            for &(input, output, w) in weights {
+                let i = z * plane + input;
+                if !self.mask[i] {
+                    sum_w[output] += w;
+                    sum_wf[output] += w * self.data[i];
+                }
+            }
+            for (k, (w, wf)) in sum_w.into_iter().zip(sum_wf).enumerate() {
+                if w > 0.0 {
+                    data[z * len + k] = wf / w;
+                }
+            }
+        }
+        data
+    }
+
+    /// Weights `(input pixel, output pixel, area)` of the overlap of each input pixel with
+    /// the output pixels, in output pixel areas.
+    fn overlap_weights(&self, wcs: &Wcs, shape: [usize; 2]) -> Vec<(usize, usize, f64)> {
+        let [nx, ny, _] = self.shape;
+        // Input pixel corners in output pixel coordinates.
+        let corners: Vec<Option<[f64; 2]>> = (0..=ny)
+            .flat_map(|j| (0..=nx).map(move |i| [i as f64 - 0.5, j as f64 - 0.5]))
+            .map(|p| {
+                self.wcs
+                    .pixel_to_sky(&p)
+                    .and_then(|sky| wcs.sky_to_pixel(&sky))
+                    .ok()
+                    .map(|(u, v)| [u, v])
+            })
+            .collect();
+        let mut weights = Vec::new();
+        for y in 0..ny {
+            for x in 0..nx {
+                let quad = [(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)]
+                    .map(|(i, j)| corners[j * (nx + 1) + i]);
+                let Some(quad) = quad.into_iter().collect::<Option<Vec<_>>>() else {
+                    continue;
+                };
+                for v in pixel_range(&quad, 1, shape[1]) {
+                    for u in pixel_range(&quad, 0, shape[0]) {
+                        let (u0, v0) = (u as f64 - 0.5, v as f64 - 0.5);
+                        let area = clipped_area(&quad, [u0, v0], [u0 + 1.0, v0 + 1.0]);
+                        if area > 0.0 {
+                            weights.push((y * nx + x, v * shape[0] + u, area));
+                        }
+                    }
+                }
+            }
+        }
+        weights
+    }
+}
+
+/// The pixels along `axis`, of `n`, that the bounding box of `polygon` touches.
+fn pixel_range(polygon: &[[f64; 2]], axis: usize, n: usize) -> Range<usize> {
+    let (lo, hi) = polygon.iter().fold((f64::MAX, f64::MIN), |(lo, hi), c| {
+        (lo.min(c[axis]), hi.max(c[axis]))
+    });
+    let lo = (lo + 0.5).floor().clamp(0.0, n as f64) as usize;
+    let hi = ((hi + 0.5).floor() + 1.0).clamp(0.0, n as f64) as usize;
+    lo..hi.max(lo)
+}
+
+/// The lower of the two pixels around position `p` on an axis of `n` pixels, and the
+/// fractional distance from it, with positions beyond the outer pixel centres clamped.
+fn corner(p: f64, n: usize) -> (usize, f64) {
+    let p = p.clamp(0.0, n.saturating_sub(1) as f64);
+    let lower = (p.floor() as usize).min(n.saturating_sub(2));
+    (lower, p - lower as f64)
+}
+
+/// Area of the part of polygon `polygon` inside the box from `lo` to `hi`.
+fn clipped_area(polygon: &[[f64; 2]], lo: [f64; 2], hi: [f64; 2]) -> f64 {
+    let mut clipped = polygon.to_vec();
+    for axis in 0..2 {
+        clipped = clip(&clipped, axis, lo[axis], true);
+        clipped = clip(&clipped, axis, hi[axis], false);
+    }
+    let n = clipped.len();
+    let twice: f64 = (0..n)
+        .map(|i| {
+            let (a, b) = (clipped[i], clipped[(i + 1) % n]);
+            a[0] * b[1] - b[0] * a[1]
+        })
+        .sum();
+    twice.abs() / 2.0
+}
+
+/// The part of `polygon` on one side of `coordinate[axis] = bound`: above it if `above`,
+/// otherwise below (Sutherland–Hodgman).
+fn clip(polygon: &[[f64; 2]], axis: usize, bound: f64, above: bool) -> Vec<[f64; 2]> {
+    let inside = |p: &[f64; 2]| {
+        if above {
+            p[axis] >= bound
+        } else {
+            p[axis] <= bound
+        }
+    };
+    let crossing = |a: &[f64; 2], b: &[f64; 2]| {
+        let t = (bound - a[axis]) / (b[axis] - a[axis]);
+        [a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])]
+    };
+    let mut out = Vec::with_capacity(polygon.len() + 2);
+    for (i, current) in polygon.iter().enumerate() {
+        let previous = &polygon[(i + polygon.len() - 1) % polygon.len()];
+        match (inside(previous), inside(current)) {
+            (true, true) => out.push(*current),
+            (true, false) => out.push(crossing(previous, current)),
+            (false, true) => {
+                out.push(crossing(previous, current));
+                out.push(*current);
+            }
+            (false, false) => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::tests::{cube_from, cube_header};
+    use crate::io::fits::Bitpix;
+    use crate::manipulation::resample::{
+        FluxConservingResampler, LinearInterpolatedResampler, OutOfRange,
+    };
+    use crate::spectrum::SpectralKind;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_spectral_interpolate() {
+        let cube = cube_from([2, 2, 8], |x, y, z| (x + y) as f64 + z as f64);
+        let freq = cube.spectral_axis().values().to_vec();
+        // Half-way between channels 2–3 and 4–5, and a point beyond the last channel.
+        let new_axis = SpectralAxis::from_si(
+            SpectralKind::Frequency,
+            vec![
+                (freq[2] + freq[3]) / 2.0,
+                (freq[4] + freq[5]) / 2.0,
+                freq[7] - 1e6,
+            ],
+        );
+        let linear = LinearInterpolatedResampler::new(OutOfRange::Nan);
+        let result = cube
+            .spectral_interpolate(&new_axis, &linear, false)
+            .unwrap();
+        assert_eq!(result.shape(), [2, 2, 3]);
+        assert_eq!(result.spectral_axis(), &new_axis);
+        assert_relative_eq!(result.get(1, 0, 0).unwrap(), 3.5, max_relative = 1e-12);
+        assert_relative_eq!(result.get(1, 1, 1).unwrap(), 6.5, max_relative = 1e-12);
+        assert!(result.get(0, 0, 2).is_none());
+
+        // Two-channel bins conserve flux: each is the mean of the pair it covers.
+        let binned_axis = SpectralAxis::from_si(
+            SpectralKind::Frequency,
+            (0..4)
+                .map(|k| (freq[2 * k] + freq[2 * k + 1]) / 2.0)
+                .collect(),
+        );
+        let conserving = FluxConservingResampler::new(OutOfRange::Nan);
+        let binned = cube
+            .spectral_interpolate(&binned_axis, &conserving, false)
+            .unwrap();
+        for k in 0..4 {
+            assert_relative_eq!(
+                binned.get(0, 0, k).unwrap(),
+                2.0 * k as f64 + 0.5,
+                max_relative = 1e-9
+            );
+        }
+
+        // Onto a single channel, the channel holding it.
+        let one = SpectralAxis::from_si(SpectralKind::Frequency, vec![freq[5]]);
+        let single = cube.spectral_interpolate(&one, &conserving, false).unwrap();
+        assert_eq!(single.shape(), [2, 2, 1]);
+        assert_relative_eq!(single.get(1, 0, 0).unwrap(), 6.0, max_relative = 1e-9);
+
+        // Pre-smoothing spreads a one-channel spike before it is sampled.
+        let spike = cube_from([1, 1, 8], |_, _, z| if z == 4 { 1.0 } else { 0.0 });
+        let sharp = spike
+            .spectral_interpolate(&binned_axis, &conserving, false)
+            .unwrap();
+        let smooth = spike
+            .spectral_interpolate(&binned_axis, &conserving, true)
+            .unwrap();
+        assert_relative_eq!(sharp.get(0, 0, 2).unwrap(), 0.5, max_relative = 1e-9);
+        assert!(smooth.get(0, 0, 2).unwrap() < 0.5);
+        assert!(smooth.get(0, 0, 1).unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_reproject_bilinear() {
+        // A linear gradient is reproduced exactly on a grid shifted by half a pixel.
+        let cube = cube_from([6, 6, 2], |x, y, z| {
+            x as f64 + 2.0 * y as f64 + 10.0 * z as f64
+        });
+        let target = cube.wcs().cutout(&[0.5, 1.5]);
+        let result = cube
+            .reproject(&target, [4, 3], Reprojection::Bilinear)
+            .unwrap();
+        assert_eq!(result.shape(), [4, 3, 2]);
+        for (x, y) in [(0, 0), (3, 2), (1, 1)] {
+            let expected = x as f64 + 0.5 + 2.0 * (y as f64 + 1.5) + 10.0;
+            assert_relative_eq!(result.get(x, y, 1).unwrap(), expected, epsilon = 1e-6);
+        }
+        // A grid beyond the edge of the cube is masked.
+        let outside = cube.wcs().cutout(&[10.0, 0.0]);
+        let result = cube
+            .reproject(&outside, [2, 2], Reprojection::Bilinear)
+            .unwrap();
+        assert_eq!(result.statistics().npix, 0);
+    }
+
+    #[test]
+    fn test_reproject_flux_conserving() {
+        // A point source onto pixels twice as large, in a 3D header like another cube's.
+        let cube = cube_from(
+            [8, 8, 1],
+            |x, y, _| if (x, y) == (3, 4) { 8.0 } else { 0.0 },
+        );
+        let mut header = cube_header([4, 4, 1]);
+        header.set("CDELT1", -2.0 / 3600.0);
+        header.set("CDELT2", 2.0 / 3600.0);
+        let mut hdu = ImageHdu::new(&[4, 4, 1], &[0.0; 16], Bitpix::F64).unwrap();
+        *hdu.header_mut() = header;
+        let result = cube
+            .reproject_like(&hdu, Reprojection::FluxConserving)
+            .unwrap();
+        assert_eq!(result.shape(), [4, 4, 1]);
+        // Flux is the sum of values times pixel area, four times larger on the new grid.
+        let sum: f64 = result.data().iter().filter(|v| v.is_finite()).sum();
+        assert_relative_eq!(4.0 * sum, 8.0, max_relative = 1e-6);
+        assert_eq!(result.statistics().npix, 16);
+    }
+
+    #[test]
+    fn test_clipped_area() {
+        let square = [[0.0, 0.0], [2.0, 0.0], [2.0, 2.0], [0.0, 2.0]];
+        assert_relative_eq!(clipped_area(&square, [1.0, 1.0], [3.0, 3.0]), 1.0);
+        let diamond = [[0.0, -1.0], [1.0, 0.0], [0.0, 1.0], [-1.0, 0.0]];
+        assert_relative_eq!(clipped_area(&diamond, [-0.5, -0.5], [0.5, 0.5]), 1.0);
+        assert_relative_eq!(clipped_area(&diamond, [0.0, 0.0], [1.0, 1.0]), 0.5);
+        assert_eq!(clipped_area(&square, [5.0, 5.0], [6.0, 6.0]), 0.0);
+    }
+}
@@ -0,0 +1,441 @@
+//! Spatial convolution to a common beam and smoothing along the spectral axis, modelled on
+//! `SpectralCube.convolve_to`, `spectral_smooth` and `downsample_axis`.
+//!
+//! Convolutions skip masked pixels and renormalise the kernel over those that remain, as the
+//! spectrum smoothers in [`crate::manipulation::smoothing`] do. The mask of the input is kept.
+
+use crate::beam::Beam;
+use crate::constants::SIGMA_TO_FWHM;
+use crate::errors::cube::CubeError;
+use crate::manipulation::smoothing::{Kernel1D, velocity_resolution_kernel};
+use crate::units::angle::{degree, radian};
+use crate::units::f64::Velocity;
+
+use super::{Beams, SpectralCube};
+
+/// A normalised two-dimensional kernel `2 half[0] + 1` pixels wide and `2 half[1] + 1` high.
+struct Kernel2D {
+    half: [usize; 2],
+    values: Vec<f64>,
+}
+
+impl Kernel2D {
+    /// An elliptical Gaussian with the FWHM and position angle of `beam` sampled on a pixel
+    /// grid whose intermediate world coordinates, in degrees east and north, are `matrix`
+    /// times the pixel offsets. The kernel is truncated at four standard deviations.
+    fn gaussian(beam: &Beam, matrix: &[Vec<f64>]) -> Self {
+        let sigma_major = beam.major.get::<degree>() / SIGMA_TO_FWHM;
+        let sigma_minor = beam.minor.get::<degree>() / SIGMA_TO_FWHM;
+        let (sin_pa, cos_pa) = beam.pa.get::<radian>().sin_cos();
+        // Covariance on the sky, with the major axis at the position angle east of north.
+        let major = [sin_pa, cos_pa];
+        let minor = [cos_pa, -sin_pa];
+        let sky = |i: usize, j: usize| {
+            sigma_major.powi(2) * major[i] * major[j] + sigma_minor.powi(2) * minor[i] * minor[j]
+        };
+        // Covariance in pixels, M⁻¹ Σ M⁻ᵀ, regularised so thin kernels stay invertible.
+        let det = matrix[0][0] * matrix[1][1] - matrix[0][1] * matrix[1][0];
+        let inverse = [
+            [matrix[1][1] / det, -matrix[0][1] / det],
+            [-matrix[1][0] / det, matrix[0][0] / det],
+        ];
+        let mut pixel = [[0.0; 2]; 2];
+        for (i, row) in pixel.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..2)
+                    .flat_map(|k| (0..2).map(move |l| (k, l)))
+                    .map(|(k, l)| inverse[i][k] * sky(k, l) * inverse[j][l])
+                    .sum();
+            }
+            row[i] += 1e-6;
+        }
+        let det = pixel[0][0] * pixel[1][1] - pixel[0][1] * pixel[1][0];
+        let precision = [
+            [pixel[1][1] / det, -pixel[0][1] / det],
+            [-pixel[1][0] / det, pixel[0][0] / det],
+        ];
+
+        let half = [0, 1].map(|i| (4.0 * pixel[i][i].sqrt()).ceil() as usize);
+        let mut values = Vec::with_capacity((2 * half[0] + 1) * (2 * half[1] + 1));
+        for j in -(half[1] as isize)..=half[1] as isize {
+            for i in -(half[0] as isize)..=half[0] as isize {
+                let (x, y) = (i as f64, j as f64);
+                let q = precision[0][0] * x * x
+                    + (precision[0][1] + precision[1][0]) * x * y
+                    + precision[1][1] * y * y;
+                values.push((-0.5 * q).exp());
+            }
+        }
+        let sum: f64 = values.iter().sum();
+        values.iter_mut().for_each(|v| *v /= sum);
+        Self { half, values }
+    }
+
+    /// Convolve an `nx × ny` plane, skipping masked pixels. Masked pixels are left as they
+    /// are.
+    fn convolve(&self, plane: &[f64], mask: &[bool], nx: usize, ny: usize) -> Vec<f64> {
+        let [hx, hy] = self.half;
+        let width = 2 * hx + 1;
+        let mut out = plane.to_vec();
+        for y in 0..ny {
+            for x in 0..nx {
+                if mask[y * nx + x] {
+                    continue;
+                }
+                let (mut sum_w, mut sum_wf) = (0.0, 0.0);
+                for v in y.saturating_sub(hy)..(y + hy + 1).min(ny) {
+                    for u in x.saturating_sub(hx)..(x + hx + 1).min(nx) {
+                        let p = v * nx + u;
+                        if mask[p] {
+                            continue;
+                        }
+                        let w = self.values[(v + hy - y) * width + (u + hx - x)];
+                        sum_w += w;
+                        sum_wf += w * plane[p];
+                    }
+                }
+                out[y * nx + x] = sum_wf / sum_w;
+            }
+        }
+        out
+    }
+}
+
+impl SpectralCube {
+    /// Convolve every channel to the resolution of `target`.
+    ///
+    /// Each channel is convolved with the Gaussian that, convolved with its own beam, gives
+    /// `target`; channels already at `target` are left unchanged. Intensities per beam
+    /// (units containing `/beam`) are scaled by the ratio of beam areas so they are per
+    /// target beam. The result has the single beam `target`.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::MissingBeam`] if the cube has no beam, and
+    /// [`CubeError::BeamTooSmall`] if `target` cannot be deconvolved from a channel beam.
+    pub fn convolve_to(&self, target: Beam) -> Result<Self, CubeError> {
+        let beams = (0..self.nchan())
+            .map(|z| self.beam(z).ok_or(CubeError::MissingBeam))
+            .collect::<Result<Vec<_>, _>>()?;
+        let per_beam = self.unit.to_lowercase().contains("/beam");
+        let matrix = self.wcs.matrix();
+        let [nx, ny, _] = self.shape;
+        let plane = nx * ny;
+
+        let mut data = self.data.clone();
+        let mut kernels: Vec<(Beam, Kernel2D)> = Vec::new();
+        for (z, beam) in beams.iter().enumerate() {
+            if *beam == target {
+                continue;
+            }
+            let kernel = target.deconvolve(*beam);
+            if kernel.major.get::<radian>() <= 0.0 {
+                return Err(CubeError::BeamTooSmall(z));
+            }
+            let index = match kernels.iter().position(|(b, _)| b == beam) {
+                Some(index) => index,
+                None => {
+                    kernels.push((*beam, Kernel2D::gaussian(&kernel, &matrix)));
+                    kernels.len() - 1
+                }
+            };
+            let channel = z * plane..(z + 1) * plane;
+            let mut convolved = kernels[index].1.convolve(
+                &self.data[channel.clone()],
+                &self.mask[channel.clone()],
+                nx,
+                ny,
+            );
+            if per_beam {
+                let scale = (target.area / beam.area).value;
+                convolved.iter_mut().for_each(|v| *v *= scale);
+            }
+            data[channel].copy_from_slice(&convolved);
+        }
+        Ok(Self {
+            data,
+            beams: Some(Beams::Single(target)),
+            ..self.clone()
+        })
+    }
+
+    /// Convolve the spectrum of every pixel with `kernel`.
+    #[must_use]
+    pub fn spectral_smooth(&self, kernel: &Kernel1D) -> Self {
+        let plane = self.shape[0] * self.shape[1];
+        let nchan = self.nchan();
+        let mut data = self.data.clone();
+        for pixel in 0..plane {
+            let indices: Vec<usize> = (0..nchan).map(|z| z * plane + pixel).collect();
+            let spectrum: Vec<f64> = indices.iter().map(|&i| self.data[i]).collect();
+            let mask: Vec<bool> = indices.iter().map(|&i| self.mask[i]).collect();
+            let (smoothed, _) = kernel.convolve(&spectrum, None, Some(&mask));
+            for (&i, value) in indices.iter().zip(smoothed) {
+                if !self.mask[i] {
+                    data[i] = value;
+                }
+            }
+        }
+        Self {
+            data,
+            ..self.clone()
+        }
+    }
+
+    /// Gaussian-smooth the spectral axis to a velocity resolution (FWHM) of `resolution`,
+    /// taking the native resolution to be the mean channel width.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::Spectrum`] if the axis cannot be expressed in velocity or is
+    /// already coarser than `resolution`.
+    pub fn spectral_smooth_to_resolution(&self, resolution: Velocity) -> Result<Self, CubeError> {
+        let kernel = velocity_resolution_kernel(&self.spectral_axis, resolution)?;
+        Ok(self.spectral_smooth(&kernel))
+    }
+
+    /// Average each run of `factor` channels into one, dropping any incomplete run at the end.
+    /// Masked values are left out of the averages; a run with no unmasked value is masked.
+    /// Per-channel beams are replaced by the largest beam of each run.
+    ///
+    /// # Errors
+    /// Returns [`CubeError::InvalidFactor`] if `factor` is zero or larger than the number of
+    /// channels.
+    pub fn downsample_spectral(&self, factor: usize) -> Result<Self, CubeError> {
+        let nchan = self.nchan() / factor.max(1);
+        if factor == 0 || nchan == 0 {
+            return Err(CubeError::InvalidFactor(factor));
+        }
+        let plane = self.shape[0] * self.shape[1];
+        let mut data = Vec::with_capacity(nchan * plane);
+        for block in 0..nchan {
+            let channels = block * factor..(block + 1) * factor;
+            for pixel in 0..plane {
+                let (sum, count) = channels
+                    .clone()
+                    .map(|z| z * plane + pixel)
+                    .filter(|&i| !self.mask[i])
+                    .fold((0.0, 0usize), |(s, n), i| (s + self.data[i], n + 1));
+                data.push(if count == 0 {
+                    f64::NAN
+                } else {
+                    sum / count as f64
+                });
+            }
+        }
+        let values = self.spectral_axis.values();
+        let axis = self.spectral_axis.with_values(
+            (0..nchan)
+                .map(|block| {
+                    values[block * factor..(block + 1) * factor]
+                        .iter()
+                        .sum::<f64>()
+                        / factor as f64
+                })
+                .collect(),
+        );
+        let beams = self.beams.as_ref().map(|beams| match beams {
+            Beams::Single(beam) => Beams::Single(*beam),
+            Beams::PerChannel(beams) => Beams::PerChannel(
+                beams
+                    .chunks_exact(factor)
+                    .map(|run| {
+                        *run.iter()
+                            .max_by(|a, b| a.area.value.total_cmp(&b.area.value))
+                            .unwrap()
+                    })
+                    .collect(),
+            ),
+        });
+        let mut cube = Self::new(
+            data,
+            [self.shape[0], self.shape[1], nchan],
+            self.wcs.clone(),
+            axis,
+            self.unit.clone(),
+        )?;
+        cube.beams = beams;
+        Ok(cube)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::tests::cube_from;
+    use crate::units::angle::arcsecond;
+    use crate::units::f64::Angle;
+    use crate::units::velocity::kilometer_per_second;
+    use approx::assert_relative_eq;
+
+    fn beam(major: f64, minor: f64, pa: f64) -> Beam {
+        Beam::new(
+            Some(Angle::new::<arcsecond>(major)),
+            Some(Angle::new::<arcsecond>(minor)),
+            Some(Angle::new::<degree>(pa)),
+            None,
+        )
+        .unwrap()
+    }
+
+    /// Second moments, in pixels, of the image of channel `z` about pixel `(cx, cy)`.
+    fn second_moments(cube: &SpectralCube, z: usize, cx: usize, cy: usize) -> [f64; 3] {
+        let [nx, ny, _] = cube.shape();
+        let (mut sum, mut xx, mut yy, mut xy) = (0.0, 0.0, 0.0, 0.0);
+        for y in 0..ny {
+            for x in 0..nx {
+                let f = cube.get(x, y, z).unwrap();
+                let (dx, dy) = (x as f64 - cx as f64, y as f64 - cy as f64);
+                sum += f;
+                xx += f * dx * dx;
+                yy += f * dy * dy;
+                xy += f * dx * dy;
+            }
+        }
+        [xx / sum, yy / sum, xy / sum]
+    }
+
+    #[test]
+    fn test_convolve_to() {
+        // Point sources in 1" pixels observed with beams of 3" and 4" FWHM.
+        let point = |x: usize, y: usize, _| if (x, y) == (20, 20) { 1.0 } else { 0.0 };
+        let cube = cube_from([41, 41, 2], point)
+            .with_beams(vec![beam(3.0, 3.0, 0.0), beam(4.0, 4.0, 0.0)])
+            .unwrap();
+        let target = beam(6.0, 5.0, 30.0);
+        let convolved = cube.convolve_to(target).unwrap();
+        assert_eq!(convolved.beams(), Some(&Beams::Single(target)));
+
+        // Channel 0 is now a Gaussian whose FWHM² is that of the target minus 3².
+        let sigma2 = |fwhm: f64| (fwhm / SIGMA_TO_FWHM).powi(2);
+        let (major, minor) = (sigma2(6.0) - sigma2(3.0), sigma2(5.0) - sigma2(3.0));
+        let (sin, cos) = 30f64.to_radians().sin_cos();
+        // East is -x because CDELT1 is negative; north is +y.
+        let expected_xx = major * sin * sin + minor * cos * cos;
+        let expected_yy = major * cos * cos + minor * sin * sin;
+        let expected_xy = -(major - minor) * sin * cos;
+        let [xx, yy, xy] = second_moments(&convolved, 0, 20, 20);
+        assert_relative_eq!(xx, expected_xx + 1e-6, max_relative = 5e-3);
+        assert_relative_eq!(yy, expected_yy + 1e-6, max_relative = 5e-3);
+        assert_relative_eq!(xy, expected_xy, max_relative = 5e-3);
+
+        // Jy/beam is rescaled to the larger beam, conserving flux density.
+        let sum = |z: usize| {
+            (0..41 * 41)
+                .map(|i| convolved.data()[z * 41 * 41 + i])
+                .sum::<f64>()
+        };
+        assert_relative_eq!(
+            sum(0),
+            target.area.value / beam(3.0, 3.0, 0.0).area.value,
+            max_relative = 1e-9
+        );
+        assert_relative_eq!(
+            sum(1),
+            target.area.value / beam(4.0, 4.0, 0.0).area.value,
+            max_relative = 1e-9
+        );
+
+        // A channel already at the target is untouched.
+        let same = cube.convolve_to(beam(4.0, 4.0, 0.0)).unwrap();
+        assert_eq!(same.data()[41 * 41..], cube.data()[41 * 41..]);
+        assert!(same.get(20, 20, 0).unwrap() < 1.0);
+
+        assert!(matches!(
+            cube.convolve_to(beam(3.5, 3.5, 0.0)),
+            Err(CubeError::BeamTooSmall(1))
+        ));
+        assert!(matches!(
+            cube_from([4, 4, 2], point).convolve_to(target),
+            Err(CubeError::MissingBeam)
+        ));
+    }
+
+    #[test]
+    fn test_convolve_masked() {
+        let cube = cube_from([9, 9, 1], |_, _, _| 2.0).with_beam(beam(2.0, 2.0, 0.0));
+        let mut mask = vec![false; 81];
+        mask[40] = true;
+        let masked = cube.with_mask(mask).unwrap();
+        let mut data = masked.data().to_vec();
+        data[40] = f64::NAN;
+        let convolved = SpectralCube::new(
+            data,
+            [9, 9, 1],
+            masked.wcs().clone(),
+            masked.spectral_axis().clone(),
+            "K",
+        )
+        .unwrap()
+        .with_beam(beam(2.0, 2.0, 0.0))
+        .convolve_to(beam(5.0, 5.0, 0.0))
+        .unwrap();
+        // Renormalising over unmasked pixels keeps a flat image flat, in K.
+        for (i, v) in convolved.data().iter().enumerate() {
+            if i == 40 {
+                assert!(v.is_nan() && convolved.mask()[i]);
+            } else {
+                assert_relative_eq!(*v, 2.0, max_relative = 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_spectral_smoothing() {
+        let cube = cube_from(
+            [2, 2, 9],
+            |x, _, z| if z == 4 { 3.0 + x as f64 } else { 0.0 },
+        );
+        let smoothed = cube.spectral_smooth(&Kernel1D::boxcar(3.0).unwrap());
+        assert_eq!(smoothed.get(1, 0, 3), Some(4.0 / 3.0));
+        assert_eq!(smoothed.get(0, 1, 5), Some(1.0));
+        assert_eq!(smoothed.get(0, 1, 6), Some(0.0));
+
+        // Channels are 0.5 MHz, about 1.3 km/s, wide.
+        let line = cube_from([1, 1, 21], |_, _, z| if z == 10 { 3.0 } else { 0.0 });
+        let coarse = line
+            .spectral_smooth_to_resolution(Velocity::new::<kilometer_per_second>(5.0))
+            .unwrap();
+        let total: f64 = (0..21).map(|z| coarse.get(0, 0, z).unwrap()).sum();
+        assert_relative_eq!(total, 3.0, max_relative = 1e-5);
+        assert!(coarse.get(0, 0, 10).unwrap() < 1.0);
+        assert!(matches!(
+            cube.spectral_smooth_to_resolution(Velocity::new::<kilometer_per_second>(1.0)),
+            Err(CubeError::Spectrum(_))
+        ));
+    }
+
+    #[test]
+    fn test_downsample() {
+        let beams: Vec<Beam> = (0..7).map(|z| beam(2.0 + z as f64, 2.0, 0.0)).collect();
+        let cube = cube_from([2, 1, 7], |x, _, z| (10 * x + z) as f64)
+            .with_beams(beams)
+            .unwrap();
+        let mut mask = vec![false; 14];
+        mask[cube.index(1, 0, 1)] = true;
+        let down = cube
+            .clone()
+            .with_mask(mask)
+            .unwrap()
+            .downsample_spectral(3)
+            .unwrap();
+        assert_eq!(down.shape(), [2, 1, 2]);
+        assert_eq!(down.get(0, 0, 0), Some(1.0));
+        assert_eq!(down.get(1, 0, 0), Some(11.0));
+        assert_eq!(down.get(0, 0, 1), Some(4.0));
+        let values = cube.spectral_axis().values();
+        assert_relative_eq!(
+            down.spectral_axis().values()[1],
+            values[4],
+            max_relative = 1e-12
+        );
+        assert_eq!(down.spectral_axis().rest(), cube.spectral_axis().rest());
+        assert_eq!(down.beam(1), Some(beam(7.0, 2.0, 0.0)));
+        assert!(matches!(
+            cube.downsample_spectral(0),
+            Err(CubeError::InvalidFactor(0))
+        ));
+        assert!(matches!(
+            cube.downsample_spectral(8),
+            Err(CubeError::InvalidFactor(8))
+        ));
+    }
+}
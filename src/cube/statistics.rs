@@ -0,0 +1,160 @@
+//! Summary statistics of the unmasked values of a cube.
+
+use super::SpectralCube;
+
+/// Statistics of the unmasked values of a cube, as `SpectralCube.statistics`. The standard
+/// deviation is the population one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Statistics {
+    pub npix: usize,
+    pub sum: f64,
+    pub mean: f64,
+    pub std: f64,
+    pub rms: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Running sums from which [`Statistics`] are computed. The variance is accumulated as the
+/// sum of squared deviations from the running mean (Welford), and sums of channels are
+/// merged with the pairwise update of Chan et al. (1979), in channel order so that chunked
+/// and in-memory statistics agree exactly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Sums {
+    count: usize,
+    sum: f64,
+    sum_sq: f64,
+    mean: f64,
+    /// Sum of squared deviations from `mean`
+    m2: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Default for Sums {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            sum: 0.0,
+            sum_sq: 0.0,
+            mean: 0.0,
+            m2: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl Sums {
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.sum_sq += value * value;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub(crate) fn merge(&mut self, other: &Self) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+        let (a, b) = (self.count as f64, other.count as f64);
+        let n = a + b;
+        let delta = other.mean - self.mean;
+        self.mean += delta * b / n;
+        self.m2 += other.m2 + delta * delta * a * b / n;
+        self.count += other.count;
+        self.sum += other.sum;
+        self.sum_sq += other.sum_sq;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Statistics of the values summed. All but `npix` are NaN if there are none.
+    pub(crate) fn finish(&self) -> Statistics {
+        if self.count == 0 {
+            return Statistics {
+                npix: 0,
+                sum: 0.0,
+                mean: f64::NAN,
+                std: f64::NAN,
+                rms: f64::NAN,
+                min: f64::NAN,
+                max: f64::NAN,
+            };
+        }
+        let n = self.count as f64;
+        let mean = self.sum / n;
+        Statistics {
+            npix: self.count,
+            sum: self.sum,
+            mean,
+            std: (self.m2 / n).sqrt(),
+            rms: (self.sum_sq / n).sqrt(),
+            min: self.min,
+            max: self.max,
+        }
+    }
+}
+
+impl SpectralCube {
+    /// Sums of the unmasked values of each channel.
+    pub(crate) fn channel_sums(&self) -> Vec<Sums> {
+        let plane = self.shape[0] * self.shape[1];
+        (0..self.nchan())
+            .map(|z| {
+                let mut sums = Sums::default();
+                let channel = z * plane..(z + 1) * plane;
+                for (&value, &masked) in self.data[channel.clone()].iter().zip(&self.mask[channel])
+                {
+                    if !masked {
+                        sums.add(value);
+                    }
+                }
+                sums
+            })
+            .collect()
+    }
+
+    /// Statistics of all unmasked values.
+    pub fn statistics(&self) -> Statistics {
+        let mut total = Sums::default();
+        for sums in self.channel_sums() {
+            total.merge(&sums);
+        }
+        total.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cube::tests::cube_from;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_statistics() {
+        let cube = cube_from([2, 2, 2], |x, y, z| (x + 2 * y + 4 * z) as f64);
+        let mut mask = vec![false; 8];
+        mask[7] = true;
+        let stats = cube.with_mask(mask).unwrap().statistics();
+        assert_eq!(stats.npix, 7);
+        assert_eq!(stats.sum, 21.0);
+        assert_eq!(stats.mean, 3.0);
+        assert_eq!((stats.min, stats.max), (0.0, 6.0));
+        assert_relative_eq!(stats.std, 2.0, max_relative = 1e-12);
+        assert_relative_eq!(stats.rms, 13.0_f64.sqrt(), max_relative = 1e-12);
+
+        // A large offset does not swamp the spread.
+        let offset = cube_from([2, 2, 2], |x, y, z| 1e9 + (x + 2 * y + 4 * z) as f64);
+        let stats = offset.statistics();
+        assert_relative_eq!(stats.mean, 1e9 + 3.5, max_relative = 1e-15);
+        assert_relative_eq!(stats.std, 5.25_f64.sqrt(), max_relative = 1e-12);
+    }
+}
@@ -37,3 +37,216 @@ pub mod radio {
         MinorGreaterThanMajor,
     }
 }
+
+pub mod spectrum {
+    use thiserror::Error;
+
+    #[derive(Debug, Error, PartialEq)]
+    pub enum SpectrumError {
+        #[error("Expected {expected} values, found {found}.")]
+        LengthMismatch { expected: usize, found: usize },
+
+        #[error(
+            "A rest value is required to convert between velocity and frequency or wavelength."
+        )]
+        MissingRestValue,
+
+        #[error("No channels within the requested spectral range.")]
+        EmptySelection,
+
+        #[error("Spectral axes do not match.")]
+        SpectralAxisMismatch,
+
+        #[error("Cannot combine {0:?} and {1:?} spectral values.")]
+        KindMismatch(crate::spectrum::SpectralKind, crate::spectrum::SpectralKind),
+
+        #[error("Incompatible flux units: {0:?} and {1:?}.")]
+        UnitMismatch(String, String),
+
+        #[error("Smoothing width must be positive and finite, got {0}.")]
+        InvalidWidth(f64),
+
+        #[error(
+            "Kernel needs an odd number of weights with a positive sum, got {len} summing to {sum}."
+        )]
+        InvalidKernel { len: usize, sum: f64 },
+
+        #[error("Target resolution is finer than the channel width.")]
+        ResolutionTooFine,
+
+        #[error("The spectrum has no uncertainty.")]
+        MissingUncertainty,
+
+        #[error("The spectral axis has no velocity reference frame.")]
+        MissingFrame,
+
+        #[error("The peak flux {peak} does not rise above {level}.")]
+        PeakBelowLevel { peak: f64, level: f64 },
+    }
+}
+
+pub mod fitting {
+    use super::spectrum::SpectrumError;
+    use thiserror::Error;
+
+    #[derive(Debug, Error, PartialEq)]
+    pub enum FitError {
+        #[error("Not enough channels to fit: need at least {required}, found {found}.")]
+        TooFewPoints { required: usize, found: usize },
+
+        #[error("The least-squares system is singular.")]
+        SingularMatrix,
+
+        #[error(
+            "Parameter {index} of component {component} is tied to a missing or circular target."
+        )]
+        InvalidTie { component: usize, index: usize },
+
+        #[error(transparent)]
+        Spectrum(#[from] SpectrumError),
+    }
+}
+
+pub mod fits {
+    use std::io;
+    use thiserror::Error;
+
+    #[derive(Debug, Error)]
+    pub enum FitsError {
+        #[error("IO error: {0}")]
+        Io(#[from] io::Error),
+
+        #[error("Invalid header card: {0:?}")]
+        InvalidCard(String),
+
+        #[error("Header has no END card.")]
+        MissingEnd,
+
+        #[error("Missing required keyword {0}.")]
+        MissingKeyword(String),
+
+        #[error("Keyword {0} has an invalid value.")]
+        InvalidKeyword(String),
+
+        #[error("Unsupported BITPIX {0}.")]
+        UnsupportedBitpix(i64),
+
+        #[error("Unsupported binary table format {0:?}.")]
+        UnsupportedFormat(String),
+
+        #[error("File is truncated: expected {expected} bytes, found {found}.")]
+        Truncated { expected: usize, found: usize },
+
+        #[error("Expected {expected} values, found {found}.")]
+        LengthMismatch { expected: usize, found: usize },
+
+        #[error("No column named {0:?}.")]
+        NoSuchColumn(String),
+
+        #[error("Column {0:?} does not hold numeric data.")]
+        NotNumeric(String),
+
+        #[error("HDU {0} is not an image.")]
+        NotAnImage(usize),
+
+        #[error("Index {index} is out of range for {len} elements.")]
+        OutOfRange { index: usize, len: usize },
+    }
+}
+
+pub mod wcs {
+    use thiserror::Error;
+
+    #[derive(Debug, Error, PartialEq)]
+    pub enum WcsError {
+        #[error("Missing required keyword {0}.")]
+        MissingKeyword(String),
+
+        #[error("Keyword {0} has an invalid value.")]
+        InvalidKeyword(String),
+
+        #[error("Unsupported projection {0:?}.")]
+        UnsupportedProjection(String),
+
+        #[error("Unsupported spectral axis type {0:?}.")]
+        UnsupportedSpectralType(String),
+
+        #[error("Unsupported unit {0:?} for axis {1}.")]
+        UnsupportedUnit(String, usize),
+
+        #[error("The linear transformation matrix is singular.")]
+        SingularMatrix,
+
+        #[error("Expected {expected} coordinates, found {found}.")]
+        DimensionMismatch { expected: usize, found: usize },
+
+        #[error("The WCS has no celestial axes.")]
+        NotCelestial,
+
+        #[error("The WCS has no spectral axis.")]
+        NotSpectral,
+
+        #[error("The longitude and latitude axes must be kept together.")]
+        SplitCelestialAxes,
+
+        #[error("Coordinate lies outside the valid region of the projection.")]
+        OutsideProjection,
+    }
+}
+
+pub mod cube {
+    use super::fits::FitsError;
+    use super::radio::BeamError;
+    use super::spectrum::SpectrumError;
+    use super::wcs::WcsError;
+    use std::io;
+    use thiserror::Error;
+
+    #[derive(Debug, Error)]
+    pub enum CubeError {
+        #[error("IO error: {0}")]
+        Io(#[from] io::Error),
+
+        #[error("Expected {expected} values, found {found}.")]
+        LengthMismatch { expected: usize, found: usize },
+
+        #[error("Not a spectral cube: {0}.")]
+        NotACube(String),
+
+        #[error("Index {index} is out of range for {len} elements.")]
+        OutOfRange { index: usize, len: usize },
+
+        #[error("The selection contains no pixels.")]
+        EmptySelection,
+
+        #[error("The spectral axis is not linear and cannot be written as a FITS WCS.")]
+        NonLinearSpectralAxis,
+
+        #[error("The cube has no beam.")]
+        MissingBeam,
+
+        #[error("No beam for channel {0}.")]
+        MissingChannelBeam(usize),
+
+        #[error("Unsupported angle unit {0:?}.")]
+        UnsupportedUnit(String),
+
+        #[error("The target beam cannot be deconvolved from the beam of channel {0}.")]
+        BeamTooSmall(usize),
+
+        #[error("Invalid downsampling factor {0}.")]
+        InvalidFactor(usize),
+
+        #[error(transparent)]
+        Wcs(#[from] WcsError),
+
+        #[error(transparent)]
+        Fits(#[from] FitsError),
+
+        #[error(transparent)]
+        Spectrum(#[from] SpectrumError),
+
+        #[error(transparent)]
+        Beam(#[from] BeamError),
+    }
+}
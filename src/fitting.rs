@@ -0,0 +1,7 @@
+//! Model fitting, modelled on `specutils.fitting`.
+
+pub mod continuum;
+pub(crate) mod linalg;
+pub mod line_finding;
+pub mod lines;
+pub mod profiles;
@@ -0,0 +1,417 @@
+//! Baseline and continuum fitting.
+//!
+//! A [`ContinuumFitter`] fits a linear model (polynomial, Chebyshev series or cubic spline)
+//! by weighted least squares to the channels of a spectrum that are not masked. The fit can
+//! be restricted to user-given line-free windows, and line emission or absorption can be
+//! excluded automatically by iterative sigma clipping of the residuals.
+//!
+//! The models are evaluated in a normalised coordinate `t`, mapped linearly from the SI
+//! values of the spectral axis onto `[-1, 1]` over the fitted spectrum's range, which keeps
+//! the least-squares problem well conditioned whatever the axis units.
+//!
+//! ```
+//! use spectre::fitting::continuum::{ContinuumFitter, ContinuumModel};
+//! use spectre::spectrum::{SpectralAxis, SpectralKind, Spectrum1D};
+//!
+//! let x: Vec<f64> = (0..50).map(|i| 1e9 + 1e6 * f64::from(i)).collect();
+//! let mut flux: Vec<f64> = (0..50).map(|i| 1.0 + 0.02 * f64::from(i)).collect();
+//! flux[25] += 10.0;
+//! let spectrum = Spectrum1D::new(SpectralAxis::from_si(SpectralKind::Frequency, x), flux, "K")?;
+//!
+//! let fit = ContinuumFitter::new(ContinuumModel::Polynomial(1))
+//!     .with_sigma_clip(3.0)
+//!     .fit(&spectrum)?;
+//! assert!(!fit.included[25]);
+//! assert!(fit.rms < 1e-10);
+//! assert!((fit.subtracted.flux()[25] - 10.0).abs() < 1e-8);
+//! # Ok::<(), spectre::errors::fitting::FitError>(())
+//! ```
+
+use super::linalg::least_squares;
+use crate::errors::fitting::FitError;
+use crate::spectrum::{SpectralAxis, SpectralRegion, SpectralValue, Spectrum1D};
+
+/// Functional form of a continuum model.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContinuumModel {
+    /// Polynomial of the given degree in the normalised coordinate.
+    Polynomial(usize),
+    /// Chebyshev series of the first kind of the given degree.
+    Chebyshev(usize),
+    /// Cubic spline with the given number of evenly spaced interior knots.
+    Spline(usize),
+}
+
+impl ContinuumModel {
+    /// Number of free parameters.
+    pub fn n_params(&self) -> usize {
+        match *self {
+            Self::Polynomial(degree) | Self::Chebyshev(degree) => degree + 1,
+            Self::Spline(knots) => knots + 4,
+        }
+    }
+
+    /// Values of the basis functions at the normalised coordinate `t`.
+    fn basis(&self, t: f64) -> Vec<f64> {
+        let n = self.n_params();
+        let mut out = Vec::with_capacity(n);
+        match *self {
+            Self::Polynomial(_) => {
+                let mut p = 1.0;
+                for _ in 0..n {
+                    out.push(p);
+                    p *= t;
+                }
+            }
+            Self::Chebyshev(_) => {
+                out.push(1.0);
+                if n > 1 {
+                    out.push(t);
+                }
+                for k in 2..n {
+                    out.push(2.0 * t * out[k - 1] - out[k - 2]);
+                }
+            }
+            Self::Spline(knots) => {
+                // Truncated power basis, which spans the cubic splines on the given knots.
+                out.extend([1.0, t, t * t, t * t * t]);
+                #[allow(clippy::cast_precision_loss)]
+                out.extend((1..=knots).map(|k| {
+                    let knot = -1.0 + 2.0 * k as f64 / (knots + 1) as f64;
+                    (t - knot).max(0.0).powi(3)
+                }));
+            }
+        }
+        out
+    }
+}
+
+/// A fitted continuum model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Continuum {
+    model: ContinuumModel,
+    domain: (f64, f64),
+    coefficients: Vec<f64>,
+}
+
+impl Continuum {
+    pub fn model(&self) -> ContinuumModel {
+        self.model
+    }
+
+    /// The SI spectral values mapped onto `t = -1` and `t = 1`.
+    pub fn domain(&self) -> (f64, f64) {
+        self.domain
+    }
+
+    /// Coefficients of the basis functions in the normalised coordinate.
+    pub fn coefficients(&self) -> &[f64] {
+        &self.coefficients
+    }
+
+    /// Evaluate the model at a spectral value in SI units of the fitted axis kind.
+    pub fn evaluate(&self, x: f64) -> f64 {
+        self.model
+            .basis(self.normalise(x))
+            .iter()
+            .zip(&self.coefficients)
+            .map(|(b, c)| b * c)
+            .sum()
+    }
+
+    /// Evaluate the model at every channel of `axis`.
+    pub fn evaluate_axis(&self, axis: &SpectralAxis) -> Vec<f64> {
+        axis.values().iter().map(|&x| self.evaluate(x)).collect()
+    }
+
+    fn normalise(&self, x: f64) -> f64 {
+        let (lo, hi) = self.domain;
+        if hi > lo {
+            2.0 * (x - lo) / (hi - lo) - 1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Result of [`ContinuumFitter::fit`].
+#[derive(Debug, Clone)]
+pub struct ContinuumFit {
+    /// The fitted model
+    pub continuum: Continuum,
+    /// The model evaluated at every channel
+    pub baseline: Vec<f64>,
+    /// The spectrum with the continuum subtracted, in the original unit
+    pub subtracted: Spectrum1D,
+    /// The spectrum divided by the continuum (dimensionless)
+    pub normalised: Spectrum1D,
+    /// Root mean square of the residuals over the channels used in the final fit
+    pub rms: f64,
+    /// Channels used in the final fit
+    pub included: Vec<bool>,
+    /// Number of fits performed
+    pub iterations: usize,
+}
+
+/// Fits a continuum to a spectrum.
+#[derive(Debug, Clone)]
+pub struct ContinuumFitter {
+    pub model: ContinuumModel,
+    /// Spectral windows (inclusive) to fit; all channels are used if empty.
+    pub windows: Vec<(SpectralValue, SpectralValue)>,
+    /// Reject channels whose residual exceeds this many times the RMS, and refit.
+    pub sigma_clip: Option<f64>,
+    /// Maximum number of fits when sigma clipping.
+    pub max_iterations: usize,
+}
+
+impl ContinuumFitter {
+    pub fn new(model: ContinuumModel) -> Self {
+        Self {
+            model,
+            windows: Vec::new(),
+            sigma_clip: None,
+            max_iterations: 10,
+        }
+    }
+
+    /// Add a line-free window between `lo` and `hi`, in any order.
+    #[must_use]
+    pub fn with_window(
+        mut self,
+        lo: impl Into<SpectralValue>,
+        hi: impl Into<SpectralValue>,
+    ) -> Self {
+        self.windows.push((lo.into(), hi.into()));
+        self
+    }
+
+    /// Add every sub-range of `region` as a line-free window.
+    #[must_use]
+    pub fn with_region(mut self, region: &SpectralRegion) -> Self {
+        self.windows.extend(region.subregions());
+        self
+    }
+
+    #[must_use]
+    pub fn with_sigma_clip(mut self, sigma: f64) -> Self {
+        self.sigma_clip = Some(sigma);
+        self
+    }
+
+    /// Fit the continuum of `spectrum`.
+    ///
+    /// Channels that are masked, have a NaN flux or a non-positive uncertainty are never
+    /// used. If the spectrum has uncertainties, channels are weighted by their inverse
+    /// variance.
+    ///
+    /// # Errors
+    /// Returns [`FitError::Spectrum`] if a window cannot be converted to the spectral axis,
+    /// [`FitError::TooFewPoints`] if fewer channels than parameters remain, or
+    /// [`FitError::SingularMatrix`] if the channels do not constrain the model.
+    pub fn fit(&self, spectrum: &Spectrum1D) -> Result<ContinuumFit, FitError> {
+        let axis = &spectrum.spectral_axis;
+        let x = axis.values();
+        let sigma = spectrum.uncertainty.as_ref().map(|u| u.std_dev());
+        let windows = self
+            .windows
+            .iter()
+            .map(|&(lo, hi)| {
+                let (lo, hi) = (axis.convert_value(lo)?, axis.convert_value(hi)?);
+                Ok((lo.min(hi), lo.max(hi)))
+            })
+            .collect::<Result<Vec<_>, FitError>>()?;
+
+        let usable: Vec<bool> = (0..spectrum.len())
+            .map(|i| {
+                !spectrum.mask[i]
+                    && spectrum.flux[i].is_finite()
+                    && sigma
+                        .as_ref()
+                        .is_none_or(|s| s[i] > 0.0 && s[i].is_finite())
+                    && (windows.is_empty()
+                        || windows.iter().any(|&(lo, hi)| (lo..=hi).contains(&x[i])))
+            })
+            .collect();
+
+        let domain = x
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| {
+                (lo.min(v), hi.max(v))
+            });
+        let mut continuum = Continuum {
+            model: self.model,
+            domain,
+            coefficients: Vec::new(),
+        };
+        let basis: Vec<Vec<f64>> = x
+            .iter()
+            .map(|&v| self.model.basis(continuum.normalise(v)))
+            .collect();
+
+        let mut included = usable.clone();
+        let mut iterations = 0;
+        let (baseline, rms) = loop {
+            iterations += 1;
+            let rows: Vec<usize> = (0..x.len()).filter(|&i| included[i]).collect();
+            if rows.len() < self.model.n_params() {
+                return Err(FitError::TooFewPoints {
+                    required: self.model.n_params(),
+                    found: rows.len(),
+                });
+            }
+            let weight = |i: usize| sigma.as_ref().map_or(1.0, |s| s[i].recip());
+            let a: Vec<Vec<f64>> = rows
+                .iter()
+                .map(|&i| basis[i].iter().map(|b| b * weight(i)).collect())
+                .collect();
+            let b: Vec<f64> = rows.iter().map(|&i| spectrum.flux[i] * weight(i)).collect();
+            continuum.coefficients = least_squares(&a, &b)?;
+
+            let baseline = continuum.evaluate_axis(axis);
+            let residual = |i: usize| spectrum.flux[i] - baseline[i];
+            #[allow(clippy::cast_precision_loss)]
+            let rms =
+                (rows.iter().map(|&i| residual(i).powi(2)).sum::<f64>() / rows.len() as f64).sqrt();
+
+            let Some(clip) = self.sigma_clip else {
+                break (baseline, rms);
+            };
+            let next: Vec<bool> = (0..x.len())
+                .map(|i| usable[i] && residual(i).abs() <= clip * rms)
+                .collect();
+            if next == included || iterations >= self.max_iterations {
+                break (baseline, rms);
+            }
+            included = next;
+        };
+
+        let model = Spectrum1D {
+            flux: baseline.clone(),
+            uncertainty: None,
+            mask: vec![false; spectrum.len()],
+            ..spectrum.clone()
+        };
+        Ok(ContinuumFit {
+            continuum,
+            subtracted: (spectrum - &model)?,
+            normalised: (spectrum / &model)?,
+            baseline,
+            rms,
+            included,
+            iterations,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectrum::{SpectralKind, Uncertainty};
+    use crate::units::f64::Frequency;
+    use crate::units::frequency::hertz;
+    use approx::assert_relative_eq;
+
+    fn spectrum(f: impl Fn(f64) -> f64) -> Spectrum1D {
+        let x: Vec<f64> = (0..101).map(|i| 100.0 + f64::from(i)).collect();
+        let flux = x.iter().map(|&v| f(v)).collect();
+        Spectrum1D::new(
+            SpectralAxis::from_si(SpectralKind::Frequency, x),
+            flux,
+            "Jy",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_polynomial_and_chebyshev_agree() {
+        let s = spectrum(|x| 2.0 - 0.01 * x + 1e-4 * x * x);
+        for model in [ContinuumModel::Polynomial(2), ContinuumModel::Chebyshev(2)] {
+            let fit = ContinuumFitter::new(model).fit(&s).unwrap();
+            assert_relative_eq!(fit.continuum.evaluate(150.0), 2.75, max_relative = 1e-10);
+            assert!(fit.rms < 1e-10);
+            assert!(fit.normalised.flux.iter().all(|v| (v - 1.0).abs() < 1e-10));
+            assert_eq!(fit.normalised.unit, "");
+            assert_eq!(fit.subtracted.unit, "Jy");
+        }
+    }
+
+    #[test]
+    fn test_spline() {
+        let s = spectrum(|x| (x / 10.0).sin());
+        let fit = ContinuumFitter::new(ContinuumModel::Spline(16))
+            .fit(&s)
+            .unwrap();
+        assert!(fit.rms < 1e-4);
+        assert_eq!(fit.continuum.coefficients().len(), 20);
+    }
+
+    #[test]
+    fn test_windows() {
+        let mut s = spectrum(|_| 1.0);
+        s.flux[40..60].iter_mut().for_each(|v| *v = 5.0);
+        let fit = ContinuumFitter::new(ContinuumModel::Polynomial(1))
+            .with_window(
+                Frequency::new::<hertz>(100.0),
+                Frequency::new::<hertz>(130.0),
+            )
+            .with_window(
+                Frequency::new::<hertz>(200.0),
+                Frequency::new::<hertz>(170.0),
+            )
+            .fit(&s)
+            .unwrap();
+        assert_eq!(fit.included.iter().filter(|&&v| v).count(), 62);
+        assert_relative_eq!(fit.subtracted.flux[50], 4.0, epsilon = 1e-10);
+
+        // The same fit with the line region masked through a `SpectralRegion`.
+        let line = SpectralRegion::new(
+            Frequency::new::<hertz>(130.5),
+            Frequency::new::<hertz>(169.5),
+        )
+        .unwrap();
+        let windows = line.invert_from_spectrum(&s).unwrap();
+        let from_region = ContinuumFitter::new(ContinuumModel::Polynomial(1))
+            .with_region(&windows)
+            .fit(&s)
+            .unwrap();
+        assert_eq!(from_region.included, fit.included);
+    }
+
+    #[test]
+    fn test_sigma_clip_and_weights() {
+        let mut s = spectrum(|x| 0.5 + 0.001 * x)
+            .with_uncertainty(Uncertainty::StdDev(vec![0.1; 101]))
+            .unwrap();
+        // Alternating noise keeps the RMS finite, plus an absorption line.
+        s.flux.iter_mut().enumerate().for_each(|(i, v)| {
+            *v += if i % 2 == 0 { 0.01 } else { -0.01 };
+        });
+        s.flux[70..73].iter_mut().for_each(|v| *v -= 1.0);
+        let fit = ContinuumFitter::new(ContinuumModel::Polynomial(1))
+            .with_sigma_clip(3.0)
+            .fit(&s)
+            .unwrap();
+        assert!(fit.iterations > 1);
+        assert_eq!(fit.included.iter().filter(|&&v| !v).count(), 3);
+        assert_relative_eq!(fit.rms, 0.01, max_relative = 1e-2);
+        assert_relative_eq!(fit.baseline[50], 0.65, max_relative = 1e-3);
+        // Uncertainties are carried over; normalising divides them by the continuum.
+        let sigma = fit.normalised.uncertainty.unwrap().std_dev();
+        assert_relative_eq!(sigma[50], 0.1 / fit.baseline[50], max_relative = 1e-12);
+    }
+
+    #[test]
+    fn test_too_few_points() {
+        let s = spectrum(|_| 1.0).with_mask(vec![true; 101]).unwrap();
+        assert_eq!(
+            ContinuumFitter::new(ContinuumModel::Chebyshev(3))
+                .fit(&s)
+                .unwrap_err(),
+            FitError::TooFewPoints {
+                required: 4,
+                found: 0
+            }
+        );
+    }
+}
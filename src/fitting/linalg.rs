@@ -0,0 +1,136 @@
+//! Small dense linear algebra routines for the fitters.
+
+use crate::errors::fitting::FitError;
+
+/// Solve the linear least-squares problem `min ||A x - b||` by Householder QR.
+///
+/// `a` holds the rows of the design matrix. Returns [`FitError::SingularMatrix`] if `A` is
+/// rank deficient to working precision.
+pub(crate) fn least_squares(a: &[Vec<f64>], b: &[f64]) -> Result<Vec<f64>, FitError> {
+    let m = a.len();
+    let n = a.first().map_or(0, Vec::len);
+    if m < n {
+        return Err(FitError::TooFewPoints {
+            required: n,
+            found: m,
+        });
+    }
+    // Column-major copy, reduced in place to R.
+    let mut r: Vec<Vec<f64>> = (0..n)
+        .map(|j| a.iter().map(|row| row[j]).collect())
+        .collect();
+    let mut qtb = b.to_vec();
+    let scale = r.iter().flatten().fold(0.0_f64, |acc, v| acc.max(v.abs()));
+    for k in 0..n {
+        let norm = r[k][k..].iter().map(|v| v * v).sum::<f64>().sqrt();
+        if norm <= f64::EPSILON * scale * 1e3 {
+            return Err(FitError::SingularMatrix);
+        }
+        let alpha = if r[k][k] > 0.0 { -norm } else { norm };
+        let mut v = r[k][k..].to_vec();
+        v[0] -= alpha;
+        let v_norm2: f64 = v.iter().map(|x| x * x).sum();
+        let reflect = |col: &mut [f64]| {
+            let dot: f64 = col.iter().zip(&v).map(|(c, v)| c * v).sum();
+            let f = 2.0 * dot / v_norm2;
+            col.iter_mut().zip(&v).for_each(|(c, v)| *c -= f * v);
+        };
+        for col in r.iter_mut().skip(k) {
+            reflect(&mut col[k..]);
+        }
+        reflect(&mut qtb[k..]);
+    }
+    let mut x = vec![0.0; n];
+    for i in (0..n).rev() {
+        let s: f64 = (i + 1..n).map(|j| r[j][i] * x[j]).sum();
+        x[i] = (qtb[i] - s) / r[i][i];
+    }
+    Ok(x)
+}
+
+/// Solve the square system `A x = b` by Gaussian elimination with partial pivoting.
+pub(crate) fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Result<Vec<f64>, FitError> {
+    let n = b.len();
+    for k in 0..n {
+        let pivot = (k..n)
+            .max_by(|&i, &j| a[i][k].abs().total_cmp(&a[j][k].abs()))
+            .unwrap();
+        if a[pivot][k].abs() <= f64::MIN_POSITIVE || !a[pivot][k].is_finite() {
+            return Err(FitError::SingularMatrix);
+        }
+        a.swap(k, pivot);
+        b.swap(k, pivot);
+        let (upper, lower) = a.split_at_mut(k + 1);
+        let pivot_row = &upper[k];
+        for (i, row) in lower.iter_mut().enumerate() {
+            let f = row[k] / pivot_row[k];
+            row.iter_mut()
+                .zip(pivot_row)
+                .skip(k)
+                .for_each(|(x, p)| *x -= f * p);
+            b[k + 1 + i] -= f * b[k];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for i in (0..n).rev() {
+        let s: f64 = (i + 1..n).map(|j| a[i][j] * x[j]).sum();
+        x[i] = (b[i] - s) / a[i][i];
+    }
+    Ok(x)
+}
+
+/// Inverse of a square matrix.
+pub(crate) fn invert(a: &[Vec<f64>]) -> Result<Vec<Vec<f64>>, FitError> {
+    let n = a.len();
+    let columns = (0..n)
+        .map(|j| {
+            solve(
+                a.to_vec(),
+                (0..n).map(|i| f64::from(u8::from(i == j))).collect(),
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((0..n)
+        .map(|i| columns.iter().map(|c| c[i]).collect())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_least_squares() {
+        // y = 1 + 2x sampled exactly, plus one redundant row.
+        let a = vec![
+            vec![1.0, 0.0],
+            vec![1.0, 1.0],
+            vec![1.0, 2.0],
+            vec![1.0, 3.0],
+        ];
+        let b = vec![1.0, 3.0, 5.0, 7.0];
+        let x = least_squares(&a, &b).unwrap();
+        assert_relative_eq!(x[0], 1.0, epsilon = 1e-12);
+        assert_relative_eq!(x[1], 2.0, epsilon = 1e-12);
+
+        let singular = vec![vec![1.0, 2.0], vec![2.0, 4.0], vec![3.0, 6.0]];
+        assert_eq!(
+            least_squares(&singular, &b[..3]).unwrap_err(),
+            FitError::SingularMatrix
+        );
+    }
+
+    #[test]
+    fn test_solve_and_invert() {
+        let a = vec![vec![0.0, 2.0], vec![4.0, 1.0]];
+        let x = solve(a.clone(), vec![2.0, 9.0]).unwrap();
+        assert_relative_eq!(x[0], 2.0);
+        assert_relative_eq!(x[1], 1.0);
+        let inv = invert(&a).unwrap();
+        assert_relative_eq!(inv[0][0], -0.125);
+        assert_relative_eq!(inv[0][1], 0.25);
+        assert_relative_eq!(inv[1][0], 0.5);
+        assert_relative_eq!(inv[1][1], 0.0);
+    }
+}
@@ -0,0 +1,298 @@
+//! Automated detection of emission and absorption lines, after `specutils`'
+//! `find_lines_threshold` and `find_lines_derivative`.
+//!
+//! The noise of each channel is taken from the spectrum's uncertainty when it has one, and
+//! otherwise estimated robustly from the median absolute deviation of the unmasked flux.
+//! Masked and NaN channels never belong to a line.
+
+use std::ops::Range;
+
+use crate::spectrum::{SpectralRegion, SpectralValue, Spectrum1D};
+use crate::stats::mad_std;
+
+/// Sign of a detected feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineType {
+    Emission,
+    Absorption,
+}
+
+/// A line detected in a spectrum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoundLine {
+    pub line_type: LineType,
+    /// Flux-weighted centroid of the line channels
+    pub center: SpectralValue,
+    /// Channels spanned by the line
+    pub channels: Range<usize>,
+    /// Channel of the extremum
+    pub peak_index: usize,
+    /// Flux at the extremum, negative for absorption
+    pub peak: f64,
+    /// Noise level at the extremum
+    pub noise: f64,
+}
+
+impl FoundLine {
+    /// Signal-to-noise ratio of the extremum.
+    pub fn snr(&self) -> f64 {
+        self.peak.abs() / self.noise
+    }
+
+    /// Spectral values of the first and last channels of the line.
+    pub fn extent(&self, spectrum: &Spectrum1D) -> (SpectralValue, SpectralValue) {
+        let axis = &spectrum.spectral_axis;
+        (
+            axis.get(self.channels.start).unwrap(),
+            axis.get(self.channels.end - 1).unwrap(),
+        )
+    }
+
+    /// The spectral range of the line, for masking it or measuring it.
+    pub fn region(&self, spectrum: &Spectrum1D) -> SpectralRegion {
+        let x = spectrum.spectral_axis.values();
+        SpectralRegion::from_si(
+            spectrum.spectral_axis.kind(),
+            [(x[self.channels.start], x[self.channels.end - 1])],
+        )
+    }
+
+    fn new(
+        spectrum: &Spectrum1D,
+        line_type: LineType,
+        channels: Range<usize>,
+        noise: &[f64],
+    ) -> Option<Self> {
+        let sign = match line_type {
+            LineType::Emission => 1.0,
+            LineType::Absorption => -1.0,
+        };
+        let usable: Vec<usize> = channels.clone().filter(|&i| usable(spectrum, i)).collect();
+        let peak_index = *usable
+            .iter()
+            .max_by(|&&a, &&b| (sign * spectrum.flux[a]).total_cmp(&(sign * spectrum.flux[b])))?;
+        let x = spectrum.spectral_axis.values();
+        let (sum_w, sum_wx) = usable.iter().fold((0.0, 0.0), |(w, wx), &i| {
+            let weight = (sign * spectrum.flux[i]).max(0.0);
+            (w + weight, wx + weight * x[i])
+        });
+        let center = if sum_w > 0.0 {
+            sum_wx / sum_w
+        } else {
+            x[peak_index]
+        };
+        Some(Self {
+            line_type,
+            center: SpectralValue::from_si(spectrum.spectral_axis.kind(), center),
+            channels,
+            peak_index,
+            peak: spectrum.flux[peak_index],
+            noise: noise[peak_index],
+        })
+    }
+}
+
+fn usable(spectrum: &Spectrum1D, i: usize) -> bool {
+    !spectrum.mask[i] && spectrum.flux[i].is_finite()
+}
+
+/// Per-channel noise: the uncertainty if present, otherwise the MAD-based standard deviation
+/// of the unmasked flux.
+pub fn noise_estimate(spectrum: &Spectrum1D) -> Vec<f64> {
+    match &spectrum.uncertainty {
+        Some(u) => u.std_dev(),
+        None => {
+            let values: Vec<f64> = (0..spectrum.len())
+                .filter(|&i| usable(spectrum, i))
+                .map(|i| spectrum.flux[i])
+                .collect();
+            vec![mad_std(&values); spectrum.len()]
+        }
+    }
+}
+
+/// Find contiguous runs of channels more than `noise_factor` times the noise above
+/// (emission) or below (absorption) zero.
+///
+/// The spectrum should be continuum subtracted. Lines are returned in channel order.
+pub fn find_lines_threshold(spectrum: &Spectrum1D, noise_factor: f64) -> Vec<FoundLine> {
+    let noise = noise_estimate(spectrum);
+    let classify = |i: usize| {
+        let limit = noise_factor * noise[i];
+        match spectrum.flux[i] {
+            _ if !usable(spectrum, i) => None,
+            f if f > limit => Some(LineType::Emission),
+            f if f < -limit => Some(LineType::Absorption),
+            _ => None,
+        }
+    };
+
+    let mut lines = Vec::new();
+    let mut i = 0;
+    while i < spectrum.len() {
+        let Some(line_type) = classify(i) else {
+            i += 1;
+            continue;
+        };
+        let start = i;
+        while i < spectrum.len() && classify(i) == Some(line_type) {
+            i += 1;
+        }
+        lines.extend(FoundLine::new(spectrum, line_type, start..i, &noise));
+    }
+    lines
+}
+
+/// Find lines at the zero crossings of the flux derivative whose flux exceeds
+/// `flux_threshold` in absolute value.
+///
+/// Unlike [`find_lines_threshold`] this separates blended lines: each line extends from its
+/// extremum for as long as the flux keeps falling towards zero and stays beyond the
+/// threshold.
+pub fn find_lines_derivative(spectrum: &Spectrum1D, flux_threshold: f64) -> Vec<FoundLine> {
+    let noise = noise_estimate(spectrum);
+    let flux = &spectrum.flux;
+    let n = spectrum.len();
+    let beyond = |i: usize, sign: f64| usable(spectrum, i) && sign * flux[i] > flux_threshold;
+
+    let mut lines = Vec::new();
+    let mut i = 0;
+    while i < n {
+        let (line_type, sign) = if beyond(i, 1.0) {
+            (LineType::Emission, 1.0)
+        } else if beyond(i, -1.0) {
+            (LineType::Absorption, -1.0)
+        } else {
+            i += 1;
+            continue;
+        };
+        // A local extremum: the (one-sided at the ends) derivative changes sign.
+        let rising = i == 0 || !usable(spectrum, i - 1) || sign * (flux[i] - flux[i - 1]) >= 0.0;
+        let falling =
+            i + 1 == n || !usable(spectrum, i + 1) || sign * (flux[i + 1] - flux[i]) < 0.0;
+        if !(rising && falling) {
+            i += 1;
+            continue;
+        }
+        let mut start = i;
+        while start > 0 && beyond(start - 1, sign) && sign * (flux[start - 1] - flux[start]) <= 0.0
+        {
+            start -= 1;
+        }
+        let mut end = i + 1;
+        while end < n && beyond(end, sign) && sign * (flux[end] - flux[end - 1]) <= 0.0 {
+            end += 1;
+        }
+        lines.extend(FoundLine::new(spectrum, line_type, start..end, &noise));
+        i = end;
+    }
+    lines
+}
+
+/// Merge lines of the same type separated by at most `max_gap` channels.
+///
+/// The merged line spans both originals and the gap between them, keeps the stronger
+/// extremum, and has its centroid recomputed.
+pub fn merge_lines(
+    spectrum: &Spectrum1D,
+    mut lines: Vec<FoundLine>,
+    max_gap: usize,
+) -> Vec<FoundLine> {
+    let noise = noise_estimate(spectrum);
+    lines.sort_by_key(|l| l.channels.start);
+    let mut merged: Vec<FoundLine> = Vec::with_capacity(lines.len());
+    for line in lines {
+        match merged.last_mut() {
+            Some(last)
+                if last.line_type == line.line_type
+                    && line.channels.start <= last.channels.end + max_gap =>
+            {
+                let channels = last.channels.start..last.channels.end.max(line.channels.end);
+                if let Some(combined) = FoundLine::new(spectrum, line.line_type, channels, &noise) {
+                    *last = combined;
+                }
+            }
+            _ => merged.push(line),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitting::profiles::gaussian;
+    use crate::spectrum::{SpectralAxis, SpectralKind, Uncertainty};
+    use approx::assert_relative_eq;
+
+    fn spectrum() -> Spectrum1D {
+        let x: Vec<f64> = (0..100).map(f64::from).collect();
+        let flux = x
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| {
+                let wiggle = if i % 2 == 0 { 0.1 } else { -0.1 };
+                wiggle + gaussian(v, 2.0, 20.0, 1.5) - gaussian(v, 1.5, 60.0, 1.0)
+                    + gaussian(v, 1.0, 80.0, 1.0)
+                    + gaussian(v, 1.0, 85.0, 1.0)
+            })
+            .collect();
+        Spectrum1D::new(SpectralAxis::from_si(SpectralKind::Velocity, x), flux, "K").unwrap()
+    }
+
+    #[test]
+    fn test_threshold() {
+        let s = spectrum();
+        let lines = find_lines_threshold(&s, 3.0);
+        let types: Vec<LineType> = lines.iter().map(|l| l.line_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                LineType::Emission,
+                LineType::Absorption,
+                LineType::Emission,
+                LineType::Emission
+            ]
+        );
+        assert_relative_eq!(lines[0].center.si(), 20.0, epsilon = 0.1);
+        assert_eq!(lines[1].peak_index, 60);
+        assert!(lines[1].peak < 0.0);
+        assert!(lines[0].snr() > 5.0);
+        let (lo, hi) = lines[0].extent(&s);
+        assert!(lo.si() < 20.0 && hi.si() > 20.0);
+        assert_eq!(lines[0].region(&s).bounds(), &[(lo.si(), hi.si())]);
+
+        // With an explicit uncertainty the threshold follows it.
+        let noisy = s
+            .with_uncertainty(Uncertainty::StdDev(vec![1.0; 100]))
+            .unwrap();
+        assert_eq!(find_lines_threshold(&noisy, 1.8).len(), 1);
+    }
+
+    #[test]
+    fn test_masked_channels_split_lines() {
+        let mut mask = vec![false; 100];
+        mask[21] = true;
+        let s = spectrum().with_mask(mask).unwrap();
+        let lines = find_lines_threshold(&s, 3.0);
+        assert_eq!(lines.len(), 5);
+        let merged = merge_lines(&s, lines, 1);
+        assert_eq!(merged.len(), 4);
+        assert_eq!(merged[0].peak_index, 20);
+        assert_eq!(merged[0].channels, 18..23);
+    }
+
+    #[test]
+    fn test_derivative_and_merge() {
+        let s = spectrum();
+        let lines = find_lines_derivative(&s, 0.5);
+        assert_eq!(lines.len(), 4);
+        let centers: Vec<usize> = lines.iter().map(|l| l.peak_index).collect();
+        assert_eq!(centers, vec![20, 60, 80, 85]);
+        assert_eq!(merge_lines(&s, lines.clone(), 1).len(), 4);
+        let merged = merge_lines(&s, lines, 2);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[2].channels, 79..87);
+        assert_relative_eq!(merged[2].center.si(), 82.5, epsilon = 0.1);
+    }
+}
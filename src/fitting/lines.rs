@@ -0,0 +1,913 @@
+//! Non-linear fitting of multi-component line profiles.
+//!
+//! A [`LineModel`] is a sum of [`Component`]s — Gaussian, Lorentzian and Voigt lines and an
+//! optional polynomial baseline — whose [`Parameter`]s may be bounded, fixed, or tied to
+//! another parameter by a fixed offset or ratio. Ties are how hyperfine structure is fitted:
+//! the centres of the satellite lines are tied to the main component with the catalogue
+//! separations, and their amplitudes with the relative intensities.
+//!
+//! [`LineFitter`] minimises χ² with the Levenberg–Marquardt algorithm, weighting channels by
+//! their inverse variance when the spectrum has an uncertainty. Parameters are in SI units
+//! of the spectral axis (Hz, m or m s⁻¹) and the flux unit of the spectrum.
+//!
+//! ```
+//! use spectre::fitting::lines::{Component, LineFitter, LineModel};
+//! use spectre::fitting::profiles::gaussian;
+//! use spectre::spectrum::{SpectralAxis, SpectralKind, Spectrum1D};
+//!
+//! let v: Vec<f64> = (-50..=50).map(|i| 100.0 * f64::from(i)).collect();
+//! let flux = v.iter().map(|&x| gaussian(x, 2.0, 300.0, 800.0)).collect();
+//! let spectrum = Spectrum1D::new(SpectralAxis::from_si(SpectralKind::Velocity, v), flux, "K")?;
+//!
+//! let model = LineModel::new(vec![Component::gaussian(1.0, 0.0, 500.0)]);
+//! let fit = LineFitter::new().fit(&spectrum, &model)?;
+//! let center = fit.model.components[0].parameters()[1].value;
+//! assert!((center - 300.0).abs() < 1e-6);
+//! # Ok::<(), spectre::errors::fitting::FitError>(())
+//! ```
+
+use super::linalg::{invert, solve};
+use super::profiles::{
+    gaussian, gaussian_area, lorentzian, lorentzian_area, voigt, voigt_area, voigt_fwhm,
+};
+use crate::constants::SIGMA_TO_FWHM;
+use crate::errors::fitting::FitError;
+use crate::spectrum::Spectrum1D;
+
+/// Identifies a parameter by the index of its component in the model and its index within
+/// [`Component::parameters`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParameterId {
+    pub component: usize,
+    pub index: usize,
+}
+
+impl ParameterId {
+    pub fn new(component: usize, index: usize) -> Self {
+        Self { component, index }
+    }
+}
+
+/// A constraint making one parameter a function of another.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tie {
+    /// `value = target + offset`, e.g. a hyperfine component at a fixed separation
+    Offset(ParameterId, f64),
+    /// `value = target * factor`, e.g. a fixed intensity ratio
+    Ratio(ParameterId, f64),
+}
+
+impl Tie {
+    fn target(&self) -> ParameterId {
+        match *self {
+            Self::Offset(id, _) | Self::Ratio(id, _) => id,
+        }
+    }
+
+    fn apply(&self, target: f64) -> f64 {
+        match *self {
+            Self::Offset(_, offset) => target + offset,
+            Self::Ratio(_, factor) => target * factor,
+        }
+    }
+
+    /// Derivative of the tied value with respect to the target.
+    fn slope(&self) -> f64 {
+        match *self {
+            Self::Offset(..) => 1.0,
+            Self::Ratio(_, factor) => factor,
+        }
+    }
+}
+
+/// A model parameter with its constraints.
+///
+/// Bounds apply to free parameters only; fixed and tied parameters take the value given or
+/// implied.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Parameter {
+    pub value: f64,
+    pub min: f64,
+    pub max: f64,
+    pub fixed: bool,
+    pub tie: Option<Tie>,
+}
+
+impl Parameter {
+    /// A free, unbounded parameter.
+    pub fn new(value: f64) -> Self {
+        Self {
+            value,
+            min: f64::NEG_INFINITY,
+            max: f64::INFINITY,
+            fixed: false,
+            tie: None,
+        }
+    }
+
+    /// A parameter held at `value`.
+    pub fn fixed(value: f64) -> Self {
+        Self {
+            fixed: true,
+            ..Self::new(value)
+        }
+    }
+
+    /// A parameter determined by `tie`; its value is filled in when fitting.
+    pub fn tied(tie: Tie) -> Self {
+        Self {
+            tie: Some(tie),
+            ..Self::new(f64::NAN)
+        }
+    }
+
+    #[must_use]
+    pub fn with_bounds(mut self, min: f64, max: f64) -> Self {
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    fn is_free(&self) -> bool {
+        !self.fixed && self.tie.is_none()
+    }
+}
+
+impl From<f64> for Parameter {
+    fn from(value: f64) -> Self {
+        Self::new(value)
+    }
+}
+
+/// One term of a [`LineModel`].
+///
+/// Line amplitudes are peak values. The parameter order used by
+/// [`parameters`](Self::parameters) and [`ParameterId`] is the field order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Component {
+    Gaussian {
+        amplitude: Parameter,
+        center: Parameter,
+        stddev: Parameter,
+    },
+    Lorentzian {
+        amplitude: Parameter,
+        center: Parameter,
+        fwhm: Parameter,
+    },
+    Voigt {
+        amplitude: Parameter,
+        center: Parameter,
+        fwhm_gaussian: Parameter,
+        fwhm_lorentzian: Parameter,
+    },
+    /// Polynomial `Σ c_k (x − reference)^k`, with `reference` not fitted
+    Baseline {
+        reference: f64,
+        coefficients: Vec<Parameter>,
+    },
+}
+
+impl Component {
+    pub fn gaussian(
+        amplitude: impl Into<Parameter>,
+        center: impl Into<Parameter>,
+        stddev: impl Into<Parameter>,
+    ) -> Self {
+        Self::Gaussian {
+            amplitude: amplitude.into(),
+            center: center.into(),
+            stddev: stddev.into(),
+        }
+    }
+
+    pub fn lorentzian(
+        amplitude: impl Into<Parameter>,
+        center: impl Into<Parameter>,
+        fwhm: impl Into<Parameter>,
+    ) -> Self {
+        Self::Lorentzian {
+            amplitude: amplitude.into(),
+            center: center.into(),
+            fwhm: fwhm.into(),
+        }
+    }
+
+    pub fn voigt(
+        amplitude: impl Into<Parameter>,
+        center: impl Into<Parameter>,
+        fwhm_gaussian: impl Into<Parameter>,
+        fwhm_lorentzian: impl Into<Parameter>,
+    ) -> Self {
+        Self::Voigt {
+            amplitude: amplitude.into(),
+            center: center.into(),
+            fwhm_gaussian: fwhm_gaussian.into(),
+            fwhm_lorentzian: fwhm_lorentzian.into(),
+        }
+    }
+
+    /// Polynomial baseline with one coefficient per power of `x − reference`, starting at 0.
+    pub fn baseline(reference: f64, coefficients: impl IntoIterator<Item = f64>) -> Self {
+        Self::Baseline {
+            reference,
+            coefficients: coefficients.into_iter().map(Parameter::new).collect(),
+        }
+    }
+
+    pub fn parameters(&self) -> Vec<&Parameter> {
+        match self {
+            Self::Gaussian {
+                amplitude,
+                center,
+                stddev: width,
+            }
+            | Self::Lorentzian {
+                amplitude,
+                center,
+                fwhm: width,
+            } => vec![amplitude, center, width],
+            Self::Voigt {
+                amplitude,
+                center,
+                fwhm_gaussian,
+                fwhm_lorentzian,
+            } => vec![amplitude, center, fwhm_gaussian, fwhm_lorentzian],
+            Self::Baseline { coefficients, .. } => coefficients.iter().collect(),
+        }
+    }
+
+    pub fn parameters_mut(&mut self) -> Vec<&mut Parameter> {
+        match self {
+            Self::Gaussian {
+                amplitude,
+                center,
+                stddev: width,
+            }
+            | Self::Lorentzian {
+                amplitude,
+                center,
+                fwhm: width,
+            } => vec![amplitude, center, width],
+            Self::Voigt {
+                amplitude,
+                center,
+                fwhm_gaussian,
+                fwhm_lorentzian,
+            } => vec![amplitude, center, fwhm_gaussian, fwhm_lorentzian],
+            Self::Baseline { coefficients, .. } => coefficients.iter_mut().collect(),
+        }
+    }
+
+    /// Evaluate the component at `x`.
+    pub fn evaluate(&self, x: f64) -> f64 {
+        let values: Vec<f64> = self.parameters().iter().map(|p| p.value).collect();
+        self.evaluate_with(&values, x)
+    }
+
+    fn evaluate_with(&self, p: &[f64], x: f64) -> f64 {
+        match self {
+            Self::Gaussian { .. } => gaussian(x, p[0], p[1], p[2]),
+            Self::Lorentzian { .. } => lorentzian(x, p[0], p[1], p[2]),
+            Self::Voigt { .. } => voigt(x, p[0], p[1], p[2], p[3]),
+            Self::Baseline { reference, .. } => {
+                p.iter().rev().fold(0.0, |acc, c| acc * (x - reference) + c)
+            }
+        }
+    }
+
+    /// Whether parameter `index` is a position or width on the spectral axis.
+    fn is_spectral(&self, index: usize) -> bool {
+        !matches!(self, Self::Baseline { .. }) && index > 0
+    }
+
+    /// Full width at half maximum of a line, `None` for the baseline.
+    pub fn fwhm(&self) -> Option<f64> {
+        match self {
+            Self::Gaussian { stddev, .. } => Some(SIGMA_TO_FWHM * stddev.value.abs()),
+            Self::Lorentzian { fwhm, .. } => Some(fwhm.value.abs()),
+            Self::Voigt {
+                fwhm_gaussian,
+                fwhm_lorentzian,
+                ..
+            } => Some(voigt_fwhm(fwhm_gaussian.value, fwhm_lorentzian.value)),
+            Self::Baseline { .. } => None,
+        }
+    }
+
+    /// Integrated area of a line, `None` for the baseline.
+    pub fn area(&self) -> Option<f64> {
+        match self {
+            Self::Gaussian {
+                amplitude, stddev, ..
+            } => Some(gaussian_area(amplitude.value, stddev.value)),
+            Self::Lorentzian {
+                amplitude, fwhm, ..
+            } => Some(lorentzian_area(amplitude.value, fwhm.value)),
+            Self::Voigt {
+                amplitude,
+                fwhm_gaussian,
+                fwhm_lorentzian,
+                ..
+            } => Some(voigt_area(
+                amplitude.value,
+                fwhm_gaussian.value,
+                fwhm_lorentzian.value,
+            )),
+            Self::Baseline { .. } => None,
+        }
+    }
+}
+
+/// A sum of components.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineModel {
+    pub components: Vec<Component>,
+}
+
+impl LineModel {
+    pub fn new(components: Vec<Component>) -> Self {
+        Self { components }
+    }
+
+    /// Evaluate the model at `x`.
+    pub fn evaluate(&self, x: f64) -> f64 {
+        self.components.iter().map(|c| c.evaluate(x)).sum()
+    }
+
+    /// All parameters, in component order.
+    pub fn parameters(&self) -> Vec<&Parameter> {
+        self.components
+            .iter()
+            .flat_map(Component::parameters)
+            .collect()
+    }
+
+    /// Position of `id` in [`parameters`](Self::parameters).
+    pub fn flat_index(&self, id: ParameterId) -> Option<usize> {
+        let component = self.components.get(id.component)?;
+        (id.index < component.parameters().len()).then(|| {
+            self.components[..id.component]
+                .iter()
+                .map(|c| c.parameters().len())
+                .sum::<usize>()
+                + id.index
+        })
+    }
+
+    fn evaluate_with(&self, values: &[f64], x: f64) -> f64 {
+        let mut offset = 0;
+        self.components
+            .iter()
+            .map(|c| {
+                let n = c.parameters().len();
+                offset += n;
+                c.evaluate_with(&values[offset - n..offset], x)
+            })
+            .sum()
+    }
+}
+
+/// How each parameter is determined during a fit.
+#[derive(Debug, Clone, Copy)]
+enum Role {
+    Free(usize),
+    Fixed,
+    Tied(usize, Tie),
+}
+
+/// Result of [`LineFitter::fit`].
+#[derive(Debug, Clone)]
+pub struct LineFit {
+    /// The model with best-fit parameter values
+    pub model: LineModel,
+    /// One-sigma errors, in the order of [`LineModel::parameters`]. Errors of parameters
+    /// the data do not constrain are NaN.
+    pub errors: Vec<f64>,
+    /// Covariance matrix of all parameters; fixed parameters have zero rows and columns, and
+    /// unconstrained ones NaN rows and columns
+    pub covariance: Vec<Vec<f64>>,
+    /// Best-fit model at every channel
+    pub best_fit: Vec<f64>,
+    /// Flux minus the best-fit model
+    pub residuals: Vec<f64>,
+    pub chi_squared: f64,
+    /// χ² per degree of freedom
+    pub reduced_chi_squared: f64,
+    pub iterations: usize,
+    /// Whether the relative change in χ² fell below the tolerance
+    pub converged: bool,
+}
+
+impl LineFit {
+    /// One-sigma error of a parameter.
+    pub fn error(&self, id: ParameterId) -> Option<f64> {
+        self.model.flat_index(id).map(|i| self.errors[i])
+    }
+}
+
+/// Levenberg–Marquardt fitter for [`LineModel`]s.
+#[derive(Debug, Clone)]
+pub struct LineFitter {
+    pub max_iterations: usize,
+    /// Stop when an accepted step reduces χ² by less than this fraction.
+    pub tolerance: f64,
+}
+
+impl Default for LineFitter {
+    fn default() -> Self {
+        Self {
+            max_iterations: 200,
+            tolerance: 1e-10,
+        }
+    }
+}
+
+impl LineFitter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fit `model` to the unmasked, finite channels of `spectrum`, starting from the
+    /// parameter values in `model`.
+    ///
+    /// Without an uncertainty all channels have unit weight, and the covariance is scaled by
+    /// the reduced χ² so that errors reflect the scatter of the residuals, as
+    /// `astropy.modeling` does. Parameters the data do not constrain keep their starting
+    /// values, with NaN errors.
+    ///
+    /// # Errors
+    /// Returns [`FitError::InvalidTie`] for ties to missing parameters or circular ties,
+    /// [`FitError::TooFewPoints`] if there are fewer usable channels than free parameters,
+    /// or [`FitError::SingularMatrix`] if no step can be computed.
+    pub fn fit(&self, spectrum: &Spectrum1D, model: &LineModel) -> Result<LineFit, FitError> {
+        let params: Vec<Parameter> = model.parameters().into_iter().copied().collect();
+        let roles = roles(model, &params)?;
+        let n_free = roles.iter().filter(|r| matches!(r, Role::Free(_))).count();
+
+        let x = spectrum.spectral_axis.values();
+        let sigma = spectrum.uncertainty.as_ref().map(|u| u.std_dev());
+        let rows: Vec<usize> = (0..spectrum.len())
+            .filter(|&i| {
+                !spectrum.mask[i]
+                    && spectrum.flux[i].is_finite()
+                    && sigma
+                        .as_ref()
+                        .is_none_or(|s| s[i] > 0.0 && s[i].is_finite())
+            })
+            .collect();
+        if rows.len() < n_free.max(1) {
+            return Err(FitError::TooFewPoints {
+                required: n_free.max(1),
+                found: rows.len(),
+            });
+        }
+        let weight = |i: usize| sigma.as_ref().map_or(1.0, |s| s[i].recip());
+
+        let expand = |free: &[f64]| -> Vec<f64> {
+            let mut values: Vec<f64> = params.iter().map(|p| p.value).collect();
+            for (i, role) in roles.iter().enumerate() {
+                if let Role::Free(j) = role {
+                    values[i] = free[*j];
+                }
+            }
+            for i in 0..values.len() {
+                values[i] = resolve(&roles, &values, i);
+            }
+            values
+        };
+        let residuals = |free: &[f64]| -> Vec<f64> {
+            let values = expand(free);
+            rows.iter()
+                .map(|&i| (spectrum.flux[i] - model.evaluate_with(&values, x[i])) * weight(i))
+                .collect()
+        };
+        let chi2 = |r: &[f64]| r.iter().map(|v| v * v).sum::<f64>();
+
+        // Finite-difference steps scale with the channel width for spectral parameters.
+        #[allow(clippy::cast_precision_loss)]
+        let channel = if x.len() > 1 {
+            ((x[x.len() - 1] - x[0]) / (x.len() - 1) as f64).abs()
+        } else {
+            1.0
+        };
+        let mut typical = vec![1.0; n_free];
+        let mut bounds = vec![(f64::NEG_INFINITY, f64::INFINITY); n_free];
+        let mut free = vec![0.0; n_free];
+        let mut flat = 0;
+        for component in &model.components {
+            for (k, p) in component.parameters().into_iter().enumerate() {
+                if let Role::Free(j) = roles[flat] {
+                    free[j] = p.value.clamp(p.min, p.max);
+                    bounds[j] = (p.min, p.max);
+                    if component.is_spectral(k) {
+                        typical[j] = channel;
+                    }
+                }
+                flat += 1;
+            }
+        }
+        let jacobian = |free: &[f64]| -> Vec<Vec<f64>> {
+            let mut columns = Vec::with_capacity(n_free);
+            for j in 0..n_free {
+                let h = 1e-6 * free[j].abs().max(typical[j]);
+                let mut up = free.to_vec();
+                let mut down = free.to_vec();
+                up[j] += h;
+                down[j] -= h;
+                // Residuals are data minus model, so this is the weighted model derivative.
+                let (r_up, r_down) = (residuals(&up), residuals(&down));
+                columns.push(
+                    r_down
+                        .iter()
+                        .zip(&r_up)
+                        .map(|(d, u)| (d - u) / (2.0 * h))
+                        .collect::<Vec<f64>>(),
+                );
+            }
+            columns
+        };
+        let normal = |columns: &[Vec<f64>]| -> Vec<Vec<f64>> {
+            columns
+                .iter()
+                .map(|a| {
+                    columns
+                        .iter()
+                        .map(|b| a.iter().zip(b).map(|(u, v)| u * v).sum())
+                        .collect()
+                })
+                .collect()
+        };
+
+        let mut r = residuals(&free);
+        let mut chi_squared = chi2(&r);
+        let mut lambda = 1e-3;
+        let mut iterations = 0;
+        let mut converged = n_free == 0;
+        while !converged && iterations < self.max_iterations {
+            iterations += 1;
+            let columns = jacobian(&free);
+            let a = normal(&columns);
+            let g: Vec<f64> = columns
+                .iter()
+                .map(|c| c.iter().zip(&r).map(|(u, v)| u * v).sum())
+                .collect();
+            let d: Vec<f64> = (0..n_free)
+                .map(|j| if a[j][j] > 0.0 { a[j][j].sqrt() } else { 1.0 })
+                .collect();
+            loop {
+                // Marquardt's damping of the diagonal, on the Jacobi-scaled system.
+                let m: Vec<Vec<f64>> = (0..n_free)
+                    .map(|i| {
+                        (0..n_free)
+                            .map(|j| a[i][j] / (d[i] * d[j]) + if i == j { lambda } else { 0.0 })
+                            .collect()
+                    })
+                    .collect();
+                let rhs: Vec<f64> = (0..n_free).map(|j| g[j] / d[j]).collect();
+                let step = solve(m, rhs)?;
+                let trial: Vec<f64> = (0..n_free)
+                    .map(|j| (free[j] + step[j] / d[j]).clamp(bounds[j].0, bounds[j].1))
+                    .collect();
+                let r_trial = residuals(&trial);
+                let chi_trial = chi2(&r_trial);
+                if chi_trial <= chi_squared {
+                    converged = chi_squared - chi_trial <= self.tolerance * chi_squared;
+                    free = trial;
+                    r = r_trial;
+                    chi_squared = chi_trial;
+                    lambda = (lambda / 10.0).max(1e-12);
+                    break;
+                }
+                lambda *= 10.0;
+                if lambda > 1e12 {
+                    // No downhill step exists: we are at a minimum to working precision.
+                    converged = true;
+                    break;
+                }
+            }
+        }
+
+        let dof = rows.len().saturating_sub(n_free);
+        #[allow(clippy::cast_precision_loss)]
+        let reduced_chi_squared = if dof > 0 {
+            chi_squared / dof as f64
+        } else {
+            f64::NAN
+        };
+        let free_covariance: Vec<Vec<f64>> = if n_free > 0 {
+            let columns = jacobian(&free);
+            let a = normal(&columns);
+            let d: Vec<f64> = (0..n_free)
+                .map(|j| if a[j][j] > 0.0 { a[j][j].sqrt() } else { 1.0 })
+                .collect();
+            let scaled: Vec<Vec<f64>> = (0..n_free)
+                .map(|i| (0..n_free).map(|j| a[i][j] / (d[i] * d[j])).collect())
+                .collect();
+            let inverse = invert(&scaled).unwrap_or_else(|_| {
+                // Parameters the data do not constrain leave the matrix singular. Their errors
+                // are unavailable (NaN), and the others come from the matrix without them.
+                let kept: Vec<usize> = (0..n_free).filter(|&j| a[j][j] > 0.0).collect();
+                let sub: Vec<Vec<f64>> = kept
+                    .iter()
+                    .map(|&i| kept.iter().map(|&j| scaled[i][j]).collect())
+                    .collect();
+                let mut inverse = vec![vec![f64::NAN; n_free]; n_free];
+                if let Ok(sub) = invert(&sub) {
+                    for (p, &i) in kept.iter().enumerate() {
+                        for (q, &j) in kept.iter().enumerate() {
+                            inverse[i][j] = sub[p][q];
+                        }
+                    }
+                }
+                inverse
+            });
+            let factor = if sigma.is_some() {
+                1.0
+            } else {
+                reduced_chi_squared
+            };
+            (0..n_free)
+                .map(|i| {
+                    (0..n_free)
+                        .map(|j| factor * inverse[i][j] / (d[i] * d[j]))
+                        .collect()
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        // Map the free covariance onto all parameters through the ties.
+        let transform: Vec<Vec<f64>> = (0..params.len())
+            .map(|i| derivative(&roles, i, n_free))
+            .collect();
+        let covariance: Vec<Vec<f64>> = transform
+            .iter()
+            .map(|ti| {
+                transform
+                    .iter()
+                    .map(|tj| {
+                        // Terms without a contribution are skipped so that NaN covariances
+                        // of unconstrained parameters do not spread to the others.
+                        (0..n_free)
+                            .flat_map(|a| (0..n_free).map(move |b| (a, b)))
+                            .filter(|&(a, b)| ti[a] != 0.0 && tj[b] != 0.0)
+                            .map(|(a, b)| ti[a] * free_covariance[a][b] * tj[b])
+                            .sum()
+                    })
+                    .collect()
+            })
+            .collect();
+        let errors = (0..params.len()).map(|i| covariance[i][i].sqrt()).collect();
+
+        let values = expand(&free);
+        let mut fitted = model.clone();
+        for (p, v) in fitted
+            .components
+            .iter_mut()
+            .flat_map(Component::parameters_mut)
+            .zip(&values)
+        {
+            p.value = *v;
+        }
+        let best_fit: Vec<f64> = x.iter().map(|&v| fitted.evaluate(v)).collect();
+        Ok(LineFit {
+            residuals: spectrum
+                .flux
+                .iter()
+                .zip(&best_fit)
+                .map(|(f, m)| f - m)
+                .collect(),
+            model: fitted,
+            errors,
+            covariance,
+            best_fit,
+            chi_squared,
+            reduced_chi_squared,
+            iterations,
+            converged,
+        })
+    }
+}
+
+/// Classify every parameter, checking that ties point at existing parameters without
+/// cycles.
+fn roles(model: &LineModel, params: &[Parameter]) -> Result<Vec<Role>, FitError> {
+    let mut n_free = 0;
+    let mut roles = Vec::with_capacity(params.len());
+    for (component, c) in model.components.iter().enumerate() {
+        for (index, p) in c.parameters().into_iter().enumerate() {
+            let invalid = FitError::InvalidTie { component, index };
+            roles.push(match p.tie {
+                Some(tie) => Role::Tied(model.flat_index(tie.target()).ok_or(invalid)?, tie),
+                None if p.is_free() => {
+                    n_free += 1;
+                    Role::Free(n_free - 1)
+                }
+                None => Role::Fixed,
+            });
+        }
+    }
+    for (component, c) in model.components.iter().enumerate() {
+        for index in 0..c.parameters().len() {
+            let mut i = model
+                .flat_index(ParameterId::new(component, index))
+                .unwrap();
+            for _ in 0..=roles.len() {
+                match roles[i] {
+                    Role::Tied(target, _) => i = target,
+                    _ => break,
+                }
+            }
+            if matches!(roles[i], Role::Tied(..)) {
+                return Err(FitError::InvalidTie { component, index });
+            }
+        }
+    }
+    Ok(roles)
+}
+
+fn resolve(roles: &[Role], values: &[f64], i: usize) -> f64 {
+    match roles[i] {
+        Role::Tied(target, tie) => tie.apply(resolve(roles, values, target)),
+        _ => values[i],
+    }
+}
+
+/// Derivative of parameter `i` with respect to each free parameter.
+fn derivative(roles: &[Role], i: usize, n_free: usize) -> Vec<f64> {
+    match roles[i] {
+        Role::Free(j) => (0..n_free).map(|k| f64::from(u8::from(k == j))).collect(),
+        Role::Fixed => vec![0.0; n_free],
+        Role::Tied(target, tie) => derivative(roles, target, n_free)
+            .into_iter()
+            .map(|d| d * tie.slope())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectrum::{SpectralAxis, SpectralKind, Uncertainty};
+    use approx::assert_relative_eq;
+
+    /// Deterministic pseudo-random noise with unit variance.
+    fn noise(n: usize) -> Vec<f64> {
+        let mut state: u64 = 12345;
+        (0..n)
+            .map(|_| {
+                let mut sum = 0.0;
+                for _ in 0..12 {
+                    state = state
+                        .wrapping_mul(6_364_136_223_846_793_005)
+                        .wrapping_add(1_442_695_040_888_963_407);
+                    #[allow(clippy::cast_precision_loss)]
+                    let u = (state >> 11) as f64 / (1_u64 << 53) as f64;
+                    sum += u;
+                }
+                sum - 6.0
+            })
+            .collect()
+    }
+
+    fn spectrum(model: &LineModel, noise_level: f64) -> Spectrum1D {
+        let x: Vec<f64> = (0..200).map(|i| 1e11 + 1e5 * f64::from(i)).collect();
+        let flux = x
+            .iter()
+            .zip(noise(x.len()))
+            .map(|(&v, n)| model.evaluate(v) + noise_level * n)
+            .collect();
+        Spectrum1D::new(SpectralAxis::from_si(SpectralKind::Frequency, x), flux, "K").unwrap()
+    }
+
+    #[test]
+    fn test_two_components_and_baseline() {
+        let truth = LineModel::new(vec![
+            Component::gaussian(3.0, 1.00005e11, 4e5),
+            Component::lorentzian(-1.5, 1.00012e11, 8e5),
+            Component::baseline(1.0001e11, [0.2, 1e-8]),
+        ]);
+        let s = spectrum(&truth, 0.0);
+        let guess = LineModel::new(vec![
+            Component::gaussian(2.0, 1.000048e11, 3e5),
+            Component::lorentzian(-1.0, 1.000121e11, 1e6),
+            Component::baseline(1.0001e11, [0.0, 0.0]),
+        ]);
+        let fit = LineFitter::new().fit(&s, &guess).unwrap();
+        assert!(fit.converged);
+        for (p, t) in fit.model.parameters().iter().zip(truth.parameters()) {
+            assert_relative_eq!(p.value, t.value, max_relative = 1e-6, epsilon = 1e-12);
+        }
+        assert!(fit.chi_squared < 1e-12);
+    }
+
+    #[test]
+    fn test_errors_from_uncertainty() {
+        let truth = LineModel::new(vec![Component::voigt(2.0, 1.0001e11, 6e5, 3e5)]);
+        let s = spectrum(&truth, 0.1)
+            .with_uncertainty(Uncertainty::StdDev(vec![0.1; 200]))
+            .unwrap();
+        let guess = LineModel::new(vec![Component::voigt(1.5, 1.00009e11, 5e5, 2e5)]);
+        let fit = LineFitter::new().fit(&s, &guess).unwrap();
+        assert!(fit.converged);
+        assert!(fit.reduced_chi_squared > 0.7 && fit.reduced_chi_squared < 1.3);
+        let center = fit.model.components[0].parameters()[1].value;
+        let error = fit.error(ParameterId::new(0, 1)).unwrap();
+        assert!(error > 0.0 && error < 1e5);
+        assert!((center - 1.0001e11).abs() < 4.0 * error);
+    }
+
+    #[test]
+    fn test_unconstrained_parameters() {
+        let truth = LineModel::new(vec![Component::gaussian(2.0, 1.0001e11, 6e5)]);
+        let s = spectrum(&truth, 0.1);
+        // The second component lies far outside the spectrum, so nothing constrains it.
+        let guess = LineModel::new(vec![
+            Component::gaussian(1.5, 1.00009e11, 5e5),
+            Component::gaussian(1.0, 2e11, 5e5),
+        ]);
+        let fit = LineFitter::new().fit(&s, &guess).unwrap();
+        let center = fit.model.components[0].parameters()[1].value;
+        assert_relative_eq!(center, 1.0001e11, max_relative = 1e-5);
+        assert!(fit.errors[..3].iter().all(|e| e.is_finite() && *e > 0.0));
+        assert!(fit.errors[3..].iter().all(|e| e.is_nan()));
+        assert_eq!(fit.model.components[1], guess.components[1]);
+    }
+
+    #[test]
+    fn test_ties_fixed_and_bounds() {
+        // Two hyperfine components with a known separation and intensity ratio.
+        let truth = LineModel::new(vec![
+            Component::gaussian(2.0, 1.00006e11, 3e5),
+            Component::gaussian(1.0, 1.00012e11, 3e5),
+        ]);
+        let s = spectrum(&truth, 0.01);
+        let guess = LineModel::new(vec![
+            Component::gaussian(
+                1.0,
+                1.000059e11,
+                Parameter::new(2.5e5).with_bounds(1e5, 5e5),
+            ),
+            Component::gaussian(
+                Parameter::tied(Tie::Ratio(ParameterId::new(0, 0), 0.5)),
+                Parameter::tied(Tie::Offset(ParameterId::new(0, 1), 6e6)),
+                Parameter::tied(Tie::Offset(ParameterId::new(0, 2), 0.0)),
+            ),
+        ]);
+        let fit = LineFitter::new().fit(&s, &guess).unwrap();
+        let p = fit.model.parameters();
+        assert_relative_eq!(p[0].value, 2.0, max_relative = 1e-2);
+        assert_relative_eq!(p[3].value, 0.5 * p[0].value);
+        assert_relative_eq!(p[4].value, p[1].value + 6e6);
+        assert_relative_eq!(fit.errors[3], 0.5 * fit.errors[0]);
+        assert_relative_eq!(fit.errors[4], fit.errors[1]);
+
+        let bounded = LineModel::new(vec![Component::gaussian(
+            1.0,
+            1.00006e11,
+            Parameter::new(2e5).with_bounds(1e5, 2.5e5),
+        )]);
+        let fit = LineFitter::new().fit(&s, &bounded).unwrap();
+        assert!(fit.model.parameters()[2].value <= 2.5e5);
+
+        let fixed = LineModel::new(vec![Component::gaussian(
+            1.0,
+            Parameter::fixed(1.00006e11),
+            3e5,
+        )]);
+        let fit = LineFitter::new().fit(&s, &fixed).unwrap();
+        assert_eq!(fit.model.parameters()[1].value, 1.00006e11);
+        assert_eq!(fit.errors[1], 0.0);
+    }
+
+    #[test]
+    fn test_invalid_ties() {
+        let s = spectrum(&LineModel::new(vec![]), 0.0);
+        let missing = LineModel::new(vec![Component::gaussian(
+            Parameter::tied(Tie::Ratio(ParameterId::new(1, 0), 1.0)),
+            0.0,
+            1.0,
+        )]);
+        assert_eq!(
+            LineFitter::new().fit(&s, &missing).unwrap_err(),
+            FitError::InvalidTie {
+                component: 0,
+                index: 0
+            }
+        );
+        let circular = LineModel::new(vec![Component::gaussian(
+            Parameter::tied(Tie::Ratio(ParameterId::new(0, 2), 1.0)),
+            0.0,
+            Parameter::tied(Tie::Ratio(ParameterId::new(0, 0), 1.0)),
+        )]);
+        assert_eq!(
+            LineFitter::new().fit(&s, &circular).unwrap_err(),
+            FitError::InvalidTie {
+                component: 0,
+                index: 0
+            }
+        );
+    }
+}
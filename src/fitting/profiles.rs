@@ -0,0 +1,212 @@
+//! Line profile functions.
+//!
+//! All profiles are parametrised by their peak value rather than their integral, as
+//! `astropy.modeling`'s `Gaussian1D`, `Lorentz1D` and `Voigt1D` are, so that fitted amplitudes
+//! read directly as peak brightness.
+
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+use std::ops::{Add, Div, Mul, Sub};
+
+use crate::constants::SIGMA_TO_FWHM;
+
+/// Gaussian with peak `amplitude` at `center` and standard deviation `stddev`.
+pub fn gaussian(x: f64, amplitude: f64, center: f64, stddev: f64) -> f64 {
+    amplitude * (-0.5 * ((x - center) / stddev).powi(2)).exp()
+}
+
+/// Lorentzian with peak `amplitude` at `center` and full width at half maximum `fwhm`.
+pub fn lorentzian(x: f64, amplitude: f64, center: f64, fwhm: f64) -> f64 {
+    let gamma2 = (0.5 * fwhm).powi(2);
+    amplitude * gamma2 / ((x - center).powi(2) + gamma2)
+}
+
+/// Voigt profile with peak `amplitude` at `center`, the convolution of a Gaussian and a
+/// Lorentzian with full widths at half maximum `fwhm_gaussian` and `fwhm_lorentzian`.
+///
+/// Evaluated with Humlíček's (1982) approximation to the Faddeeva function, which is
+/// accurate to about 10⁻⁴ relative to the peak.
+pub fn voigt(x: f64, amplitude: f64, center: f64, fwhm_gaussian: f64, fwhm_lorentzian: f64) -> f64 {
+    if fwhm_gaussian == 0.0 {
+        return lorentzian(x, amplitude, center, fwhm_lorentzian);
+    }
+    let scale = FRAC_1_SQRT_2 * SIGMA_TO_FWHM / fwhm_gaussian.abs();
+    let y = 0.5 * fwhm_lorentzian.abs() * scale;
+    amplitude * faddeeva(scale * (x - center), y).re / faddeeva(0.0, y).re
+}
+
+/// Integral of [`gaussian`] over all `x`.
+pub fn gaussian_area(amplitude: f64, stddev: f64) -> f64 {
+    amplitude * stddev.abs() * (2.0 * PI).sqrt()
+}
+
+/// Integral of [`lorentzian`] over all `x`.
+pub fn lorentzian_area(amplitude: f64, fwhm: f64) -> f64 {
+    amplitude * 0.5 * PI * fwhm.abs()
+}
+
+/// Integral of [`voigt`] over all `x`.
+pub fn voigt_area(amplitude: f64, fwhm_gaussian: f64, fwhm_lorentzian: f64) -> f64 {
+    if fwhm_gaussian == 0.0 {
+        return lorentzian_area(amplitude, fwhm_lorentzian);
+    }
+    let stddev = fwhm_gaussian.abs() / SIGMA_TO_FWHM;
+    let y = 0.5 * fwhm_lorentzian.abs() * FRAC_1_SQRT_2 / stddev;
+    gaussian_area(amplitude, stddev) / faddeeva(0.0, y).re
+}
+
+/// Approximate full width at half maximum of a Voigt profile (Olivero & Longbothum 1977),
+/// accurate to 0.02%.
+pub fn voigt_fwhm(fwhm_gaussian: f64, fwhm_lorentzian: f64) -> f64 {
+    let (g, l) = (fwhm_gaussian.abs(), fwhm_lorentzian.abs());
+    0.5346 * l + (0.2166 * l * l + g * g).sqrt()
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    const fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    fn exp(self) -> Self {
+        let r = self.re.exp();
+        Self::new(r * self.im.cos(), r * self.im.sin())
+    }
+}
+
+impl Add<f64> for Complex {
+    type Output = Self;
+    fn add(self, rhs: f64) -> Self {
+        Self::new(self.re + rhs, self.im)
+    }
+}
+
+impl Sub<Complex> for f64 {
+    type Output = Complex;
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex::new(self - rhs.re, -rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Mul<f64> for Complex {
+    type Output = Self;
+    fn mul(self, rhs: f64) -> Self {
+        Self::new(self.re * rhs, self.im * rhs)
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self {
+        let d = rhs.re * rhs.re + rhs.im * rhs.im;
+        Self::new(
+            (self.re * rhs.re + self.im * rhs.im) / d,
+            (self.im * rhs.re - self.re * rhs.im) / d,
+        )
+    }
+}
+
+/// Faddeeva function `w(x + iy)` for `y >= 0`, Humlíček (1982) algorithm W4.
+#[allow(clippy::excessive_precision)]
+fn faddeeva(x: f64, y: f64) -> Complex {
+    let t = Complex::new(y, -x);
+    let s = x.abs() + y;
+    let u = t * t;
+    if s >= 15.0 {
+        t * 0.564_189_6 / (u + 0.5)
+    } else if s >= 5.5 {
+        t * (u * 0.564_189_6 + 1.410_474) / (u * (u + 3.0) + 0.75)
+    } else if y >= 0.195 * x.abs() - 0.176 {
+        let num = t * (t * (t * (t * 0.564_223_6 + 3.778_987) + 11.964_82) + 20.209_33) + 16.4955;
+        let den =
+            t * (t * (t * (t * (t + 6.699_398) + 21.692_74) + 39.271_21) + 38.823_63) + 16.4955;
+        num / den
+    } else {
+        let num = t
+            * (36_183.31
+                - u * (3_321.990_5
+                    - u * (1_540.787
+                        - u * (219.031_3 - u * (35.766_83 - u * (1.320_522 - u * 0.564_19))))));
+        let den = 32_066.6
+            - u * (24_322.84
+                - u * (9_022.228
+                    - u * (2_186.181 - u * (364.219_1 - u * (61.570_37 - u * (1.841_439 - u))))));
+        u.exp() - num / den
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_half_maximum() {
+        let fwhm = 2.0;
+        assert_relative_eq!(
+            gaussian(1.0, 3.0, 0.0, fwhm / SIGMA_TO_FWHM),
+            1.5,
+            max_relative = 1e-10
+        );
+        assert_relative_eq!(lorentzian(1.0, 3.0, 0.0, fwhm), 1.5);
+        assert_relative_eq!(voigt(0.0, 3.0, 0.0, 1.0, 1.0), 3.0, max_relative = 1e-12);
+        let half = voigt_fwhm(1.0, 1.0) / 2.0;
+        assert_relative_eq!(voigt(half, 3.0, 0.0, 1.0, 1.0), 1.5, max_relative = 1e-3);
+    }
+
+    #[test]
+    fn test_voigt_limits() {
+        for x in [0.0, 0.3, 1.0, 2.5] {
+            assert_relative_eq!(
+                voigt(x, 1.0, 0.0, 1.0, 1e-8),
+                gaussian(x, 1.0, 0.0, 1.0 / SIGMA_TO_FWHM),
+                epsilon = 2e-4
+            );
+            assert_relative_eq!(
+                voigt(x, 1.0, 0.0, 1e-6, 1.0),
+                lorentzian(x, 1.0, 0.0, 1.0),
+                epsilon = 2e-4
+            );
+        }
+    }
+
+    #[test]
+    fn test_areas() {
+        let integrate = |f: &dyn Fn(f64) -> f64| {
+            (-200_000..=200_000)
+                .map(|i| f(f64::from(i) * 1e-3) * 1e-3)
+                .sum::<f64>()
+        };
+        assert_relative_eq!(
+            integrate(&|x| gaussian(x, 2.0, 0.0, 1.5)),
+            gaussian_area(2.0, 1.5),
+            max_relative = 1e-8
+        );
+        assert_relative_eq!(
+            integrate(&|x| voigt(x, 2.0, 0.0, 1.0, 0.5)),
+            voigt_area(2.0, 1.0, 0.5),
+            max_relative = 5e-3
+        );
+    }
+}
@@ -1,3 +1,5 @@
+pub mod fits;
+
 use std::io::BufRead;
 use std::io::Error;
 
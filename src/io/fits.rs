@@ -0,0 +1,565 @@
+//! Reading and writing FITS files, after `astropy.io.fits`.
+//!
+//! A [`Fits`] file is a list of header–data units. Primary HDUs and `IMAGE` extensions are
+//! read as [`ImageHdu`]s, `BINTABLE` extensions as [`BinTableHdu`]s, and any other extension
+//! is kept verbatim as an [`UnknownHdu`]. [`Fits::open`] memory-maps the file: headers are
+//! parsed up front, but data are only decoded when asked for, so a single channel of a cube
+//! much larger than memory can be read with [`ImageHdu::read_plane`].
+//!
+//! ```
+//! use spectre::io::fits::{Bitpix, Fits, ImageHdu};
+//!
+//! let mut image = ImageHdu::new(&[3, 2], &[1.0, 2.0, 3.0, 4.0, 5.0, f64::NAN], Bitpix::I16)
+//!     .unwrap();
+//! image.header_mut().set("BUNIT", "K");
+//! let mut fits = Fits::new();
+//! fits.push(image);
+//!
+//! let read = Fits::from_bytes(fits.to_bytes()).unwrap();
+//! let image = read.image(0).unwrap();
+//! assert_eq!(image.shape(), vec![3, 2]);
+//! assert_eq!(image.header().get_str("BUNIT"), Some("K"));
+//! assert_eq!(image.read_plane(0).unwrap()[..5], [1.0, 2.0, 3.0, 4.0, 5.0]);
+//! assert!(image.read().unwrap()[5].is_nan());
+//! ```
+
+mod header;
+mod image;
+mod table;
+
+pub use header::{CARD_SIZE, Card, Header, Value};
+pub use image::{Bitpix, ImageHdu};
+pub use table::{BinTableHdu, Column, ColumnData, ColumnType};
+
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::Arc;
+
+use memmap2::Mmap;
+
+use crate::errors::fits::FitsError;
+
+/// Size of a FITS logical record; headers and data are padded to a multiple of it.
+pub const BLOCK_SIZE: usize = 2880;
+
+/// Bytes backing the data of HDUs: a memory-mapped file or an owned buffer.
+#[derive(Debug)]
+enum Storage {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Deref for Storage {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Mapped(m) => m,
+            Self::Owned(v) => v,
+        }
+    }
+}
+
+/// The data of one HDU, shared with the other HDUs of the same file.
+#[derive(Debug, Clone)]
+pub(crate) struct DataBlock {
+    storage: Arc<Storage>,
+    offset: usize,
+    len: usize,
+}
+
+impl DataBlock {
+    pub(crate) fn owned(bytes: Vec<u8>) -> Self {
+        Self {
+            len: bytes.len(),
+            storage: Arc::new(Storage::Owned(bytes)),
+            offset: 0,
+        }
+    }
+
+    pub(crate) fn bytes(&self) -> &[u8] {
+        &self.storage[self.offset..self.offset + self.len]
+    }
+}
+
+/// An HDU of a kind this module does not interpret, kept so it can be written back.
+#[derive(Debug, Clone)]
+pub struct UnknownHdu {
+    header: Header,
+    data: DataBlock,
+}
+
+impl UnknownHdu {
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// The raw data bytes, without padding.
+    pub fn data(&self) -> &[u8] {
+        self.data.bytes()
+    }
+}
+
+/// A header–data unit.
+#[derive(Debug, Clone)]
+pub enum Hdu {
+    Image(ImageHdu),
+    BinTable(BinTableHdu),
+    Unknown(UnknownHdu),
+}
+
+impl Hdu {
+    pub fn header(&self) -> &Header {
+        match self {
+            Self::Image(h) => h.header(),
+            Self::BinTable(h) => h.header(),
+            Self::Unknown(h) => h.header(),
+        }
+    }
+
+    /// The `EXTNAME` of the HDU.
+    pub fn name(&self) -> Option<&str> {
+        self.header().get_str("EXTNAME")
+    }
+}
+
+impl From<ImageHdu> for Hdu {
+    fn from(value: ImageHdu) -> Self {
+        Self::Image(value)
+    }
+}
+
+impl From<BinTableHdu> for Hdu {
+    fn from(value: BinTableHdu) -> Self {
+        Self::BinTable(value)
+    }
+}
+
+/// A FITS file.
+#[derive(Debug, Clone, Default)]
+pub struct Fits {
+    hdus: Vec<Hdu>,
+}
+
+impl Fits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a FITS file, memory-mapping it.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::Io`] if the file cannot be mapped, or a parse error if the file
+    /// is not valid FITS.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, FitsError> {
+        let file = File::open(path)?;
+        // SAFETY: the mapping is read-only. As with any memory-mapped file, modifying the
+        // file while it is open is undefined behaviour; FITS files are written once.
+        let map = unsafe { Mmap::map(&file)? };
+        Self::parse(Arc::new(Storage::Mapped(map)))
+    }
+
+    /// Parse a FITS file held in memory.
+    ///
+    /// # Errors
+    /// Returns a parse error if the bytes are not valid FITS.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, FitsError> {
+        Self::parse(Arc::new(Storage::Owned(bytes)))
+    }
+
+    fn parse(storage: Arc<Storage>) -> Result<Self, FitsError> {
+        let mut hdus = Vec::new();
+        let mut offset = 0;
+        // Trailing bytes shorter than a block are tolerated, as by most readers.
+        // The last data unit may also end unpadded, leaving `offset` past the end.
+        while storage.len().saturating_sub(offset) >= BLOCK_SIZE {
+            let (header, header_len) = Header::parse(&storage[offset..])?;
+            let len = data_len(&header)?;
+            let start = offset + header_len;
+            let end = start
+                .checked_add(len)
+                .ok_or_else(|| FitsError::InvalidKeyword("NAXIS".to_string()))?;
+            if end > storage.len() {
+                return Err(FitsError::Truncated {
+                    expected: end,
+                    found: storage.len(),
+                });
+            }
+            let data = DataBlock {
+                storage: storage.clone(),
+                offset: start,
+                len,
+            };
+            hdus.push(match header.get_str("XTENSION").map(str::trim) {
+                None | Some("IMAGE") => Hdu::Image(ImageHdu::from_parts(header, data)?),
+                Some("BINTABLE") => Hdu::BinTable(BinTableHdu::from_parts(header, data)?),
+                Some(_) => Hdu::Unknown(UnknownHdu { header, data }),
+            });
+            offset = start + len.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+        }
+        Ok(Self { hdus })
+    }
+
+    pub fn hdus(&self) -> &[Hdu] {
+        &self.hdus
+    }
+
+    pub fn len(&self) -> usize {
+        self.hdus.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hdus.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Hdu> {
+        self.hdus.get(index)
+    }
+
+    /// The first HDU with the given `EXTNAME`, compared case-insensitively.
+    pub fn find(&self, name: &str) -> Option<&Hdu> {
+        self.hdus
+            .iter()
+            .find(|h| h.name().is_some_and(|n| n.eq_ignore_ascii_case(name)))
+    }
+
+    /// The image HDU at `index`.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::OutOfRange`] if there is no such HDU and [`FitsError::NotAnImage`]
+    /// if it is not an image.
+    pub fn image(&self, index: usize) -> Result<&ImageHdu, FitsError> {
+        match self.hdus.get(index) {
+            Some(Hdu::Image(image)) => Ok(image),
+            Some(_) => Err(FitsError::NotAnImage(index)),
+            None => Err(FitsError::OutOfRange {
+                index,
+                len: self.hdus.len(),
+            }),
+        }
+    }
+
+    /// Append an HDU. A table pushed first is preceded by an empty primary HDU on writing.
+    pub fn push(&mut self, hdu: impl Into<Hdu>) {
+        self.hdus.push(hdu.into());
+    }
+
+    /// Serialise the file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        if !matches!(self.hdus.first(), Some(Hdu::Image(_))) {
+            write_hdu(&mut out, ImageHdu::empty().write_header(true), &[]);
+        }
+        for hdu in &self.hdus {
+            let primary = out.is_empty();
+            match hdu {
+                Hdu::Image(h) => write_hdu(&mut out, h.write_header(primary), h.data_bytes()),
+                Hdu::BinTable(h) => write_hdu(&mut out, h.write_header(), h.data_bytes()),
+                Hdu::Unknown(h) => write_hdu(&mut out, h.header.clone(), h.data()),
+            }
+        }
+        out
+    }
+
+    /// Write the file to `writer`.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::Io`] if writing fails.
+    pub fn write_to(&self, mut writer: impl Write) -> Result<(), FitsError> {
+        writer.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Write the file to `path`, replacing any existing file.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::Io`] if the file cannot be written.
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), FitsError> {
+        self.write_to(std::io::BufWriter::new(File::create(path)?))
+    }
+}
+
+/// A primary image written to a file in pieces, for images larger than memory.
+///
+/// The header is written and the file sized on creation; pixels, counted in storage order,
+/// may then be written in any order. Pixels never written read as zero.
+#[derive(Debug)]
+pub struct ImageWriter {
+    file: File,
+    bitpix: Bitpix,
+    data_start: u64,
+    len: usize,
+}
+
+impl ImageWriter {
+    /// Create the file at `path` for an image of the given shape, `NAXIS1` first, with the
+    /// non-structural cards of `header`. Values are stored unscaled, so `BSCALE` and `BZERO`
+    /// are not copied.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::Io`] if the file cannot be created.
+    pub fn create(
+        path: impl AsRef<Path>,
+        shape: &[usize],
+        bitpix: Bitpix,
+        header: &Header,
+    ) -> Result<Self, FitsError> {
+        let mut file = File::create(path)?;
+        let header = ImageHdu::header_only(shape, bitpix, header).write_header(true);
+        let header = header.to_bytes();
+        file.write_all(&header)?;
+        let len = shape.iter().product::<usize>();
+        let data_len = (len * bitpix.size()).div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+        file.set_len((header.len() + data_len) as u64)?;
+        Ok(Self {
+            file,
+            bitpix,
+            data_start: header.len() as u64,
+            len,
+        })
+    }
+
+    /// Write physical values starting at pixel `offset`.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::OutOfRange`] if the values extend past the image, and
+    /// [`FitsError::Io`] if writing fails.
+    pub fn write_at(&mut self, offset: usize, data: &[f64]) -> Result<(), FitsError> {
+        if offset + data.len() > self.len {
+            return Err(FitsError::OutOfRange {
+                index: offset + data.len(),
+                len: self.len,
+            });
+        }
+        let encoded = ImageHdu::new(&[data.len()], data, self.bitpix)?;
+        let position = self.data_start + (offset * self.bitpix.size()) as u64;
+        self.file.seek(SeekFrom::Start(position))?;
+        self.file.write_all(encoded.data_bytes())?;
+        Ok(())
+    }
+
+    /// Append `extensions` after the image and close the file.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::Io`] if writing fails.
+    pub fn finish(mut self, extensions: &[Hdu]) -> Result<(), FitsError> {
+        let mut out = Vec::new();
+        for hdu in extensions {
+            match hdu {
+                Hdu::Image(h) => write_hdu(&mut out, h.write_header(false), h.data_bytes()),
+                Hdu::BinTable(h) => write_hdu(&mut out, h.write_header(), h.data_bytes()),
+                Hdu::Unknown(h) => write_hdu(&mut out, h.header.clone(), h.data()),
+            }
+        }
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.write_all(&out)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+fn write_hdu(out: &mut Vec<u8>, header: Header, data: &[u8]) {
+    out.extend(header.to_bytes());
+    out.extend_from_slice(data);
+    out.resize(out.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE, 0);
+}
+
+/// Size in bytes of the data described by a header, without padding.
+///
+/// # Errors
+/// Returns [`FitsError::InvalidKeyword`] for a negative axis length, `PCOUNT` or `GCOUNT`, or
+/// if the size overflows.
+fn data_len(header: &Header) -> Result<usize, FitsError> {
+    let bitpix = header.require_i64("BITPIX")?;
+    let naxis = header.require_i64("NAXIS")?;
+    if naxis == 0 {
+        return Ok(0);
+    }
+    let count = |keyword: &str, value: i64| {
+        usize::try_from(value).map_err(|_| FitsError::InvalidKeyword(keyword.to_string()))
+    };
+    let overflow = || FitsError::InvalidKeyword("NAXIS".to_string());
+    let mut elements: usize = 1;
+    for i in 1..=naxis {
+        let keyword = format!("NAXIS{i}");
+        let n = count(&keyword, header.require_i64(&keyword)?)?;
+        elements = elements.checked_mul(n).ok_or_else(overflow)?;
+    }
+    let pcount = count("PCOUNT", header.get_i64("PCOUNT").unwrap_or(0))?;
+    let gcount = count("GCOUNT", header.get_i64("GCOUNT").unwrap_or(1))?;
+    pcount
+        .checked_add(elements)
+        .and_then(|n| n.checked_mul(gcount))
+        .and_then(|n| n.checked_mul(bitpix.unsigned_abs() as usize / 8))
+        .ok_or_else(overflow)
+}
+
+/// Cards regenerated from the data when an HDU is written.
+pub(crate) fn is_structural(keyword: &str) -> bool {
+    matches!(
+        keyword,
+        "SIMPLE" | "XTENSION" | "BITPIX" | "NAXIS" | "EXTEND" | "PCOUNT" | "GCOUNT" | "TFIELDS"
+    ) || keyword
+        .strip_prefix("NAXIS")
+        .is_some_and(|n| n.parse::<usize>().is_ok())
+}
+
+/// `structural` followed by the non-structural cards of `header`.
+pub(crate) fn with_structure(mut structural: Header, header: &Header) -> Header {
+    for card in header.cards() {
+        if card.value.is_none() || !is_structural(&card.keyword) {
+            structural.push(card.clone());
+        }
+    }
+    structural
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_extension_round_trip() {
+        let mut primary = ImageHdu::new(&[2, 2], &[1.0, 2.0, 3.0, 4.0], Bitpix::F32).unwrap();
+        primary.header_mut().set("OBJECT", "M82");
+        let mut cube = ImageHdu::new(
+            &[2, 2, 3],
+            &(0..12).map(f64::from).collect::<Vec<_>>(),
+            Bitpix::F64,
+        )
+        .unwrap();
+        cube.header_mut().set("EXTNAME", "CUBE");
+        let table = BinTableHdu::new(2)
+            .with_column("FREQ", Some("Hz"), ColumnData::Float64(vec![1e9, 2e9]))
+            .unwrap();
+        let mut fits = Fits::new();
+        fits.push(primary);
+        fits.push(cube);
+        fits.push(table);
+
+        let path = std::env::temp_dir().join(format!("spectre-fits-{}.fits", std::process::id()));
+        fits.write(&path).unwrap();
+        let read = Fits::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.len(), 3);
+        let primary = read.image(0).unwrap();
+        assert_eq!(primary.header().get_bool("SIMPLE"), Some(true));
+        assert_eq!(primary.header().get_bool("EXTEND"), Some(true));
+        assert_eq!(primary.header().get_str("OBJECT"), Some("M82"));
+        let Some(Hdu::Image(cube)) = read.find("cube") else {
+            panic!("missing cube");
+        };
+        assert_eq!(cube.header().get_str("XTENSION"), Some("IMAGE"));
+        assert_eq!(cube.read_plane(2).unwrap(), vec![8.0, 9.0, 10.0, 11.0]);
+        assert!(matches!(read.image(2), Err(FitsError::NotAnImage(2))));
+        let Some(Hdu::BinTable(table)) = read.get(2) else {
+            panic!("missing table");
+        };
+        assert_eq!(table.read_column_f64("FREQ").unwrap(), vec![1e9, 2e9]);
+    }
+
+    #[test]
+    fn test_image_writer() {
+        let mut header = Header::new();
+        header.set("BUNIT", "K");
+        // Scaling of the header the image was taken from does not apply to the values written.
+        header.set("BSCALE", 2.0);
+        header.set("BZERO", 10.0);
+        let path = std::env::temp_dir().join(format!("spectre-writer-{}.fits", std::process::id()));
+        let mut writer = ImageWriter::create(&path, &[3, 2], Bitpix::I16, &header).unwrap();
+        writer.write_at(3, &[4.0, f64::NAN, 6.0]).unwrap();
+        writer.write_at(0, &[1.0, 2.0, 3.0]).unwrap();
+        assert!(matches!(
+            writer.write_at(4, &[0.0; 3]),
+            Err(FitsError::OutOfRange { index: 7, len: 6 })
+        ));
+        let table = BinTableHdu::new(1)
+            .with_column("X", None, ColumnData::Int32(vec![7]))
+            .unwrap();
+        writer.finish(&[Hdu::BinTable(table)]).unwrap();
+        let read = Fits::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.len(), 2);
+        let image = read.image(0).unwrap();
+        assert_eq!(image.shape(), vec![3, 2]);
+        assert_eq!(image.header().get_str("BUNIT"), Some("K"));
+        assert!(!image.header().contains("BSCALE"));
+        let data = image.read_range(0..6).unwrap();
+        assert_eq!(data[..4], [1.0, 2.0, 3.0, 4.0]);
+        assert!(data[4].is_nan());
+        assert_eq!(data[5], 6.0);
+    }
+
+    #[test]
+    fn test_table_only_gets_primary() {
+        let mut fits = Fits::new();
+        fits.push(
+            BinTableHdu::new(1)
+                .with_column("X", None, ColumnData::Int32(vec![7]))
+                .unwrap(),
+        );
+        let read = Fits::from_bytes(fits.to_bytes()).unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read.image(0).unwrap().shape(), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_unpadded_last_data_unit() {
+        let mut fits = Fits::new();
+        fits.push(ImageHdu::new(&[2000], &[1.5; 2000], Bitpix::F64).unwrap());
+        let mut bytes = fits.to_bytes();
+        bytes.truncate(BLOCK_SIZE + 2000 * 8);
+        let read = Fits::from_bytes(bytes).unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(
+            read.image(0).unwrap().read_range(1998..2000).unwrap(),
+            [1.5; 2]
+        );
+    }
+
+    #[test]
+    fn test_invalid_data_size() {
+        let parse = |cards: &[(&str, i64)]| {
+            let mut header = Header::new();
+            header.set("SIMPLE", true);
+            for &(keyword, value) in cards {
+                header.set(keyword, value);
+            }
+            let mut bytes = header.to_bytes();
+            bytes.resize(bytes.len() + BLOCK_SIZE, 0);
+            Fits::from_bytes(bytes)
+        };
+        let image = [("BITPIX", 8), ("NAXIS", 2), ("NAXIS1", 4), ("NAXIS2", 4)];
+        assert!(parse(&image).is_ok());
+        for (cards, keyword) in [
+            (&[("NAXIS1", i64::MAX), ("NAXIS2", i64::MAX)][..], "NAXIS"),
+            (&[("NAXIS2", -4)][..], "NAXIS2"),
+            (&[("PCOUNT", -1)][..], "PCOUNT"),
+            (&[("GCOUNT", -1)][..], "GCOUNT"),
+            (&[("GCOUNT", i64::MAX)][..], "NAXIS"),
+        ] {
+            let mut all = image.to_vec();
+            all.extend_from_slice(cards);
+            assert!(
+                matches!(parse(&all), Err(FitsError::InvalidKeyword(k)) if k == keyword),
+                "{cards:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut fits = Fits::new();
+        fits.push(ImageHdu::new(&[2000], &[0.0; 2000], Bitpix::F64).unwrap());
+        let mut bytes = fits.to_bytes();
+        bytes.truncate(2 * BLOCK_SIZE);
+        assert!(matches!(
+            Fits::from_bytes(bytes),
+            Err(FitsError::Truncated { .. })
+        ));
+    }
+}
@@ -0,0 +1,747 @@
+//! FITS header cards.
+
+use std::fmt;
+
+use crate::errors::fits::FitsError;
+
+use super::BLOCK_SIZE;
+
+/// Length of a header card.
+pub const CARD_SIZE: usize = 80;
+
+/// Longest string that fits in a card after `KEYWORD = '` and before the closing quote.
+const MAX_STRING: usize = 68;
+
+/// Keywords whose cards hold free text rather than a value.
+const COMMENTARY: [&str; 3] = ["COMMENT", "HISTORY", ""];
+
+/// Value of a header card.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Logical(bool),
+    Integer(i64),
+    Float(f64),
+    Complex(f64, f64),
+    String(String),
+    /// A keyword present without a value
+    Undefined,
+}
+
+impl Value {
+    /// The value as a float, converting integers.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Self::Integer(i) => Some(i as f64),
+            Self::Float(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    /// The value as an integer. Floats with an integral value are accepted.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Self::Integer(i) => Some(i),
+            Self::Float(f) if f.fract() == 0.0 => Some(f as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Self::Logical(b) => Some(b),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Logical(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Self::Integer(value)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Self::Integer(value.into())
+    }
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Self::Integer(value as i64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl fmt::Display for Value {
+    /// Fixed-format rendering of the value, without padding.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Logical(b) => write!(f, "{}", if *b { "T" } else { "F" }),
+            Self::Integer(i) => write!(f, "{i}"),
+            // FITS has no token for NaN or infinity, so such values are left undefined.
+            Self::Float(x) if !x.is_finite() => Ok(()),
+            Self::Complex(re, im) if !(re.is_finite() && im.is_finite()) => Ok(()),
+            Self::Float(x) => write!(f, "{}", format_float(*x)),
+            Self::Complex(re, im) => write!(f, "({}, {})", format_float(*re), format_float(*im)),
+            Self::String(s) => write!(f, "'{}'", quote(s)),
+            Self::Undefined => Ok(()),
+        }
+    }
+}
+
+/// A header card: a keyword with a value and comment, or commentary text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Card {
+    pub keyword: String,
+    /// `None` for `COMMENT`, `HISTORY` and blank-keyword cards
+    pub value: Option<Value>,
+    /// The comment, or the text of a commentary card
+    pub comment: Option<String>,
+}
+
+impl Card {
+    pub fn new(keyword: &str, value: impl Into<Value>, comment: Option<&str>) -> Self {
+        Self {
+            keyword: keyword.trim().to_ascii_uppercase(),
+            value: Some(value.into()),
+            comment: comment.map(str::to_string),
+        }
+    }
+
+    /// A `COMMENT`, `HISTORY` or blank-keyword card.
+    pub fn commentary(keyword: &str, text: &str) -> Self {
+        Self {
+            keyword: keyword.trim().to_ascii_uppercase(),
+            value: None,
+            comment: Some(text.to_string()),
+        }
+    }
+
+    fn is_hierarch(&self) -> bool {
+        self.keyword.len() > 8 || self.keyword.contains(' ')
+    }
+
+    /// Render the card as one or more 80-character records, using `CONTINUE` for long
+    /// strings and the `HIERARCH` convention for long keywords. The comment of a string
+    /// that leaves it no room goes on a `CONTINUE` record of its own; other comments are cut
+    /// at the end of the record.
+    fn to_records(&self) -> Vec<String> {
+        let Some(value) = &self.value else {
+            let text = self.comment.as_deref().unwrap_or("");
+            let chunks = split_commentary(text, CARD_SIZE - 8);
+            if chunks.is_empty() {
+                return vec![pad(self.keyword.clone())];
+            }
+            return chunks
+                .into_iter()
+                .map(|c| pad(format!("{:<8}{c}", self.keyword)))
+                .collect();
+        };
+        let comment = |record: &mut String| {
+            if let Some(c) = &self.comment
+                && record.len() + 3 < CARD_SIZE
+            {
+                record.push_str(" / ");
+                record.push_str(c);
+            }
+        };
+
+        let hierarch = self.is_hierarch();
+        if let Value::String(s) = value {
+            let prefix = if hierarch {
+                format!("HIERARCH {} = '", self.keyword)
+            } else {
+                format!("{:<8}= '", self.keyword)
+            };
+            let quoted = quote(s);
+            // Room for the string before the closing quote of the first record.
+            let room = CARD_SIZE.saturating_sub(prefix.len() + 1);
+            let fits_comment = |len: usize| {
+                self.comment
+                    .as_ref()
+                    .is_none_or(|c| len + 1 + 3 + c.len() <= CARD_SIZE)
+            };
+            let single = if hierarch {
+                format!("{prefix}{quoted}")
+            } else {
+                format!("{prefix}{quoted:<8}")
+            };
+            if quoted.len() <= room && fits_comment(single.len()) {
+                let mut record = single + "'";
+                comment(&mut record);
+                return vec![pad(record)];
+            }
+            // Too long for one record, with its comment: continue the string with `&` and
+            // put the comment after the last piece, on an empty piece of its own if need be.
+            let mut chunks = split_quoted(&quoted, room.saturating_sub(1).max(1), MAX_STRING - 1);
+            let start = if chunks.len() == 1 {
+                prefix.len()
+            } else {
+                "CONTINUE  '".len()
+            };
+            if !fits_comment(start + chunks[chunks.len() - 1].len()) {
+                chunks.push(String::new());
+            }
+            let last = chunks.len() - 1;
+            return chunks
+                .iter()
+                .enumerate()
+                .map(|(i, chunk)| {
+                    let amp = if i == last { "" } else { "&" };
+                    let mut record = if i == 0 {
+                        format!("{prefix}{chunk}{amp}'")
+                    } else {
+                        format!("CONTINUE  '{chunk}{amp}'")
+                    };
+                    if i == last {
+                        comment(&mut record);
+                    }
+                    pad(record)
+                })
+                .collect();
+        }
+
+        if hierarch {
+            let mut record = format!("HIERARCH {} = {value}", self.keyword);
+            comment(&mut record);
+            return vec![pad(record)];
+        }
+
+        let mut record = format!("{:<8}= {:>20}", self.keyword, value.to_string());
+        comment(&mut record);
+        vec![pad(record)]
+    }
+}
+
+/// An ordered list of header cards.
+///
+/// Keyword lookup is case-insensitive. Structural keywords (`SIMPLE`, `BITPIX`, `NAXISn`, …)
+/// are kept as read, but are regenerated from the data when an HDU is written.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Header {
+    cards: Vec<Card>,
+}
+
+impl Header {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cards(&self) -> &[Card] {
+        &self.cards
+    }
+
+    pub fn len(&self) -> usize {
+        self.cards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cards.is_empty()
+    }
+
+    fn position(&self, keyword: &str) -> Option<usize> {
+        let keyword = keyword.trim().to_ascii_uppercase();
+        self.cards
+            .iter()
+            .position(|c| c.value.is_some() && c.keyword == keyword)
+    }
+
+    pub fn contains(&self, keyword: &str) -> bool {
+        self.position(keyword).is_some()
+    }
+
+    /// Value of the first card with `keyword`.
+    pub fn get(&self, keyword: &str) -> Option<&Value> {
+        self.position(keyword)
+            .and_then(|i| self.cards[i].value.as_ref())
+    }
+
+    /// Comment of the first card with `keyword`.
+    pub fn comment(&self, keyword: &str) -> Option<&str> {
+        self.position(keyword)
+            .and_then(|i| self.cards[i].comment.as_deref())
+    }
+
+    pub fn get_f64(&self, keyword: &str) -> Option<f64> {
+        self.get(keyword).and_then(Value::as_f64)
+    }
+
+    pub fn get_i64(&self, keyword: &str) -> Option<i64> {
+        self.get(keyword).and_then(Value::as_i64)
+    }
+
+    pub fn get_str(&self, keyword: &str) -> Option<&str> {
+        self.get(keyword).and_then(Value::as_str)
+    }
+
+    pub fn get_bool(&self, keyword: &str) -> Option<bool> {
+        self.get(keyword).and_then(Value::as_bool)
+    }
+
+    /// Integer value of a keyword that must be present.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::MissingKeyword`] if the keyword is absent and
+    /// [`FitsError::InvalidKeyword`] if its value is not an integer.
+    pub fn require_i64(&self, keyword: &str) -> Result<i64, FitsError> {
+        self.get(keyword)
+            .ok_or_else(|| FitsError::MissingKeyword(keyword.to_string()))?
+            .as_i64()
+            .ok_or_else(|| FitsError::InvalidKeyword(keyword.to_string()))
+    }
+
+    /// Set the value of `keyword`, keeping its position and comment if it exists and
+    /// appending it otherwise.
+    pub fn set(&mut self, keyword: &str, value: impl Into<Value>) {
+        match self.position(keyword) {
+            Some(i) => self.cards[i].value = Some(value.into()),
+            None => self.cards.push(Card::new(keyword, value, None)),
+        }
+    }
+
+    /// Set the value and comment of `keyword`.
+    pub fn set_with_comment(&mut self, keyword: &str, value: impl Into<Value>, comment: &str) {
+        match self.position(keyword) {
+            Some(i) => self.cards[i] = Card::new(keyword, value, Some(comment)),
+            None => self.cards.push(Card::new(keyword, value, Some(comment))),
+        }
+    }
+
+    /// Remove the first card with `keyword`, returning its value.
+    pub fn remove(&mut self, keyword: &str) -> Option<Value> {
+        self.position(keyword)
+            .and_then(|i| self.cards.remove(i).value)
+    }
+
+    pub fn add_comment(&mut self, text: &str) {
+        self.cards.push(Card::commentary("COMMENT", text));
+    }
+
+    pub fn add_history(&mut self, text: &str) {
+        self.cards.push(Card::commentary("HISTORY", text));
+    }
+
+    pub fn push(&mut self, card: Card) {
+        self.cards.push(card);
+    }
+
+    /// Parse a header from the start of `bytes`, returning it with the number of bytes it
+    /// occupies, a multiple of 2880.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::MissingEnd`] if there is no `END` card and
+    /// [`FitsError::InvalidCard`] for a malformed card.
+    pub fn parse(bytes: &[u8]) -> Result<(Self, usize), FitsError> {
+        let mut header = Self::new();
+        for (i, record) in bytes.chunks_exact(CARD_SIZE).enumerate() {
+            // Headers are restricted to printable ASCII; anything else is replaced so that
+            // column slicing stays on character boundaries.
+            let record: String = record
+                .iter()
+                .map(|&b| if b.is_ascii() { b as char } else { '?' })
+                .collect();
+            let keyword = record[..8].trim_end();
+            if keyword == "END" {
+                let used = (i + 1) * CARD_SIZE;
+                return Ok((header, used.div_ceil(BLOCK_SIZE) * BLOCK_SIZE));
+            }
+            if keyword == "CONTINUE" {
+                header.append_continue(&record)?;
+                continue;
+            }
+            header.cards.push(parse_card(&record)?);
+        }
+        Err(FitsError::MissingEnd)
+    }
+
+    fn append_continue(&mut self, record: &str) -> Result<(), FitsError> {
+        let (value, comment) = parse_value(&record[8..], record)?;
+        let previous = self.cards.last_mut().and_then(|c| match &mut c.value {
+            Some(Value::String(s)) if s.ends_with('&') => Some((s, &mut c.comment)),
+            _ => None,
+        });
+        match (previous, value) {
+            (Some((s, previous_comment)), Value::String(more)) => {
+                s.pop();
+                s.push_str(&more);
+                if comment.is_some() {
+                    *previous_comment = comment;
+                }
+            }
+            // A stray CONTINUE is kept as commentary rather than rejected.
+            _ => self
+                .cards
+                .push(Card::commentary("CONTINUE", record[8..].trim_end())),
+        }
+        Ok(())
+    }
+
+    /// Render the header, terminated by `END` and padded with spaces to a multiple of 2880
+    /// bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = String::new();
+        for card in &self.cards {
+            for record in card.to_records() {
+                out.push_str(&record);
+            }
+        }
+        out.push_str(&pad("END".to_string()));
+        let len = out.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+        let mut bytes = out.into_bytes();
+        bytes.resize(len, b' ');
+        bytes
+    }
+}
+
+fn parse_card(record: &str) -> Result<Card, FitsError> {
+    let keyword = record[..8].trim_end();
+    if keyword == "HIERARCH" {
+        let rest = &record[8..];
+        let eq = rest
+            .find('=')
+            .ok_or_else(|| FitsError::InvalidCard(record.to_string()))?;
+        let (value, comment) = parse_value(&rest[eq + 1..], record)?;
+        return Ok(Card {
+            keyword: rest[..eq].trim().to_ascii_uppercase(),
+            value: Some(value),
+            comment,
+        });
+    }
+    if COMMENTARY.contains(&keyword) || &record[8..10] != "= " {
+        return Ok(Card::commentary(keyword, record[8..].trim_end()));
+    }
+    let (value, comment) = parse_value(&record[10..], record)?;
+    Ok(Card {
+        keyword: keyword.to_string(),
+        value: Some(value),
+        comment,
+    })
+}
+
+/// Parse a value field and its optional `/ comment`.
+fn parse_value(field: &str, record: &str) -> Result<(Value, Option<String>), FitsError> {
+    let invalid = || FitsError::InvalidCard(record.to_string());
+    let trimmed = field.trim_start();
+    let (value, rest) = if let Some(quoted) = trimmed.strip_prefix('\'') {
+        let mut s = String::new();
+        let mut chars = quoted.char_indices().peekable();
+        let end = loop {
+            match chars.next() {
+                Some((_, '\'')) if matches!(chars.peek(), Some((_, '\''))) => {
+                    chars.next();
+                    s.push('\'');
+                }
+                Some((i, '\'')) => break i + 1,
+                Some((_, c)) => s.push(c),
+                None => return Err(invalid()),
+            }
+        };
+        // Trailing spaces in strings are not significant; leading ones are.
+        (Value::String(s.trim_end().to_string()), &quoted[end..])
+    } else {
+        let (token, rest) = trimmed.split_at(trimmed.find('/').unwrap_or(trimmed.len()));
+        (parse_token(token.trim()).ok_or_else(invalid)?, rest)
+    };
+    let comment = rest
+        .trim_start()
+        .strip_prefix('/')
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty());
+    Ok((value, comment))
+}
+
+fn parse_token(token: &str) -> Option<Value> {
+    match token {
+        "" => Some(Value::Undefined),
+        "T" => Some(Value::Logical(true)),
+        "F" => Some(Value::Logical(false)),
+        _ if token.starts_with('(') => {
+            let inner = token.strip_prefix('(')?.strip_suffix(')')?;
+            let (re, im) = inner.split_once(',')?;
+            Some(Value::Complex(parse_float(re)?, parse_float(im)?))
+        }
+        _ => token
+            .parse::<i64>()
+            .map(Value::Integer)
+            .ok()
+            .or_else(|| parse_float(token).map(Value::Float)),
+    }
+}
+
+fn parse_float(s: &str) -> Option<f64> {
+    s.trim().replace(['D', 'd'], "E").parse().ok()
+}
+
+/// Shortest representation that reads back exactly, with a decimal point and an upper-case
+/// exponent as FITS requires.
+fn format_float(x: f64) -> String {
+    let repr = format!("{x:?}").to_uppercase();
+    let (mantissa, exponent) = match repr.split_once('E') {
+        Some((m, e)) => (m.to_string(), format!("E{e}")),
+        None => (repr, String::new()),
+    };
+    if mantissa.contains('.') {
+        format!("{mantissa}{exponent}")
+    } else {
+        format!("{mantissa}.0{exponent}")
+    }
+}
+
+fn quote(s: &str) -> String {
+    s.replace('\'', "''")
+}
+
+/// Split an already-quoted string into a first chunk of at most `first` bytes and further
+/// chunks of at most `size` bytes, without separating a doubled quote.
+fn split_quoted(quoted: &str, first: usize, size: usize) -> Vec<String> {
+    let mut chunks = vec![String::new()];
+    let mut chars = quoted.chars().peekable();
+    while let Some(c) = chars.next() {
+        let pair = c == '\'' && chars.peek() == Some(&'\'');
+        let width = if pair { 2 } else { c.len_utf8() };
+        let limit = if chunks.len() == 1 { first } else { size };
+        if chunks.last().unwrap().len() + width > limit {
+            chunks.push(String::new());
+        }
+        let chunk = chunks.last_mut().unwrap();
+        chunk.push(c);
+        if pair {
+            chunk.push(chars.next().unwrap());
+        }
+    }
+    chunks
+}
+
+/// Split commentary text into chunks of at most `size` characters. Trailing spaces of a card
+/// are not significant, so a chunk ends before a run of spaces, which starts the next one.
+fn split_commentary(text: &str, size: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let mut end = (start + size).min(chars.len());
+        if end < chars.len()
+            && let Some(last) = chars[start..end].iter().rposition(|&c| c != ' ')
+        {
+            end = start + last + 1;
+        }
+        chunks.push(chars[start..end].iter().collect());
+        start = end;
+    }
+    chunks
+}
+
+/// Pad or cut a record to 80 characters. Headers can only hold printable ASCII, so any other
+/// character is written as `?`, as it is when read.
+fn pad(record: String) -> String {
+    let record: String = record
+        .chars()
+        .map(|c| {
+            if c == ' ' || c.is_ascii_graphic() {
+                c
+            } else {
+                '?'
+            }
+        })
+        .take(CARD_SIZE)
+        .collect();
+    format!("{record:<CARD_SIZE$}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(cards: &[&str]) -> Header {
+        let mut bytes: Vec<u8> = cards
+            .iter()
+            .flat_map(|c| pad(c.to_string()).into_bytes())
+            .collect();
+        bytes.extend(pad("END".to_string()).into_bytes());
+        Header::parse(&bytes).unwrap().0
+    }
+
+    #[test]
+    fn test_parse_values() {
+        let header = parse(&[
+            "SIMPLE  =                    T / conforms to FITS standard",
+            "BITPIX  =                  -32",
+            "CRVAL3  =   1.152712018000D+11 / [Hz]",
+            "OBJECT  = 'O''Brien  '         / name",
+            "CZERO   = (1.5, -2.0)",
+            "BLANKV  =",
+            "HISTORY   processed by spectre",
+            "HIERARCH ESO DET CHIP NAME = 'CCD-1' / detector",
+        ]);
+        assert_eq!(header.get_bool("simple"), Some(true));
+        assert_eq!(header.comment("SIMPLE"), Some("conforms to FITS standard"));
+        assert_eq!(header.get_i64("BITPIX"), Some(-32));
+        assert_eq!(header.get_f64("CRVAL3"), Some(1.152_712_018e11));
+        assert_eq!(header.get_str("OBJECT"), Some("O'Brien"));
+        assert_eq!(header.get("CZERO"), Some(&Value::Complex(1.5, -2.0)));
+        assert_eq!(header.get("BLANKV"), Some(&Value::Undefined));
+        assert_eq!(
+            header.cards()[6].comment.as_deref(),
+            Some("  processed by spectre")
+        );
+        assert_eq!(header.get_str("ESO DET CHIP NAME"), Some("CCD-1"));
+    }
+
+    #[test]
+    fn test_continue() {
+        let header = parse(&[
+            "LONGSTR = 'This keyword value is long enough that it needs to be continued &'",
+            "CONTINUE  'onto a second card&'",
+            "CONTINUE  '' / and a comment",
+        ]);
+        assert_eq!(
+            header.get_str("LONGSTR"),
+            Some(
+                "This keyword value is long enough that it needs to be continued onto a second card"
+            )
+        );
+        assert_eq!(header.comment("LONGSTR"), Some("and a comment"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut header = Header::new();
+        header.set_with_comment("NAXIS", 2, "number of axes");
+        header.set("CDELT1", -2.5e-5);
+        header.set("RESTFRQ", 230.538e9);
+        header.set("ONE", 1.0);
+        header.set("TELESCOP", "ALMA");
+        header.set("QUOTE", "it's");
+        header.set("LONG", "x'".repeat(60));
+        header.set("ESO OBS NAME", "test");
+        header.add_history(
+            "a history card that is long enough to need more than one card to hold all of its text",
+        );
+        let bytes = header.to_bytes();
+        assert_eq!(bytes.len() % BLOCK_SIZE, 0);
+        let text = String::from_utf8(bytes.clone()).unwrap();
+        assert!(text.contains("CDELT1  =              -2.5E-5"));
+        assert!(text.contains("ONE     =                  1.0"));
+        assert!(text.contains("TELESCOP= 'ALMA    '"));
+        assert!(text.contains("HIERARCH ESO OBS NAME = 'test'"));
+
+        let (parsed, len) = Header::parse(&bytes).unwrap();
+        assert_eq!(len, bytes.len());
+        for key in [
+            "NAXIS",
+            "CDELT1",
+            "RESTFRQ",
+            "ONE",
+            "TELESCOP",
+            "QUOTE",
+            "LONG",
+            "ESO OBS NAME",
+        ] {
+            assert_eq!(parsed.get(key), header.get(key), "{key}");
+        }
+        assert_eq!(parsed.comment("NAXIS"), Some("number of axes"));
+        let history: String = parsed
+            .cards()
+            .iter()
+            .filter(|c| c.keyword == "HISTORY")
+            .map(|c| c.comment.clone().unwrap())
+            .collect();
+        // Trailing spaces of each commentary card are not significant.
+        assert_eq!(history, header.cards()[8].comment.clone().unwrap());
+
+        // Spaces where the text is split between cards are kept.
+        let text = format!("{}  spaces at the split", "x".repeat(71));
+        let mut header = Header::new();
+        header.add_comment(&text);
+        let (parsed, _) = Header::parse(&header.to_bytes()).unwrap();
+        let comment: String = parsed
+            .cards()
+            .iter()
+            .map(|c| c.comment.clone().unwrap())
+            .collect();
+        assert_eq!(comment, text);
+    }
+
+    #[test]
+    fn test_long_hierarch() {
+        let mut header = Header::new();
+        let value = format!(
+            "{}'s calibration",
+            "a very long observing programme name ".repeat(3)
+        );
+        header.set_with_comment("ESO OBS PROG NAME", value.as_str(), "programme");
+        header.set_with_comment("ESO OBS TARG NAME", "NGC 253", &"c".repeat(60));
+        header.set_with_comment("OBJECT", "M82", &"d".repeat(70));
+        let bytes = header.to_bytes();
+        let text = String::from_utf8(bytes.clone()).unwrap();
+        assert!(text.starts_with("HIERARCH ESO OBS PROG NAME = 'a very long"));
+        assert!(text.contains("CONTINUE  '"));
+
+        let (parsed, _) = Header::parse(&bytes).unwrap();
+        assert_eq!(parsed.get_str("ESO OBS PROG NAME"), Some(value.as_str()));
+        assert_eq!(parsed.comment("ESO OBS PROG NAME"), Some("programme"));
+        assert_eq!(parsed.get_str("ESO OBS TARG NAME"), Some("NGC 253"));
+        assert_eq!(
+            parsed.comment("ESO OBS TARG NAME"),
+            header.comment("ESO OBS TARG NAME")
+        );
+        assert_eq!(parsed.get_str("OBJECT"), Some("M82"));
+        // Longer than a `CONTINUE` record can hold, so cut at its end.
+        assert_eq!(parsed.comment("OBJECT"), Some("d".repeat(65).as_str()));
+    }
+
+    #[test]
+    fn test_write_invalid_text() {
+        let mut header = Header::new();
+        // A multi-byte character straddling column 80, and one in a comment.
+        header.set("LONGKEY", format!("{}é", "x".repeat(68)));
+        header.set_with_comment("OBJECT", "Ω Cen", "naïve");
+        header.set_with_comment("NOTE", 1, &"é".repeat(40));
+        header.set("BAD", f64::NAN);
+        header.set("HUGE", f64::INFINITY);
+        let bytes = header.to_bytes();
+        assert!(bytes.is_ascii());
+        let (parsed, _) = Header::parse(&bytes).unwrap();
+        assert_eq!(parsed.get_str("OBJECT"), Some("? Cen"));
+        assert_eq!(parsed.comment("OBJECT"), Some("na?ve"));
+        assert_eq!(parsed.get("BAD"), Some(&Value::Undefined));
+        assert_eq!(parsed.get("HUGE"), Some(&Value::Undefined));
+    }
+
+    #[test]
+    fn test_missing_end() {
+        let bytes = pad("SIMPLE  =                    T".to_string()).into_bytes();
+        assert!(matches!(Header::parse(&bytes), Err(FitsError::MissingEnd)));
+    }
+}
@@ -0,0 +1,397 @@
+//! Primary and `IMAGE` extension HDUs.
+
+use std::ops::Range;
+
+use crate::errors::fits::FitsError;
+
+use super::{DataBlock, Header, with_structure};
+
+/// Data type of image pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bitpix {
+    U8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl Bitpix {
+    /// Parse a `BITPIX` keyword value.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::UnsupportedBitpix`] for any value other than 8, 16, 32, 64, −32
+    /// and −64.
+    pub fn from_value(value: i64) -> Result<Self, FitsError> {
+        Ok(match value {
+            8 => Self::U8,
+            16 => Self::I16,
+            32 => Self::I32,
+            64 => Self::I64,
+            -32 => Self::F32,
+            -64 => Self::F64,
+            _ => return Err(FitsError::UnsupportedBitpix(value)),
+        })
+    }
+
+    /// The `BITPIX` keyword value.
+    pub fn value(&self) -> i64 {
+        match self {
+            Self::U8 => 8,
+            Self::I16 => 16,
+            Self::I32 => 32,
+            Self::I64 => 64,
+            Self::F32 => -32,
+            Self::F64 => -64,
+        }
+    }
+
+    /// Bytes per pixel.
+    pub fn size(&self) -> usize {
+        self.value().unsigned_abs() as usize / 8
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.value() > 0
+    }
+
+    /// Value used for `BLANK` when writing NaNs to an integer image.
+    fn default_blank(&self) -> i64 {
+        match self {
+            Self::U8 => u8::MAX.into(),
+            Self::I16 => i16::MIN.into(),
+            Self::I32 => i32::MIN.into(),
+            _ => i64::MIN,
+        }
+    }
+
+    /// Decode the big-endian pixel at the start of `bytes` to a raw integer or float.
+    fn decode(&self, bytes: &[u8]) -> Raw {
+        match self {
+            Self::U8 => Raw::Int(bytes[0].into()),
+            Self::I16 => Raw::Int(i16::from_be_bytes([bytes[0], bytes[1]]).into()),
+            Self::I32 => Raw::Int(i32::from_be_bytes(bytes[..4].try_into().unwrap()).into()),
+            Self::I64 => Raw::Int(i64::from_be_bytes(bytes[..8].try_into().unwrap())),
+            Self::F32 => Raw::Float(f32::from_be_bytes(bytes[..4].try_into().unwrap()).into()),
+            Self::F64 => Raw::Float(f64::from_be_bytes(bytes[..8].try_into().unwrap())),
+        }
+    }
+
+    fn encode(&self, raw: Raw, out: &mut Vec<u8>) {
+        match (self, raw) {
+            (Self::F32, Raw::Float(f)) => out.extend((f as f32).to_be_bytes()),
+            (Self::F64, Raw::Float(f)) => out.extend(f.to_be_bytes()),
+            (Self::U8, Raw::Int(i)) => out.push(i.clamp(0, u8::MAX.into()) as u8),
+            (Self::I16, Raw::Int(i)) => {
+                out.extend((i.clamp(i16::MIN.into(), i16::MAX.into()) as i16).to_be_bytes());
+            }
+            (Self::I32, Raw::Int(i)) => {
+                out.extend((i.clamp(i32::MIN.into(), i32::MAX.into()) as i32).to_be_bytes());
+            }
+            (Self::I64, Raw::Int(i)) => out.extend(i.to_be_bytes()),
+            _ => unreachable!("raw value does not match BITPIX"),
+        }
+    }
+}
+
+/// A stored pixel value, before scaling.
+#[derive(Debug, Clone, Copy)]
+enum Raw {
+    Int(i64),
+    Float(f64),
+}
+
+/// An image: the primary HDU or an `IMAGE` extension.
+///
+/// Pixels are returned as `f64` physical values, `BZERO + BSCALE × stored`, with `BLANK`
+/// integers read as NaN. The shape is in FITS order, `NAXIS1` (fastest varying) first.
+#[derive(Debug, Clone)]
+pub struct ImageHdu {
+    header: Header,
+    bitpix: Bitpix,
+    shape: Vec<usize>,
+    data: DataBlock,
+}
+
+impl ImageHdu {
+    /// Create an image from physical values, stored with the given `bitpix`.
+    ///
+    /// Values are rounded for integer types, and NaNs are stored as `BLANK`.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::LengthMismatch`] if `data` does not match `shape`.
+    pub fn new(shape: &[usize], data: &[f64], bitpix: Bitpix) -> Result<Self, FitsError> {
+        Self::new_scaled(shape, data, bitpix, 1.0, 0.0)
+    }
+
+    /// Create an image whose integer pixels are stored as `(value − bzero) / bscale`.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::LengthMismatch`] if `data` does not match `shape`.
+    pub fn new_scaled(
+        shape: &[usize],
+        data: &[f64],
+        bitpix: Bitpix,
+        bscale: f64,
+        bzero: f64,
+    ) -> Result<Self, FitsError> {
+        let expected = shape.iter().product::<usize>();
+        if data.len() != expected {
+            return Err(FitsError::LengthMismatch {
+                expected,
+                found: data.len(),
+            });
+        }
+        let mut header = Header::new();
+        let blank = bitpix.default_blank();
+        let needs_blank = bitpix.is_integer() && data.iter().any(|v| v.is_nan());
+        if bscale != 1.0 || bzero != 0.0 {
+            header.set("BSCALE", bscale);
+            header.set("BZERO", bzero);
+        }
+        if needs_blank {
+            header.set("BLANK", blank);
+        }
+        let mut bytes = Vec::with_capacity(expected * bitpix.size());
+        for &v in data {
+            let stored = (v - bzero) / bscale;
+            let raw = match bitpix {
+                Bitpix::F32 | Bitpix::F64 => Raw::Float(stored),
+                _ if v.is_nan() => Raw::Int(blank),
+                // `as` saturates, and encoding clamps to the range of the type.
+                _ => Raw::Int(stored.round() as i64),
+            };
+            bitpix.encode(raw, &mut bytes);
+        }
+        Ok(Self {
+            header,
+            bitpix,
+            shape: shape.to_vec(),
+            data: DataBlock::owned(bytes),
+        })
+    }
+
+    /// A primary HDU without data.
+    pub fn empty() -> Self {
+        Self {
+            header: Header::new(),
+            bitpix: Bitpix::U8,
+            shape: Vec::new(),
+            data: DataBlock::owned(Vec::new()),
+        }
+    }
+
+    /// An image with the given shape and header but no data, used to render the header of an
+    /// image written in pieces. Pieces are written unscaled, so `BSCALE` and `BZERO` are
+    /// dropped, and integer images get a `BLANK` card.
+    pub(crate) fn header_only(shape: &[usize], bitpix: Bitpix, header: &Header) -> Self {
+        let mut header = header.clone();
+        header.remove("BSCALE");
+        header.remove("BZERO");
+        if bitpix.is_integer() {
+            header.set("BLANK", bitpix.default_blank());
+        }
+        Self {
+            header,
+            bitpix,
+            shape: shape.to_vec(),
+            data: DataBlock::owned(Vec::new()),
+        }
+    }
+
+    pub(crate) fn from_parts(header: Header, data: DataBlock) -> Result<Self, FitsError> {
+        let bitpix = Bitpix::from_value(header.require_i64("BITPIX")?)?;
+        let naxis = header.require_i64("NAXIS")?;
+        let shape = (1..=naxis)
+            .map(|i| header.require_i64(&format!("NAXIS{i}")).map(|n| n as usize))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            header,
+            bitpix,
+            shape,
+            data,
+        })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+
+    pub fn bitpix(&self) -> Bitpix {
+        self.bitpix
+    }
+
+    /// Axis lengths, `NAXIS1` first.
+    pub fn shape(&self) -> Vec<usize> {
+        self.shape.clone()
+    }
+
+    /// Number of pixels.
+    pub fn len(&self) -> usize {
+        if self.shape.is_empty() {
+            0
+        } else {
+            self.shape.iter().product()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of pixels in a plane, `NAXIS1 × NAXIS2`.
+    pub fn plane_len(&self) -> usize {
+        self.shape.iter().take(2).product()
+    }
+
+    /// All pixels as physical values.
+    ///
+    /// # Errors
+    /// Never fails for an image read from a valid file; kept fallible for symmetry with the
+    /// partial readers.
+    pub fn read(&self) -> Result<Vec<f64>, FitsError> {
+        self.read_range(0..self.len())
+    }
+
+    /// Pixels `range`, counted in storage order, as physical values.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::OutOfRange`] if the range extends past the data.
+    pub fn read_range(&self, range: Range<usize>) -> Result<Vec<f64>, FitsError> {
+        if range.end > self.len() || range.start > range.end {
+            return Err(FitsError::OutOfRange {
+                index: range.end,
+                len: self.len(),
+            });
+        }
+        let size = self.bitpix.size();
+        let bscale = self.header.get_f64("BSCALE").unwrap_or(1.0);
+        let bzero = self.header.get_f64("BZERO").unwrap_or(0.0);
+        let blank = self.header.get_i64("BLANK");
+        let bytes = &self.data.bytes()[range.start * size..range.end * size];
+        Ok(bytes
+            .chunks_exact(size)
+            .map(|b| match self.bitpix.decode(b) {
+                Raw::Int(i) if Some(i) == blank => f64::NAN,
+                Raw::Int(i) => bzero + bscale * i as f64,
+                Raw::Float(f) => bzero + bscale * f,
+            })
+            .collect())
+    }
+
+    /// Plane `index` of an image with three or more axes: the `NAXIS1 × NAXIS2` pixels at
+    /// position `index` along the remaining axes, flattened.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::OutOfRange`] if there is no such plane.
+    pub fn read_plane(&self, index: usize) -> Result<Vec<f64>, FitsError> {
+        let plane = self.plane_len();
+        let planes = self.len().checked_div(plane).unwrap_or(0);
+        if index >= planes {
+            return Err(FitsError::OutOfRange { index, len: planes });
+        }
+        self.read_range(index * plane..(index + 1) * plane)
+    }
+
+    pub(crate) fn data_bytes(&self) -> &[u8] {
+        self.data.bytes()
+    }
+
+    /// The header to write: structural keywords from the data, then the other cards.
+    pub(crate) fn write_header(&self, primary: bool) -> Header {
+        let mut structural = Header::new();
+        if primary {
+            structural.set_with_comment("SIMPLE", true, "conforms to FITS standard");
+        } else {
+            structural.set_with_comment("XTENSION", "IMAGE", "image extension");
+        }
+        structural.set_with_comment("BITPIX", self.bitpix.value(), "array data type");
+        structural.set_with_comment("NAXIS", self.shape.len(), "number of array dimensions");
+        for (i, &n) in self.shape.iter().enumerate() {
+            structural.set(&format!("NAXIS{}", i + 1), n);
+        }
+        if primary {
+            structural.set("EXTEND", true);
+        } else {
+            structural.set("PCOUNT", 0);
+            structural.set("GCOUNT", 1);
+        }
+        with_structure(structural, &self.header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    fn round_trip(image: &ImageHdu) -> ImageHdu {
+        let mut bytes = image.write_header(true).to_bytes();
+        bytes.extend_from_slice(image.data_bytes());
+        let (header, len) = Header::parse(&bytes).unwrap();
+        ImageHdu::from_parts(header, DataBlock::owned(bytes[len..].to_vec())).unwrap()
+    }
+
+    #[test]
+    fn test_all_bitpix() {
+        let data = [-3.0, 0.0, 1.0, 2.0, 100.0, 120.0];
+        for bitpix in [
+            Bitpix::I16,
+            Bitpix::I32,
+            Bitpix::I64,
+            Bitpix::F32,
+            Bitpix::F64,
+        ] {
+            let image = round_trip(&ImageHdu::new(&[3, 2], &data, bitpix).unwrap());
+            assert_eq!(image.bitpix(), bitpix);
+            assert_eq!(image.read().unwrap(), data);
+        }
+        let image = round_trip(&ImageHdu::new(&[2], &[5.6, 300.0], Bitpix::U8).unwrap());
+        assert_eq!(image.read().unwrap(), vec![6.0, 255.0]);
+        assert!(matches!(
+            Bitpix::from_value(24),
+            Err(FitsError::UnsupportedBitpix(24))
+        ));
+    }
+
+    #[test]
+    fn test_scaling_and_blank() {
+        let data = [0.0, 0.001, f64::NAN, -0.5];
+        let image = round_trip(&ImageHdu::new_scaled(&[4], &data, Bitpix::I16, 1e-4, 0.5).unwrap());
+        assert_eq!(image.header().get_i64("BLANK"), Some(i64::from(i16::MIN)));
+        let read = image.read().unwrap();
+        assert_relative_eq!(read[1], 0.001, epsilon = 1e-12);
+        assert!(read[2].is_nan());
+        assert_relative_eq!(read[3], -0.5, epsilon = 1e-12);
+
+        // Unsigned 16-bit data stored with the conventional BZERO offset.
+        let unsigned =
+            ImageHdu::new_scaled(&[2], &[0.0, 65_535.0], Bitpix::I16, 1.0, 32_768.0).unwrap();
+        assert_eq!(round_trip(&unsigned).read().unwrap(), vec![0.0, 65_535.0]);
+    }
+
+    #[test]
+    fn test_partial_reads() {
+        let data: Vec<f64> = (0..24).map(f64::from).collect();
+        let image = ImageHdu::new(&[3, 2, 4], &data, Bitpix::F32).unwrap();
+        assert_eq!(image.plane_len(), 6);
+        assert_eq!(image.read_plane(3).unwrap(), data[18..].to_vec());
+        assert_eq!(image.read_range(4..7).unwrap(), vec![4.0, 5.0, 6.0]);
+        assert!(matches!(
+            image.read_plane(4),
+            Err(FitsError::OutOfRange { index: 4, len: 4 })
+        ));
+        assert!(matches!(
+            ImageHdu::new(&[3, 3], &data, Bitpix::F32),
+            Err(FitsError::LengthMismatch {
+                expected: 9,
+                found: 24
+            })
+        ));
+    }
+}
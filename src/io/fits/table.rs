@@ -0,0 +1,568 @@
+//! `BINTABLE` extension HDUs.
+
+use crate::errors::fits::FitsError;
+
+use super::{DataBlock, Header, with_structure};
+
+/// Data type of a binary table column, from the `TFORMn` letter code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColumnType {
+    /// `L`
+    Logical,
+    /// `X`, packed bits
+    Bit,
+    /// `B`
+    UInt8,
+    /// `I`
+    Int16,
+    /// `J`
+    Int32,
+    /// `K`
+    Int64,
+    /// `A`, with the repeat count as the string length
+    Char,
+    /// `E`
+    Float32,
+    /// `D`
+    Float64,
+    /// `C`
+    Complex64,
+    /// `M`
+    Complex128,
+    /// `P`, variable-length array descriptor
+    VarArray32,
+    /// `Q`, variable-length array descriptor
+    VarArray64,
+}
+
+impl ColumnType {
+    fn from_code(code: char) -> Option<Self> {
+        Some(match code {
+            'L' => Self::Logical,
+            'X' => Self::Bit,
+            'B' => Self::UInt8,
+            'I' => Self::Int16,
+            'J' => Self::Int32,
+            'K' => Self::Int64,
+            'A' => Self::Char,
+            'E' => Self::Float32,
+            'D' => Self::Float64,
+            'C' => Self::Complex64,
+            'M' => Self::Complex128,
+            'P' => Self::VarArray32,
+            'Q' => Self::VarArray64,
+            _ => return None,
+        })
+    }
+
+    /// The `TFORMn` letter code.
+    pub fn code(&self) -> char {
+        match self {
+            Self::Logical => 'L',
+            Self::Bit => 'X',
+            Self::UInt8 => 'B',
+            Self::Int16 => 'I',
+            Self::Int32 => 'J',
+            Self::Int64 => 'K',
+            Self::Char => 'A',
+            Self::Float32 => 'E',
+            Self::Float64 => 'D',
+            Self::Complex64 => 'C',
+            Self::Complex128 => 'M',
+            Self::VarArray32 => 'P',
+            Self::VarArray64 => 'Q',
+        }
+    }
+
+    /// Bytes taken in a row by `repeat` elements.
+    fn width(&self, repeat: usize) -> usize {
+        match self {
+            Self::Bit => repeat.div_ceil(8),
+            Self::Logical | Self::UInt8 | Self::Char => repeat,
+            Self::Int16 => 2 * repeat,
+            Self::Int32 | Self::Float32 => 4 * repeat,
+            Self::Int64 | Self::Float64 | Self::Complex64 | Self::VarArray32 => 8 * repeat,
+            Self::Complex128 | Self::VarArray64 => 16 * repeat,
+        }
+    }
+}
+
+/// Description of a binary table column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub kind: ColumnType,
+    /// Elements per row, or characters for a string column
+    pub repeat: usize,
+    pub unit: Option<String>,
+    /// `TSCALn`
+    pub scale: f64,
+    /// `TZEROn`
+    pub zero: f64,
+    /// `TNULLn`, the stored integer marking an undefined value
+    pub null: Option<i64>,
+    /// Byte offset of the column within a row
+    offset: usize,
+}
+
+impl Column {
+    /// The `TFORMn` value.
+    pub fn format(&self) -> String {
+        format!("{}{}", self.repeat, self.kind.code())
+    }
+
+    fn width(&self) -> usize {
+        self.kind.width(self.repeat)
+    }
+}
+
+/// Values of a column, flattened row by row with `repeat` elements per row. String columns
+/// hold one string per row.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnData {
+    Logical(Vec<bool>),
+    UInt8(Vec<u8>),
+    Int16(Vec<i16>),
+    Int32(Vec<i32>),
+    Int64(Vec<i64>),
+    Float32(Vec<f32>),
+    Float64(Vec<f64>),
+    Text(Vec<String>),
+}
+
+impl ColumnData {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Logical(v) => v.len(),
+            Self::UInt8(v) => v.len(),
+            Self::Int16(v) => v.len(),
+            Self::Int32(v) => v.len(),
+            Self::Int64(v) => v.len(),
+            Self::Float32(v) => v.len(),
+            Self::Float64(v) => v.len(),
+            Self::Text(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn kind(&self) -> ColumnType {
+        match self {
+            Self::Logical(_) => ColumnType::Logical,
+            Self::UInt8(_) => ColumnType::UInt8,
+            Self::Int16(_) => ColumnType::Int16,
+            Self::Int32(_) => ColumnType::Int32,
+            Self::Int64(_) => ColumnType::Int64,
+            Self::Float32(_) => ColumnType::Float32,
+            Self::Float64(_) => ColumnType::Float64,
+            Self::Text(_) => ColumnType::Char,
+        }
+    }
+
+    /// Big-endian bytes of row `row`, `repeat` elements wide.
+    fn encode_row(&self, row: usize, repeat: usize, out: &mut Vec<u8>) {
+        let range = row * repeat..(row + 1) * repeat;
+        match self {
+            Self::Logical(v) => out.extend(v[range].iter().map(|&b| if b { b'T' } else { b'F' })),
+            Self::UInt8(v) => out.extend_from_slice(&v[range]),
+            Self::Int16(v) => v[range].iter().for_each(|x| out.extend(x.to_be_bytes())),
+            Self::Int32(v) => v[range].iter().for_each(|x| out.extend(x.to_be_bytes())),
+            Self::Int64(v) => v[range].iter().for_each(|x| out.extend(x.to_be_bytes())),
+            Self::Float32(v) => v[range].iter().for_each(|x| out.extend(x.to_be_bytes())),
+            Self::Float64(v) => v[range].iter().for_each(|x| out.extend(x.to_be_bytes())),
+            Self::Text(v) => {
+                let mut bytes = v[row].as_bytes().to_vec();
+                bytes.resize(repeat, b' ');
+                out.extend(bytes);
+            }
+        }
+    }
+}
+
+/// A binary table extension.
+#[derive(Debug, Clone)]
+pub struct BinTableHdu {
+    header: Header,
+    columns: Vec<Column>,
+    nrows: usize,
+    row_len: usize,
+    data: DataBlock,
+}
+
+impl BinTableHdu {
+    /// An empty table with `nrows` rows, to be filled with [`Self::with_column`].
+    pub fn new(nrows: usize) -> Self {
+        let mut header = Header::new();
+        header.set("TFIELDS", 0);
+        Self {
+            header,
+            columns: Vec::new(),
+            nrows,
+            row_len: 0,
+            data: DataBlock::owned(Vec::new()),
+        }
+    }
+
+    /// Append a column. The repeat count is the number of values per row; string columns
+    /// are as wide as their longest value.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::LengthMismatch`] if the values do not divide evenly into rows.
+    pub fn with_column(
+        mut self,
+        name: &str,
+        unit: Option<&str>,
+        data: ColumnData,
+    ) -> Result<Self, FitsError> {
+        let repeat = match &data {
+            ColumnData::Text(v) => {
+                if v.len() != self.nrows {
+                    return Err(FitsError::LengthMismatch {
+                        expected: self.nrows,
+                        found: v.len(),
+                    });
+                }
+                v.iter().map(String::len).max().unwrap_or(0).max(1)
+            }
+            _ if self.nrows == 0 => 1,
+            _ => {
+                if !data.len().is_multiple_of(self.nrows) || data.is_empty() {
+                    return Err(FitsError::LengthMismatch {
+                        expected: self.nrows,
+                        found: data.len(),
+                    });
+                }
+                data.len() / self.nrows
+            }
+        };
+        let column = Column {
+            name: name.to_string(),
+            kind: data.kind(),
+            repeat,
+            unit: unit.map(str::to_string),
+            scale: 1.0,
+            zero: 0.0,
+            null: None,
+            offset: self.row_len,
+        };
+
+        let old = self.data.bytes();
+        let row_len = self.row_len + column.width();
+        let mut bytes = Vec::with_capacity(row_len * self.nrows);
+        for row in 0..self.nrows {
+            bytes.extend_from_slice(&old[row * self.row_len..(row + 1) * self.row_len]);
+            data.encode_row(row, repeat, &mut bytes);
+        }
+
+        let n = self.columns.len() + 1;
+        self.header.set("TFIELDS", n);
+        self.header.set(&format!("TTYPE{n}"), name);
+        self.header.set(&format!("TFORM{n}"), column.format());
+        if let Some(unit) = unit {
+            self.header.set(&format!("TUNIT{n}"), unit);
+        }
+        self.columns.push(column);
+        self.row_len = row_len;
+        self.data = DataBlock::owned(bytes);
+        Ok(self)
+    }
+
+    pub(crate) fn from_parts(header: Header, data: DataBlock) -> Result<Self, FitsError> {
+        let row_len = header.require_i64("NAXIS1")? as usize;
+        let nrows = header.require_i64("NAXIS2")? as usize;
+        let nfields = header.require_i64("TFIELDS")?;
+        let mut columns = Vec::new();
+        let mut offset = 0;
+        for n in 1..=nfields {
+            let key = |k: &str| format!("{k}{n}");
+            let tform = header
+                .get_str(&key("TFORM"))
+                .ok_or_else(|| FitsError::MissingKeyword(key("TFORM")))?;
+            let (repeat, kind) = parse_tform(tform)?;
+            let column = Column {
+                name: header
+                    .get_str(&key("TTYPE"))
+                    .unwrap_or_default()
+                    .to_string(),
+                kind,
+                repeat,
+                unit: header.get_str(&key("TUNIT")).map(str::to_string),
+                scale: header.get_f64(&key("TSCAL")).unwrap_or(1.0),
+                zero: header.get_f64(&key("TZERO")).unwrap_or(0.0),
+                null: header.get_i64(&key("TNULL")),
+                offset,
+            };
+            offset += column.width();
+            columns.push(column);
+        }
+        if offset != row_len {
+            return Err(FitsError::InvalidKeyword("NAXIS1".to_string()));
+        }
+        Ok(Self {
+            header,
+            columns,
+            nrows,
+            row_len,
+            data,
+        })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+
+    pub fn nrows(&self) -> usize {
+        self.nrows
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// The column called `name`, compared case-insensitively.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::NoSuchColumn`] if there is none.
+    pub fn column(&self, name: &str) -> Result<&Column, FitsError> {
+        self.columns
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| FitsError::NoSuchColumn(name.to_string()))
+    }
+
+    /// Stored values of a column, without `TSCALn`/`TZEROn` scaling. Bit columns are
+    /// unpacked into logicals.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::NoSuchColumn`] for an unknown column and
+    /// [`FitsError::UnsupportedFormat`] for complex and variable-length columns.
+    pub fn read_column(&self, name: &str) -> Result<ColumnData, FitsError> {
+        let column = self.column(name)?;
+        let cells = self.cells(column);
+        let elements = cells.clone().flat_map(|cell| cell.iter().copied());
+        macro_rules! numeric {
+            ($variant:ident, $t:ty) => {{
+                let size = std::mem::size_of::<$t>();
+                ColumnData::$variant(
+                    cells
+                        .flat_map(|cell| cell.chunks_exact(size))
+                        .map(|b| <$t>::from_be_bytes(b.try_into().unwrap()))
+                        .collect(),
+                )
+            }};
+        }
+        Ok(match column.kind {
+            ColumnType::Logical => ColumnData::Logical(elements.map(|b| b == b'T').collect()),
+            ColumnType::Bit => ColumnData::Logical(
+                cells
+                    .flat_map(|cell| {
+                        (0..column.repeat).map(move |i| cell[i / 8] >> (7 - i % 8) & 1 == 1)
+                    })
+                    .collect(),
+            ),
+            ColumnType::UInt8 => ColumnData::UInt8(elements.collect()),
+            ColumnType::Int16 => numeric!(Int16, i16),
+            ColumnType::Int32 => numeric!(Int32, i32),
+            ColumnType::Int64 => numeric!(Int64, i64),
+            ColumnType::Float32 => numeric!(Float32, f32),
+            ColumnType::Float64 => numeric!(Float64, f64),
+            ColumnType::Char => ColumnData::Text(
+                cells
+                    .map(|cell| {
+                        let end = cell.iter().position(|&b| b == 0).unwrap_or(cell.len());
+                        String::from_utf8_lossy(&cell[..end]).trim_end().to_string()
+                    })
+                    .collect(),
+            ),
+            _ => return Err(FitsError::UnsupportedFormat(column.format())),
+        })
+    }
+
+    /// Physical values of a numeric column, `TZEROn + TSCALn × stored`, with `TNULLn`
+    /// integers read as NaN.
+    ///
+    /// # Errors
+    /// Returns [`FitsError::NoSuchColumn`] for an unknown column and
+    /// [`FitsError::NotNumeric`] for logical and string columns.
+    pub fn read_column_f64(&self, name: &str) -> Result<Vec<f64>, FitsError> {
+        let column = self.column(name)?;
+        let (scale, zero, null) = (column.scale, column.zero, column.null);
+        let integer = |v: i64| {
+            if Some(v) == null {
+                f64::NAN
+            } else {
+                zero + scale * v as f64
+            }
+        };
+        Ok(match self.read_column(name)? {
+            ColumnData::UInt8(v) => v.into_iter().map(|x| integer(x.into())).collect(),
+            ColumnData::Int16(v) => v.into_iter().map(|x| integer(x.into())).collect(),
+            ColumnData::Int32(v) => v.into_iter().map(|x| integer(x.into())).collect(),
+            ColumnData::Int64(v) => v.into_iter().map(integer).collect(),
+            ColumnData::Float32(v) => v.into_iter().map(|x| zero + scale * f64::from(x)).collect(),
+            ColumnData::Float64(v) => v.into_iter().map(|x| zero + scale * x).collect(),
+            ColumnData::Logical(_) | ColumnData::Text(_) => {
+                return Err(FitsError::NotNumeric(name.to_string()));
+            }
+        })
+    }
+
+    /// The bytes of `column` in each row.
+    fn cells<'a>(&'a self, column: &Column) -> impl Iterator<Item = &'a [u8]> + Clone {
+        let bytes = self.data.bytes();
+        let (offset, width, row_len) = (column.offset, column.width(), self.row_len);
+        (0..self.nrows)
+            .map(move |row| &bytes[row * row_len + offset..row * row_len + offset + width])
+    }
+
+    pub(crate) fn data_bytes(&self) -> &[u8] {
+        self.data.bytes()
+    }
+
+    /// The header to write: structural keywords from the data, then the other cards.
+    pub(crate) fn write_header(&self) -> Header {
+        let mut structural = Header::new();
+        structural.set_with_comment("XTENSION", "BINTABLE", "binary table extension");
+        structural.set("BITPIX", 8);
+        structural.set("NAXIS", 2);
+        structural.set_with_comment("NAXIS1", self.row_len, "width of table in bytes");
+        structural.set_with_comment("NAXIS2", self.nrows, "number of rows in table");
+        structural.set(
+            "PCOUNT",
+            self.data.bytes().len() - self.row_len * self.nrows,
+        );
+        structural.set("GCOUNT", 1);
+        structural.set_with_comment("TFIELDS", self.columns.len(), "number of table fields");
+        with_structure(structural, &self.header)
+    }
+}
+
+/// Parse a `TFORMn` value into its repeat count and type.
+fn parse_tform(tform: &str) -> Result<(usize, ColumnType), FitsError> {
+    let unsupported = || FitsError::UnsupportedFormat(tform.to_string());
+    let tform = tform.trim();
+    let digits = tform
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(unsupported)?;
+    let repeat = if digits == 0 {
+        1
+    } else {
+        tform[..digits].parse().map_err(|_| unsupported())?
+    };
+    let code = tform[digits..].chars().next().ok_or_else(unsupported)?;
+    let kind = ColumnType::from_code(code.to_ascii_uppercase()).ok_or_else(unsupported)?;
+    Ok((repeat, kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(table: &BinTableHdu) -> BinTableHdu {
+        let mut bytes = table.write_header().to_bytes();
+        bytes.extend_from_slice(table.data_bytes());
+        let (header, len) = Header::parse(&bytes).unwrap();
+        BinTableHdu::from_parts(header, DataBlock::owned(bytes[len..].to_vec())).unwrap()
+    }
+
+    #[test]
+    fn test_columns_round_trip() {
+        let table = BinTableHdu::new(3)
+            .with_column("FLAG", None, ColumnData::Logical(vec![true, false, true]))
+            .unwrap()
+            .with_column(
+                "SPECIES",
+                None,
+                ColumnData::Text(vec!["CO".into(), "HCO+".into(), "".into()]),
+            )
+            .unwrap()
+            .with_column("CHAN", None, ColumnData::Int16(vec![1, 2, 3]))
+            .unwrap()
+            .with_column("ID", None, ColumnData::Int64(vec![10, 20, 30]))
+            .unwrap()
+            .with_column(
+                "SPEC",
+                Some("K"),
+                ColumnData::Float32(vec![0.5, 1.0, 1.5, 2.0, 2.5, 3.0]),
+            )
+            .unwrap()
+            .with_column(
+                "FREQ",
+                Some("Hz"),
+                ColumnData::Float64(vec![1e11, 2e11, 3e11]),
+            )
+            .unwrap();
+        let read = round_trip(&table);
+        assert_eq!(read.nrows(), 3);
+        assert_eq!(read.column("spec").unwrap().format(), "2E");
+        assert_eq!(read.column("SPECIES").unwrap().format(), "4A");
+        assert_eq!(read.column("FREQ").unwrap().unit.as_deref(), Some("Hz"));
+        for name in ["FLAG", "SPECIES", "CHAN", "ID", "SPEC", "FREQ"] {
+            assert_eq!(
+                read.read_column(name).unwrap(),
+                table.read_column(name).unwrap()
+            );
+        }
+        assert_eq!(
+            read.read_column("SPECIES").unwrap(),
+            ColumnData::Text(vec!["CO".into(), "HCO+".into(), "".into()])
+        );
+        assert_eq!(read.read_column_f64("CHAN").unwrap(), vec![1.0, 2.0, 3.0]);
+        assert!(matches!(
+            read.read_column_f64("FLAG"),
+            Err(FitsError::NotNumeric(_))
+        ));
+        assert!(matches!(
+            read.read_column("NOPE"),
+            Err(FitsError::NoSuchColumn(_))
+        ));
+        assert!(matches!(
+            BinTableHdu::new(3).with_column("X", None, ColumnData::Int32(vec![1, 2])),
+            Err(FitsError::LengthMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_scaling_null_and_bits() {
+        let mut table = BinTableHdu::new(3)
+            .with_column("RAW", None, ColumnData::Int16(vec![0, 10, -1]))
+            .unwrap()
+            .with_column(
+                "BITS",
+                None,
+                ColumnData::UInt8(vec![0b1010_0000, 0b0100_0000, 0]),
+            )
+            .unwrap();
+        table.header_mut().set("TSCAL1", 0.5);
+        table.header_mut().set("TZERO1", 100.0);
+        table.header_mut().set("TNULL1", -1);
+        table.header_mut().set("TFORM2", "3X");
+        let read = round_trip(&table);
+        let values = read.read_column_f64("RAW").unwrap();
+        assert_eq!(values[..2], [100.0, 105.0]);
+        assert!(values[2].is_nan());
+        assert_eq!(
+            read.read_column("BITS").unwrap(),
+            ColumnData::Logical(vec![
+                true, false, true, false, true, false, false, false, false
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_tform() {
+        assert_eq!(parse_tform("D").unwrap(), (1, ColumnType::Float64));
+        assert_eq!(parse_tform("16A").unwrap(), (16, ColumnType::Char));
+        assert_eq!(parse_tform("1PE(20)").unwrap(), (1, ColumnType::VarArray32));
+        assert!(matches!(
+            parse_tform("3Z"),
+            Err(FitsError::UnsupportedFormat(_))
+        ));
+    }
+}
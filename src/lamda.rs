@@ -6,8 +6,12 @@ use std::io::BufRead;
 use std::io::BufReader;
 use std::path::Path;
 
+use crate::catalog::{Line, LineCatalog, Transition};
 use crate::errors::database::LAMDAError;
 use crate::io::skip_line;
+use crate::units::f64::{Frequency, Temperature};
+use crate::units::frequency::gigahertz;
+use crate::units::thermodynamic_temperature::kelvin;
 
 #[derive(Debug, Clone)]
 pub struct Level {
@@ -19,6 +23,8 @@ pub struct Level {
     pub weight: f64,
     /// Total angular momentum quantum number of the level.
     pub j: usize,
+    /// Quantum number label of the level as written in the file, e.g. `1` or `1_1_0`.
+    pub label: String,
 }
 
 #[derive(Debug, Clone)]
@@ -126,13 +132,19 @@ impl LAMDAData {
                 .next()
                 .ok_or_else(|| LAMDAError::ParseError("Missing level weight".into()))?
                 .parse()?;
-            let j = fields.next().map_or(0, |s| s.parse().unwrap_or(0));
+            let label = fields.collect::<Vec<_>>().join(" ");
+            // Labels of asymmetric tops are `J_Ka_Kc`, so J is the leading number.
+            let j = label
+                .split(['_', ' '])
+                .next()
+                .map_or(0, |s| s.parse().unwrap_or(0));
 
             levels.push(Level {
                 id,
                 energy,
                 weight,
                 j,
+                label,
             });
         }
 
@@ -322,4 +334,69 @@ impl LAMDAData {
         let reader = BufReader::new(file);
         Self::from_reader(reader)
     }
+
+    /// The radiative transitions, with the name of the molecule and their levels.
+    pub fn transitions(&self) -> impl Iterator<Item = LamdaTransition<'_>> {
+        self.radset.iter().map(|transition| LamdaTransition {
+            molecule: self,
+            transition,
+        })
+    }
+
+    fn level(&self, id: usize) -> Option<&Level> {
+        self.levels.iter().find(|level| level.id == id)
+    }
+}
+
+impl LineCatalog for LAMDAData {
+    fn lines(&self) -> Vec<Line> {
+        self.transitions()
+            .map(|t| Line::from_transition(&t))
+            .collect()
+    }
+}
+
+/// A radiative transition of a LAMDA molecule, with the molecule it belongs to.
+#[derive(Debug, Clone, Copy)]
+pub struct LamdaTransition<'a> {
+    pub molecule: &'a LAMDAData,
+    pub transition: &'a RadTransition,
+}
+
+impl Transition for LamdaTransition<'_> {
+    fn species(&self) -> &str {
+        &self.molecule.name
+    }
+
+    fn rest_frequency(&self) -> Frequency {
+        Frequency::new::<gigahertz>(self.transition.freq)
+    }
+
+    fn upper_energy(&self) -> Option<Temperature> {
+        Some(Temperature::new::<kelvin>(self.transition.energy))
+    }
+
+    fn upper_degeneracy(&self) -> Option<f64> {
+        self.molecule
+            .level(self.transition.up)
+            .map(|level| level.weight)
+    }
+
+    fn einstein_a(&self) -> Option<f64> {
+        Some(self.transition.einst_a)
+    }
+
+    /// `<up>-<low>` from [`Level::label`] of the two levels, such as `1_1_0-1_0_1`, or
+    /// from the level IDs if a level is missing or unlabelled.
+    fn quantum_numbers(&self) -> String {
+        match (
+            self.molecule.level(self.transition.up),
+            self.molecule.level(self.transition.low),
+        ) {
+            (Some(up), Some(low)) if !up.label.is_empty() && !low.label.is_empty() => {
+                format!("{}-{}", up.label, low.label)
+            }
+            _ => format!("{}-{}", self.transition.up, self.transition.low),
+        }
+    }
 }
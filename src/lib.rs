@@ -13,12 +13,24 @@
 //! * `spectral_cube` - Likely full-fledged implementation.
 //! * `radio_beam` - Likely full-fledged implementation.
 
-// pub mod beam;
+pub mod analysis;
+pub mod beam;
+pub mod catalog;
 pub mod cdms;
 pub mod constants;
+pub mod coordinates;
+pub mod cube;
 pub mod errors;
+pub mod fitting;
 pub mod hitran;
 pub mod io;
 pub mod jpl;
 pub mod lamda;
-// pub mod utils;
+pub mod manipulation;
+pub mod radiation;
+pub mod spectrum;
+pub mod stats;
+pub mod time;
+pub mod units;
+pub mod utils;
+pub mod wcs;
@@ -0,0 +1,4 @@
+//! Operations that transform spectra, modelled on `specutils.manipulation`.
+
+pub mod resample;
+pub mod smoothing;
@@ -0,0 +1,569 @@
+//! Resampling spectra onto a new spectral axis.
+//!
+//! The three resamplers mirror `specutils`' `FluxConservingResampler`,
+//! `LinearInterpolatedResampler` and `SplineInterpolatedResampler`. Each works on plain slices
+//! through [`Resampler::resample_values`] and on spectra through [`Resampler::resample`].
+//! Input channels may be in increasing or decreasing order, and so may the output grid.
+//! Input channels at the same spectral coordinate are averaged into one. A single input
+//! channel is taken as constant, so it fills every output channel with
+//! [`OutOfRange::Extrapolate`]; without input channels every output channel is masked.
+//!
+//! ```
+//! use spectre::manipulation::resample::{LinearInterpolatedResampler, OutOfRange, Resampler};
+//!
+//! let resampler = LinearInterpolatedResampler::new(OutOfRange::Nan);
+//! let y = resampler.resample_slice(&[1.0, 2.0, 3.0], &[10.0, 20.0, 30.0], &[1.5, 4.0]);
+//! assert_eq!(y[0], 15.0);
+//! assert!(y[1].is_nan());
+//! ```
+
+use crate::errors::spectrum::SpectrumError;
+use crate::spectrum::{SpectralAxis, Spectrum1D, bin_edges};
+
+/// Treatment of output channels that fall outside the input spectral range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutOfRange {
+    /// Fill with NaN and mask the channel.
+    #[default]
+    Nan,
+    /// Fill with zero and mask the channel.
+    Zero,
+    /// Extrapolate from the nearest input channels.
+    Extrapolate,
+}
+
+/// Result of resampling plain arrays.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resampled {
+    pub flux: Vec<f64>,
+    /// Variance of the resampled flux, if an input variance was given
+    pub variance: Option<Vec<f64>>,
+    pub mask: Vec<bool>,
+}
+
+/// A scheme for resampling a spectrum onto a new spectral grid.
+pub trait Resampler {
+    /// Resample `y(x)` onto `new_x`.
+    ///
+    /// `variance` and `mask` are per input channel; masked input channels mask every output
+    /// channel they contribute to.
+    fn resample_values(
+        &self,
+        x: &[f64],
+        y: &[f64],
+        variance: Option<&[f64]>,
+        mask: Option<&[bool]>,
+        new_x: &[f64],
+    ) -> Resampled;
+
+    /// Resample `y(x)` onto `new_x`, ignoring uncertainties and masks.
+    fn resample_slice(&self, x: &[f64], y: &[f64], new_x: &[f64]) -> Vec<f64> {
+        self.resample_values(x, y, None, None, new_x).flux
+    }
+
+    /// Resample a spectrum onto `new_axis`.
+    ///
+    /// The new axis may be of a different spectral kind; it is converted to the kind of the
+    /// input spectrum using the input's rest frequency and convention. The result uses
+    /// `new_axis` as its spectral axis.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::MissingRestValue`] if the new axis cannot be converted to the
+    /// spectral kind of the input.
+    fn resample(
+        &self,
+        spectrum: &Spectrum1D,
+        new_axis: &SpectralAxis,
+    ) -> Result<Spectrum1D, SpectrumError> {
+        let axis = &spectrum.spectral_axis;
+        let new_x = (0..new_axis.len())
+            .map(|i| axis.convert_value(new_axis.get(i).unwrap()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let variance = spectrum.uncertainty.as_ref().map(|u| u.variance());
+        let result = self.resample_values(
+            axis.values(),
+            &spectrum.flux,
+            variance.as_deref(),
+            Some(&spectrum.mask),
+            &new_x,
+        );
+        let mut resampled = Spectrum1D::new(new_axis.clone(), result.flux, &spectrum.unit)?
+            .with_mask(result.mask)?;
+        if let (Some(u), Some(v)) = (&spectrum.uncertainty, result.variance) {
+            resampled = resampled.with_uncertainty(u.with_variance(v))?;
+        }
+        resampled.meta.clone_from(&spectrum.meta);
+        Ok(resampled)
+    }
+}
+
+/// Input arrays sorted by strictly increasing spectral coordinate, with channels at the same
+/// coordinate averaged.
+struct Sorted {
+    x: Vec<f64>,
+    y: Vec<f64>,
+    variance: Option<Vec<f64>>,
+    mask: Vec<bool>,
+}
+
+impl Sorted {
+    fn new(x: &[f64], y: &[f64], variance: Option<&[f64]>, mask: Option<&[bool]>) -> Self {
+        let mut order: Vec<usize> = (0..x.len()).collect();
+        order.sort_by(|&a, &b| x[a].total_cmp(&x[b]));
+        let mut sorted = Self {
+            x: Vec::with_capacity(x.len()),
+            y: Vec::with_capacity(x.len()),
+            variance: variance.map(|_| Vec::with_capacity(x.len())),
+            mask: Vec::with_capacity(x.len()),
+        };
+        for run in order.chunk_by(|&a, &b| x[a] == x[b]) {
+            let n = run.len() as f64;
+            sorted.x.push(x[run[0]]);
+            sorted.y.push(run.iter().map(|&i| y[i]).sum::<f64>() / n);
+            if let (Some(out), Some(v)) = (sorted.variance.as_mut(), variance) {
+                out.push(run.iter().map(|&i| v[i]).sum::<f64>() / (n * n));
+            }
+            sorted
+                .mask
+                .push(run.iter().any(|&i| mask.is_some_and(|m| m[i])));
+        }
+        sorted
+    }
+
+    /// Set output channel `k` to the only input channel.
+    fn constant(&self, result: &mut Resampled, k: usize) {
+        result.flux[k] = self.y[0];
+        if let (Some(v), Some(iv)) = (result.variance.as_mut(), &self.variance) {
+            v[k] = iv[0];
+        }
+        result.mask[k] = self.mask[0];
+    }
+
+    /// Index `i` of the interval `[x[i], x[i + 1]]` used to interpolate at `x0`.
+    fn interval(&self, x0: f64) -> usize {
+        let n = self.x.len();
+        self.x.partition_point(|&v| v <= x0).clamp(1, n - 1) - 1
+    }
+
+    fn in_range(&self, x0: f64) -> bool {
+        (self.x[0]..=self.x[self.x.len() - 1]).contains(&x0)
+    }
+
+    /// Interpolation of the variance with linear weights between `x[i]` and `x[i + 1]`.
+    fn linear_variance(&self, i: usize, t: f64) -> Option<f64> {
+        self.variance
+            .as_ref()
+            .map(|v| (1.0 - t).powi(2) * v[i] + t * t * v[i + 1])
+    }
+}
+
+fn fill(out_of_range: OutOfRange, result: &mut Resampled, index: usize) {
+    let value = match out_of_range {
+        OutOfRange::Zero => 0.0,
+        OutOfRange::Nan | OutOfRange::Extrapolate => f64::NAN,
+    };
+    result.flux[index] = value;
+    if let Some(v) = result.variance.as_mut() {
+        v[index] = value;
+    }
+    result.mask[index] = true;
+}
+
+fn empty_result(n: usize, with_variance: bool) -> Resampled {
+    Resampled {
+        flux: vec![f64::NAN; n],
+        variance: with_variance.then(|| vec![f64::NAN; n]),
+        mask: vec![true; n],
+    }
+}
+
+/// Flux-conserving resampling.
+///
+/// Each output channel is the mean of the input channels it overlaps, weighted by the width
+/// of the overlap, so the integrated flux is preserved. Masked input channels are excluded
+/// from the mean and mask the output channel. A lone output channel has no width and takes
+/// the value of the input channel it falls in, or the mean of the two sharing its edge.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FluxConservingResampler {
+    pub out_of_range: OutOfRange,
+}
+
+impl FluxConservingResampler {
+    pub fn new(out_of_range: OutOfRange) -> Self {
+        Self { out_of_range }
+    }
+}
+
+impl Resampler for FluxConservingResampler {
+    fn resample_values(
+        &self,
+        x: &[f64],
+        y: &[f64],
+        variance: Option<&[f64]>,
+        mask: Option<&[bool]>,
+        new_x: &[f64],
+    ) -> Resampled {
+        let mut result = empty_result(new_x.len(), variance.is_some());
+        if x.is_empty() {
+            return result;
+        }
+        let input = Sorted::new(x, y, variance, mask);
+        let edges = bin_edges(&input.x);
+        let (first, last) = (edges[0], edges[edges.len() - 1]);
+        let new_edges = bin_edges(new_x);
+
+        // Copy input channel `j` into output channel `k`.
+        let take = |result: &mut Resampled, k: usize, j: usize| {
+            result.flux[k] = input.y[j];
+            if let (Some(v), Some(iv)) = (result.variance.as_mut(), &input.variance) {
+                v[k] = iv[j];
+            }
+            result.mask[k] = input.mask[j];
+        };
+
+        for (k, w) in new_edges.windows(2).enumerate() {
+            let (lo, hi) = (w[0].min(w[1]), w[0].max(w[1]));
+            let tol = 1e-9 * (hi - lo);
+            let covered = lo >= first - tol && hi <= last + tol;
+            if !covered && self.out_of_range != OutOfRange::Extrapolate {
+                fill(self.out_of_range, &mut result, k);
+                continue;
+            }
+            // A single output channel has no width. Averaging over a vanishing bin around it
+            // takes the input channel holding it, or both channels on a shared edge.
+            let (lo, hi) = if lo == hi {
+                let half = 1e-9 * (last - first);
+                (lo - half, hi + half)
+            } else {
+                (lo, hi)
+            };
+
+            let (mut sum_w, mut sum_wy, mut sum_w2v, mut masked) = (0.0, 0.0, 0.0, false);
+            let start = edges.partition_point(|&e| e <= lo).saturating_sub(1);
+            for j in start..input.x.len() {
+                if edges[j] >= hi {
+                    break;
+                }
+                let overlap = hi.min(edges[j + 1]) - lo.max(edges[j]);
+                if overlap <= 0.0 {
+                    continue;
+                }
+                if input.mask[j] {
+                    masked = true;
+                    continue;
+                }
+                sum_w += overlap;
+                sum_wy += overlap * input.y[j];
+                if let Some(v) = &input.variance {
+                    sum_w2v += overlap * overlap * v[j];
+                }
+            }
+
+            if sum_w > 0.0 {
+                result.flux[k] = sum_wy / sum_w;
+                if let Some(v) = result.variance.as_mut() {
+                    v[k] = sum_w2v / (sum_w * sum_w);
+                }
+                result.mask[k] = masked;
+            } else if !masked {
+                // Entirely outside the input range: use the nearest input channel.
+                let j = if hi <= first { 0 } else { input.x.len() - 1 };
+                take(&mut result, k, j);
+            }
+        }
+        result
+    }
+}
+
+/// Linear interpolation between the two input channels bracketing each output channel.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LinearInterpolatedResampler {
+    pub out_of_range: OutOfRange,
+}
+
+impl LinearInterpolatedResampler {
+    pub fn new(out_of_range: OutOfRange) -> Self {
+        Self { out_of_range }
+    }
+}
+
+impl Resampler for LinearInterpolatedResampler {
+    fn resample_values(
+        &self,
+        x: &[f64],
+        y: &[f64],
+        variance: Option<&[f64]>,
+        mask: Option<&[bool]>,
+        new_x: &[f64],
+    ) -> Resampled {
+        let mut result = empty_result(new_x.len(), variance.is_some());
+        if x.is_empty() {
+            return result;
+        }
+        let input = Sorted::new(x, y, variance, mask);
+        for (k, &x0) in new_x.iter().enumerate() {
+            if !input.in_range(x0) && self.out_of_range != OutOfRange::Extrapolate {
+                fill(self.out_of_range, &mut result, k);
+                continue;
+            }
+            if input.x.len() == 1 {
+                input.constant(&mut result, k);
+                continue;
+            }
+            let i = input.interval(x0);
+            let t = (x0 - input.x[i]) / (input.x[i + 1] - input.x[i]);
+            result.flux[k] = (1.0 - t) * input.y[i] + t * input.y[i + 1];
+            if let Some(v) = result.variance.as_mut() {
+                v[k] = input.linear_variance(i, t).unwrap();
+            }
+            result.mask[k] = input.mask[i] || input.mask[i + 1];
+        }
+        result
+    }
+}
+
+/// Natural cubic spline interpolation.
+///
+/// The flux uncertainty is propagated with the weights of linear interpolation between the
+/// two bracketing input channels, which ignores the (small) contribution of more distant
+/// channels to the spline.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SplineInterpolatedResampler {
+    pub out_of_range: OutOfRange,
+}
+
+impl SplineInterpolatedResampler {
+    pub fn new(out_of_range: OutOfRange) -> Self {
+        Self { out_of_range }
+    }
+}
+
+impl Resampler for SplineInterpolatedResampler {
+    fn resample_values(
+        &self,
+        x: &[f64],
+        y: &[f64],
+        variance: Option<&[f64]>,
+        mask: Option<&[bool]>,
+        new_x: &[f64],
+    ) -> Resampled {
+        let mut result = empty_result(new_x.len(), variance.is_some());
+        if x.is_empty() {
+            return result;
+        }
+        let input = Sorted::new(x, y, variance, mask);
+        let spline = CubicSpline::natural(&input.x, &input.y);
+        for (k, &x0) in new_x.iter().enumerate() {
+            if !input.in_range(x0) && self.out_of_range != OutOfRange::Extrapolate {
+                fill(self.out_of_range, &mut result, k);
+                continue;
+            }
+            if input.x.len() == 1 {
+                input.constant(&mut result, k);
+                continue;
+            }
+            let i = input.interval(x0);
+            let t = (x0 - input.x[i]) / (input.x[i + 1] - input.x[i]);
+            result.flux[k] = spline.eval_in(i, x0);
+            if let Some(v) = result.variance.as_mut() {
+                v[k] = input.linear_variance(i, t).unwrap();
+            }
+            result.mask[k] = input.mask[i] || input.mask[i + 1];
+        }
+        result
+    }
+}
+
+/// Natural cubic spline through points with strictly increasing `x`.
+#[derive(Debug, Clone)]
+struct CubicSpline {
+    x: Vec<f64>,
+    y: Vec<f64>,
+    /// Second derivatives at the knots
+    m: Vec<f64>,
+}
+
+impl CubicSpline {
+    fn natural(x: &[f64], y: &[f64]) -> Self {
+        let n = x.len();
+        let mut m = vec![0.0; n];
+        if n > 2 {
+            // Thomas algorithm for the tridiagonal system in the interior second derivatives.
+            let mut c_prime = vec![0.0; n];
+            let mut d_prime = vec![0.0; n];
+            for i in 1..n - 1 {
+                let h0 = x[i] - x[i - 1];
+                let h1 = x[i + 1] - x[i];
+                let a = h0 / 6.0;
+                let b = (h0 + h1) / 3.0;
+                let c = h1 / 6.0;
+                let d = (y[i + 1] - y[i]) / h1 - (y[i] - y[i - 1]) / h0;
+                let denom = b - a * c_prime[i - 1];
+                c_prime[i] = c / denom;
+                d_prime[i] = (d - a * d_prime[i - 1]) / denom;
+            }
+            for i in (1..n - 1).rev() {
+                m[i] = d_prime[i] - c_prime[i] * m[i + 1];
+            }
+        }
+        Self {
+            x: x.to_vec(),
+            y: y.to_vec(),
+            m,
+        }
+    }
+
+    /// Evaluate the cubic of interval `[x[i], x[i + 1]]` at `x0`.
+    fn eval_in(&self, i: usize, x0: f64) -> f64 {
+        let h = self.x[i + 1] - self.x[i];
+        let a = (self.x[i + 1] - x0) / h;
+        let b = (x0 - self.x[i]) / h;
+        a * self.y[i]
+            + b * self.y[i + 1]
+            + ((a.powi(3) - a) * self.m[i] + (b.powi(3) - b) * self.m[i + 1]) * h * h / 6.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectrum::{SpectralKind, Uncertainty};
+    use approx::assert_relative_eq;
+
+    fn spectrum(x: Vec<f64>, y: Vec<f64>) -> Spectrum1D {
+        Spectrum1D::new(SpectralAxis::from_si(SpectralKind::Frequency, x), y, "Jy").unwrap()
+    }
+
+    #[test]
+    fn test_flux_conserving_preserves_integral() {
+        let x: Vec<f64> = (0..20).map(f64::from).collect();
+        let y: Vec<f64> = x.iter().map(|v| (v * 0.7).sin() + 2.0).collect();
+        let new_x: Vec<f64> = (0..10).map(|i| 0.5 + 2.0 * f64::from(i)).collect();
+        let resampler = FluxConservingResampler::new(OutOfRange::Nan);
+        let out = resampler.resample_slice(&x, &y, &new_x);
+        let total_in: f64 = y.iter().sum();
+        let total_out: f64 = out.iter().map(|v| 2.0 * v).sum();
+        assert_relative_eq!(total_in, total_out, max_relative = 1e-12);
+        assert_relative_eq!(out[0], 0.5 * (y[0] + y[1]), max_relative = 1e-12);
+    }
+
+    #[test]
+    fn test_flux_conserving_uncertainty_and_mask() {
+        let x: Vec<f64> = (0..4).map(f64::from).collect();
+        let y = vec![1.0, 3.0, 5.0, 7.0];
+        let var = vec![1.0; 4];
+        let mask = vec![false, false, true, false];
+        let new_x = vec![0.5, 2.5];
+        let out = FluxConservingResampler::default().resample_values(
+            &x,
+            &y,
+            Some(&var),
+            Some(&mask),
+            &new_x,
+        );
+        assert_eq!(out.flux[0], 2.0);
+        // Averaging two channels halves the variance.
+        assert_eq!(out.variance.as_ref().unwrap()[0], 0.5);
+        assert!(!out.mask[0]);
+        assert_eq!(out.flux[1], 7.0);
+        assert!(out.mask[1]);
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let x = vec![1.0, 2.0, 3.0];
+        let y = vec![1.0, 2.0, 3.0];
+        let new_x = vec![2.0, 5.0];
+
+        let nan = LinearInterpolatedResampler::new(OutOfRange::Nan)
+            .resample_values(&x, &y, None, None, &new_x);
+        assert!(nan.flux[1].is_nan() && nan.mask[1]);
+        let zero =
+            LinearInterpolatedResampler::new(OutOfRange::Zero).resample_slice(&x, &y, &new_x);
+        assert_eq!(zero, vec![2.0, 0.0]);
+        let extrap = LinearInterpolatedResampler::new(OutOfRange::Extrapolate)
+            .resample_slice(&x, &y, &new_x);
+        assert_eq!(extrap, vec![2.0, 5.0]);
+
+        let flux = FluxConservingResampler::new(OutOfRange::Zero).resample_slice(&x, &y, &new_x);
+        assert_eq!(flux, vec![2.0, 0.0]);
+    }
+
+    #[test]
+    fn test_degenerate_inputs() {
+        let extrapolate: [&dyn Resampler; 3] = [
+            &FluxConservingResampler::new(OutOfRange::Extrapolate),
+            &LinearInterpolatedResampler::new(OutOfRange::Extrapolate),
+            &SplineInterpolatedResampler::new(OutOfRange::Extrapolate),
+        ];
+        for resampler in extrapolate {
+            // A single channel is constant.
+            let out = resampler.resample_values(&[2.0], &[7.0], Some(&[1.0]), None, &[1.0, 3.0]);
+            assert_eq!(out.flux, vec![7.0, 7.0]);
+            assert_eq!(out.variance, Some(vec![1.0, 1.0]));
+            assert_eq!(out.mask, vec![false, false]);
+
+            // Channels at the same coordinate are averaged.
+            let (x, y) = ([1.0, 2.0, 2.0, 3.0], [1.0, 1.0, 3.0, 3.0]);
+            let out = resampler.resample_slice(&x, &y, &[1.5, 2.0, 2.5]);
+            assert_eq!(out[1], 2.0);
+
+            let out = resampler.resample_values(&[], &[], None, None, &[1.0]);
+            assert!(out.flux[0].is_nan() && out.mask[0]);
+
+            // A single output channel inside the input range.
+            let (x, y) = ([0.0, 1.0, 2.0, 3.0], [0.0, 10.0, 20.0, 30.0]);
+            assert_relative_eq!(resampler.resample_slice(&x, &y, &[1.5])[0], 15.0);
+        }
+        let flux = FluxConservingResampler::new(OutOfRange::Nan);
+        let (x, y) = ([0.0, 1.0, 2.0, 3.0], [0.0, 10.0, 20.0, 30.0]);
+        let out = flux.resample_values(&x, &y, Some(&[1.0, 2.0, 4.0, 8.0]), None, &[1.2]);
+        assert_eq!((out.flux[0], out.variance.unwrap()[0]), (10.0, 2.0));
+        let out = flux.resample_values(&x, &y, None, Some(&[false, true, false, false]), &[1.5]);
+        assert!(out.mask[0]);
+        assert!(flux.resample_slice(&x, &y, &[5.0])[0].is_nan());
+        let single = LinearInterpolatedResampler::new(OutOfRange::Nan).resample_values(
+            &[2.0],
+            &[7.0],
+            None,
+            None,
+            &[2.0, 3.0],
+        );
+        assert_eq!(single.flux[0], 7.0);
+        assert!(single.flux[1].is_nan() && single.mask[1]);
+    }
+
+    #[test]
+    fn test_linear_decreasing_axes() {
+        let x = vec![3.0, 2.0, 1.0];
+        let y = vec![30.0, 20.0, 10.0];
+        let out = LinearInterpolatedResampler::default().resample_slice(&x, &y, &[2.75, 1.25]);
+        assert_eq!(out, vec![27.5, 12.5]);
+    }
+
+    #[test]
+    fn test_spline_reproduces_cubic_interior() {
+        let x: Vec<f64> = (0..50).map(|i| f64::from(i) * 0.1).collect();
+        let y: Vec<f64> = x.iter().map(|v| v.sin()).collect();
+        let new_x = vec![1.05, 2.33, 3.71];
+        let out = SplineInterpolatedResampler::default().resample_slice(&x, &y, &new_x);
+        for (v, x0) in out.iter().zip(&new_x) {
+            assert_relative_eq!(*v, x0.sin(), epsilon = 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_resample_spectrum() {
+        let s = spectrum(vec![1e9, 2e9, 3e9, 4e9], vec![1.0, 2.0, 3.0, 4.0])
+            .with_uncertainty(Uncertainty::StdDev(vec![2.0; 4]))
+            .unwrap();
+        let new_axis = SpectralAxis::from_si(SpectralKind::Frequency, vec![1.5e9, 2.5e9]);
+        let out = LinearInterpolatedResampler::default()
+            .resample(&s, &new_axis)
+            .unwrap();
+        assert_eq!(out.flux, vec![1.5, 2.5]);
+        assert_eq!(out.spectral_axis, new_axis);
+        let sigma = out.uncertainty.unwrap().std_dev();
+        assert_relative_eq!(sigma[0], 2.0 * 0.5_f64.sqrt(), max_relative = 1e-12);
+    }
+}
@@ -0,0 +1,413 @@
+//! Smoothing spectra along the spectral axis.
+//!
+//! The convolution-based smoothers mirror `specutils`' `box_smooth`, `gaussian_smooth` and
+//! `trapezoid_smooth`, with kernels sampled as `astropy.convolution` does. Masked and NaN
+//! channels are skipped and the kernel is renormalised over the channels that remain, which
+//! also handles the ends of the spectrum. Uncertainties are propagated assuming independent
+//! channels. The spectral axis and mask of the input are kept, except when Hanning smoothing
+//! with decimation.
+
+use crate::constants::SIGMA_TO_FWHM;
+use crate::errors::spectrum::SpectrumError;
+use crate::spectrum::{SpectralAxis, SpectralKind, Spectrum1D};
+use crate::stats::median;
+use crate::units::f64::Velocity;
+use crate::units::velocity::meter_per_second;
+
+/// A normalised, odd-sized one-dimensional convolution kernel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kernel1D {
+    values: Vec<f64>,
+}
+
+impl Kernel1D {
+    /// Build a kernel from arbitrary weights, normalised to unit sum.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::InvalidKernel`] if the number of weights is even or the weights
+    /// do not have a positive sum.
+    pub fn from_values(values: Vec<f64>) -> Result<Self, SpectrumError> {
+        let sum: f64 = values.iter().sum();
+        if values.len().is_multiple_of(2) || sum <= 0.0 || !sum.is_finite() {
+            return Err(SpectrumError::InvalidKernel {
+                len: values.len(),
+                sum,
+            });
+        }
+        Ok(Self {
+            values: values.into_iter().map(|v| v / sum).collect(),
+        })
+    }
+
+    /// Boxcar of `width` channels. Non-integer widths get fractional end weights, as in
+    /// astropy's `Box1DKernel` with `mode="linear_interp"`.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::InvalidWidth`] if `width` is not positive.
+    pub fn boxcar(width: f64) -> Result<Self, SpectrumError> {
+        check_width(width)?;
+        Self::sampled(width, |x| {
+            let half = width / 2.0;
+            // Fraction of the unit-wide channel centred on x that lies inside the box.
+            (half - (x.abs() - 0.5)).clamp(0.0, 1.0)
+        })
+    }
+
+    /// Gaussian with standard deviation `stddev` channels, truncated at ±4σ.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::InvalidWidth`] if `stddev` is not positive.
+    pub fn gaussian(stddev: f64) -> Result<Self, SpectrumError> {
+        check_width(stddev)?;
+        Self::sampled(8.0 * stddev, |x| (-0.5 * (x / stddev).powi(2)).exp())
+    }
+
+    /// Trapezoid with a flat top `width` channels wide and sides falling by `slope` per
+    /// channel, as astropy's `Trapezoid1DKernel`.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::InvalidWidth`] if `width` or `slope` is not positive.
+    pub fn trapezoid(width: f64, slope: f64) -> Result<Self, SpectrumError> {
+        check_width(width)?;
+        check_width(slope)?;
+        let size = width + 2.0 / slope;
+        Self::sampled(size, |x| {
+            (1.0 - slope * (x.abs() - width / 2.0)).clamp(0.0, 1.0)
+        })
+    }
+
+    /// Hanning kernel, `[1/4, 1/2, 1/4]`.
+    pub fn hanning() -> Self {
+        Self {
+            values: vec![0.25, 0.5, 0.25],
+        }
+    }
+
+    /// Sample `f` at the integer offsets of an odd-sized window at least `size` wide.
+    fn sampled(size: f64, f: impl Fn(f64) -> f64) -> Result<Self, SpectrumError> {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let half = (size.ceil() as i64 / 2).max(1);
+        #[allow(clippy::cast_precision_loss)]
+        let values = (-half..=half).map(|i| f(i as f64)).collect();
+        Self::from_values(values)
+    }
+
+    /// Normalised kernel weights.
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// Convolve `data`, skipping NaN values and channels flagged in `mask`.
+    ///
+    /// Returns the smoothed values and, if `variance` is given, their variance.
+    pub fn convolve(
+        &self,
+        data: &[f64],
+        variance: Option<&[f64]>,
+        mask: Option<&[bool]>,
+    ) -> (Vec<f64>, Option<Vec<f64>>) {
+        let n = data.len();
+        let half = self.values.len() / 2;
+        let mut out = vec![f64::NAN; n];
+        let mut out_var = variance.map(|_| vec![f64::NAN; n]);
+        for i in 0..n {
+            let (mut sum_w, mut sum_wy, mut sum_w2v) = (0.0, 0.0, 0.0);
+            for (k, &w) in self.values.iter().enumerate() {
+                let Some(j) = (i + k).checked_sub(half).filter(|&j| j < n) else {
+                    continue;
+                };
+                if data[j].is_nan() || mask.is_some_and(|m| m[j]) {
+                    continue;
+                }
+                sum_w += w;
+                sum_wy += w * data[j];
+                if let Some(v) = variance {
+                    sum_w2v += w * w * v[j];
+                }
+            }
+            if sum_w > 0.0 {
+                out[i] = sum_wy / sum_w;
+                if let Some(v) = out_var.as_mut() {
+                    v[i] = sum_w2v / (sum_w * sum_w);
+                }
+            }
+        }
+        (out, out_var)
+    }
+}
+
+fn check_width(width: f64) -> Result<(), SpectrumError> {
+    if width > 0.0 && width.is_finite() {
+        Ok(())
+    } else {
+        Err(SpectrumError::InvalidWidth(width))
+    }
+}
+
+/// Convolve a spectrum with `kernel`.
+pub fn convolution_smooth(spectrum: &Spectrum1D, kernel: &Kernel1D) -> Spectrum1D {
+    let variance = spectrum.uncertainty.as_ref().map(|u| u.variance());
+    let (flux, variance) =
+        kernel.convolve(&spectrum.flux, variance.as_deref(), Some(&spectrum.mask));
+    Spectrum1D {
+        flux,
+        uncertainty: spectrum
+            .uncertainty
+            .as_ref()
+            .zip(variance)
+            .map(|(u, v)| u.with_variance(v)),
+        ..spectrum.clone()
+    }
+}
+
+/// Smooth with a boxcar `width` channels wide.
+///
+/// # Errors
+/// Returns [`SpectrumError::InvalidWidth`] if `width` is not positive.
+pub fn box_smooth(spectrum: &Spectrum1D, width: f64) -> Result<Spectrum1D, SpectrumError> {
+    Ok(convolution_smooth(spectrum, &Kernel1D::boxcar(width)?))
+}
+
+/// Smooth with a Gaussian of standard deviation `stddev` channels.
+///
+/// # Errors
+/// Returns [`SpectrumError::InvalidWidth`] if `stddev` is not positive.
+pub fn gaussian_smooth(spectrum: &Spectrum1D, stddev: f64) -> Result<Spectrum1D, SpectrumError> {
+    Ok(convolution_smooth(spectrum, &Kernel1D::gaussian(stddev)?))
+}
+
+/// Smooth with a trapezoid with a flat top `width` channels wide and unit slope.
+///
+/// # Errors
+/// Returns [`SpectrumError::InvalidWidth`] if `width` is not positive.
+pub fn trapezoid_smooth(spectrum: &Spectrum1D, width: f64) -> Result<Spectrum1D, SpectrumError> {
+    Ok(convolution_smooth(
+        spectrum,
+        &Kernel1D::trapezoid(width, 1.0)?,
+    ))
+}
+
+/// Hanning smooth, optionally keeping only every other channel (starting with the first).
+///
+/// Decimation discards the channels that Hanning smoothing makes strongly correlated with
+/// their neighbours, as backends that apply Hanning smoothing on-line do.
+pub fn hanning_smooth(spectrum: &Spectrum1D, decimate: bool) -> Spectrum1D {
+    let smoothed = convolution_smooth(spectrum, &Kernel1D::hanning());
+    if decimate {
+        smoothed.select(&(0..smoothed.len()).step_by(2).collect::<Vec<_>>())
+    } else {
+        smoothed
+    }
+}
+
+/// Median filter over a window of `width` channels (rounded up to an odd number).
+///
+/// The variance of each output channel is that of the mean of the channels in the window,
+/// scaled by the π/2 efficiency loss of the median for Gaussian noise.
+///
+/// # Errors
+/// Returns [`SpectrumError::InvalidWidth`] if `width` is zero.
+pub fn median_smooth(spectrum: &Spectrum1D, width: usize) -> Result<Spectrum1D, SpectrumError> {
+    if width == 0 {
+        return Err(SpectrumError::InvalidWidth(0.0));
+    }
+    let half = width / 2;
+    let n = spectrum.len();
+    let variance = spectrum.uncertainty.as_ref().map(|u| u.variance());
+    let mut flux = vec![f64::NAN; n];
+    let mut out_var = variance.as_ref().map(|_| vec![f64::NAN; n]);
+    for i in 0..n {
+        let window: Vec<usize> = (i.saturating_sub(half)..(i + half + 1).min(n))
+            .filter(|&j| !spectrum.mask[j] && !spectrum.flux[j].is_nan())
+            .collect();
+        if window.is_empty() {
+            continue;
+        }
+        let values: Vec<f64> = window.iter().map(|&j| spectrum.flux[j]).collect();
+        flux[i] = median(&values);
+        if let (Some(out), Some(var)) = (out_var.as_mut(), &variance) {
+            #[allow(clippy::cast_precision_loss)]
+            let m = window.len() as f64;
+            let mean_var = window.iter().map(|&j| var[j]).sum::<f64>() / (m * m);
+            out[i] = std::f64::consts::FRAC_PI_2 * mean_var;
+        }
+    }
+    Ok(Spectrum1D {
+        flux,
+        uncertainty: spectrum
+            .uncertainty
+            .as_ref()
+            .zip(out_var)
+            .map(|(u, v)| u.with_variance(v)),
+        ..spectrum.clone()
+    })
+}
+
+/// Gaussian-smooth a spectrum to a velocity resolution (FWHM) of `resolution`.
+///
+/// The native resolution is taken to be the mean channel width, so the kernel FWHM is
+/// `sqrt(resolution² − width²)`.
+///
+/// # Errors
+/// Returns [`SpectrumError::MissingRestValue`] if the spectral axis cannot be expressed in
+/// velocity, or [`SpectrumError::ResolutionTooFine`] if `resolution` is not larger than the
+/// channel width.
+pub fn smooth_to_velocity_resolution(
+    spectrum: &Spectrum1D,
+    resolution: Velocity,
+) -> Result<Spectrum1D, SpectrumError> {
+    let kernel = velocity_resolution_kernel(&spectrum.spectral_axis, resolution)?;
+    Ok(convolution_smooth(spectrum, &kernel))
+}
+
+/// The Gaussian kernel taking channels of `axis` to a velocity resolution of `resolution`,
+/// as described for [`smooth_to_velocity_resolution`].
+pub(crate) fn velocity_resolution_kernel(
+    axis: &SpectralAxis,
+    resolution: Velocity,
+) -> Result<Kernel1D, SpectrumError> {
+    let velocities = axis.to_kind(SpectralKind::Velocity)?;
+    let v = velocities.values();
+    if v.len() < 2 {
+        return Err(SpectrumError::ResolutionTooFine);
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let width = ((v[v.len() - 1] - v[0]) / (v.len() - 1) as f64).abs();
+    let target = resolution.get::<meter_per_second>();
+    if target <= width {
+        return Err(SpectrumError::ResolutionTooFine);
+    }
+    let fwhm_channels = (target * target - width * width).sqrt() / width;
+    Kernel1D::gaussian(fwhm_channels / SIGMA_TO_FWHM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectrum::{SpectralAxis, Uncertainty};
+    use crate::units::equivalencies::DopplerConvention;
+    use crate::units::f64::Frequency;
+    use crate::units::frequency::{gigahertz, megahertz};
+    use crate::units::velocity::kilometer_per_second;
+    use approx::assert_relative_eq;
+
+    fn spectrum(flux: Vec<f64>) -> Spectrum1D {
+        #[allow(clippy::cast_precision_loss)]
+        let axis: Vec<f64> = (0..flux.len()).map(|i| 1e9 + 1e6 * i as f64).collect();
+        Spectrum1D::new(
+            SpectralAxis::from_si(SpectralKind::Frequency, axis),
+            flux,
+            "K",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_kernels() {
+        assert_eq!(Kernel1D::boxcar(3.0).unwrap().values(), &[1.0 / 3.0; 3]);
+        assert_eq!(
+            Kernel1D::boxcar(4.0).unwrap().values(),
+            &[0.125, 0.25, 0.25, 0.25, 0.125]
+        );
+        let g = Kernel1D::gaussian(2.0).unwrap();
+        assert_eq!(g.values().len(), 17);
+        assert_relative_eq!(g.values().iter().sum::<f64>(), 1.0, max_relative = 1e-12);
+        let t = Kernel1D::trapezoid(3.0, 1.0).unwrap();
+        assert_eq!(t.values(), &[0.125, 0.25, 0.25, 0.25, 0.125]);
+        assert_eq!(
+            Kernel1D::gaussian(-1.0).unwrap_err(),
+            SpectrumError::InvalidWidth(-1.0)
+        );
+        assert_eq!(
+            Kernel1D::from_values(vec![1.0; 4]).unwrap_err(),
+            SpectrumError::InvalidKernel { len: 4, sum: 4.0 }
+        );
+    }
+
+    #[test]
+    fn test_box_smooth_and_uncertainty() {
+        let s = spectrum(vec![0.0, 0.0, 3.0, 0.0, 0.0])
+            .with_uncertainty(Uncertainty::StdDev(vec![1.0; 5]))
+            .unwrap();
+        let out = box_smooth(&s, 3.0).unwrap();
+        assert_eq!(out.flux, vec![0.0, 1.0, 1.0, 1.0, 0.0]);
+        let sigma = out.uncertainty.unwrap().std_dev();
+        assert_relative_eq!(sigma[2], 1.0 / 3.0_f64.sqrt(), max_relative = 1e-12);
+        // Edge channels average over the two available channels.
+        assert_relative_eq!(sigma[0], 1.0 / 2.0_f64.sqrt(), max_relative = 1e-12);
+        assert_eq!(out.spectral_axis, s.spectral_axis);
+    }
+
+    #[test]
+    fn test_masked_channels_skipped() {
+        let s = spectrum(vec![1.0, 100.0, 1.0])
+            .with_mask(vec![false, true, false])
+            .unwrap();
+        let out = box_smooth(&s, 3.0).unwrap();
+        assert_eq!(out.flux, vec![1.0, 1.0, 1.0]);
+        assert_eq!(out.mask, s.mask);
+    }
+
+    #[test]
+    fn test_gaussian_preserves_flux() {
+        let mut flux = vec![0.0; 41];
+        flux[20] = 1.0;
+        let out = gaussian_smooth(&spectrum(flux), 2.0).unwrap();
+        assert_relative_eq!(out.flux.iter().sum::<f64>(), 1.0, max_relative = 1e-12);
+        assert!(out.flux[20] > out.flux[21] && out.flux[21] > out.flux[22]);
+    }
+
+    #[test]
+    fn test_hanning() {
+        let s = spectrum(vec![0.0, 0.0, 4.0, 0.0, 0.0, 0.0]);
+        let out = hanning_smooth(&s, false);
+        assert_eq!(out.flux[1..4], [1.0, 2.0, 1.0]);
+        let decimated = hanning_smooth(&s, true);
+        assert_eq!(decimated.len(), 3);
+        assert_eq!(decimated.flux, vec![0.0, 2.0, 0.0]);
+        assert_eq!(
+            decimated.spectral_axis.values()[1],
+            s.spectral_axis.values()[2]
+        );
+    }
+
+    #[test]
+    fn test_median_smooth() {
+        let s = spectrum(vec![1.0, 1.0, 50.0, 1.0, 1.0]);
+        let out = median_smooth(&s, 3).unwrap();
+        assert_eq!(out.flux, vec![1.0, 1.0, 1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_smooth_to_velocity_resolution() {
+        let rest = Frequency::new::<gigahertz>(100.0);
+        let freqs: Vec<Frequency> = (0..64)
+            .map(|i| Frequency::new::<megahertz>(100_000.0 + 0.1 * f64::from(i)))
+            .collect();
+        let mut flux = vec![0.0; 64];
+        flux[32] = 1.0;
+        let s = Spectrum1D::new(
+            SpectralAxis::from_frequencies(&freqs).with_rest(rest, DopplerConvention::Radio),
+            flux,
+            "K",
+        )
+        .unwrap();
+        // Channels are ~0.3 km/s wide.
+        let out =
+            smooth_to_velocity_resolution(&s, Velocity::new::<kilometer_per_second>(1.5)).unwrap();
+        assert!(out.flux[32] < 0.5);
+        assert_eq!(
+            smooth_to_velocity_resolution(&s, Velocity::new::<kilometer_per_second>(0.1))
+                .unwrap_err(),
+            SpectrumError::ResolutionTooFine
+        );
+        assert_eq!(
+            smooth_to_velocity_resolution(
+                &spectrum(vec![0.0; 4]),
+                Velocity::new::<kilometer_per_second>(1.0)
+            )
+            .unwrap_err(),
+            SpectrumError::MissingRestValue
+        );
+    }
+}
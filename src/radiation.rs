@@ -0,0 +1,292 @@
+//! Blackbody radiation and radiation temperatures.
+//!
+//! Each function uses the CODATA 2018 constants and has a `_with` variant taking another
+//! [`Codata`] adjustment, such as [`CODATA2014`](crate::constants::CODATA2014). The Planck
+//! expressions are evaluated with `exp_m1`/`ln_1p`, so they stay accurate deep in the
+//! Rayleigh-Jeans limit (hν ≪ kT) and decay to zero instead of overflowing in the Wien limit
+//! (hν ≫ kT).
+
+use crate::constants::{CMB_TEMPERATURE, CODATA2018, Codata};
+use crate::units::f64::{
+    Frequency, Length, SurfaceBrightness, Temperature, VolumetricPowerDensity,
+};
+use crate::units::frequency::hertz;
+use crate::units::length::meter;
+use crate::units::surface_brightness::watt_per_square_meter_per_hertz_per_steradian;
+use crate::units::thermodynamic_temperature::kelvin;
+use crate::units::volumetric_power_density::watt_per_cubic_meter;
+
+/// Planck function per unit frequency, B_ν(T) = 2hν³/c² / (exp(hν/kT) − 1).
+pub fn planck_frequency(frequency: Frequency, temperature: Temperature) -> SurfaceBrightness {
+    planck_frequency_with(frequency, temperature, &CODATA2018)
+}
+
+/// [`planck_frequency`] with the constants of `codata`.
+pub fn planck_frequency_with(
+    frequency: Frequency,
+    temperature: Temperature,
+    codata: &Codata,
+) -> SurfaceBrightness {
+    let Codata { c, h, k_b, .. } = *codata;
+    let nu = frequency.get::<hertz>();
+    let t = temperature.get::<kelvin>();
+    let x = h * nu / (k_b * t);
+    let value = 2.0 * h * nu.powi(3) / c.powi(2) / x.exp_m1();
+    SurfaceBrightness::new::<watt_per_square_meter_per_hertz_per_steradian>(value)
+}
+
+/// Planck function per unit wavelength, B_λ(T) = 2hc²/λ⁵ / (exp(hc/λkT) − 1).
+///
+/// The result is a spectral radiance in W m⁻³ sr⁻¹.
+pub fn planck_wavelength(wavelength: Length, temperature: Temperature) -> VolumetricPowerDensity {
+    planck_wavelength_with(wavelength, temperature, &CODATA2018)
+}
+
+/// [`planck_wavelength`] with the constants of `codata`.
+pub fn planck_wavelength_with(
+    wavelength: Length,
+    temperature: Temperature,
+    codata: &Codata,
+) -> VolumetricPowerDensity {
+    let Codata { c, h, k_b, .. } = *codata;
+    let lambda = wavelength.get::<meter>();
+    let t = temperature.get::<kelvin>();
+    let x = h * c / (lambda * k_b * t);
+    let value = 2.0 * h * c.powi(2) / lambda.powi(5) / x.exp_m1();
+    VolumetricPowerDensity::new::<watt_per_cubic_meter>(value)
+}
+
+/// Rayleigh-Jeans approximation to the Planck function, B_ν(T) ≈ 2ν²kT/c².
+pub fn rayleigh_jeans_frequency(
+    frequency: Frequency,
+    temperature: Temperature,
+) -> SurfaceBrightness {
+    rayleigh_jeans_frequency_with(frequency, temperature, &CODATA2018)
+}
+
+/// [`rayleigh_jeans_frequency`] with the constants of `codata`.
+pub fn rayleigh_jeans_frequency_with(
+    frequency: Frequency,
+    temperature: Temperature,
+    codata: &Codata,
+) -> SurfaceBrightness {
+    let nu = frequency.get::<hertz>();
+    let value = 2.0 * nu * nu * codata.k_b * temperature.get::<kelvin>() / codata.c.powi(2);
+    SurfaceBrightness::new::<watt_per_square_meter_per_hertz_per_steradian>(value)
+}
+
+/// Planck brightness temperature of a specific intensity, i.e. the inverse of
+/// [`planck_frequency`].
+pub fn brightness_temperature(intensity: SurfaceBrightness, frequency: Frequency) -> Temperature {
+    brightness_temperature_with(intensity, frequency, &CODATA2018)
+}
+
+/// [`brightness_temperature`] with the constants of `codata`.
+pub fn brightness_temperature_with(
+    intensity: SurfaceBrightness,
+    frequency: Frequency,
+    codata: &Codata,
+) -> Temperature {
+    let Codata { c, h, k_b, .. } = *codata;
+    let nu = frequency.get::<hertz>();
+    let i = intensity.get::<watt_per_square_meter_per_hertz_per_steradian>();
+    let t0 = h * nu / k_b;
+    let value = t0 / (2.0 * h * nu.powi(3) / (c.powi(2) * i)).ln_1p();
+    Temperature::new::<kelvin>(value)
+}
+
+/// Rayleigh-Jeans brightness temperature of a specific intensity, T = c²I / 2kν².
+pub fn rayleigh_jeans_temperature(
+    intensity: SurfaceBrightness,
+    frequency: Frequency,
+) -> Temperature {
+    rayleigh_jeans_temperature_with(intensity, frequency, &CODATA2018)
+}
+
+/// [`rayleigh_jeans_temperature`] with the constants of `codata`.
+pub fn rayleigh_jeans_temperature_with(
+    intensity: SurfaceBrightness,
+    frequency: Frequency,
+    codata: &Codata,
+) -> Temperature {
+    let nu = frequency.get::<hertz>();
+    let i = intensity.get::<watt_per_square_meter_per_hertz_per_steradian>();
+    Temperature::new::<kelvin>(codata.c.powi(2) * i / (2.0 * codata.k_b * nu * nu))
+}
+
+/// Radiation temperature, J_ν(T) = (hν/k) / (exp(hν/kT) − 1).
+///
+/// This is the Planck function expressed as a Rayleigh-Jeans temperature. It tends to
+/// T − hν/2k for hν ≪ kT and to zero for hν ≫ kT.
+pub fn radiation_temperature(frequency: Frequency, temperature: Temperature) -> Temperature {
+    radiation_temperature_with(frequency, temperature, &CODATA2018)
+}
+
+/// [`radiation_temperature`] with the constants of `codata`.
+pub fn radiation_temperature_with(
+    frequency: Frequency,
+    temperature: Temperature,
+    codata: &Codata,
+) -> Temperature {
+    let t0 = codata.h * frequency.get::<hertz>() / codata.k_b;
+    let t = temperature.get::<kelvin>();
+    Temperature::new::<kelvin>(t0 / (t0 / t).exp_m1())
+}
+
+/// Temperature of the cosmic microwave background at redshift `z`, T₀ (1 + z).
+pub fn cmb_temperature(z: f64) -> Temperature {
+    Temperature::new::<kelvin>(CMB_TEMPERATURE * (1.0 + z))
+}
+
+/// Radiation temperature of a uniform slab against a background, the detection equation
+/// T_R = (J_ν(T_ex) − J_ν(T_bg)) (1 − exp(−τ)).
+pub fn detection_temperature(
+    frequency: Frequency,
+    excitation_temperature: Temperature,
+    background_temperature: Temperature,
+    tau: f64,
+) -> Temperature {
+    detection_temperature_with(
+        frequency,
+        excitation_temperature,
+        background_temperature,
+        tau,
+        &CODATA2018,
+    )
+}
+
+/// [`detection_temperature`] with the constants of `codata`.
+pub fn detection_temperature_with(
+    frequency: Frequency,
+    excitation_temperature: Temperature,
+    background_temperature: Temperature,
+    tau: f64,
+    codata: &Codata,
+) -> Temperature {
+    let j_ex = radiation_temperature_with(frequency, excitation_temperature, codata);
+    let j_bg = radiation_temperature_with(frequency, background_temperature, codata);
+    let (j_ex, j_bg) = (j_ex.get::<kelvin>(), j_bg.get::<kelvin>());
+    Temperature::new::<kelvin>((j_ex - j_bg) * -(-tau).exp_m1())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{CODATA2014, SPEED_OF_LIGHT};
+    use crate::units::frequency::gigahertz;
+    use crate::units::length::micrometer;
+    use crate::units::surface_brightness::megajansky_per_steradian;
+    use approx::assert_relative_eq;
+
+    fn ghz(v: f64) -> Frequency {
+        Frequency::new::<gigahertz>(v)
+    }
+
+    fn k(v: f64) -> Temperature {
+        Temperature::new::<kelvin>(v)
+    }
+
+    #[test]
+    fn test_planck_cmb() {
+        // The CMB intensity peaks near 160 GHz at ~384 MJy/sr.
+        let b = planck_frequency(ghz(160.0), k(CMB_TEMPERATURE));
+        assert_relative_eq!(
+            b.get::<megajansky_per_steradian>(),
+            383.876,
+            max_relative = 1e-5
+        );
+    }
+
+    #[test]
+    fn test_planck_wavelength_matches_frequency() {
+        let lambda = Length::new::<micrometer>(500.0);
+        let nu = Frequency::new::<hertz>(SPEED_OF_LIGHT / lambda.get::<meter>());
+        let b_nu =
+            planck_frequency(nu, k(30.0)).get::<watt_per_square_meter_per_hertz_per_steradian>();
+        let b_lambda = planck_wavelength(lambda, k(30.0)).get::<watt_per_cubic_meter>();
+        assert_relative_eq!(
+            b_lambda,
+            b_nu * SPEED_OF_LIGHT / lambda.get::<meter>().powi(2),
+            max_relative = 1e-12
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        // Rayleigh-Jeans limit.
+        let nu = Frequency::new::<hertz>(1.0);
+        let b = planck_frequency(nu, k(1e4));
+        let rj = rayleigh_jeans_frequency(nu, k(1e4));
+        assert_relative_eq!(b.value, rj.value, max_relative = 1e-12);
+        assert_relative_eq!(
+            radiation_temperature(nu, k(1e4)).get::<kelvin>(),
+            1e4,
+            max_relative = 1e-12
+        );
+
+        // Wien limit does not overflow.
+        let nu = Frequency::new::<hertz>(1e18);
+        assert_eq!(planck_frequency(nu, k(1.0)).value, 0.0);
+        assert_eq!(radiation_temperature(nu, k(1.0)).value, 0.0);
+        assert_eq!(planck_frequency(nu, k(0.0)).value, 0.0);
+    }
+
+    #[test]
+    fn test_brightness_temperature_round_trip() {
+        for (nu, t) in [
+            (1.0, 10.0),
+            (115.27, 2.7255),
+            (691.47, 150.0),
+            (1.0e4, 20.0),
+        ] {
+            let b = planck_frequency(ghz(nu), k(t));
+            assert_relative_eq!(
+                brightness_temperature(b, ghz(nu)).get::<kelvin>(),
+                t,
+                max_relative = 1e-10
+            );
+            let rj = rayleigh_jeans_frequency(ghz(nu), k(t));
+            assert_relative_eq!(
+                rayleigh_jeans_temperature(rj, ghz(nu)).get::<kelvin>(),
+                t,
+                max_relative = 1e-12
+            );
+        }
+    }
+
+    #[test]
+    fn test_detection_temperature() {
+        let nu = ghz(115.271_201_8);
+        let t_bg = cmb_temperature(0.0);
+        // Optically thick: T_R = J(T_ex) - J(T_bg).
+        let t_r = detection_temperature(nu, k(20.0), t_bg, 100.0);
+        let expected = radiation_temperature(nu, k(20.0)).get::<kelvin>()
+            - radiation_temperature(nu, t_bg).get::<kelvin>();
+        assert_relative_eq!(t_r.get::<kelvin>(), expected);
+        // Optically thin: T_R ~ tau (J(T_ex) - J(T_bg)).
+        let t_r = detection_temperature(nu, k(20.0), t_bg, 1e-6);
+        assert_relative_eq!(t_r.get::<kelvin>(), 1e-6 * expected, max_relative = 1e-5);
+        // Excitation temperature equal to the background gives no signal.
+        assert_relative_eq!(
+            detection_temperature(nu, t_bg, t_bg, 1.0).get::<kelvin>(),
+            0.0
+        );
+        assert_relative_eq!(cmb_temperature(1.0).get::<kelvin>(), 2.0 * CMB_TEMPERATURE);
+    }
+
+    #[test]
+    fn test_codata_versions() {
+        // The constants differ by parts in 10⁸, so CODATA 2014 gives close but distinct values.
+        let (nu, t) = (ghz(230.538), k(20.0));
+        let b2018 = planck_frequency(nu, t);
+        let b2014 = planck_frequency_with(nu, t, &CODATA2014);
+        assert_eq!(planck_frequency_with(nu, t, &CODATA2018), b2018);
+        assert_ne!(b2014, b2018);
+        assert_relative_eq!(b2014.value, b2018.value, max_relative = 1e-6);
+        assert_relative_eq!(
+            brightness_temperature_with(b2014, nu, &CODATA2014).get::<kelvin>(),
+            20.0,
+            max_relative = 1e-12
+        );
+    }
+}
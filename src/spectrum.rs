@@ -0,0 +1,851 @@
+//! One-dimensional spectra, modelled on `specutils.Spectrum1D`.
+//!
+//! A [`Spectrum1D`] pairs a flux array with a [`SpectralAxis`]. The spectral axis is stored in
+//! SI base units (Hz, m or m s⁻¹) together with its [`SpectralKind`], and carries the rest
+//! frequency and [`DopplerConvention`] needed to move between frequency, wavelength and
+//! velocity, and optionally the [`VelocityFrame`] it is measured in. Flux values are plain
+//! `f64` with a unit label, as found in a FITS `BUNIT`.
+
+mod arithmetic;
+mod region;
+
+pub(crate) use arithmetic::unit_product;
+pub use region::SpectralRegion;
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::coordinates::{Observation, VelocityFrame};
+use crate::errors::spectrum::SpectrumError;
+use crate::units::equivalencies::{DopplerConvention, SpectralQuantity};
+use crate::units::f64::{Frequency, Length, Velocity};
+use crate::units::frequency::hertz;
+use crate::units::length::meter;
+use crate::units::velocity::meter_per_second;
+
+/// Physical type of a spectral coordinate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpectralKind {
+    Frequency,
+    Wavelength,
+    Velocity,
+}
+
+impl SpectralKind {
+    /// SI base unit in which values of this kind are stored.
+    pub fn si_unit(&self) -> &'static str {
+        match self {
+            Self::Frequency => "Hz",
+            Self::Wavelength => "m",
+            Self::Velocity => "m / s",
+        }
+    }
+}
+
+/// A single spectral coordinate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpectralValue {
+    Frequency(Frequency),
+    Wavelength(Length),
+    Velocity(Velocity),
+}
+
+impl SpectralValue {
+    /// Create a value of `kind` from its SI base unit (Hz, m or m s⁻¹).
+    pub fn from_si(kind: SpectralKind, value: f64) -> Self {
+        match kind {
+            SpectralKind::Frequency => Self::Frequency(Frequency::new::<hertz>(value)),
+            SpectralKind::Wavelength => Self::Wavelength(Length::new::<meter>(value)),
+            SpectralKind::Velocity => Self::Velocity(Velocity::new::<meter_per_second>(value)),
+        }
+    }
+
+    /// Physical type of the value.
+    pub fn kind(&self) -> SpectralKind {
+        match self {
+            Self::Frequency(_) => SpectralKind::Frequency,
+            Self::Wavelength(_) => SpectralKind::Wavelength,
+            Self::Velocity(_) => SpectralKind::Velocity,
+        }
+    }
+
+    /// Value in SI base units (Hz, m or m s⁻¹).
+    pub fn si(&self) -> f64 {
+        match self {
+            Self::Frequency(f) => f.get::<hertz>(),
+            Self::Wavelength(w) => w.get::<meter>(),
+            Self::Velocity(v) => v.get::<meter_per_second>(),
+        }
+    }
+}
+
+impl From<Frequency> for SpectralValue {
+    fn from(value: Frequency) -> Self {
+        Self::Frequency(value)
+    }
+}
+
+impl From<Length> for SpectralValue {
+    fn from(value: Length) -> Self {
+        Self::Wavelength(value)
+    }
+}
+
+impl From<Velocity> for SpectralValue {
+    fn from(value: Velocity) -> Self {
+        Self::Velocity(value)
+    }
+}
+
+/// Spectral coordinates of the channels of a spectrum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpectralAxis {
+    kind: SpectralKind,
+    values: Vec<f64>,
+    rest: Option<Frequency>,
+    convention: DopplerConvention,
+    frame: Option<VelocityFrame>,
+}
+
+impl SpectralAxis {
+    /// Create an axis from values in the SI base unit of `kind` (Hz, m or m s⁻¹).
+    pub fn from_si(kind: SpectralKind, values: Vec<f64>) -> Self {
+        Self {
+            kind,
+            values,
+            rest: None,
+            convention: DopplerConvention::Radio,
+            frame: None,
+        }
+    }
+
+    pub fn from_frequencies(values: &[Frequency]) -> Self {
+        Self::from_si(
+            SpectralKind::Frequency,
+            values.iter().map(|v| v.get::<hertz>()).collect(),
+        )
+    }
+
+    pub fn from_wavelengths(values: &[Length]) -> Self {
+        Self::from_si(
+            SpectralKind::Wavelength,
+            values.iter().map(|v| v.get::<meter>()).collect(),
+        )
+    }
+
+    /// Create a velocity axis relative to `rest` in the given convention.
+    pub fn from_velocities(
+        values: &[Velocity],
+        rest: Frequency,
+        convention: DopplerConvention,
+    ) -> Self {
+        Self::from_si(
+            SpectralKind::Velocity,
+            values.iter().map(|v| v.get::<meter_per_second>()).collect(),
+        )
+        .with_rest(rest, convention)
+    }
+
+    /// Set the rest frequency and velocity convention used for conversions.
+    #[must_use]
+    pub fn with_rest(mut self, rest: Frequency, convention: DopplerConvention) -> Self {
+        self.rest = Some(rest);
+        self.convention = convention;
+        self
+    }
+
+    /// Set the velocity reference frame the axis values are measured in.
+    #[must_use]
+    pub fn with_frame(mut self, frame: VelocityFrame) -> Self {
+        self.frame = Some(frame);
+        self
+    }
+
+    pub fn kind(&self) -> SpectralKind {
+        self.kind
+    }
+
+    pub fn frame(&self) -> Option<VelocityFrame> {
+        self.frame
+    }
+
+    pub fn rest(&self) -> Option<Frequency> {
+        self.rest
+    }
+
+    pub fn convention(&self) -> DopplerConvention {
+        self.convention
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Channel values in the SI base unit of the axis kind.
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// Spectral coordinate of channel `index`.
+    pub fn get(&self, index: usize) -> Option<SpectralValue> {
+        self.values
+            .get(index)
+            .map(|&v| SpectralValue::from_si(self.kind, v))
+    }
+
+    /// Whether the axis values increase with channel index.
+    pub fn is_increasing(&self) -> bool {
+        self.values.first() <= self.values.last()
+    }
+
+    /// Express a spectral coordinate in the SI base unit of this axis' kind.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::MissingRestValue`] if the conversion goes through a velocity
+    /// and the axis has no rest frequency.
+    pub fn convert_value(&self, value: impl Into<SpectralValue>) -> Result<f64, SpectrumError> {
+        let value = value.into();
+        if value.kind() == self.kind {
+            return Ok(value.si());
+        }
+        let frequency = match value {
+            SpectralValue::Frequency(f) => f,
+            SpectralValue::Wavelength(w) => w.to_frequency(),
+            SpectralValue::Velocity(v) => {
+                let rest = self.rest.ok_or(SpectrumError::MissingRestValue)?;
+                self.convention.from_velocity(v, rest)
+            }
+        };
+        self.frequency_to_si(frequency)
+    }
+
+    fn frequency_to_si(&self, frequency: Frequency) -> Result<f64, SpectrumError> {
+        Ok(match self.kind {
+            SpectralKind::Frequency => frequency.get::<hertz>(),
+            SpectralKind::Wavelength => Length::from_frequency(frequency).get::<meter>(),
+            SpectralKind::Velocity => {
+                let rest = self.rest.ok_or(SpectrumError::MissingRestValue)?;
+                self.convention
+                    .to_velocity(frequency, rest)
+                    .get::<meter_per_second>()
+            }
+        })
+    }
+
+    /// Convert the axis to another spectral kind, keeping the rest frequency and convention.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::MissingRestValue`] if the conversion goes through a velocity
+    /// and the axis has no rest frequency.
+    pub fn to_kind(&self, kind: SpectralKind) -> Result<Self, SpectrumError> {
+        let target = Self {
+            kind,
+            values: Vec::new(),
+            rest: self.rest,
+            convention: self.convention,
+            frame: self.frame,
+        };
+        let values = self
+            .values
+            .iter()
+            .map(|&v| target.convert_value(SpectralValue::from_si(self.kind, v)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { values, ..target })
+    }
+
+    /// Doppler-shift the axis into another velocity reference frame.
+    ///
+    /// Frequencies and wavelengths are scaled by the relativistic Doppler factor between the
+    /// frames; velocities are shifted through the equivalent frequencies, so the rest
+    /// frequency and convention are kept.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::MissingFrame`] if the axis has no frame, and
+    /// [`SpectrumError::MissingRestValue`] for a velocity axis without rest frequency.
+    pub fn to_frame(
+        &self,
+        frame: VelocityFrame,
+        observation: &Observation,
+    ) -> Result<Self, SpectrumError> {
+        let from = self.frame.ok_or(SpectrumError::MissingFrame)?;
+        let factor = observation.doppler_factor(from, frame);
+        let values = match self.kind {
+            SpectralKind::Frequency => self.values.iter().map(|v| v * factor).collect(),
+            SpectralKind::Wavelength => self.values.iter().map(|v| v / factor).collect(),
+            SpectralKind::Velocity => self
+                .frequencies()?
+                .into_iter()
+                .map(|f| self.convert_value(f * factor))
+                .collect::<Result<_, _>>()?,
+        };
+        Ok(Self {
+            values,
+            frame: Some(frame),
+            ..self.clone()
+        })
+    }
+
+    /// Channel frequencies.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::MissingRestValue`] for a velocity axis without rest frequency.
+    pub fn frequencies(&self) -> Result<Vec<Frequency>, SpectrumError> {
+        Ok(self
+            .to_kind(SpectralKind::Frequency)?
+            .values
+            .into_iter()
+            .map(Frequency::new::<hertz>)
+            .collect())
+    }
+
+    /// Channel wavelengths.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::MissingRestValue`] for a velocity axis without rest frequency.
+    pub fn wavelengths(&self) -> Result<Vec<Length>, SpectrumError> {
+        Ok(self
+            .to_kind(SpectralKind::Wavelength)?
+            .values
+            .into_iter()
+            .map(Length::new::<meter>)
+            .collect())
+    }
+
+    /// Channel velocities.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::MissingRestValue`] if the axis has no rest frequency.
+    pub fn velocities(&self) -> Result<Vec<Velocity>, SpectrumError> {
+        Ok(self
+            .to_kind(SpectralKind::Velocity)?
+            .values
+            .into_iter()
+            .map(Velocity::new::<meter_per_second>)
+            .collect())
+    }
+
+    /// Channel boundaries, half way between channel centres and extrapolated at both ends.
+    ///
+    /// The result has one more element than the axis.
+    pub fn bin_edges(&self) -> Vec<f64> {
+        bin_edges(&self.values)
+    }
+
+    pub(crate) fn select(&self, indices: &[usize]) -> Self {
+        self.with_values(indices.iter().map(|&i| self.values[i]).collect())
+    }
+
+    /// Axis of the same kind, rest frequency and frame with new values.
+    pub(crate) fn with_values(&self, values: Vec<f64>) -> Self {
+        Self {
+            values,
+            ..self.clone()
+        }
+    }
+}
+
+/// Per-channel flux uncertainty, in the flavours of astropy's `NDUncertainty` classes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Uncertainty {
+    /// Standard deviation, in the flux unit.
+    StdDev(Vec<f64>),
+    /// Variance, in the square of the flux unit.
+    Variance(Vec<f64>),
+    /// Inverse variance, in the inverse square of the flux unit.
+    InverseVariance(Vec<f64>),
+}
+
+impl Uncertainty {
+    fn values(&self) -> &[f64] {
+        match self {
+            Self::StdDev(v) | Self::Variance(v) | Self::InverseVariance(v) => v,
+        }
+    }
+
+    fn map(&self, values: Vec<f64>) -> Self {
+        match self {
+            Self::StdDev(_) => Self::StdDev(values),
+            Self::Variance(_) => Self::Variance(values),
+            Self::InverseVariance(_) => Self::InverseVariance(values),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values().is_empty()
+    }
+
+    /// Uncertainty as standard deviations.
+    pub fn std_dev(&self) -> Vec<f64> {
+        match self {
+            Self::StdDev(v) => v.clone(),
+            Self::Variance(v) => v.iter().map(|x| x.sqrt()).collect(),
+            Self::InverseVariance(v) => v.iter().map(|x| x.sqrt().recip()).collect(),
+        }
+    }
+
+    /// Uncertainty as variances.
+    pub fn variance(&self) -> Vec<f64> {
+        match self {
+            Self::StdDev(v) => v.iter().map(|x| x * x).collect(),
+            Self::Variance(v) => v.clone(),
+            Self::InverseVariance(v) => v.iter().map(|x| x.recip()).collect(),
+        }
+    }
+
+    /// Same flavour of uncertainty as `self`, built from variances.
+    pub(crate) fn with_variance(&self, variance: Vec<f64>) -> Self {
+        match self {
+            Self::StdDev(_) => Self::StdDev(variance.into_iter().map(f64::sqrt).collect()),
+            Self::Variance(_) => Self::Variance(variance),
+            Self::InverseVariance(_) => {
+                Self::InverseVariance(variance.into_iter().map(f64::recip).collect())
+            }
+        }
+    }
+
+    fn select(&self, indices: &[usize]) -> Self {
+        let values = self.values();
+        self.map(indices.iter().map(|&i| values[i]).collect())
+    }
+}
+
+/// One channel of a spectrum, as yielded by [`Spectrum1D::iter`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectralPoint {
+    pub spectral: SpectralValue,
+    pub flux: f64,
+    /// Standard deviation of the flux, if the spectrum has an uncertainty
+    pub uncertainty: Option<f64>,
+    pub masked: bool,
+}
+
+/// A one-dimensional spectrum.
+///
+/// The spectral axis, flux, uncertainty and mask always have the same length, so they are
+/// only reachable through accessors and checked setters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectrum1D {
+    pub(crate) spectral_axis: SpectralAxis,
+    pub(crate) flux: Vec<f64>,
+    /// Unit of the flux values, e.g. `"Jy"` or `"K"`
+    pub unit: String,
+    pub(crate) uncertainty: Option<Uncertainty>,
+    /// Channels flagged as invalid (`true` means masked, as in `specutils`)
+    pub(crate) mask: Vec<bool>,
+    pub meta: HashMap<String, String>,
+}
+
+impl Spectrum1D {
+    /// Create a spectrum without uncertainty and with no channels masked.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::LengthMismatch`] if `flux` and `spectral_axis` differ in length.
+    pub fn new(
+        spectral_axis: SpectralAxis,
+        flux: Vec<f64>,
+        unit: impl Into<String>,
+    ) -> Result<Self, SpectrumError> {
+        check_len(spectral_axis.len(), flux.len())?;
+        Ok(Self {
+            mask: vec![false; flux.len()],
+            spectral_axis,
+            flux,
+            unit: unit.into(),
+            uncertainty: None,
+            meta: HashMap::new(),
+        })
+    }
+
+    /// Attach an uncertainty.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::LengthMismatch`] if the uncertainty and flux differ in length.
+    pub fn with_uncertainty(mut self, uncertainty: Uncertainty) -> Result<Self, SpectrumError> {
+        check_len(self.len(), uncertainty.len())?;
+        self.uncertainty = Some(uncertainty);
+        Ok(self)
+    }
+
+    /// Attach a mask.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::LengthMismatch`] if the mask and flux differ in length.
+    pub fn with_mask(mut self, mask: Vec<bool>) -> Result<Self, SpectrumError> {
+        check_len(self.len(), mask.len())?;
+        self.mask = mask;
+        Ok(self)
+    }
+
+    /// Replace the flux values, keeping the unit.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::LengthMismatch`] if the new flux and the spectrum differ in
+    /// length.
+    pub fn with_flux(mut self, flux: Vec<f64>) -> Result<Self, SpectrumError> {
+        check_len(self.len(), flux.len())?;
+        self.flux = flux;
+        Ok(self)
+    }
+
+    /// Replace the spectral axis.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::LengthMismatch`] if the axis and the spectrum differ in
+    /// length.
+    pub fn with_spectral_axis(mut self, axis: SpectralAxis) -> Result<Self, SpectrumError> {
+        check_len(self.len(), axis.len())?;
+        self.spectral_axis = axis;
+        Ok(self)
+    }
+
+    /// Remove the uncertainty.
+    #[must_use]
+    pub fn without_uncertainty(mut self) -> Self {
+        self.uncertainty = None;
+        self
+    }
+
+    pub fn spectral_axis(&self) -> &SpectralAxis {
+        &self.spectral_axis
+    }
+
+    pub fn flux(&self) -> &[f64] {
+        &self.flux
+    }
+
+    /// Flux values, to modify in place.
+    pub fn flux_mut(&mut self) -> &mut [f64] {
+        &mut self.flux
+    }
+
+    pub fn uncertainty(&self) -> Option<&Uncertainty> {
+        self.uncertainty.as_ref()
+    }
+
+    /// Channels flagged as invalid, `true` where masked.
+    pub fn mask(&self) -> &[bool] {
+        &self.mask
+    }
+
+    /// The mask, to modify in place.
+    pub fn mask_mut(&mut self) -> &mut [bool] {
+        &mut self.mask
+    }
+
+    pub fn len(&self) -> usize {
+        self.flux.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flux.is_empty()
+    }
+
+    /// Iterate over the channels of the spectrum.
+    pub fn iter(&self) -> impl Iterator<Item = SpectralPoint> + '_ {
+        let std_dev = self.uncertainty.as_ref().map(Uncertainty::std_dev);
+        (0..self.len()).map(move |i| SpectralPoint {
+            spectral: SpectralValue::from_si(self.spectral_axis.kind, self.spectral_axis.values[i]),
+            flux: self.flux[i],
+            uncertainty: std_dev.as_ref().map(|s| s[i]),
+            masked: self.mask[i],
+        })
+    }
+
+    /// Spectrum restricted to the channels with indices in `range`.
+    ///
+    /// # Panics
+    /// Panics if `range` is out of bounds.
+    #[must_use]
+    pub fn slice_index(&self, range: Range<usize>) -> Self {
+        assert!(range.end <= self.len(), "slice out of bounds");
+        self.select(&range.collect::<Vec<_>>())
+    }
+
+    /// Spectrum restricted to the channels whose spectral coordinate lies between `lo` and
+    /// `hi` (inclusive, in either order). The bounds may be of any spectral kind.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::MissingRestValue`] if a bound cannot be converted to the axis
+    /// kind, or [`SpectrumError::EmptySelection`] if no channel falls in the range.
+    pub fn slice(
+        &self,
+        lo: impl Into<SpectralValue>,
+        hi: impl Into<SpectralValue>,
+    ) -> Result<Self, SpectrumError> {
+        let a = self.spectral_axis.convert_value(lo)?;
+        let b = self.spectral_axis.convert_value(hi)?;
+        let (lo, hi) = (a.min(b), a.max(b));
+        let indices: Vec<usize> = (0..self.len())
+            .filter(|&i| (lo..=hi).contains(&self.spectral_axis.values[i]))
+            .collect();
+        if indices.is_empty() {
+            return Err(SpectrumError::EmptySelection);
+        }
+        Ok(self.select(&indices))
+    }
+
+    /// Spectrum with its spectral axis converted to another kind.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::MissingRestValue`] if the conversion goes through a velocity
+    /// and the axis has no rest frequency.
+    pub fn with_spectral_kind(&self, kind: SpectralKind) -> Result<Self, SpectrumError> {
+        Ok(Self {
+            spectral_axis: self.spectral_axis.to_kind(kind)?,
+            ..self.clone()
+        })
+    }
+
+    /// Spectrum with its spectral axis Doppler-shifted into another velocity frame. See
+    /// [`SpectralAxis::to_frame`].
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::MissingFrame`] if the axis has no frame, and
+    /// [`SpectrumError::MissingRestValue`] for a velocity axis without rest frequency.
+    pub fn to_frame(
+        &self,
+        frame: VelocityFrame,
+        observation: &Observation,
+    ) -> Result<Self, SpectrumError> {
+        Ok(Self {
+            spectral_axis: self.spectral_axis.to_frame(frame, observation)?,
+            ..self.clone()
+        })
+    }
+
+    /// Spectrum with the rest frequency and velocity convention of the axis replaced.
+    #[must_use]
+    pub fn with_rest(&self, rest: Frequency, convention: DopplerConvention) -> Self {
+        Self {
+            spectral_axis: self.spectral_axis.clone().with_rest(rest, convention),
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn select(&self, indices: &[usize]) -> Self {
+        Self {
+            spectral_axis: self.spectral_axis.select(indices),
+            flux: indices.iter().map(|&i| self.flux[i]).collect(),
+            unit: self.unit.clone(),
+            uncertainty: self.uncertainty.as_ref().map(|u| u.select(indices)),
+            mask: indices.iter().map(|&i| self.mask[i]).collect(),
+            meta: self.meta.clone(),
+        }
+    }
+}
+
+/// Boundaries of the bins centred on `values`. See [`SpectralAxis::bin_edges`].
+pub(crate) fn bin_edges(v: &[f64]) -> Vec<f64> {
+    match v.len() {
+        0 => Vec::new(),
+        1 => vec![v[0], v[0]],
+        n => {
+            let mut edges = Vec::with_capacity(n + 1);
+            edges.push(v[0] - 0.5 * (v[1] - v[0]));
+            edges.extend(v.windows(2).map(|w| 0.5 * (w[0] + w[1])));
+            edges.push(v[n - 1] + 0.5 * (v[n - 1] - v[n - 2]));
+            edges
+        }
+    }
+}
+
+fn check_len(expected: usize, found: usize) -> Result<(), SpectrumError> {
+    if expected == found {
+        Ok(())
+    } else {
+        Err(SpectrumError::LengthMismatch { expected, found })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::frequency::gigahertz;
+    use crate::units::length::millimeter;
+    use crate::units::velocity::kilometer_per_second;
+    use approx::assert_relative_eq;
+
+    fn ghz(v: f64) -> Frequency {
+        Frequency::new::<gigahertz>(v)
+    }
+
+    fn spectrum() -> Spectrum1D {
+        let freqs: Vec<Frequency> = (0..11).map(|i| ghz(115.0 + 0.1 * f64::from(i))).collect();
+        let axis = SpectralAxis::from_frequencies(&freqs)
+            .with_rest(ghz(115.271_201_8), DopplerConvention::Radio);
+        Spectrum1D::new(axis, (0..11).map(f64::from).collect(), "K").unwrap()
+    }
+
+    #[test]
+    fn test_new_length_mismatch() {
+        let axis = SpectralAxis::from_frequencies(&[ghz(1.0), ghz(2.0)]);
+        let err = Spectrum1D::new(axis, vec![1.0], "Jy").unwrap_err();
+        assert_eq!(
+            err,
+            SpectrumError::LengthMismatch {
+                expected: 2,
+                found: 1
+            }
+        );
+        let err = spectrum()
+            .with_uncertainty(Uncertainty::StdDev(vec![1.0; 3]))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            SpectrumError::LengthMismatch {
+                expected: 11,
+                found: 3
+            }
+        );
+
+        // Setters keep the lengths consistent.
+        assert!(spectrum().with_flux(vec![0.0; 10]).is_err());
+        assert!(spectrum().with_mask(vec![false; 12]).is_err());
+        let axis = SpectralAxis::from_frequencies(&[ghz(1.0), ghz(2.0)]);
+        assert!(spectrum().with_spectral_axis(axis).is_err());
+        let mut s = spectrum().with_flux(vec![1.0; 11]).unwrap();
+        s.flux_mut()[2] = 5.0;
+        s.mask_mut()[2] = true;
+        assert_eq!(s.flux()[..3], [1.0, 1.0, 5.0]);
+        assert!(s.iter().nth(2).unwrap().masked);
+    }
+
+    #[test]
+    fn test_axis_conversion() {
+        let s = spectrum();
+        let v = s.with_spectral_kind(SpectralKind::Velocity).unwrap();
+        let vel = v.spectral_axis.velocities().unwrap();
+        let c_kms = 299_792.458;
+        assert_relative_eq!(
+            vel[0].get::<kilometer_per_second>(),
+            c_kms * (115.271_201_8 - 115.0) / 115.271_201_8,
+            max_relative = 1e-12
+        );
+
+        // Velocity -> wavelength -> frequency round trip.
+        let w = v.with_spectral_kind(SpectralKind::Wavelength).unwrap();
+        let back = w.spectral_axis.frequencies().unwrap();
+        for (a, b) in s.spectral_axis.frequencies().unwrap().iter().zip(&back) {
+            assert_relative_eq!(a.get::<hertz>(), b.get::<hertz>(), max_relative = 1e-12);
+        }
+        assert_relative_eq!(
+            w.spectral_axis.wavelengths().unwrap()[0].get::<millimeter>(),
+            299.792_458 / 115.0,
+            max_relative = 1e-12
+        );
+
+        let no_rest = SpectralAxis::from_frequencies(&[ghz(1.0)]);
+        assert_eq!(
+            no_rest.velocities().unwrap_err(),
+            SpectrumError::MissingRestValue
+        );
+    }
+
+    #[test]
+    fn test_slice() {
+        let s = spectrum();
+        let sub = s.slice(ghz(115.35), ghz(115.15)).unwrap();
+        assert_eq!(sub.flux, vec![2.0, 3.0]);
+        assert_eq!(sub.len(), sub.spectral_axis.len());
+
+        // Bounds in velocity are converted through the rest frequency.
+        let v = s.spectral_axis.velocities().unwrap();
+        let sub = s.slice((v[6] + v[7]) / 2.0, (v[9] + v[10]) / 2.0).unwrap();
+        assert_eq!(sub.flux, vec![7.0, 8.0, 9.0]);
+
+        assert_eq!(
+            s.slice(ghz(200.0), ghz(201.0)).unwrap_err(),
+            SpectrumError::EmptySelection
+        );
+        assert_eq!(s.slice_index(1..3).flux, vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_iter_and_uncertainty() {
+        let s = spectrum()
+            .with_uncertainty(Uncertainty::Variance(vec![4.0; 11]))
+            .unwrap()
+            .with_mask((0..11).map(|i| i == 3).collect())
+            .unwrap();
+        let points: Vec<SpectralPoint> = s.iter().collect();
+        assert_eq!(points.len(), 11);
+        assert_eq!(points[3].flux, 3.0);
+        assert!(points[3].masked);
+        assert_eq!(points[0].uncertainty, Some(2.0));
+        assert_eq!(points[0].spectral, SpectralValue::Frequency(ghz(115.0)));
+        assert_relative_eq!(Uncertainty::InverseVariance(vec![0.25]).std_dev()[0], 2.0);
+    }
+
+    #[test]
+    fn test_bin_edges() {
+        let axis = SpectralAxis::from_si(SpectralKind::Frequency, vec![1.0, 2.0, 4.0]);
+        assert_eq!(axis.bin_edges(), vec![0.5, 1.5, 3.0, 5.0]);
+    }
+
+    #[test]
+    fn test_to_frame() {
+        use crate::coordinates::{EarthLocation, SkyCoord};
+        use crate::time::Time;
+        use crate::units::angle::degree;
+        use crate::units::f64::Angle;
+
+        let observation = Observation::new(
+            SkyCoord::new(Angle::new::<degree>(83.82), Angle::new::<degree>(-5.39)),
+            EarthLocation::from_geodetic(
+                Angle::new::<degree>(-67.755),
+                Angle::new::<degree>(-23.029),
+                Length::new::<meter>(5_058.7),
+            ),
+            Time::from_calendar_utc(2022, 11, 20, 4, 30, 0.0),
+        );
+        assert_eq!(
+            spectrum()
+                .to_frame(VelocityFrame::Lsrk, &observation)
+                .unwrap_err(),
+            SpectrumError::MissingFrame
+        );
+        let topo = spectrum()
+            .spectral_axis
+            .with_frame(VelocityFrame::Topocentric);
+        let lsrk = topo.to_frame(VelocityFrame::Lsrk, &observation).unwrap();
+        assert_eq!(lsrk.frame(), Some(VelocityFrame::Lsrk));
+        let factor = observation.doppler_factor(VelocityFrame::Topocentric, VelocityFrame::Lsrk);
+        assert_relative_eq!(lsrk.values()[0], 115e9 * factor, max_relative = 1e-12);
+
+        // Near the rest frequency a velocity axis shifts by the velocity correction.
+        let v_topo = topo.to_kind(SpectralKind::Velocity).unwrap();
+        let v_lsrk = v_topo.to_frame(VelocityFrame::Lsrk, &observation).unwrap();
+        let correction = observation
+            .velocity_correction(VelocityFrame::Topocentric, VelocityFrame::Lsrk)
+            .get::<meter_per_second>();
+        assert_relative_eq!(
+            v_lsrk.values()[3] - v_topo.values()[3],
+            correction,
+            epsilon = 2.0
+        );
+        let back = v_lsrk
+            .to_frame(VelocityFrame::Topocentric, &observation)
+            .unwrap();
+        assert_relative_eq!(back.values()[5], v_topo.values()[5], epsilon = 1e-6);
+        let wavelengths = topo
+            .to_kind(SpectralKind::Wavelength)
+            .unwrap()
+            .to_frame(VelocityFrame::Lsrk, &observation)
+            .unwrap()
+            .to_kind(SpectralKind::Frequency)
+            .unwrap();
+        assert_relative_eq!(
+            wavelengths.values()[0],
+            lsrk.values()[0],
+            max_relative = 1e-12
+        );
+    }
+}
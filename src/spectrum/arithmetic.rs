@@ -0,0 +1,391 @@
+//! Arithmetic between spectra and with scalars.
+//!
+//! Operations between two spectra return a `Result`: the spectral axes must match and, for
+//! addition and subtraction, so must the flux units. Masks are combined with a logical or and
+//! uncertainties are propagated assuming uncorrelated errors, as astropy's `NDData` does.
+//! The result keeps the uncertainty flavour of the left operand (or of the right one if the
+//! left has none).
+//!
+//! ```
+//! use spectre::spectrum::{SpectralAxis, SpectralKind, Spectrum1D, Uncertainty};
+//!
+//! let axis = SpectralAxis::from_si(SpectralKind::Frequency, vec![1e9, 2e9]);
+//! let on = Spectrum1D::new(axis.clone(), vec![5.0, 6.0], "K")?
+//!     .with_uncertainty(Uncertainty::StdDev(vec![3.0, 3.0]))?;
+//! let off = Spectrum1D::new(axis, vec![1.0, 2.0], "K")?
+//!     .with_uncertainty(Uncertainty::StdDev(vec![4.0, 4.0]))?;
+//!
+//! let diff = (&on - &off)?;
+//! assert_eq!(diff.flux(), [4.0, 4.0]);
+//! assert_eq!(diff.uncertainty(), Some(&Uncertainty::StdDev(vec![5.0, 5.0])));
+//! # Ok::<(), spectre::errors::spectrum::SpectrumError>(())
+//! ```
+
+use std::ops::{Add, Div, Mul, Sub};
+
+use super::{SpectralAxis, Spectrum1D, Uncertainty};
+use crate::errors::spectrum::SpectrumError;
+
+/// Relative tolerance when comparing the spectral axes of two operands.
+const AXIS_RTOL: f64 = 1e-10;
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Op {
+    fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            Self::Add => a + b,
+            Self::Sub => a - b,
+            Self::Mul => a * b,
+            Self::Div => a / b,
+        }
+    }
+
+    /// Variance of `a op b` for uncorrelated `a` and `b`.
+    fn variance(self, a: f64, var_a: f64, b: f64, var_b: f64) -> f64 {
+        match self {
+            Self::Add | Self::Sub => var_a + var_b,
+            Self::Mul => b * b * var_a + a * a * var_b,
+            Self::Div => var_a / (b * b) + a * a * var_b / b.powi(4),
+        }
+    }
+
+    fn unit(self, a: &str, b: &str) -> String {
+        match self {
+            Self::Add | Self::Sub => a.to_string(),
+            Self::Mul => unit_product(a, b),
+            Self::Div => unit_quotient(a, b),
+        }
+    }
+}
+
+/// The unit string of the product of quantities in units `a` and `b`. A left operand that is
+/// itself a quotient is parenthesised, so the result has at most one bare `/`, as FITS
+/// requires: `Jy/beam` times `m / s` is `(Jy/beam) m / s`. The right operand needs no
+/// parentheses, since `a n / d` reads as `(a n) / d`, which equals `a (n / d)`.
+pub(crate) fn unit_product(a: &str, b: &str) -> String {
+    match (a, b) {
+        ("", b) => b.to_string(),
+        (a, "") => a.to_string(),
+        (a, b) => match b.strip_prefix("1 / ") {
+            Some(denominator) => unit_quotient(a, denominator),
+            None => format!("{} {b}", group(a, &['/'])),
+        },
+    }
+}
+
+/// The unit string of the quotient of quantities in units `a` and `b`, e.g. `K / (Jy/beam)`.
+fn unit_quotient(a: &str, b: &str) -> String {
+    match (a, b) {
+        (a, b) if a == b => String::new(),
+        (a, "") => a.to_string(),
+        ("", b) => format!("1 / {}", group(b, &[' ', '/'])),
+        (a, b) => format!("{} / {}", group(a, &['/']), group(b, &[' ', '/'])),
+    }
+}
+
+/// `unit` in parentheses if it contains any of `separators`.
+fn group(unit: &str, separators: &[char]) -> String {
+    if unit.contains(separators) {
+        format!("({unit})")
+    } else {
+        unit.to_string()
+    }
+}
+
+impl Uncertainty {
+    fn scaled(&self, factor: f64) -> Self {
+        let factor = factor.abs();
+        match self {
+            Self::StdDev(v) => Self::StdDev(v.iter().map(|x| x * factor).collect()),
+            Self::Variance(v) => Self::Variance(v.iter().map(|x| x * factor * factor).collect()),
+            Self::InverseVariance(v) => {
+                Self::InverseVariance(v.iter().map(|x| x / (factor * factor)).collect())
+            }
+        }
+    }
+}
+
+impl SpectralAxis {
+    /// Whether two axes have the same kind, rest frequency, Doppler convention, frame and
+    /// channel values.
+    pub fn matches(&self, other: &Self) -> bool {
+        let scale = self
+            .values
+            .iter()
+            .fold(0.0_f64, |acc, v| acc.max(v.abs()))
+            .max(f64::MIN_POSITIVE);
+        self.kind == other.kind
+            && self.rest == other.rest
+            && self.convention == other.convention
+            && self.frame == other.frame
+            && self.len() == other.len()
+            && self
+                .values
+                .iter()
+                .zip(&other.values)
+                .all(|(a, b)| (a - b).abs() <= AXIS_RTOL * scale)
+    }
+}
+
+impl Spectrum1D {
+    fn combine(&self, other: &Self, op: Op) -> Result<Self, SpectrumError> {
+        if !self.spectral_axis.matches(&other.spectral_axis) {
+            return Err(SpectrumError::SpectralAxisMismatch);
+        }
+        if matches!(op, Op::Add | Op::Sub) && self.unit != other.unit {
+            return Err(SpectrumError::UnitMismatch(
+                self.unit.clone(),
+                other.unit.clone(),
+            ));
+        }
+
+        let flux = self
+            .flux
+            .iter()
+            .zip(&other.flux)
+            .map(|(&a, &b)| op.apply(a, b))
+            .collect();
+
+        let uncertainty = match (&self.uncertainty, &other.uncertainty) {
+            (None, None) => None,
+            (left, right) => {
+                let like = left.as_ref().or(right.as_ref()).unwrap();
+                let zeros = || vec![0.0; self.len()];
+                let var_a = left.as_ref().map_or_else(zeros, Uncertainty::variance);
+                let var_b = right.as_ref().map_or_else(zeros, Uncertainty::variance);
+                let variance = (0..self.len())
+                    .map(|i| op.variance(self.flux[i], var_a[i], other.flux[i], var_b[i]))
+                    .collect();
+                Some(like.with_variance(variance))
+            }
+        };
+
+        Ok(Self {
+            spectral_axis: self.spectral_axis.clone(),
+            flux,
+            unit: op.unit(&self.unit, &other.unit),
+            uncertainty,
+            mask: self
+                .mask
+                .iter()
+                .zip(&other.mask)
+                .map(|(a, b)| a | b)
+                .collect(),
+            meta: self.meta.clone(),
+        })
+    }
+
+    fn combine_scalar(&self, scalar: f64, op: Op) -> Self {
+        let uncertainty = match op {
+            Op::Add | Op::Sub => self.uncertainty.clone(),
+            Op::Mul => self.uncertainty.as_ref().map(|u| u.scaled(scalar)),
+            Op::Div => self.uncertainty.as_ref().map(|u| u.scaled(scalar.recip())),
+        };
+        Self {
+            flux: self.flux.iter().map(|&a| op.apply(a, scalar)).collect(),
+            uncertainty,
+            ..self.clone()
+        }
+    }
+}
+
+macro_rules! impl_ops {
+    ($($trait:ident, $method:ident, $op:expr;)+) => {
+        $(
+            impl $trait<&Spectrum1D> for &Spectrum1D {
+                type Output = Result<Spectrum1D, SpectrumError>;
+
+                fn $method(self, other: &Spectrum1D) -> Self::Output {
+                    self.combine(other, $op)
+                }
+            }
+
+            impl $trait<Spectrum1D> for Spectrum1D {
+                type Output = Result<Spectrum1D, SpectrumError>;
+
+                fn $method(self, other: Spectrum1D) -> Self::Output {
+                    self.combine(&other, $op)
+                }
+            }
+
+            impl $trait<f64> for &Spectrum1D {
+                type Output = Spectrum1D;
+
+                fn $method(self, scalar: f64) -> Self::Output {
+                    self.combine_scalar(scalar, $op)
+                }
+            }
+
+            impl $trait<f64> for Spectrum1D {
+                type Output = Spectrum1D;
+
+                fn $method(self, scalar: f64) -> Self::Output {
+                    self.combine_scalar(scalar, $op)
+                }
+            }
+        )+
+    };
+}
+
+impl_ops! {
+    Add, add, Op::Add;
+    Sub, sub, Op::Sub;
+    Mul, mul, Op::Mul;
+    Div, div, Op::Div;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectrum::SpectralKind;
+    use crate::units::equivalencies::DopplerConvention;
+    use crate::units::f64::Frequency;
+    use crate::units::frequency::gigahertz;
+    use approx::assert_relative_eq;
+
+    fn axis() -> SpectralAxis {
+        SpectralAxis::from_si(SpectralKind::Frequency, vec![1e9, 2e9, 3e9])
+    }
+
+    fn spectrum(flux: Vec<f64>, unit: &str, uncertainty: Option<Uncertainty>) -> Spectrum1D {
+        let s = Spectrum1D::new(axis(), flux, unit).unwrap();
+        match uncertainty {
+            Some(u) => s.with_uncertainty(u).unwrap(),
+            None => s,
+        }
+    }
+
+    #[test]
+    fn test_add_sub_propagation() {
+        let a = spectrum(
+            vec![1.0, 2.0, 3.0],
+            "Jy",
+            Some(Uncertainty::StdDev(vec![3.0; 3])),
+        );
+        let b = spectrum(
+            vec![1.0, 1.0, 1.0],
+            "Jy",
+            Some(Uncertainty::Variance(vec![16.0; 3])),
+        );
+
+        let sum = (&a + &b).unwrap();
+        assert_eq!(sum.flux, vec![2.0, 3.0, 4.0]);
+        assert_eq!(sum.uncertainty, Some(Uncertainty::StdDev(vec![5.0; 3])));
+
+        let diff = (&b - &a).unwrap();
+        assert_eq!(diff.flux, vec![0.0, -1.0, -2.0]);
+        assert_eq!(diff.uncertainty, Some(Uncertainty::Variance(vec![25.0; 3])));
+
+        // Only one operand with an uncertainty.
+        let c = spectrum(vec![0.0; 3], "Jy", None);
+        let sum = (c + a.clone()).unwrap();
+        assert_eq!(sum.uncertainty, a.uncertainty);
+    }
+
+    #[test]
+    fn test_mul_div_propagation() {
+        let a = spectrum(vec![2.0; 3], "K", Some(Uncertainty::StdDev(vec![0.2; 3])));
+        let b = spectrum(
+            vec![4.0; 3],
+            "K",
+            Some(Uncertainty::InverseVariance(vec![1.0 / 0.16; 3])),
+        );
+
+        let prod = (&a * &b).unwrap();
+        assert_eq!(prod.flux, vec![8.0; 3]);
+        assert_eq!(prod.unit, "K K");
+        // sigma^2 = (4 * 0.2)^2 + (2 * 0.4)^2
+        let sigma = prod.uncertainty.unwrap().std_dev();
+        assert_relative_eq!(sigma[0], (0.64_f64 + 0.64).sqrt(), max_relative = 1e-12);
+
+        let ratio = (&a / &b).unwrap();
+        assert_eq!(ratio.flux, vec![0.5; 3]);
+        assert_eq!(ratio.unit, "");
+        // (sigma_f / f)^2 = (0.2 / 2)^2 + (0.4 / 4)^2
+        let sigma = ratio.uncertainty.unwrap().std_dev();
+        assert_relative_eq!(sigma[0], 0.5 * 0.02_f64.sqrt(), max_relative = 1e-12);
+    }
+
+    #[test]
+    fn test_scalar_ops() {
+        let a = spectrum(
+            vec![1.0, 2.0, 3.0],
+            "K",
+            Some(Uncertainty::Variance(vec![1.0; 3])),
+        );
+        let b = &a * -2.0;
+        assert_eq!(b.flux, vec![-2.0, -4.0, -6.0]);
+        assert_eq!(b.uncertainty, Some(Uncertainty::Variance(vec![4.0; 3])));
+        let c = a.clone() - 1.0;
+        assert_eq!(c.flux, vec![0.0, 1.0, 2.0]);
+        assert_eq!(c.uncertainty, a.uncertainty);
+        let d = a / 2.0;
+        assert_eq!(d.uncertainty, Some(Uncertainty::Variance(vec![0.25; 3])));
+    }
+
+    #[test]
+    fn test_masks_combine() {
+        let a = spectrum(vec![1.0; 3], "K", None)
+            .with_mask(vec![true, false, false])
+            .unwrap();
+        let b = spectrum(vec![1.0; 3], "K", None)
+            .with_mask(vec![false, false, true])
+            .unwrap();
+        assert_eq!((a + b).unwrap().mask, vec![true, false, true]);
+    }
+
+    #[test]
+    fn test_mismatch_errors() {
+        let a = spectrum(vec![1.0; 3], "K", None);
+        let b = spectrum(vec![1.0; 3], "Jy", None);
+        assert_eq!(
+            (&a + &b).unwrap_err(),
+            SpectrumError::UnitMismatch("K".into(), "Jy".into())
+        );
+        assert_eq!((&a * &b).unwrap().unit, "K Jy");
+        assert_eq!((&a / &b).unwrap().unit, "K / Jy");
+
+        let beam = spectrum(vec![1.0; 3], "Jy / beam", None);
+        assert_eq!((&beam * &a).unwrap().unit, "(Jy / beam) K");
+        assert_eq!((&a * &beam).unwrap().unit, "K Jy / beam");
+        assert_eq!((&beam / &a).unwrap().unit, "(Jy / beam) / K");
+        assert_eq!((&a / &beam).unwrap().unit, "K / (Jy / beam)");
+
+        let shifted = Spectrum1D::new(
+            SpectralAxis::from_si(SpectralKind::Frequency, vec![1e9, 2e9, 3.1e9]),
+            vec![1.0; 3],
+            "K",
+        )
+        .unwrap();
+        assert_eq!(
+            (&a - &shifted).unwrap_err(),
+            SpectrumError::SpectralAxisMismatch
+        );
+
+        let with_rest = a.with_rest(Frequency::new::<gigahertz>(1.0), DopplerConvention::Radio);
+        assert_eq!(
+            (&a - &with_rest).unwrap_err(),
+            SpectrumError::SpectralAxisMismatch
+        );
+
+        // Radio and optical velocities with the same numbers are different axes.
+        let velocity = |convention| {
+            let axis = SpectralAxis::from_si(SpectralKind::Velocity, vec![-1e3, 0.0, 1e3])
+                .with_rest(Frequency::new::<gigahertz>(1.0), convention);
+            Spectrum1D::new(axis, vec![1.0; 3], "K").unwrap()
+        };
+        let radio = velocity(DopplerConvention::Radio);
+        assert!((&radio + &radio).is_ok());
+        assert_eq!(
+            (&radio + &velocity(DopplerConvention::Optical)).unwrap_err(),
+            SpectrumError::SpectralAxisMismatch
+        );
+    }
+}
@@ -0,0 +1,398 @@
+//! Spectral regions: unions of disjoint spectral ranges, after `specutils.SpectralRegion`.
+//!
+//! ```
+//! use spectre::spectrum::SpectralRegion;
+//! use spectre::units::f64::Frequency;
+//! use spectre::units::frequency::gigahertz;
+//!
+//! let ghz = Frequency::new::<gigahertz>;
+//! let lines = SpectralRegion::new(ghz(100.0), ghz(100.1))?
+//!     .union(&SpectralRegion::new(ghz(100.5), ghz(100.6))?)?;
+//! let continuum = lines.invert(ghz(99.0), ghz(101.0))?;
+//! assert_eq!(continuum.len(), 3);
+//! assert!(lines.intersection(&continuum)?.is_empty());
+//! # Ok::<(), spectre::errors::spectrum::SpectrumError>(())
+//! ```
+
+use super::{SpectralAxis, SpectralKind, SpectralValue, Spectrum1D};
+use crate::errors::spectrum::SpectrumError;
+use crate::units::equivalencies::DopplerConvention;
+use crate::units::f64::Frequency;
+
+/// One or more disjoint spectral ranges of a single kind.
+///
+/// Bounds are stored in SI base units, sorted, with overlapping or touching ranges merged.
+/// Bounds are inclusive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpectralRegion {
+    kind: SpectralKind,
+    bounds: Vec<(f64, f64)>,
+}
+
+impl SpectralRegion {
+    /// A region covering `lo` to `hi`, in either order.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::KindMismatch`] if the bounds are of different kinds.
+    pub fn new(
+        lo: impl Into<SpectralValue>,
+        hi: impl Into<SpectralValue>,
+    ) -> Result<Self, SpectrumError> {
+        let (lo, hi) = (lo.into(), hi.into());
+        check_kind(lo.kind(), hi.kind())?;
+        Ok(Self::from_si(lo.kind(), [(lo.si(), hi.si())]))
+    }
+
+    /// A region from ranges given in the SI base unit of `kind`.
+    pub fn from_si(kind: SpectralKind, bounds: impl IntoIterator<Item = (f64, f64)>) -> Self {
+        let mut bounds: Vec<(f64, f64)> = bounds
+            .into_iter()
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .collect();
+        bounds.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut merged: Vec<(f64, f64)> = Vec::with_capacity(bounds.len());
+        for (lo, hi) in bounds {
+            match merged.last_mut() {
+                Some(last) if lo <= last.1 => last.1 = last.1.max(hi),
+                _ => merged.push((lo, hi)),
+            }
+        }
+        Self {
+            kind,
+            bounds: merged,
+        }
+    }
+
+    /// An empty region of `kind`.
+    pub fn empty(kind: SpectralKind) -> Self {
+        Self {
+            kind,
+            bounds: Vec::new(),
+        }
+    }
+
+    pub fn kind(&self) -> SpectralKind {
+        self.kind
+    }
+
+    /// Sub-ranges in SI base units, in increasing order.
+    pub fn bounds(&self) -> &[(f64, f64)] {
+        &self.bounds
+    }
+
+    /// Sub-ranges as spectral values, in increasing order.
+    pub fn subregions(&self) -> Vec<(SpectralValue, SpectralValue)> {
+        self.bounds
+            .iter()
+            .map(|&(lo, hi)| {
+                (
+                    SpectralValue::from_si(self.kind, lo),
+                    SpectralValue::from_si(self.kind, hi),
+                )
+            })
+            .collect()
+    }
+
+    /// Number of disjoint sub-ranges.
+    pub fn len(&self) -> usize {
+        self.bounds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bounds.is_empty()
+    }
+
+    /// Whether a value in the SI base unit of the region's kind lies inside the region.
+    pub fn contains(&self, value: f64) -> bool {
+        self.bounds
+            .iter()
+            .any(|&(lo, hi)| (lo..=hi).contains(&value))
+    }
+
+    /// Ranges covered by either region.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::KindMismatch`] if the regions are of different kinds.
+    pub fn union(&self, other: &Self) -> Result<Self, SpectrumError> {
+        check_kind(self.kind, other.kind)?;
+        Ok(Self::from_si(
+            self.kind,
+            self.bounds.iter().chain(&other.bounds).copied(),
+        ))
+    }
+
+    /// Ranges covered by both regions. Ranges that only touch intersect at the shared bound.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::KindMismatch`] if the regions are of different kinds.
+    pub fn intersection(&self, other: &Self) -> Result<Self, SpectrumError> {
+        check_kind(self.kind, other.kind)?;
+        let overlaps = self.bounds.iter().flat_map(|&(a_lo, a_hi)| {
+            other.bounds.iter().filter_map(move |&(b_lo, b_hi)| {
+                let (lo, hi) = (a_lo.max(b_lo), a_hi.min(b_hi));
+                (lo <= hi).then_some((lo, hi))
+            })
+        });
+        Ok(Self::from_si(self.kind, overlaps.collect::<Vec<_>>()))
+    }
+
+    /// The complement of the region between `lo` and `hi`, inclusive.
+    ///
+    /// The bounds of the region itself are left out of the complement: each gap starts and
+    /// ends at the next representable value beyond the region, so no value lies in both.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::KindMismatch`] if `lo` or `hi` is not of the region's kind.
+    pub fn invert(
+        &self,
+        lo: impl Into<SpectralValue>,
+        hi: impl Into<SpectralValue>,
+    ) -> Result<Self, SpectrumError> {
+        let (lo, hi) = (lo.into(), hi.into());
+        check_kind(self.kind, lo.kind())?;
+        check_kind(self.kind, hi.kind())?;
+        let (a, b) = (lo.si(), hi.si());
+        let (lo, hi) = (a.min(b), a.max(b));
+        let mut gaps = Vec::with_capacity(self.len() + 1);
+        let mut start = lo;
+        for &(r_lo, r_hi) in &self.bounds {
+            if r_lo > start {
+                gaps.push((start, r_lo.next_down().min(hi)));
+            }
+            start = start.max(r_hi.next_up());
+            if start > hi {
+                break;
+            }
+        }
+        if start <= hi {
+            gaps.push((start, hi));
+        }
+        gaps.retain(|&(a, b)| a <= b);
+        Ok(Self::from_si(self.kind, gaps))
+    }
+
+    /// The complement of the region within the span of `spectrum`'s spectral axis.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::MissingRestValue`] if the region cannot be converted to the
+    /// axis kind, or [`SpectrumError::EmptySelection`] for an empty spectrum.
+    pub fn invert_from_spectrum(&self, spectrum: &Spectrum1D) -> Result<Self, SpectrumError> {
+        let axis = &spectrum.spectral_axis;
+        let region = self.to_axis(axis)?;
+        let (lo, hi) = axis
+            .values
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| {
+                (lo.min(v), hi.max(v))
+            });
+        if lo > hi {
+            return Err(SpectrumError::EmptySelection);
+        }
+        region.invert(
+            SpectralValue::from_si(axis.kind, lo),
+            SpectralValue::from_si(axis.kind, hi),
+        )
+    }
+
+    /// Convert to another spectral kind, using `rest` and `convention` for velocities.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::MissingRestValue`] if the conversion goes through a velocity
+    /// and `rest` is `None`.
+    pub fn to_kind(
+        &self,
+        kind: SpectralKind,
+        rest: Option<Frequency>,
+        convention: DopplerConvention,
+    ) -> Result<Self, SpectrumError> {
+        let target = SpectralAxis {
+            kind,
+            values: Vec::new(),
+            rest,
+            convention,
+            frame: None,
+        };
+        self.to_axis(&target)
+    }
+
+    /// Convert to the kind of `axis`, using its rest frequency and convention.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::MissingRestValue`] if the conversion goes through a velocity
+    /// and the axis has no rest frequency.
+    pub fn to_axis(&self, axis: &SpectralAxis) -> Result<Self, SpectrumError> {
+        if axis.kind == self.kind {
+            return Ok(self.clone());
+        }
+        let convert = |v: f64| axis.convert_value(SpectralValue::from_si(self.kind, v));
+        let bounds = self
+            .bounds
+            .iter()
+            .map(|&(lo, hi)| Ok((convert(lo)?, convert(hi)?)))
+            .collect::<Result<Vec<_>, SpectrumError>>()?;
+        Ok(Self::from_si(axis.kind, bounds))
+    }
+
+    /// For each channel of `axis`, whether it lies inside the region.
+    ///
+    /// # Errors
+    /// As [`to_axis`](Self::to_axis).
+    pub fn channel_mask(&self, axis: &SpectralAxis) -> Result<Vec<bool>, SpectrumError> {
+        let region = self.to_axis(axis)?;
+        Ok(axis.values.iter().map(|&v| region.contains(v)).collect())
+    }
+}
+
+impl Spectrum1D {
+    /// Spectrum restricted to the channels inside `region`, concatenated in channel order.
+    ///
+    /// # Errors
+    /// Returns [`SpectrumError::MissingRestValue`] if the region cannot be converted to the
+    /// axis kind, or [`SpectrumError::EmptySelection`] if no channel lies in the region.
+    pub fn extract_region(&self, region: &SpectralRegion) -> Result<Self, SpectrumError> {
+        let inside = region.channel_mask(&self.spectral_axis)?;
+        let indices: Vec<usize> = (0..self.len()).filter(|&i| inside[i]).collect();
+        if indices.is_empty() {
+            return Err(SpectrumError::EmptySelection);
+        }
+        Ok(self.select(&indices))
+    }
+
+    /// One spectrum per sub-range of `region`, in increasing spectral order of the region.
+    ///
+    /// # Errors
+    /// As [`extract_region`](Self::extract_region), for any sub-range.
+    pub fn extract_subregions(&self, region: &SpectralRegion) -> Result<Vec<Self>, SpectrumError> {
+        region
+            .to_axis(&self.spectral_axis)?
+            .subregions()
+            .into_iter()
+            .map(|(lo, hi)| self.slice(lo, hi))
+            .collect()
+    }
+}
+
+fn check_kind(a: SpectralKind, b: SpectralKind) -> Result<(), SpectrumError> {
+    if a == b {
+        Ok(())
+    } else {
+        Err(SpectrumError::KindMismatch(a, b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::f64::Velocity;
+    use crate::units::frequency::{gigahertz, megahertz};
+    use crate::units::velocity::kilometer_per_second;
+    use approx::assert_relative_eq;
+
+    fn hz(lo: f64, hi: f64) -> SpectralRegion {
+        SpectralRegion::from_si(SpectralKind::Frequency, [(lo, hi)])
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let a = hz(1.0, 3.0).union(&hz(5.0, 7.0)).unwrap();
+        let b = hz(2.0, 6.0);
+        assert_eq!(a.union(&b).unwrap().bounds(), &[(1.0, 7.0)]);
+        assert_eq!(
+            a.intersection(&b).unwrap().bounds(),
+            &[(2.0, 3.0), (5.0, 6.0)]
+        );
+        let f = |v| SpectralValue::from_si(SpectralKind::Frequency, v);
+        // The complement excludes the bounds of the region.
+        let (before, after) = (f64::next_down, f64::next_up);
+        let outside = a.invert(f(0.0), f(10.0)).unwrap();
+        assert_eq!(
+            outside.bounds(),
+            &[
+                (0.0, before(1.0)),
+                (after(3.0), before(5.0)),
+                (after(7.0), 10.0)
+            ]
+        );
+        assert!(a.intersection(&outside).unwrap().is_empty());
+        assert!(!outside.contains(1.0) && !outside.contains(7.0));
+        assert_eq!(
+            a.invert(f(2.0), f(6.0)).unwrap().bounds(),
+            &[(after(3.0), before(5.0))]
+        );
+        let v = |v| SpectralValue::from_si(SpectralKind::Velocity, v);
+        assert_eq!(
+            a.invert(v(0.0), v(10.0)).unwrap_err(),
+            SpectrumError::KindMismatch(SpectralKind::Frequency, SpectralKind::Velocity)
+        );
+        assert!(a.contains(6.5) && !a.contains(4.0));
+        // Bounds are inclusive, so touching and degenerate ranges survive intersection.
+        assert_eq!(
+            hz(2.0, 2.0).intersection(&hz(1.0, 3.0)).unwrap().bounds(),
+            &[(2.0, 2.0)]
+        );
+        assert_eq!(
+            hz(1.0, 2.0).intersection(&hz(2.0, 3.0)).unwrap().bounds(),
+            &[(2.0, 2.0)]
+        );
+
+        let v = SpectralRegion::from_si(SpectralKind::Velocity, [(0.0, 1.0)]);
+        assert_eq!(
+            a.union(&v).unwrap_err(),
+            SpectrumError::KindMismatch(SpectralKind::Frequency, SpectralKind::Velocity)
+        );
+    }
+
+    #[test]
+    fn test_conversion() {
+        let rest = Frequency::new::<gigahertz>(100.0);
+        let region = SpectralRegion::new(
+            Velocity::new::<kilometer_per_second>(-10.0),
+            Velocity::new::<kilometer_per_second>(10.0),
+        )
+        .unwrap();
+        assert_eq!(
+            region
+                .to_kind(SpectralKind::Frequency, None, DopplerConvention::Radio)
+                .unwrap_err(),
+            SpectrumError::MissingRestValue
+        );
+        let freq = region
+            .to_kind(
+                SpectralKind::Frequency,
+                Some(rest),
+                DopplerConvention::Radio,
+            )
+            .unwrap();
+        let (lo, hi) = freq.bounds()[0];
+        assert!(lo < hi);
+        assert_relative_eq!(hi - lo, 2e11 * 1e4 / 299_792_458.0, max_relative = 1e-12);
+    }
+
+    #[test]
+    fn test_extract() {
+        let x: Vec<f64> = (0..10).map(|i| 1e9 + 1e6 * f64::from(i)).collect();
+        let s = Spectrum1D::new(
+            SpectralAxis::from_si(SpectralKind::Frequency, x),
+            (0..10).map(f64::from).collect(),
+            "K",
+        )
+        .unwrap();
+        let mhz = Frequency::new::<megahertz>;
+        let region = SpectralRegion::new(mhz(1001.0), mhz(1002.0))
+            .unwrap()
+            .union(&SpectralRegion::new(mhz(1006.5), mhz(1008.0)).unwrap())
+            .unwrap();
+        assert_eq!(
+            s.extract_region(&region).unwrap().flux,
+            vec![1.0, 2.0, 7.0, 8.0]
+        );
+        let parts = s.extract_subregions(&region).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1].flux, vec![7.0, 8.0]);
+        let outside = region.invert_from_spectrum(&s).unwrap();
+        assert_eq!(
+            s.extract_region(&outside).unwrap().flux,
+            vec![0.0, 3.0, 4.0, 5.0, 6.0, 9.0]
+        );
+    }
+}
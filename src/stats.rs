@@ -0,0 +1,90 @@
+//! Robust statistics, modelled on `astropy.stats`.
+//!
+//! NaN values are ignored, and statistics of an empty set are NaN.
+
+/// Scale factor from the median absolute deviation to the standard deviation of a Gaussian,
+/// 1 / Φ⁻¹(3/4).
+pub const MAD_TO_STD: f64 = 1.482_602_218_505_602;
+
+/// Median of the values that are not NaN. Infinities are kept, as by `numpy.nanmedian`.
+pub fn median(values: &[f64]) -> f64 {
+    let mut sorted: Vec<f64> = values.iter().copied().filter(|v| !v.is_nan()).collect();
+    let n = sorted.len();
+    if n == 0 {
+        return f64::NAN;
+    }
+    sorted.sort_by(f64::total_cmp);
+    if n % 2 == 1 {
+        sorted[n / 2]
+    } else {
+        0.5 * (sorted[n / 2 - 1] + sorted[n / 2])
+    }
+}
+
+/// Median absolute deviation from the median.
+pub fn median_absolute_deviation(values: &[f64]) -> f64 {
+    let m = median(values);
+    let deviations: Vec<f64> = values.iter().map(|v| (v - m).abs()).collect();
+    median(&deviations)
+}
+
+/// Standard deviation estimated from the median absolute deviation, robust to outliers
+/// such as spectral lines.
+pub fn mad_std(values: &[f64]) -> f64 {
+    MAD_TO_STD * median_absolute_deviation(values)
+}
+
+/// Mean, median and standard deviation of the values left after iteratively rejecting those
+/// more than `sigma` standard deviations from the median, as `sigma_clipped_stats`. Clipping
+/// stops when no value is rejected or after `max_iters` iterations.
+pub fn sigma_clipped_stats(values: &[f64], sigma: f64, max_iters: usize) -> (f64, f64, f64) {
+    let mut kept: Vec<f64> = values.iter().copied().filter(|v| !v.is_nan()).collect();
+    for _ in 0..max_iters {
+        let (_, centre, std) = mean_median_std(&kept);
+        let len = kept.len();
+        kept.retain(|v| (v - centre).abs() <= sigma * std);
+        if kept.len() == len {
+            break;
+        }
+    }
+    mean_median_std(&kept)
+}
+
+fn mean_median_std(values: &[f64]) -> (f64, f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, median(values), variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&[3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&[4.0, f64::NAN, 1.0, 2.0, 3.0]), 2.5);
+        assert!(median(&[f64::NAN]).is_nan());
+        assert_eq!(median(&[f64::INFINITY, 1.0, f64::NAN, 2.0]), 2.0);
+    }
+
+    #[test]
+    fn test_mad_std() {
+        let values = [1.0, 2.0, 3.0, 4.0, 100.0];
+        assert_eq!(median_absolute_deviation(&values), 1.0);
+        assert_relative_eq!(mad_std(&values), MAD_TO_STD);
+    }
+
+    #[test]
+    fn test_sigma_clipped_stats() {
+        let mut values: Vec<f64> = (0..20).map(|i| f64::from(i % 5) - 2.0).collect();
+        values.extend([50.0, f64::NAN]);
+        let (mean, median, std) = sigma_clipped_stats(&values, 3.0, 5);
+        assert_eq!((mean, median), (0.0, 0.0));
+        assert_relative_eq!(std, 2.0_f64.sqrt(), max_relative = 1e-12);
+        let (mean, _, _) = sigma_clipped_stats(&values, 3.0, 0);
+        assert_relative_eq!(mean, 50.0 / 21.0, max_relative = 1e-12);
+    }
+}
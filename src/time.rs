@@ -0,0 +1,150 @@
+//! Instants of observation, a small subset of `astropy.time`.
+//!
+//! A [`Time`] is stored as a UTC Julian date. Terrestrial Time, used as the argument of the
+//! solar-system ephemeris, is derived from it with the table of leap seconds; before 1972
+//! TAI − UTC is taken as 10 s. UT1 is approximated by UTC, which is accurate to 0.9 s.
+
+/// Julian date of the Modified Julian Date epoch.
+pub const MJD_OFFSET: f64 = 2_400_000.5;
+
+/// Julian date of the J2000.0 epoch, 2000-01-01 12:00 TT.
+pub const J2000: f64 = 2_451_545.0;
+
+/// Days per Julian century.
+pub const DAYS_PER_CENTURY: f64 = 36_525.0;
+
+/// TT − TAI in seconds.
+const TT_MINUS_TAI: f64 = 32.184;
+
+/// Leap second table as `(year, month, seconds)`: from the first day of each month, TAI − UTC
+/// is `seconds`.
+const LEAP_SECONDS: [(i64, i64, f64); 28] = [
+    (1972, 1, 10.0),
+    (1972, 7, 11.0),
+    (1973, 1, 12.0),
+    (1974, 1, 13.0),
+    (1975, 1, 14.0),
+    (1976, 1, 15.0),
+    (1977, 1, 16.0),
+    (1978, 1, 17.0),
+    (1979, 1, 18.0),
+    (1980, 1, 19.0),
+    (1981, 7, 20.0),
+    (1982, 7, 21.0),
+    (1983, 7, 22.0),
+    (1985, 7, 23.0),
+    (1988, 1, 24.0),
+    (1990, 1, 25.0),
+    (1991, 1, 26.0),
+    (1992, 7, 27.0),
+    (1993, 7, 28.0),
+    (1994, 7, 29.0),
+    (1996, 1, 30.0),
+    (1997, 7, 31.0),
+    (1999, 1, 32.0),
+    (2006, 1, 33.0),
+    (2009, 1, 34.0),
+    (2012, 7, 35.0),
+    (2015, 7, 36.0),
+    (2017, 1, 37.0),
+];
+
+/// An instant, in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Time {
+    jd_utc: f64,
+}
+
+impl Time {
+    /// Create a time from a UTC Julian date.
+    pub fn from_jd_utc(jd: f64) -> Self {
+        Self { jd_utc: jd }
+    }
+
+    /// Create a time from a UTC Modified Julian Date.
+    pub fn from_mjd_utc(mjd: f64) -> Self {
+        Self::from_jd_utc(mjd + MJD_OFFSET)
+    }
+
+    /// Create a time from a Terrestrial Time Julian date.
+    pub fn from_jd_tt(jd: f64) -> Self {
+        let approx = jd - (TT_MINUS_TAI + tai_minus_utc(jd - MJD_OFFSET)) / 86_400.0;
+        Self::from_jd_utc(jd - (TT_MINUS_TAI + tai_minus_utc(approx - MJD_OFFSET)) / 86_400.0)
+    }
+
+    /// Create a time from a proleptic Gregorian UTC calendar date and time of day.
+    pub fn from_calendar_utc(
+        year: i64,
+        month: i64,
+        day: i64,
+        hour: i64,
+        minute: i64,
+        second: f64,
+    ) -> Self {
+        let day_fraction = (hour as f64 * 3_600.0 + minute as f64 * 60.0 + second) / 86_400.0;
+        Self::from_mjd_utc(calendar_to_mjd(year, month, day) as f64 + day_fraction)
+    }
+
+    /// UTC Julian date.
+    pub fn jd_utc(&self) -> f64 {
+        self.jd_utc
+    }
+
+    /// UTC Modified Julian Date.
+    pub fn mjd_utc(&self) -> f64 {
+        self.jd_utc - MJD_OFFSET
+    }
+
+    /// Terrestrial Time Julian date.
+    pub fn jd_tt(&self) -> f64 {
+        self.jd_utc + (TT_MINUS_TAI + tai_minus_utc(self.mjd_utc())) / 86_400.0
+    }
+
+    /// Julian centuries of Terrestrial Time since J2000.0.
+    pub fn centuries_since_j2000(&self) -> f64 {
+        (self.jd_tt() - J2000) / DAYS_PER_CENTURY
+    }
+}
+
+/// Modified Julian Date at 0h of a proleptic Gregorian calendar date.
+fn calendar_to_mjd(year: i64, month: i64, day: i64) -> i64 {
+    let my = (month - 14) / 12;
+    let y = year + my;
+    (1_461 * (y + 4_800)) / 4 + (367 * (month - 2 - 12 * my)) / 12 - (3 * ((y + 4_900) / 100)) / 4
+        + day
+        - 2_432_076
+}
+
+/// TAI − UTC in seconds at a UTC Modified Julian Date.
+fn tai_minus_utc(mjd: f64) -> f64 {
+    LEAP_SECONDS
+        .iter()
+        .rev()
+        .find(|&&(year, month, _)| mjd >= calendar_to_mjd(year, month, 1) as f64)
+        .map_or(LEAP_SECONDS[0].2, |&(_, _, seconds)| seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_calendar() {
+        assert_eq!(calendar_to_mjd(1858, 11, 17), 0);
+        assert_eq!(calendar_to_mjd(2000, 1, 1), 51_544);
+        assert_eq!(calendar_to_mjd(2017, 1, 1), 57_754);
+        let t = Time::from_calendar_utc(2000, 1, 1, 12, 0, 0.0);
+        assert_relative_eq!(t.jd_utc(), J2000);
+    }
+
+    #[test]
+    fn test_time_scales() {
+        let t = Time::from_calendar_utc(2016, 12, 31, 12, 0, 0.0);
+        assert_relative_eq!((t.jd_tt() - t.jd_utc()) * 86_400.0, 68.184, epsilon = 1e-3);
+        let t = Time::from_calendar_utc(2017, 1, 1, 12, 0, 0.0);
+        assert_relative_eq!((t.jd_tt() - t.jd_utc()) * 86_400.0, 69.184, epsilon = 1e-3);
+        let round_trip = Time::from_jd_tt(t.jd_tt());
+        assert_relative_eq!(round_trip.jd_utc(), t.jd_utc(), epsilon = 1e-9);
+    }
+}
@@ -0,0 +1,110 @@
+//! Physical quantities and units.
+//!
+//! Quantities are thin, zero-cost wrappers around `f64` provided by [`uom`], so mixing up
+//! dimensions (adding a frequency to a velocity, say) is a compile-time error. Each quantity
+//! module re-exports the corresponding `uom::si` module and adds the units that astronomy
+//! needs but the SI system does not ship, e.g. milliarcseconds, hour angles and janskys.
+//!
+//! The steradian is dimensionless in SI, so to `uom` a surface brightness has the dimension of
+//! a spectral flux density. [`f64::SurfaceBrightness`] is kept apart as its own type, and
+//! converts to and from [`f64::SpectralFluxDensity`] only through a solid angle.
+//!
+//! Quantities are formatted with `into_format_args`:
+//!
+//! ```
+//! use spectre::units::DisplayStyle::Abbreviation;
+//! use spectre::units::f64::SpectralFluxDensity;
+//! use spectre::units::spectral_flux_density::{jansky, millijansky};
+//!
+//! let s = SpectralFluxDensity::new::<millijansky>(250.0);
+//! assert_eq!(format!("{:.2}", s.into_format_args(jansky, Abbreviation)), "0.25 Jy");
+//! ```
+//!
+//! ```
+//! use spectre::units::angle::{AngleExt, arcsecond, degree};
+//! use spectre::units::f64::Angle;
+//!
+//! let a = Angle::new::<degree>(370.0).wrap_at(Angle::new::<degree>(180.0));
+//! assert!((a.get::<degree>() - 10.0).abs() < 1e-12);
+//! assert!((Angle::new::<arcsecond>(3600.0).get::<degree>() - 1.0).abs() < 1e-12);
+//! ```
+
+/// Add units to an existing `uom::si` quantity for `f64` storage.
+///
+/// This is a reduced form of `uom`'s own `unit!` macro, whose expansion carries
+/// `#[cfg(test)]` items that only compile inside `uom` itself.
+macro_rules! units {
+    (
+        quantity: $quantity:path;
+
+        $($(#[$unit_attr:meta])* @$unit:ident: $coefficient:expr;
+            $abbreviation:expr, $singular:expr, $plural:expr;)+
+    ) => {
+        use $quantity as __quantity;
+
+        $(
+            $(#[$unit_attr])*
+            #[allow(non_camel_case_types)]
+            #[derive(Clone, Copy, Debug, Hash)]
+            pub struct $unit;
+
+            impl uom::si::Unit for $unit {
+                fn abbreviation() -> &'static str {
+                    $abbreviation
+                }
+
+                fn singular() -> &'static str {
+                    $singular
+                }
+
+                fn plural() -> &'static str {
+                    $plural
+                }
+            }
+
+            impl __quantity::Unit for $unit {}
+
+            impl uom::Conversion<f64> for $unit {
+                type T = f64;
+
+                fn coefficient() -> Self::T {
+                    $coefficient
+                }
+            }
+
+            impl __quantity::Conversion<f64> for $unit {}
+        )+
+    };
+}
+
+pub mod angle;
+pub mod column_density;
+pub mod equivalencies;
+pub mod solid_angle;
+pub mod spectral_flux_density;
+pub mod surface_brightness;
+pub mod wavenumber;
+
+pub use uom::fmt::DisplayStyle;
+pub use uom::si::{
+    area, energy, frequency, length, mass, ratio, thermodynamic_temperature, time, velocity,
+    volumetric_power_density,
+};
+
+/// Quantities using `f64` as the underlying storage type.
+pub mod f64 {
+    pub use uom::si::f64::*;
+
+    pub use super::surface_brightness::SurfaceBrightness;
+
+    /// Spectral flux density, e.g. in janskys.
+    pub type SpectralFluxDensity = RadiantExposure;
+    /// Column density, e.g. in cm⁻².
+    pub type ColumnDensity = ArealNumberDensity;
+    /// Spectroscopic wavenumber, e.g. in cm⁻¹.
+    pub type Wavenumber = ReciprocalLength;
+    /// Wavelength.
+    pub type Wavelength = Length;
+    /// Absolute temperature, e.g. excitation or brightness temperature.
+    pub type Temperature = ThermodynamicTemperature;
+}
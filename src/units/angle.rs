@@ -0,0 +1,214 @@
+//! Plane angle.
+//!
+//! Re-exports [`uom::si::angle`] and adds the astronomical units `arcminute`, `arcsecond`,
+//! `milliarcsecond`, `microarcsecond` and `hour_angle`.
+
+use std::f64::consts::TAU;
+use std::fmt;
+
+pub use uom::si::angle::*;
+
+use uom::si::SI;
+
+type AngleF64 = Angle<SI<f64>, f64>;
+
+units! {
+    quantity: uom::si::angle;
+
+    @arcminute: 2.908_882_086_657_216_E-4; "arcmin", "arcminute", "arcminutes";
+    @arcsecond: 4.848_136_811_095_36_E-6; "arcsec", "arcsecond", "arcseconds";
+    @milliarcsecond: 4.848_136_811_095_36_E-9; "mas", "milliarcsecond", "milliarcseconds";
+    @microarcsecond: 4.848_136_811_095_36_E-12; "µas", "microarcsecond", "microarcseconds";
+    /// One hour of right ascension, i.e. 15 degrees.
+    @hour_angle: 2.617_993_877_991_494_E-1; "hourangle", "hour angle", "hour angles";
+}
+
+/// Helpers for working with angles on the sky.
+pub trait AngleExt: Sized {
+    /// Wrap the angle into the range `[wrap_angle - 360°, wrap_angle)`.
+    ///
+    /// This mirrors `astropy.coordinates.Angle.wrap_at`: a wrap angle of 360° gives the
+    /// range `[0°, 360°)` and a wrap angle of 180° gives `[-180°, 180°)`.
+    #[must_use]
+    fn wrap_at(self, wrap_angle: AngleF64) -> Self;
+
+    /// Check whether the angle lies within `[lower, upper)`.
+    fn is_within_bounds(&self, lower: AngleF64, upper: AngleF64) -> bool;
+
+    /// Split the angle into sexagesimal degrees, arcminutes and arcseconds.
+    fn dms(self) -> Sexagesimal;
+
+    /// Split the angle into sexagesimal hours, minutes and seconds.
+    fn hms(self) -> Sexagesimal;
+}
+
+impl AngleExt for AngleF64 {
+    fn wrap_at(self, wrap_angle: AngleF64) -> Self {
+        let lower = wrap_angle.value - TAU;
+        Self::new::<radian>(lower + (self.value - lower).rem_euclid(TAU))
+    }
+
+    fn is_within_bounds(&self, lower: AngleF64, upper: AngleF64) -> bool {
+        *self >= lower && *self < upper
+    }
+
+    fn dms(self) -> Sexagesimal {
+        Sexagesimal::from_value(self.get::<degree>(), SexagesimalUnit::Degree)
+    }
+
+    fn hms(self) -> Sexagesimal {
+        Sexagesimal::from_value(self.get::<hour_angle>(), SexagesimalUnit::Hour)
+    }
+}
+
+/// Leading unit of a [`Sexagesimal`] representation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SexagesimalUnit {
+    /// Degrees, arcminutes and arcseconds.
+    Degree,
+    /// Hours, minutes and seconds of right ascension.
+    Hour,
+}
+
+/// Sexagesimal representation of an angle.
+///
+/// The [`Display`](fmt::Display) implementation follows astropy, e.g. `-12d30m00s` or
+/// `5h34m31.94s`. The formatter precision controls the number of decimals on the seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sexagesimal {
+    pub negative: bool,
+    pub whole: u32,
+    pub minutes: u32,
+    pub seconds: f64,
+    pub unit: SexagesimalUnit,
+}
+
+impl Sexagesimal {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn from_value(value: f64, unit: SexagesimalUnit) -> Self {
+        let negative = value.is_sign_negative() && value != 0.0;
+        let value = value.abs();
+        let whole = value.trunc();
+        let minutes = ((value - whole) * 60.0).trunc();
+        let seconds = (value - whole - minutes / 60.0) * 3600.0;
+        Self {
+            negative,
+            whole: whole as u32,
+            minutes: minutes as u32,
+            seconds,
+            unit,
+        }
+    }
+
+    /// Convert back to an angle.
+    #[must_use]
+    pub fn to_angle(&self) -> AngleF64 {
+        let value = f64::from(self.whole) + f64::from(self.minutes) / 60.0 + self.seconds / 3600.0;
+        let value = if self.negative { -value } else { value };
+        match self.unit {
+            SexagesimalUnit::Degree => AngleF64::new::<degree>(value),
+            SexagesimalUnit::Hour => AngleF64::new::<hour_angle>(value),
+        }
+    }
+}
+
+impl fmt::Display for Sexagesimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (u, m, s) = match self.unit {
+            SexagesimalUnit::Degree => ("d", "m", "s"),
+            SexagesimalUnit::Hour => ("h", "m", "s"),
+        };
+        let precision = f.precision().unwrap_or(0);
+        // Rounding the seconds may carry over into minutes and whole units.
+        let scale = 10f64.powi(i32::try_from(precision).unwrap_or(i32::MAX));
+        let mut seconds = (self.seconds * scale).round() / scale;
+        let mut minutes = self.minutes;
+        let mut whole = self.whole;
+        if seconds >= 60.0 {
+            seconds -= 60.0;
+            minutes += 1;
+        }
+        if minutes >= 60 {
+            minutes -= 60;
+            whole += 1;
+        }
+        let width = if precision > 0 { precision + 3 } else { 2 };
+        write!(
+            f,
+            "{}{whole}{u}{minutes:02}{m}{seconds:0width$.precision$}{s}",
+            if self.negative { "-" } else { "" },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::f64::Angle;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_astronomical_units() {
+        assert_relative_eq!(Angle::new::<arcsecond>(3600.0).get::<degree>(), 1.0);
+        assert_relative_eq!(Angle::new::<arcminute>(60.0).get::<degree>(), 1.0);
+        assert_relative_eq!(Angle::new::<milliarcsecond>(1000.0).get::<arcsecond>(), 1.0);
+        assert_relative_eq!(Angle::new::<hour_angle>(1.0).get::<degree>(), 15.0);
+        assert_relative_eq!(Angle::new::<hour_angle>(24.0).get::<radian>(), TAU);
+    }
+
+    #[test]
+    fn test_arithmetic_and_trig() {
+        let a = Angle::new::<degree>(30.0);
+        let b = Angle::new::<arcminute>(30.0 * 60.0);
+        assert_relative_eq!((a + b).get::<degree>(), 60.0);
+        assert_relative_eq!((2.0 * a).get::<degree>(), 60.0);
+        assert_relative_eq!(a.sin().value, 0.5, epsilon = 1e-12);
+        assert_relative_eq!((a + b).cos().value, 0.5, epsilon = 1e-12);
+        let y = Angle::new::<radian>(1.0);
+        let x = Angle::new::<radian>(1.0);
+        assert_relative_eq!(y.atan2(x).get::<degree>(), 45.0);
+        assert!(a < a + b);
+    }
+
+    #[test]
+    fn test_wrap_at() {
+        let wrap = Angle::new::<degree>(180.0);
+        assert_relative_eq!(
+            Angle::new::<degree>(190.0).wrap_at(wrap).get::<degree>(),
+            -170.0,
+            epsilon = 1e-12
+        );
+        assert_relative_eq!(
+            Angle::new::<degree>(-180.0).wrap_at(wrap).get::<degree>(),
+            -180.0,
+            epsilon = 1e-12
+        );
+        let wrap = Angle::new::<degree>(360.0);
+        assert_relative_eq!(
+            Angle::new::<degree>(-10.0).wrap_at(wrap).get::<degree>(),
+            350.0,
+            epsilon = 1e-12
+        );
+        assert_relative_eq!(
+            Angle::new::<degree>(725.0).wrap_at(wrap).get::<degree>(),
+            5.0,
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn test_sexagesimal() {
+        let a = Angle::new::<degree>(-12.5);
+        assert_eq!(format!("{}", a.dms()), "-12d30m00s");
+        let ra = Angle::new::<hour_angle>(5.0 + 34.0 / 60.0 + 31.94 / 3600.0);
+        assert_eq!(format!("{:.2}", ra.hms()), "5h34m31.94s");
+        assert_relative_eq!(
+            ra.hms().to_angle().get::<degree>(),
+            ra.get::<degree>(),
+            epsilon = 1e-12
+        );
+        // Rounding carries into the minutes.
+        let a = Angle::new::<degree>(1.0 + 59.0 / 60.0 + 59.9999 / 3600.0);
+        assert_eq!(format!("{:.1}", a.dms()), "2d00m00.0s");
+    }
+}
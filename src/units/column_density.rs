@@ -0,0 +1,6 @@
+//! Column density.
+//!
+//! Re-exports [`uom::si::areal_number_density`], whose `per_square_centimeter` is the usual
+//! cm⁻² unit of column densities.
+
+pub use uom::si::areal_number_density::*;
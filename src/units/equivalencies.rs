@@ -0,0 +1,265 @@
+//! Spectral equivalencies.
+//!
+//! Conversions between the interchangeable forms of a spectral coordinate (frequency,
+//! wavelength, wavenumber and photon energy), and between a spectral coordinate and a Doppler
+//! velocity relative to a rest value. These mirror `astropy.units.spectral()` and
+//! `astropy.units.doppler_{radio,optical,relativistic}`.
+//!
+//! ```
+//! use spectre::units::equivalencies::{DopplerConvention, spectral};
+//! use spectre::units::f64::{Frequency, Length, Velocity};
+//! use spectre::units::frequency::gigahertz;
+//! use spectre::units::length::millimeter;
+//! use spectre::units::velocity::kilometer_per_second;
+//!
+//! let f = Frequency::new::<gigahertz>(115.271_201_8);
+//! let lambda: Length = spectral(f);
+//! assert!((lambda.get::<millimeter>() - 2.600_757).abs() < 1e-6);
+//!
+//! let v: Velocity = DopplerConvention::Radio.to_velocity(Frequency::new::<gigahertz>(115.0), f);
+//! assert!((v.get::<kilometer_per_second>() - 705.3).abs() < 0.1);
+//! ```
+
+use crate::constants::{PLANCK, SPEED_OF_LIGHT};
+use crate::units::energy::joule;
+use crate::units::f64::{Energy, Frequency, Length, ReciprocalLength, Velocity};
+use crate::units::frequency::hertz;
+use crate::units::length::meter;
+use crate::units::velocity::meter_per_second;
+use crate::units::wavenumber::reciprocal_meter;
+
+/// A quantity that can stand in for the spectral coordinate of a photon.
+pub trait SpectralQuantity: Copy {
+    /// Convert to the equivalent frequency.
+    fn to_frequency(self) -> Frequency;
+
+    /// Convert from the equivalent frequency.
+    fn from_frequency(frequency: Frequency) -> Self;
+}
+
+impl SpectralQuantity for Frequency {
+    fn to_frequency(self) -> Frequency {
+        self
+    }
+
+    fn from_frequency(frequency: Frequency) -> Self {
+        frequency
+    }
+}
+
+/// Wavelength, λ = c / ν.
+impl SpectralQuantity for Length {
+    fn to_frequency(self) -> Frequency {
+        Frequency::new::<hertz>(SPEED_OF_LIGHT / self.get::<meter>())
+    }
+
+    fn from_frequency(frequency: Frequency) -> Self {
+        Self::new::<meter>(SPEED_OF_LIGHT / frequency.get::<hertz>())
+    }
+}
+
+/// Spectroscopic wavenumber, k = ν / c (not the angular wavenumber 2π / λ).
+impl SpectralQuantity for ReciprocalLength {
+    fn to_frequency(self) -> Frequency {
+        Frequency::new::<hertz>(self.get::<reciprocal_meter>() * SPEED_OF_LIGHT)
+    }
+
+    fn from_frequency(frequency: Frequency) -> Self {
+        Self::new::<reciprocal_meter>(frequency.get::<hertz>() / SPEED_OF_LIGHT)
+    }
+}
+
+/// Photon energy, E = h ν.
+impl SpectralQuantity for Energy {
+    fn to_frequency(self) -> Frequency {
+        Frequency::new::<hertz>(self.get::<joule>() / PLANCK)
+    }
+
+    fn from_frequency(frequency: Frequency) -> Self {
+        Self::new::<joule>(frequency.get::<hertz>() * PLANCK)
+    }
+}
+
+/// Convert between spectral coordinates, e.g. from a frequency to a wavelength.
+pub fn spectral<T: SpectralQuantity, U: SpectralQuantity>(value: T) -> U {
+    U::from_frequency(value.to_frequency())
+}
+
+/// Convert a slice of spectral coordinates element-wise. See [`spectral`].
+pub fn spectral_array<T: SpectralQuantity, U: SpectralQuantity>(values: &[T]) -> Vec<U> {
+    values.iter().map(|&v| spectral(v)).collect()
+}
+
+/// Convention used to define a Doppler velocity from a spectral coordinate.
+///
+/// With ν₀ the rest frequency:
+///
+/// * `Radio`: V = c (ν₀ − ν) / ν₀
+/// * `Optical`: V = c (ν₀ − ν) / ν = c (λ − λ₀) / λ₀
+/// * `Relativistic`: V = c (ν₀² − ν²) / (ν₀² + ν²)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DopplerConvention {
+    Radio,
+    Optical,
+    Relativistic,
+}
+
+impl DopplerConvention {
+    /// Velocity of a spectral coordinate relative to `rest`.
+    pub fn to_velocity<T, R>(self, value: T, rest: R) -> Velocity
+    where
+        T: SpectralQuantity,
+        R: SpectralQuantity,
+    {
+        let nu = value.to_frequency().get::<hertz>();
+        let nu0 = rest.to_frequency().get::<hertz>();
+        let beta = match self {
+            Self::Radio => (nu0 - nu) / nu0,
+            Self::Optical => (nu0 - nu) / nu,
+            Self::Relativistic => (nu0 * nu0 - nu * nu) / (nu0 * nu0 + nu * nu),
+        };
+        Velocity::new::<meter_per_second>(beta * SPEED_OF_LIGHT)
+    }
+
+    /// Spectral coordinate corresponding to a velocity relative to `rest`.
+    pub fn from_velocity<T, R>(self, velocity: Velocity, rest: R) -> T
+    where
+        T: SpectralQuantity,
+        R: SpectralQuantity,
+    {
+        let beta = velocity.get::<meter_per_second>() / SPEED_OF_LIGHT;
+        let nu0 = rest.to_frequency().get::<hertz>();
+        let nu = match self {
+            Self::Radio => nu0 * (1.0 - beta),
+            Self::Optical => nu0 / (1.0 + beta),
+            Self::Relativistic => nu0 * ((1.0 - beta) / (1.0 + beta)).sqrt(),
+        };
+        T::from_frequency(Frequency::new::<hertz>(nu))
+    }
+
+    /// Convert a slice of spectral coordinates to velocities. See [`Self::to_velocity`].
+    pub fn to_velocity_array<T, R>(self, values: &[T], rest: R) -> Vec<Velocity>
+    where
+        T: SpectralQuantity,
+        R: SpectralQuantity,
+    {
+        values.iter().map(|&v| self.to_velocity(v, rest)).collect()
+    }
+
+    /// Convert a slice of velocities to spectral coordinates. See [`Self::from_velocity`].
+    pub fn from_velocity_array<T, R>(self, velocities: &[Velocity], rest: R) -> Vec<T>
+    where
+        T: SpectralQuantity,
+        R: SpectralQuantity,
+    {
+        velocities
+            .iter()
+            .map(|&v| self.from_velocity(v, rest))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::energy::electronvolt;
+    use crate::units::frequency::gigahertz;
+    use crate::units::length::{micrometer, millimeter};
+    use crate::units::velocity::kilometer_per_second;
+    use crate::units::wavenumber::reciprocal_centimeter;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_spectral() {
+        let f = Frequency::new::<gigahertz>(299.792_458);
+        let lambda: Length = spectral(f);
+        assert_relative_eq!(lambda.get::<millimeter>(), 1.0, max_relative = 1e-12);
+
+        let k: ReciprocalLength = spectral(lambda);
+        assert_relative_eq!(k.get::<reciprocal_centimeter>(), 10.0, max_relative = 1e-12);
+
+        // 1 eV ~ 1.239842 µm
+        let e = Energy::new::<electronvolt>(1.0);
+        let lambda: Length = spectral(e);
+        assert_relative_eq!(
+            lambda.get::<micrometer>(),
+            1.239_841_98,
+            max_relative = 1e-8
+        );
+    }
+
+    #[test]
+    fn test_spectral_round_trip() {
+        let freqs: Vec<Frequency> = [1.0, 115.271_201_8, 230.538, 1900.537]
+            .iter()
+            .map(|&f| Frequency::new::<gigahertz>(f))
+            .collect();
+        let waves: Vec<Length> = spectral_array(&freqs);
+        let kays: Vec<ReciprocalLength> = spectral_array(&waves);
+        let energies: Vec<Energy> = spectral_array(&kays);
+        let back: Vec<Frequency> = spectral_array(&energies);
+        for (f, b) in freqs.iter().zip(&back) {
+            assert_relative_eq!(f.get::<hertz>(), b.get::<hertz>(), max_relative = 1e-14);
+        }
+    }
+
+    #[test]
+    fn test_doppler_conventions() {
+        let rest = Frequency::new::<gigahertz>(100.0);
+        let f = Frequency::new::<gigahertz>(99.0);
+        let c_kms = SPEED_OF_LIGHT / 1e3;
+
+        let v = DopplerConvention::Radio.to_velocity(f, rest);
+        assert_relative_eq!(
+            v.get::<kilometer_per_second>(),
+            0.01 * c_kms,
+            max_relative = 1e-12
+        );
+
+        let v = DopplerConvention::Optical.to_velocity(f, rest);
+        assert_relative_eq!(
+            v.get::<kilometer_per_second>(),
+            c_kms / 99.0,
+            max_relative = 1e-12
+        );
+
+        let v = DopplerConvention::Relativistic.to_velocity(f, rest);
+        assert_relative_eq!(
+            v.get::<kilometer_per_second>(),
+            c_kms * (1e4 - 9801.0) / (1e4 + 9801.0),
+            max_relative = 1e-12
+        );
+
+        // Rest value given as a wavelength gives the same answer.
+        let rest_wave: Length = spectral(rest);
+        let v_wave = DopplerConvention::Relativistic.to_velocity(f, rest_wave);
+        assert_relative_eq!(
+            v.get::<meter_per_second>(),
+            v_wave.get::<meter_per_second>()
+        );
+    }
+
+    #[test]
+    fn test_doppler_round_trip() {
+        let rest = Frequency::new::<gigahertz>(230.538);
+        let velocities: Vec<Velocity> = [-300.0, -10.0, 0.0, 25.5, 1.0e4]
+            .iter()
+            .map(|&v| Velocity::new::<kilometer_per_second>(v))
+            .collect();
+        for convention in [
+            DopplerConvention::Radio,
+            DopplerConvention::Optical,
+            DopplerConvention::Relativistic,
+        ] {
+            let waves: Vec<Length> = convention.from_velocity_array(&velocities, rest);
+            let back = convention.to_velocity_array(&waves, rest);
+            for (v, b) in velocities.iter().zip(&back) {
+                assert_relative_eq!(
+                    v.get::<kilometer_per_second>(),
+                    b.get::<kilometer_per_second>(),
+                    epsilon = 1e-9
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! Solid angle.
+//!
+//! Re-exports [`uom::si::solid_angle`] and adds `square_arcminute` and `square_arcsecond`
+//! under their astronomical names.
+
+pub use uom::si::solid_angle::*;
+
+units! {
+    quantity: uom::si::solid_angle;
+
+    @square_arcminute: 8.461_594_994_075_237_E-8; "arcmin²", "square arcminute",
+        "square arcminutes";
+    @square_arcsecond: 2.350_443_053_909_788_5_E-11; "arcsec²", "square arcsecond",
+        "square arcseconds";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::angle::{arcsecond, degree};
+    use crate::units::f64::{Angle, SolidAngle};
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_square_units() {
+        let one_deg = Angle::new::<degree>(1.0);
+        let area: SolidAngle = (one_deg * one_deg).into();
+        assert_relative_eq!(area.get::<square_degree>(), 1.0, epsilon = 1e-12);
+        assert_relative_eq!(
+            area.get::<square_arcsecond>(),
+            3600.0 * 3600.0,
+            max_relative = 1e-12
+        );
+        let one_arcsec = Angle::new::<arcsecond>(1.0);
+        let area: SolidAngle = (one_arcsec * one_arcsec).into();
+        assert_relative_eq!(area.get::<square_arcsecond>(), 1.0, max_relative = 1e-12);
+        assert_relative_eq!(
+            SolidAngle::new::<square_degree>(1.0).get::<square_arcminute>(),
+            3600.0,
+            max_relative = 1e-12
+        );
+    }
+}
@@ -0,0 +1,49 @@
+//! Spectral flux density (base unit W m⁻² Hz⁻¹).
+//!
+//! A spectral flux density has the dimension of a radiant exposure (J m⁻² = kg s⁻²), so these
+//! units extend [`uom::si::radiant_exposure`] and values are stored as
+//! [`SpectralFluxDensity`](crate::units::f64::SpectralFluxDensity).
+
+units! {
+    quantity: uom::si::radiant_exposure;
+
+    @watt_per_square_meter_per_hertz: 1.0_E0; "W m⁻² Hz⁻¹", "watt per square meter per hertz",
+        "watts per square meter per hertz";
+    @erg_per_second_per_square_centimeter_per_hertz: 1.0_E-3; "erg s⁻¹ cm⁻² Hz⁻¹",
+        "erg per second per square centimeter per hertz",
+        "ergs per second per square centimeter per hertz";
+    @megajansky: 1.0_E-20; "MJy", "megajansky", "megajanskys";
+    @kilojansky: 1.0_E-23; "kJy", "kilojansky", "kilojanskys";
+    @jansky: 1.0_E-26; "Jy", "jansky", "janskys";
+    @millijansky: 1.0_E-29; "mJy", "millijansky", "millijanskys";
+    @microjansky: 1.0_E-32; "µJy", "microjansky", "microjanskys";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::DisplayStyle::Abbreviation;
+    use crate::units::f64::SpectralFluxDensity;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_conversions() {
+        let s = SpectralFluxDensity::new::<jansky>(1.0);
+        assert_relative_eq!(s.get::<millijansky>(), 1000.0, max_relative = 1e-12);
+        assert_relative_eq!(s.get::<watt_per_square_meter_per_hertz>(), 1e-26);
+        assert_relative_eq!(
+            s.get::<erg_per_second_per_square_centimeter_per_hertz>(),
+            1e-23,
+            max_relative = 1e-12
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        let s = SpectralFluxDensity::new::<jansky>(134.0);
+        assert_eq!(
+            format!("{:.1}", s.into_format_args(millijansky, Abbreviation)),
+            "134000.0 mJy"
+        );
+    }
+}
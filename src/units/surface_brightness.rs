@@ -0,0 +1,196 @@
+//! Surface brightness, i.e. specific intensity (base unit W m⁻² Hz⁻¹ sr⁻¹).
+//!
+//! The steradian is dimensionless in SI, so to `uom` a surface brightness has the dimension of
+//! a spectral flux density. [`SurfaceBrightness`] is therefore its own type rather than a
+//! `uom` quantity, and the units in this module only convert surface brightnesses. Multiply by
+//! a [`SolidAngle`] to get a [`SpectralFluxDensity`], and use
+//! [`SurfaceBrightness::from_flux_density`] for the reverse.
+//!
+//! A flux density cannot be passed where a surface brightness is expected:
+//!
+//! ```compile_fail
+//! use spectre::radiation::brightness_temperature;
+//! use spectre::units::f64::{Frequency, SpectralFluxDensity};
+//! use spectre::units::frequency::gigahertz;
+//! use spectre::units::spectral_flux_density::jansky;
+//!
+//! let flux = SpectralFluxDensity::new::<jansky>(1.0);
+//! brightness_temperature(flux, Frequency::new::<gigahertz>(100.0));
+//! ```
+//!
+//! ```compile_fail
+//! use spectre::units::f64::SurfaceBrightness;
+//! use spectre::units::spectral_flux_density::jansky;
+//!
+//! let intensity = SurfaceBrightness::new::<jansky>(1.0);
+//! ```
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::units::f64::{SolidAngle, SpectralFluxDensity};
+use crate::units::solid_angle::steradian;
+use crate::units::spectral_flux_density::watt_per_square_meter_per_hertz;
+
+/// Marker trait for units of [`SurfaceBrightness`].
+pub trait Unit: uom::si::Unit {}
+
+/// Units of [`SurfaceBrightness`] with a conversion factor to the base unit.
+pub trait Conversion<V>: Unit + uom::Conversion<V, T = V>
+where
+    V: uom::Conversion<V>,
+{
+}
+
+units! {
+    quantity: crate::units::surface_brightness;
+
+    @watt_per_square_meter_per_hertz_per_steradian: 1.0_E0; "W m⁻² Hz⁻¹ sr⁻¹",
+        "watt per square meter per hertz per steradian",
+        "watts per square meter per hertz per steradian";
+    @erg_per_second_per_square_centimeter_per_hertz_per_steradian: 1.0_E-3;
+        "erg s⁻¹ cm⁻² Hz⁻¹ sr⁻¹", "erg per second per square centimeter per hertz per steradian",
+        "ergs per second per square centimeter per hertz per steradian";
+    @megajansky_per_steradian: 1.0_E-20; "MJy sr⁻¹", "megajansky per steradian",
+        "megajanskys per steradian";
+    @jansky_per_steradian: 1.0_E-26; "Jy sr⁻¹", "jansky per steradian", "janskys per steradian";
+    @jansky_per_square_degree: 3.282_806_350_011_744_E-23; "Jy deg⁻²",
+        "jansky per square degree", "janskys per square degree";
+    @jansky_per_square_arcsecond: 4.254_517_029_615_22_E-16; "Jy arcsec⁻²",
+        "jansky per square arcsecond", "janskys per square arcsecond";
+}
+
+/// Surface brightness (specific intensity), e.g. in janskys per steradian.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct SurfaceBrightness {
+    /// Value in the base unit, W m⁻² Hz⁻¹ sr⁻¹.
+    pub value: f64,
+}
+
+impl SurfaceBrightness {
+    /// A surface brightness of `value` in unit `N`.
+    pub fn new<N: Conversion<f64>>(value: f64) -> Self {
+        Self {
+            value: value * N::coefficient(),
+        }
+    }
+
+    /// The value in unit `N`.
+    pub fn get<N: Conversion<f64>>(&self) -> f64 {
+        self.value / N::coefficient()
+    }
+
+    /// The surface brightness of a flux density spread evenly over `solid_angle`.
+    pub fn from_flux_density(flux: SpectralFluxDensity, solid_angle: SolidAngle) -> Self {
+        Self {
+            value: flux.get::<watt_per_square_meter_per_hertz>() / solid_angle.get::<steradian>(),
+        }
+    }
+}
+
+impl Add for SurfaceBrightness {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            value: self.value + rhs.value,
+        }
+    }
+}
+
+impl Sub for SurfaceBrightness {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            value: self.value - rhs.value,
+        }
+    }
+}
+
+impl Neg for SurfaceBrightness {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self { value: -self.value }
+    }
+}
+
+impl Mul<f64> for SurfaceBrightness {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self {
+        Self {
+            value: self.value * rhs,
+        }
+    }
+}
+
+impl Div<f64> for SurfaceBrightness {
+    type Output = Self;
+
+    fn div(self, rhs: f64) -> Self {
+        Self {
+            value: self.value / rhs,
+        }
+    }
+}
+
+impl Div for SurfaceBrightness {
+    type Output = f64;
+
+    fn div(self, rhs: Self) -> f64 {
+        self.value / rhs.value
+    }
+}
+
+impl Mul<SolidAngle> for SurfaceBrightness {
+    type Output = SpectralFluxDensity;
+
+    fn mul(self, rhs: SolidAngle) -> SpectralFluxDensity {
+        SpectralFluxDensity::new::<watt_per_square_meter_per_hertz>(
+            self.value * rhs.get::<steradian>(),
+        )
+    }
+}
+
+impl Mul<SurfaceBrightness> for SolidAngle {
+    type Output = SpectralFluxDensity;
+
+    fn mul(self, rhs: SurfaceBrightness) -> SpectralFluxDensity {
+        rhs * self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::solid_angle::square_arcsecond;
+    use crate::units::spectral_flux_density::jansky;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_conversions() {
+        let i = SurfaceBrightness::new::<jansky_per_square_arcsecond>(1.0);
+        assert_relative_eq!(
+            i.get::<jansky_per_steradian>(),
+            4.254_517_029_615_22e10,
+            max_relative = 1e-12
+        );
+        let i = SurfaceBrightness::new::<megajansky_per_steradian>(1.0);
+        assert_relative_eq!(
+            i.get::<watt_per_square_meter_per_hertz_per_steradian>(),
+            1e-20,
+            max_relative = 1e-12
+        );
+    }
+
+    #[test]
+    fn test_flux_density() {
+        let beam = SolidAngle::new::<square_arcsecond>(2.0);
+        let i = SurfaceBrightness::new::<jansky_per_square_arcsecond>(3.0);
+        assert_relative_eq!((i * beam).get::<jansky>(), 6.0, max_relative = 1e-12);
+        assert_relative_eq!((beam * i).get::<jansky>(), 6.0, max_relative = 1e-12);
+        let back = SurfaceBrightness::from_flux_density(i * beam, beam);
+        assert_relative_eq!(back / i, 1.0, max_relative = 1e-12);
+    }
+}
@@ -0,0 +1,40 @@
+//! Wavenumber.
+//!
+//! Re-exports [`uom::si::reciprocal_length`] and adds the `kayser` (cm⁻¹), the unit LAMDA and
+//! most spectroscopic catalogs use for level energies.
+
+pub use uom::si::reciprocal_length::*;
+
+units! {
+    quantity: uom::si::reciprocal_length;
+
+    @kayser: 1.0_E2; "cm⁻¹", "kayser", "kaysers";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::DisplayStyle::Abbreviation;
+    use crate::units::f64::Wavenumber;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_conversions() {
+        let k = Wavenumber::new::<kayser>(1.0);
+        assert_relative_eq!(k.get::<reciprocal_meter>(), 100.0);
+        assert_relative_eq!(
+            Wavenumber::new::<reciprocal_centimeter>(3.5).get::<kayser>(),
+            3.5,
+            max_relative = 1e-12
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        let k = Wavenumber::new::<kayser>(2.5);
+        assert_eq!(
+            format!("{:.1}", k.into_format_args(kayser, Abbreviation)),
+            "2.5 cm⁻¹"
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use std::ops::Sub;
 
+use crate::units::f64::Angle;
+
 pub trait ApproxEq: Copy + PartialOrd + Sub<Output = Self> {
     fn abs_diff(self, other: Self) -> Self;
     fn approx_eq(self, other: Self, tolerance: Self) -> bool {
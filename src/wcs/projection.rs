@@ -0,0 +1,291 @@
+//! Celestial projections and the spherical rotation between native and celestial coordinates,
+//! following Calabretta & Greisen (2002, A&A 395, 1077; FITS WCS Paper II). Angles are in
+//! degrees throughout.
+
+use crate::errors::wcs::WcsError;
+
+/// A celestial projection, from the last three letters of `CTYPEi`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Projection {
+    /// Orthographic. Headers with the ξ and η of slant SIN (`PVi_1`, `PVi_2` on the latitude
+    /// axis) are refused.
+    Sin,
+    /// Gnomonic
+    Tan,
+    /// Zenithal equidistant
+    Arc,
+    /// Zenithal equal-area
+    Zea,
+    /// Plate carrée
+    Car,
+    /// Sanson-Flamsteed. The AIPS `GLS` is read as `SFL` with its fiducial point at the
+    /// reference declination.
+    Sfl,
+}
+
+impl Projection {
+    /// Parse a projection code such as `"TAN"`. `"GLS"` is read as [`Self::Sfl`].
+    pub fn from_code(code: &str) -> Option<Self> {
+        Some(match code {
+            "SIN" => Self::Sin,
+            "TAN" => Self::Tan,
+            "ARC" => Self::Arc,
+            "ZEA" => Self::Zea,
+            "CAR" => Self::Car,
+            "SFL" | "GLS" => Self::Sfl,
+            _ => return None,
+        })
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Sin => "SIN",
+            Self::Tan => "TAN",
+            Self::Arc => "ARC",
+            Self::Zea => "ZEA",
+            Self::Car => "CAR",
+            Self::Sfl => "SFL",
+        }
+    }
+
+    fn is_zenithal(&self) -> bool {
+        matches!(self, Self::Sin | Self::Tan | Self::Arc | Self::Zea)
+    }
+
+    /// Native latitude of the fiducial point, θ₀, by default.
+    pub(crate) fn default_theta0(&self) -> f64 {
+        if self.is_zenithal() { 90.0 } else { 0.0 }
+    }
+
+    /// Project native spherical coordinates (φ, θ) onto the plane of intermediate world
+    /// coordinates (x, y).
+    pub(crate) fn project(&self, phi: f64, theta: f64) -> Result<(f64, f64), WcsError> {
+        if self.is_zenithal() {
+            let r = match self {
+                Self::Sin if theta >= 0.0 => theta.to_radians().cos().to_degrees(),
+                Self::Tan if theta > 0.0 => {
+                    (theta.to_radians().cos() / theta.to_radians().sin()).to_degrees()
+                }
+                Self::Arc => 90.0 - theta,
+                Self::Zea => (2.0 * ((90.0 - theta) / 2.0).to_radians().sin()).to_degrees(),
+                _ => return Err(WcsError::OutsideProjection),
+            };
+            let (sin_phi, cos_phi) = phi.to_radians().sin_cos();
+            return Ok((r * sin_phi, -r * cos_phi));
+        }
+        let phi = wrap_180(phi);
+        match self {
+            Self::Car => Ok((phi, theta)),
+            _ => Ok((phi * theta.to_radians().cos(), theta)),
+        }
+    }
+
+    /// Invert [`Self::project`].
+    pub(crate) fn deproject(&self, x: f64, y: f64) -> Result<(f64, f64), WcsError> {
+        if self.is_zenithal() {
+            let r = x.hypot(y);
+            let phi = if r == 0.0 {
+                0.0
+            } else {
+                x.atan2(-y).to_degrees()
+            };
+            let r_rad = r.to_radians();
+            let theta = match self {
+                Self::Sin if r_rad <= 1.0 => r_rad.acos().to_degrees(),
+                Self::Tan => 1.0_f64.atan2(r_rad).to_degrees(),
+                Self::Arc if r <= 180.0 => 90.0 - r,
+                Self::Zea if r_rad <= 2.0 => 90.0 - 2.0 * (r_rad / 2.0).asin().to_degrees(),
+                _ => return Err(WcsError::OutsideProjection),
+            };
+            return Ok((phi, theta));
+        }
+        if y.abs() > 90.0 {
+            return Err(WcsError::OutsideProjection);
+        }
+        let phi = match self {
+            Self::Car => x,
+            _ => {
+                let cos_theta = y.to_radians().cos();
+                if cos_theta == 0.0 {
+                    if x != 0.0 {
+                        return Err(WcsError::OutsideProjection);
+                    }
+                    0.0
+                } else {
+                    x / cos_theta
+                }
+            }
+        };
+        if phi.abs() > 180.0 + 1e-10 {
+            return Err(WcsError::OutsideProjection);
+        }
+        Ok((phi, y))
+    }
+}
+
+/// The rotation taking native spherical coordinates to celestial ones, given by the celestial
+/// coordinates of the native pole (α_p, δ_p) and the native longitude of the celestial pole
+/// φ_p (`LONPOLE`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct SphericalRotation {
+    alpha_p: f64,
+    delta_p: f64,
+    phi_p: f64,
+}
+
+impl SphericalRotation {
+    /// Solve for the native pole given that the fiducial point (φ₀, θ₀) lies at celestial
+    /// (α₀, δ₀) (Paper II, Sect. 2.4). Of the two possible pole latitudes, the one closest to
+    /// `latpole` is taken.
+    pub(crate) fn new(
+        (alpha0, delta0): (f64, f64),
+        (phi0, theta0): (f64, f64),
+        lonpole: Option<f64>,
+        latpole: f64,
+    ) -> Result<Self, WcsError> {
+        let phi_p = lonpole.unwrap_or(if delta0 >= theta0 { 0.0 } else { 180.0 });
+        if theta0 == 90.0 {
+            return Ok(Self {
+                alpha_p: alpha0,
+                delta_p: delta0,
+                phi_p,
+            });
+        }
+
+        let (sin_t0, cos_t0) = theta0.to_radians().sin_cos();
+        let dphi = (phi_p - phi0).to_radians();
+        let u = sin_t0.atan2(cos_t0 * dphi.cos()).to_degrees();
+        let ratio = delta0.to_radians().sin() / (1.0 - (cos_t0 * dphi.sin()).powi(2)).sqrt();
+        if ratio.abs() > 1.0 + 1e-12 {
+            return Err(WcsError::InvalidKeyword("LONPOLE".to_string()));
+        }
+        let v = ratio.clamp(-1.0, 1.0).acos().to_degrees();
+        let delta_p = [u + v, u - v]
+            .into_iter()
+            .map(wrap_180)
+            .filter(|d| d.abs() <= 90.0 + 1e-12)
+            .min_by(|a, b| (a - latpole).abs().total_cmp(&(b - latpole).abs()))
+            .ok_or_else(|| WcsError::InvalidKeyword("LATPOLE".to_string()))?
+            .clamp(-90.0, 90.0);
+
+        let alpha_p = if (delta_p - 90.0).abs() < 1e-12 {
+            alpha0 + phi_p - phi0 - 180.0
+        } else if (delta_p + 90.0).abs() < 1e-12 {
+            alpha0 - phi_p + phi0
+        } else {
+            let (sin_d0, cos_d0) = delta0.to_radians().sin_cos();
+            let (sin_dp, cos_dp) = delta_p.to_radians().sin_cos();
+            alpha0
+                - (dphi.sin() * cos_t0 / cos_d0)
+                    .atan2((sin_t0 - sin_dp * sin_d0) / (cos_dp * cos_d0))
+                    .to_degrees()
+        };
+        Ok(Self {
+            alpha_p,
+            delta_p,
+            phi_p,
+        })
+    }
+
+    /// Native (φ, θ) to celestial (α, δ), with α in [0, 360).
+    pub(crate) fn to_celestial(self, phi: f64, theta: f64) -> (f64, f64) {
+        let (sin_t, cos_t) = theta.to_radians().sin_cos();
+        let (sin_dp, cos_dp) = self.delta_p.to_radians().sin_cos();
+        let (sin_dphi, cos_dphi) = (phi - self.phi_p).to_radians().sin_cos();
+        let (x, y) = (
+            -cos_t * sin_dphi,
+            sin_t * cos_dp - cos_t * sin_dp * cos_dphi,
+        );
+        let alpha = self.alpha_p + x.atan2(y).to_degrees();
+        // atan2 rather than asin keeps full precision near the poles.
+        let delta = (sin_t * sin_dp + cos_t * cos_dp * cos_dphi)
+            .atan2(x.hypot(y))
+            .to_degrees();
+        (alpha.rem_euclid(360.0), delta)
+    }
+
+    /// Celestial (α, δ) to native (φ, θ), with φ in [−180, 180].
+    pub(crate) fn to_native(self, alpha: f64, delta: f64) -> (f64, f64) {
+        let (sin_d, cos_d) = delta.to_radians().sin_cos();
+        let (sin_dp, cos_dp) = self.delta_p.to_radians().sin_cos();
+        let (sin_da, cos_da) = (alpha - self.alpha_p).to_radians().sin_cos();
+        let (x, y) = (-cos_d * sin_da, sin_d * cos_dp - cos_d * sin_dp * cos_da);
+        let phi = self.phi_p + x.atan2(y).to_degrees();
+        let theta = (sin_d * sin_dp + cos_d * cos_dp * cos_da)
+            .atan2(x.hypot(y))
+            .to_degrees();
+        (wrap_180(phi), theta)
+    }
+}
+
+/// Wrap an angle in degrees into [−180, 180].
+fn wrap_180(angle: f64) -> f64 {
+    if (-180.0..=180.0).contains(&angle) {
+        angle
+    } else {
+        (angle + 180.0).rem_euclid(360.0) - 180.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_projection_round_trip() {
+        for projection in [
+            Projection::Sin,
+            Projection::Tan,
+            Projection::Arc,
+            Projection::Zea,
+            Projection::Car,
+            Projection::Sfl,
+        ] {
+            for (phi, theta) in [(0.0, 89.0), (35.0, 60.0), (-120.0, 45.0), (170.0, 10.0)] {
+                let (x, y) = projection.project(phi, theta).unwrap();
+                let (p, t) = projection.deproject(x, y).unwrap();
+                assert_relative_eq!(p, phi, epsilon = 1e-9);
+                assert_relative_eq!(t, theta, epsilon = 1e-9);
+            }
+        }
+        assert_eq!(
+            Projection::Tan.project(0.0, -10.0),
+            Err(WcsError::OutsideProjection)
+        );
+        assert_eq!(
+            Projection::Sin.deproject(60.0, 0.0),
+            Err(WcsError::OutsideProjection)
+        );
+    }
+
+    #[test]
+    fn test_rotation() {
+        // Zenithal: the native pole is the reference point.
+        let rotation = SphericalRotation::new((150.0, 30.0), (0.0, 90.0), None, 90.0).unwrap();
+        let (alpha, delta) = rotation.to_celestial(0.0, 90.0);
+        assert_relative_eq!(alpha, 150.0, epsilon = 1e-9);
+        assert_relative_eq!(delta, 30.0, epsilon = 1e-9);
+        let (alpha, delta) = rotation.to_celestial(0.0, 89.0);
+        assert_relative_eq!(alpha, 150.0, epsilon = 1e-9);
+        assert_relative_eq!(delta, 29.0, epsilon = 1e-9);
+
+        // Cylindrical with an oblique reference point.
+        let rotation = SphericalRotation::new((20.0, -35.0), (0.0, 0.0), None, 90.0).unwrap();
+        let (alpha, delta) = rotation.to_celestial(0.0, 0.0);
+        assert_relative_eq!(alpha, 20.0, epsilon = 1e-9);
+        assert_relative_eq!(delta, -35.0, epsilon = 1e-9);
+        let (phi, theta) = rotation.to_native(33.0, -12.0);
+        let (alpha, delta) = rotation.to_celestial(phi, theta);
+        assert_relative_eq!(alpha, 33.0, epsilon = 1e-9);
+        assert_relative_eq!(delta, -12.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_wrap_180() {
+        assert_eq!(wrap_180(190.0), -170.0);
+        assert_eq!(wrap_180(-190.0), 170.0);
+        assert_eq!(wrap_180(540.0), -180.0);
+        assert_eq!(wrap_180(180.0), 180.0);
+    }
+}
@@ -0,0 +1,179 @@
+//! Spectral axis types, following Greisen et al. (2006, A&A 446, 747; FITS WCS Paper III).
+//! Only axes linear in their own type are supported, plus the AIPS `FREQ-xxx` and
+//! `VELO-xxx` conventions whose suffix names the velocity frame.
+
+use crate::coordinates::VelocityFrame;
+use crate::errors::wcs::WcsError;
+use crate::spectrum::SpectralKind;
+use crate::units::equivalencies::DopplerConvention;
+
+/// Type of a spectral axis, from the first four letters of `CTYPEi`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpectralType {
+    /// `FREQ`
+    Frequency,
+    /// `VRAD`
+    RadioVelocity,
+    /// `VOPT`
+    OpticalVelocity,
+    /// `VELO`, apparent radial velocity
+    RelativisticVelocity,
+    /// `WAVE`, vacuum wavelength
+    Wavelength,
+    /// `AWAV`, wavelength in standard air
+    AirWavelength,
+}
+
+impl SpectralType {
+    /// Parse a type code such as `"VRAD"`.
+    pub fn from_code(code: &str) -> Option<Self> {
+        Some(match code {
+            "FREQ" => Self::Frequency,
+            "VRAD" => Self::RadioVelocity,
+            "VOPT" => Self::OpticalVelocity,
+            "VELO" => Self::RelativisticVelocity,
+            "WAVE" => Self::Wavelength,
+            "AWAV" => Self::AirWavelength,
+            _ => return None,
+        })
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Frequency => "FREQ",
+            Self::RadioVelocity => "VRAD",
+            Self::OpticalVelocity => "VOPT",
+            Self::RelativisticVelocity => "VELO",
+            Self::Wavelength => "WAVE",
+            Self::AirWavelength => "AWAV",
+        }
+    }
+
+    /// SI unit in which world coordinates of this type are expressed, as written in `CUNITi`.
+    pub fn si_unit(&self) -> &'static str {
+        match self {
+            Self::Frequency => "Hz",
+            Self::RadioVelocity | Self::OpticalVelocity | Self::RelativisticVelocity => "m/s",
+            Self::Wavelength | Self::AirWavelength => "m",
+        }
+    }
+
+    /// Kind of the [`SpectralAxis`](crate::spectrum::SpectralAxis) built from this type.
+    /// Air wavelengths become vacuum wavelengths.
+    pub fn kind(&self) -> SpectralKind {
+        match self {
+            Self::Frequency => SpectralKind::Frequency,
+            Self::RadioVelocity | Self::OpticalVelocity | Self::RelativisticVelocity => {
+                SpectralKind::Velocity
+            }
+            Self::Wavelength | Self::AirWavelength => SpectralKind::Wavelength,
+        }
+    }
+
+    /// Velocity convention of a velocity type.
+    pub fn convention(&self) -> Option<DopplerConvention> {
+        match self {
+            Self::RadioVelocity => Some(DopplerConvention::Radio),
+            Self::OpticalVelocity => Some(DopplerConvention::Optical),
+            Self::RelativisticVelocity => Some(DopplerConvention::Relativistic),
+            _ => None,
+        }
+    }
+}
+
+/// Type codes of Paper III that are recognised as spectral but not supported.
+const UNSUPPORTED: [&str; 6] = ["ENER", "WAVN", "ZOPT", "BETA", "FELO", "VELOCITY"];
+
+/// Parse a spectral `CTYPEi`, returning `None` if it is not spectral. The frame is only set
+/// for AIPS-style types such as `VELO-LSR`; `VELO` is a radio velocity if `VELREF` is
+/// absent or at least 256, and optical otherwise.
+pub(crate) fn parse_ctype(
+    ctype: &str,
+    velref: Option<i64>,
+) -> Result<Option<(SpectralType, Option<VelocityFrame>)>, WcsError> {
+    let unsupported = || WcsError::UnsupportedSpectralType(ctype.to_string());
+    let (code, suffix) = match ctype.split_once('-') {
+        Some((code, rest)) => (code, rest.trim_start_matches('-')),
+        None => (ctype, ""),
+    };
+    if suffix.is_empty() {
+        return match SpectralType::from_code(code) {
+            Some(kind) => Ok(Some((kind, None))),
+            None if UNSUPPORTED.contains(&code) => Err(unsupported()),
+            None => Ok(None),
+        };
+    }
+
+    let frame = match suffix {
+        "LSR" => VelocityFrame::Lsrk,
+        "LSD" => VelocityFrame::Lsrd,
+        "HEL" | "BAR" => VelocityFrame::Barycentric,
+        "OBS" | "TOP" => VelocityFrame::Topocentric,
+        "GEO" => VelocityFrame::Geocentric,
+        "GAL" => VelocityFrame::Galactocentric,
+        _ if SpectralType::from_code(code).is_some() || UNSUPPORTED.contains(&code) => {
+            return Err(unsupported());
+        }
+        _ => return Ok(None),
+    };
+    let kind = match code {
+        "FREQ" => SpectralType::Frequency,
+        "VELO" if velref.is_none_or(|v| v >= 256) => SpectralType::RadioVelocity,
+        "VELO" => SpectralType::OpticalVelocity,
+        _ => return Err(unsupported()),
+    };
+    Ok(Some((kind, Some(frame))))
+}
+
+/// Refractive index of standard air at a vacuum wavelength in metres, from the dispersion
+/// formula of Edlén (1966) also used by WCSLIB.
+fn air_index(wavelength: f64) -> f64 {
+    let s = wavelength.powi(-2);
+    1.000_064_328 + 2.949_81e10 / (1.46e14 - s) + 2.554e8 / (0.41e14 - s)
+}
+
+/// Vacuum wavelength of an air wavelength, both in metres.
+pub(crate) fn air_to_vacuum(wavelength: f64) -> f64 {
+    (0..4).fold(wavelength, |vacuum, _| wavelength * air_index(vacuum))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_parse_ctype() {
+        assert_eq!(
+            parse_ctype("FREQ", None).unwrap(),
+            Some((SpectralType::Frequency, None))
+        );
+        assert_eq!(
+            parse_ctype("VELO-LSR", None).unwrap(),
+            Some((SpectralType::RadioVelocity, Some(VelocityFrame::Lsrk)))
+        );
+        assert_eq!(
+            parse_ctype("VELO-HEL", Some(2)).unwrap(),
+            Some((
+                SpectralType::OpticalVelocity,
+                Some(VelocityFrame::Barycentric)
+            ))
+        );
+        assert_eq!(
+            parse_ctype("VELO", None).unwrap(),
+            Some((SpectralType::RelativisticVelocity, None))
+        );
+        assert_eq!(parse_ctype("RA---TAN", None).unwrap(), None);
+        assert_eq!(parse_ctype("STOKES", None).unwrap(), None);
+        assert!(parse_ctype("VOPT-F2W", None).is_err());
+        assert!(parse_ctype("ENER", None).is_err());
+    }
+
+    #[test]
+    fn test_air_wavelength() {
+        // Hα: 6564.61 Å in vacuum, 6562.80 Å in air.
+        let vacuum = air_to_vacuum(656.280e-9);
+        assert_relative_eq!(vacuum, 656.461e-9, max_relative = 2e-6);
+        assert_relative_eq!(vacuum / air_index(vacuum), 656.280e-9, max_relative = 1e-14);
+    }
+}
@@ -0,0 +1,116 @@
+"""Reference values for the WCS fixtures, from WCSLIB through astropy.wcs:
+
+    python generate.py
+
+`all_pix2world` gives the world coordinates, and `all_world2pix` is checked to recover the
+pixels. Each fixture records the astropy and WCSLIB versions that produced it, and the Rust
+tests refuse fixtures without them.
+"""
+import math
+import os
+
+
+def card_line(k, v):
+    if isinstance(v, str):
+        return f"{k:<8}= '{v:<8}'"
+    return f"{k:<8}= {v!r:>20}"
+
+
+def astropy_reference(cards, pixels):
+    import astropy
+    import numpy as np
+    from astropy.io import fits
+    from astropy.wcs import WCS, _wcs
+
+    header = fits.Header()
+    for k, v in cards:
+        header[k] = v
+    wcs = WCS(header)
+    pixels = np.array(pixels, dtype=float)
+    world = wcs.all_pix2world(pixels, 0)
+    back = wcs.all_world2pix(world, 0)
+    assert np.allclose(back, pixels, atol=1e-8), back - pixels
+    # astropy reports spectral coordinates in SI and celestial ones in degrees.
+    return (f"Generated with astropy {astropy.__version__}, WCSLIB {_wcs.__version__}.",
+            [list(map(float, w)) for w in world])
+
+
+def generate(name, cards, pixels, note=None):
+    path = os.path.join(os.path.dirname(__file__), f"{name}.txt")
+    lines = [
+        "# Header cards, then zero-based pixel coordinates and the expected world",
+        "# coordinates (degrees, and SI units for the spectral axis).",
+    ]
+    if pixels:
+        source, world = astropy_reference(cards, pixels)
+        lines.append(f"# {source}")
+    if note:
+        lines.append(f"# {note}")
+    lines += [card_line(k, v) for k, v in cards]
+    if pixels:
+        for p, w in zip(pixels, world):
+            lines.append(" ".join(repr(float(x)) for x in list(p) + list(w)))
+    with open(path, "w") as out:
+        out.write("\n".join(lines) + "\n")
+
+
+s30, c30 = math.sin(math.radians(30)), math.cos(math.radians(30))
+pix2 = [(0, 0), (10, 5), (49.5, 49.5), (99, 99), (-3, 20), (70.25, 12.75)]
+generate("tan", [
+    ("NAXIS", 2), ("CTYPE1", "RA---TAN"), ("CTYPE2", "DEC--TAN"),
+    ("CUNIT1", "deg"), ("CUNIT2", "deg"),
+    ("CRPIX1", 50.5), ("CRPIX2", 50.5), ("CRVAL1", 150.1), ("CRVAL2", 2.2),
+    ("CDELT1", -0.05), ("CDELT2", 0.05),
+    ("PC1_1", c30), ("PC1_2", -s30), ("PC2_1", s30), ("PC2_2", c30)], pix2)
+
+pix3 = [(0, 0, 0), (10, 5, 3), (15.5, 15.5, 0), (31, 31, 7), (-3, 20, 4.5), (25.25, 2.75, 1)]
+sin_freq = [
+    ("NAXIS", 3), ("CTYPE1", "RA---SIN"), ("CTYPE2", "DEC--SIN"), ("CTYPE3", "FREQ"),
+    ("CUNIT1", "arcsec"), ("CUNIT2", "arcsec"), ("CUNIT3", "GHz"),
+    ("CRPIX1", 16.5), ("CRPIX2", 16.5), ("CRPIX3", 1.0),
+    ("CRVAL1", 83.82 * 3600), ("CRVAL2", -5.39 * 3600), ("CRVAL3", 230.538),
+    ("CDELT1", -1080.0), ("CDELT2", 1080.0), ("CDELT3", -0.000488),
+    ("RESTFRQ", 230.538e9), ("SPECSYS", "LSRK")]
+generate("sin_freq", sin_freq, pix3)
+
+# Slant SIN is not supported, so the fixture has no rows and the header must be refused.
+generate("sin_slant", sin_freq + [("PV2_1", 0.2), ("PV2_2", -0.1)], [],
+         note="Slant SIN (non-zero PV2_1 and PV2_2), which must be refused.")
+
+generate("car_vrad", [
+    ("NAXIS", 3), ("CTYPE1", "GLON-CAR"), ("CTYPE2", "GLAT-CAR"), ("CTYPE3", "VRAD"),
+    ("CUNIT3", "km/s"),
+    ("CRPIX1", 20.0), ("CRPIX2", 10.0), ("CRPIX3", 5.0),
+    ("CRVAL1", 30.0), ("CRVAL2", 25.0), ("CRVAL3", -20.0),
+    ("CDELT1", -0.5), ("CDELT2", 0.5), ("CDELT3", 0.25),
+    ("RESTFRQ", 115.2712018e9), ("SPECSYS", "LSRK")], pix3)
+
+generate("sfl_vopt", [
+    ("NAXIS", 3), ("CTYPE1", "RA---SFL"), ("CTYPE2", "DEC--SFL"), ("CTYPE3", "VOPT"),
+    ("CUNIT3", "m/s"),
+    ("CRPIX1", 12.0), ("CRPIX2", 14.0), ("CRPIX3", 3.0),
+    ("CRVAL1", 210.0), ("CRVAL2", -40.0), ("CRVAL3", 1.5e6),
+    ("CD1_1", -0.1), ("CD1_2", 0.01), ("CD2_1", 0.02), ("CD2_2", 0.1), ("CD3_3", 5000.0),
+    ("RESTFRQ", 1.420405752e9), ("SPECSYS", "BARYCENT")], pix3)
+
+generate("gls_velo_lsr", [
+    ("NAXIS", 3), ("CTYPE1", "RA---GLS"), ("CTYPE2", "DEC--GLS"), ("CTYPE3", "VELO-LSR"),
+    ("CRPIX1", 16.0), ("CRPIX2", 16.0), ("CRPIX3", 8.0),
+    ("CRVAL1", 266.4), ("CRVAL2", -28.9), ("CRVAL3", 5000.0),
+    ("CDELT1", -0.2), ("CDELT2", 0.2), ("CDELT3", -1000.0),
+    ("VELREF", 257), ("RESTFREQ", 4.8296594e9)], pix3)
+
+generate("arc_wave", [
+    ("NAXIS", 3), ("CTYPE1", "RA---ARC"), ("CTYPE2", "DEC--ARC"), ("CTYPE3", "WAVE"),
+    ("CUNIT3", "Angstrom"),
+    ("CRPIX1", 16.0), ("CRPIX2", 16.0), ("CRPIX3", 1.0),
+    ("CRVAL1", 10.68), ("CRVAL2", 41.27), ("CRVAL3", 6563.0),
+    ("CDELT1", -0.2), ("CDELT2", 0.2), ("CDELT3", 1.25),
+    ("CROTA2", 15.0), ("LONPOLE", 170.0)], pix3)
+
+generate("zea_awav", [
+    ("NAXIS", 3), ("CTYPE1", "RA---ZEA"), ("CTYPE2", "DEC--ZEA"), ("CTYPE3", "AWAV"),
+    ("CUNIT3", "nm"),
+    ("CRPIX1", 16.0), ("CRPIX2", 16.0), ("CRPIX3", 2.0),
+    ("CRVAL1", 45.0), ("CRVAL2", 85.0), ("CRVAL3", 500.0),
+    ("CDELT1", -1.0), ("CDELT2", 1.0), ("CDELT3", 0.1)], pix3)